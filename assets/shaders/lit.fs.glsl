@@ -0,0 +1,87 @@
+#version 330 core
+
+#ifndef MAX_LIGHTS
+#define MAX_LIGHTS 8
+#endif
+
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+in vec3 frag_position;
+in vec3 frag_normal;
+in vec2 frag_uv;
+
+out vec4 out_color;
+
+uniform vec3 camera_position;
+uniform vec3 ambient_light;
+
+uniform vec3 material_ambient;
+uniform vec3 material_diffuse;
+uniform vec3 material_specular;
+uniform vec3 material_emissive;
+uniform float material_shininess;
+uniform float material_opacity;
+
+uniform bool use_diffuse_texture;
+uniform sampler2D diffuse_texture;
+
+// World space, directions point the way the light travels
+uniform int light_count;
+uniform int light_types[MAX_LIGHTS];
+uniform vec3 light_positions[MAX_LIGHTS];
+uniform vec3 light_directions[MAX_LIGHTS];
+uniform vec3 light_colors[MAX_LIGHTS];      // Color times intensity
+uniform float light_ranges[MAX_LIGHTS];
+uniform vec2 light_cones[MAX_LIGHTS];       // Cosines of the outer and inner angle
+
+// Inverse square, windowed to reach 0 at the range
+float attenuation(float light_distance, float range) {
+   float ratio = light_distance / range;
+   float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+   return window * window / (light_distance * light_distance + 1.0);
+}
+
+void main() {
+   vec4 albedo = vec4(1.0);
+   if (use_diffuse_texture) {
+      albedo = texture(diffuse_texture, frag_uv);
+   }
+
+   // Back faces are lit like front faces
+   vec3 normal = normalize(frag_normal);
+   if (!gl_FrontFacing) {
+      normal = -normal;
+   }
+   vec3 view_direction = normalize(camera_position - frag_position);
+
+   vec3 diffuse = vec3(0.0);
+   vec3 specular = vec3(0.0);
+   for (int i = 0; i < light_count && i < MAX_LIGHTS; i++) {
+      vec3 light_direction = -light_directions[i];
+      float strength = 1.0;
+      if (light_types[i] != DIRECTIONAL) {
+         vec3 to_light = light_positions[i] - frag_position;
+         float light_distance = length(to_light);
+         light_direction = to_light / max(light_distance, 0.0001);
+         strength = attenuation(light_distance, light_ranges[i]);
+         if (light_types[i] == SPOT) {
+            strength *= smoothstep(light_cones[i].x, light_cones[i].y, dot(-light_direction, light_directions[i]));
+         }
+      }
+
+      float lambert = max(dot(normal, light_direction), 0.0);
+      diffuse += light_colors[i] * strength * lambert;
+      if (lambert > 0.0) {
+         vec3 halfway = normalize(light_direction + view_direction);
+         specular += light_colors[i] * strength * pow(max(dot(normal, halfway), 0.0), material_shininess);
+      }
+   }
+
+   vec3 color = ambient_light * material_ambient * albedo.rgb
+      + diffuse * material_diffuse * albedo.rgb
+      + specular * material_specular
+      + material_emissive;
+   out_color = vec4(color, albedo.a * material_opacity);
+}
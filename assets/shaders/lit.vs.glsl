@@ -0,0 +1,20 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 2) in vec2 uv_coordinates;
+layout (location = 3) in vec3 normal;
+
+uniform mat4 mvp;
+uniform mat4 model;
+uniform mat3 normal_matrix;
+
+out vec3 frag_position;
+out vec3 frag_normal;
+out vec2 frag_uv;
+
+void main() {
+   gl_Position = mvp * vec4(position, 1.0);
+   frag_position = (model * vec4(position, 1.0)).xyz;
+   frag_normal = normal_matrix * normal;
+   frag_uv = uv_coordinates;
+}
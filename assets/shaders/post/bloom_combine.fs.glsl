@@ -0,0 +1,15 @@
+#version 330 core
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+uniform sampler2D bloom_texture;
+uniform float intensity;
+
+out vec4 out_color;
+
+void main() {
+   vec3 color = texture(input_texture, frag_uv).rgb;
+   vec3 bloom = texture(bloom_texture, frag_uv).rgb;
+   out_color = vec4(color + bloom * intensity, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 330 core
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+
+out vec4 out_color;
+
+void main() {
+   // Four bilinear taps between texels average a 4x4 block of the larger level
+   vec2 texel = 1.0 / vec2(textureSize(input_texture, 0));
+   vec3 color = texture(input_texture, frag_uv + vec2(-1.0, -1.0) * texel).rgb;
+   color += texture(input_texture, frag_uv + vec2(1.0, -1.0) * texel).rgb;
+   color += texture(input_texture, frag_uv + vec2(-1.0, 1.0) * texel).rgb;
+   color += texture(input_texture, frag_uv + vec2(1.0, 1.0) * texel).rgb;
+   out_color = vec4(color * 0.25, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 330 core
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+uniform float threshold;   // Brightness where bloom starts
+uniform float knee;        // Width of the soft transition below the threshold
+
+out vec4 out_color;
+
+void main() {
+   vec3 color = texture(input_texture, frag_uv).rgb;
+   float brightness = max(color.r, max(color.g, color.b));
+   float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+   soft = soft * soft / (4.0 * knee + 0.00001);
+   float contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+   out_color = vec4(color * contribution, 1.0);
+}
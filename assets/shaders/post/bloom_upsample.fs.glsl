@@ -0,0 +1,22 @@
+#version 330 core
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+
+out vec4 out_color;
+
+void main() {
+   // 3x3 tent filter over the smaller level, added on top of the larger one by blending
+   vec2 texel = 1.0 / vec2(textureSize(input_texture, 0));
+   vec3 color = texture(input_texture, frag_uv).rgb * 4.0;
+   color += texture(input_texture, frag_uv + vec2(-1.0, 0.0) * texel).rgb * 2.0;
+   color += texture(input_texture, frag_uv + vec2(1.0, 0.0) * texel).rgb * 2.0;
+   color += texture(input_texture, frag_uv + vec2(0.0, -1.0) * texel).rgb * 2.0;
+   color += texture(input_texture, frag_uv + vec2(0.0, 1.0) * texel).rgb * 2.0;
+   color += texture(input_texture, frag_uv + vec2(-1.0, -1.0) * texel).rgb;
+   color += texture(input_texture, frag_uv + vec2(1.0, -1.0) * texel).rgb;
+   color += texture(input_texture, frag_uv + vec2(-1.0, 1.0) * texel).rgb;
+   color += texture(input_texture, frag_uv + vec2(1.0, 1.0) * texel).rgb;
+   out_color = vec4(color / 16.0, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 330 core
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+uniform sampler3D lut;
+uniform float strength;   // Blend between the input (0) and the graded colors (1)
+
+out vec4 out_color;
+
+void main() {
+   vec3 color = clamp(texture(input_texture, frag_uv).rgb, 0.0, 1.0);
+   // Sample texel centers so 0 and 1 hit the first and last entry of the table
+   float size = float(textureSize(lut, 0).x);
+   vec3 graded = texture(lut, color * ((size - 1.0) / size) + 0.5 / size).rgb;
+   out_color = vec4(mix(color, graded, strength), 1.0);
+}
@@ -0,0 +1,10 @@
+#version 330 core
+
+out vec2 frag_uv;
+
+void main() {
+   // One triangle that covers the screen, made from the vertex index without any buffers
+   vec2 corner = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+   frag_uv = corner;
+   gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
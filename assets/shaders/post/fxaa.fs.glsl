@@ -0,0 +1,50 @@
+#version 330 core
+
+// FXAA in the spirit of Timothy Lottes' console version: blur along the edge direction
+// found from the luma of the four diagonal neighbours. Runs on gamma corrected colors.
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+uniform float span_max;     // Longest blur in pixels
+uniform float reduce_mul;
+uniform float reduce_min;
+
+out vec4 out_color;
+
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+void main() {
+   vec2 texel = 1.0 / vec2(textureSize(input_texture, 0));
+
+   vec3 rgb_nw = texture(input_texture, frag_uv + vec2(-1.0, -1.0) * texel).rgb;
+   vec3 rgb_ne = texture(input_texture, frag_uv + vec2(1.0, -1.0) * texel).rgb;
+   vec3 rgb_sw = texture(input_texture, frag_uv + vec2(-1.0, 1.0) * texel).rgb;
+   vec3 rgb_se = texture(input_texture, frag_uv + vec2(1.0, 1.0) * texel).rgb;
+   vec3 rgb_m = texture(input_texture, frag_uv).rgb;
+
+   float luma_nw = dot(rgb_nw, LUMA);
+   float luma_ne = dot(rgb_ne, LUMA);
+   float luma_sw = dot(rgb_sw, LUMA);
+   float luma_se = dot(rgb_se, LUMA);
+   float luma_m = dot(rgb_m, LUMA);
+   float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+   float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+   vec2 direction = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)),
+      (luma_nw + luma_sw) - (luma_ne + luma_se));
+   float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * reduce_mul, reduce_min);
+   float scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+   direction = clamp(direction * scale, vec2(-span_max), vec2(span_max)) * texel;
+
+   vec3 rgb_a = 0.5 * (
+      texture(input_texture, frag_uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+      texture(input_texture, frag_uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+   vec3 rgb_b = rgb_a * 0.5 + 0.25 * (
+      texture(input_texture, frag_uv + direction * -0.5).rgb +
+      texture(input_texture, frag_uv + direction * 0.5).rgb);
+
+   // The wider blur crossed another edge when it leaves the local luma range
+   float luma_b = dot(rgb_b, LUMA);
+   out_color = vec4((luma_b < luma_min || luma_b > luma_max) ? rgb_a : rgb_b, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 330 core
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+uniform float gamma;
+
+out vec4 out_color;
+
+void main() {
+   vec3 color = texture(input_texture, frag_uv).rgb;
+   out_color = vec4(pow(max(color, vec3(0.0)), vec3(1.0 / gamma)), 1.0);
+}
@@ -0,0 +1,29 @@
+#version 330 core
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+uniform float exposure;
+uniform int tonemapper;   // 0 Reinhard, 1 ACES
+
+out vec4 out_color;
+
+vec3 reinhard(vec3 color) {
+   return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 color) {
+   const float a = 2.51;
+   const float b = 0.03;
+   const float c = 2.43;
+   const float d = 0.59;
+   const float e = 0.14;
+   return clamp((color * (a * color + b)) / (color * (c * color + d) + e), 0.0, 1.0);
+}
+
+void main() {
+   vec3 color = texture(input_texture, frag_uv).rgb * exposure;
+   color = tonemapper == 1 ? aces(color) : reinhard(color);
+   out_color = vec4(color, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 330 core
+
+in vec2 frag_uv;
+
+uniform sampler2D input_texture;
+uniform float strength;   // 0 leaves the image alone, 1 is black in the corners
+uniform float radius;     // Distance from the center where darkening starts, 0.5 is the edge
+uniform float softness;
+
+out vec4 out_color;
+
+void main() {
+   vec3 color = texture(input_texture, frag_uv).rgb;
+   float center_distance = length(frag_uv - 0.5);
+   float shade = smoothstep(radius, radius + softness, center_distance);
+   out_color = vec4(color * (1.0 - shade * strength), 1.0);
+}
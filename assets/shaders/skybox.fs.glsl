@@ -0,0 +1,11 @@
+#version 330 core
+
+in vec3 frag_direction;
+
+uniform samplerCube skybox;
+
+out vec4 out_color;
+
+void main() {
+   out_color = texture(skybox, frag_direction);
+}
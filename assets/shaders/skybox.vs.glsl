@@ -0,0 +1,13 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+
+uniform mat4 rotation_projection;
+
+out vec3 frag_direction;
+
+void main() {
+   frag_direction = position;
+   // z = w puts every fragment on the far plane, behind the rest of the scene
+   gl_Position = (rotation_projection * vec4(position, 1.0)).xyww;
+}
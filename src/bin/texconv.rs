@@ -0,0 +1,148 @@
+// Converts png, tga and bmp images to dds files with a full mipmap chain, replacing the
+// AMDCompress scripts in tools/. Runs without a GL context.
+//
+// nitrust-texconv [options] <input> <output.dds>
+//   -f, --format <bc1|bc2|bc3|bc4|bc5|rgba>   bc1 for opaque images and bc3 otherwise by default
+//   --srgb         Store an sRGB format, needs a DX10 header
+//   --linear       Filter mipmaps without gamma correction, for normal maps and other data
+//   --no-mipmaps   Only store the full size image
+//   --no-flip      Keep the rows in file order
+//
+// Images are flipped vertically by default. Texture flips png, tga and bmp files on upload
+// but uploads dds surfaces as they are, so this gives both the same orientation.
+
+extern crate nitrust_oxide;
+
+use std::env;
+use std::io;
+use std::process;
+
+use nitrust_oxide::framework::graphics::image;
+use nitrust_oxide::framework::graphics::image::bcn;
+use nitrust_oxide::framework::graphics::image::{DdsFormat, DdsImage, RgbaImage};
+
+struct Options {
+	format: Option<DdsFormat>,
+	srgb: bool,
+	linear: bool,
+	mipmaps: bool,
+	flip: bool,
+	input: String,
+	output: String,
+}
+
+const USAGE: &'static str = "usage: nitrust-texconv [-f bc1|bc2|bc3|bc4|bc5|rgba] [--srgb] [--linear] [--no-mipmaps] [--no-flip] <input> <output.dds>";
+
+fn main() {
+	let options = match parse_args(env::args().skip(1).collect()) {
+		Ok(options) => options,
+		Err(message) => {
+			eprintln!("{}\n{}", message, USAGE);
+			process::exit(2);
+		},
+	};
+
+	if let Err(error) = convert(&options) {
+		eprintln!("nitrust-texconv: {}: {}", options.input, error);
+		process::exit(1);
+	}
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+	let mut options = Options {
+		format: None,
+		srgb: false,
+		linear: false,
+		mipmaps: true,
+		flip: true,
+		input: String::new(),
+		output: String::new(),
+	};
+	let mut paths = Vec::new();
+
+	let mut args = args.into_iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"-f" | "--format" => {
+				let name = try!(args.next().ok_or(format!("{} needs a format", arg)));
+				options.format = Some(match name.to_lowercase().as_str() {
+					"bc1" | "dxt1" => DdsFormat::Bc1,
+					"bc2" | "dxt3" => DdsFormat::Bc2,
+					"bc3" | "dxt5" => DdsFormat::Bc3,
+					"bc4" => DdsFormat::Bc4,
+					"bc5" => DdsFormat::Bc5,
+					"rgba" => DdsFormat::Rgba8,
+					_ => return Err(format!("unknown format {}", name)),
+				});
+			},
+			"--srgb" => options.srgb = true,
+			"--linear" => options.linear = true,
+			"--no-mipmaps" => options.mipmaps = false,
+			"--no-flip" => options.flip = false,
+			"-h" | "--help" => return Err(String::from("converts png, tga and bmp images to dds")),
+			_ if arg.starts_with("-") => return Err(format!("unknown option {}", arg)),
+			_ => paths.push(arg),
+		}
+	}
+
+	if options.srgb && (options.format == Some(DdsFormat::Bc4) || options.format == Some(DdsFormat::Bc5)) {
+		return Err(String::from("bc4 and bc5 have no srgb variant"));
+	}
+	if paths.len() != 2 {
+		return Err(String::from("expected an input and an output path"));
+	}
+	options.output = paths.pop().unwrap();
+	options.input = paths.pop().unwrap();
+	Ok(options)
+}
+
+fn convert(options: &Options) -> io::Result<()> {
+	let mut source = try!(image::load(&options.input));
+	if options.flip {
+		source.flip_vertically();
+	}
+
+	let format = options.format.unwrap_or(if source.is_opaque() { DdsFormat::Bc1 } else { DdsFormat::Bc3 });
+	// One and two channel formats hold data, never colors
+	let color = format != DdsFormat::Bc4 && format != DdsFormat::Bc5;
+	let format = if !options.srgb {
+		format
+	} else {
+		match format {
+			DdsFormat::Bc1 => DdsFormat::Bc1Srgb,
+			DdsFormat::Bc2 => DdsFormat::Bc2Srgb,
+			DdsFormat::Bc3 => DdsFormat::Bc3Srgb,
+			_ => DdsFormat::Rgba8Srgb,
+		}
+	};
+
+	let levels = if options.mipmaps {
+		source.mipmaps(color && !options.linear)
+	} else {
+		vec![source]
+	};
+
+	let mut data = Vec::new();
+	for level in &levels {
+		data.extend(try!(encode(format, level)));
+	}
+
+	let dds = DdsImage {
+		width: levels[0].width,
+		height: levels[0].height,
+		depth: 1,
+		mipmap_count: levels.len() as u32,
+		array_size: 1,
+		cubemap: false,
+		format: format,
+		data: data,
+	};
+	dds.save(&options.output)
+}
+
+fn encode(format: DdsFormat, level: &RgbaImage) -> io::Result<Vec<u8>> {
+	match format {
+		DdsFormat::Rgba8 | DdsFormat::Rgba8Srgb => Ok(level.data.clone()),
+		_ => bcn::encode(format, level),
+	}
+}
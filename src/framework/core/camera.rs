@@ -3,6 +3,8 @@ use framework::core::Transform;
 
 pub struct Camera {
 	pub view_projection: Mat4x4,
+	pub view: Mat4x4,
+	pub projection: Mat4x4,
 }
 
 impl Camera {
@@ -12,6 +14,8 @@ impl Camera {
 	       view_projection:
 		      Mat4x4::ortho(0.0, width as f32, 0.0, height as f32, z_near, z_far) * 
 			  Mat4x4::camera(&transform.position, &transform.orientation),
+		   view: Mat4x4::camera(&transform.position, &transform.orientation),
+		   projection: Mat4x4::ortho(0.0, width as f32, 0.0, height as f32, z_near, z_far),
 		}
     }
 
@@ -21,6 +25,30 @@ impl Camera {
 	       view_projection:
 		      Mat4x4::perspective(fovy, width as f32 / height as f32, z_near, z_far) *
 			  Mat4x4::camera(&transform.position, &transform.orientation),
+		   view: Mat4x4::camera(&transform.position, &transform.orientation),
+		   projection: Mat4x4::perspective(fovy, width as f32 / height as f32, z_near, z_far),
 		}
 	}
-}
\ No newline at end of file
+
+	// World position, taken back out of the view matrix
+	pub fn position(&self) -> Vec3 {
+		let v = &self.view.m;
+		Vec3 {
+			x: -(v[0] * v[3] + v[4] * v[7] + v[8] * v[11]),
+			y: -(v[1] * v[3] + v[5] * v[7] + v[9] * v[11]),
+			z: -(v[2] * v[3] + v[6] * v[7] + v[10] * v[11]),
+		}
+	}
+
+	// Projection times the view without its translation, for things at infinity like a skybox
+	pub fn rotation_projection(&self) -> Mat4x4 {
+		let v = &self.view.m;
+		let rotation = Mat4x4 { m: [
+			v[0], v[1], v[2], 0.0,
+			v[4], v[5], v[6], 0.0,
+			v[8], v[9], v[10], 0.0,
+			0.0, 0.0, 0.0, 1.0,
+		]};
+		&self.projection * &rotation
+	}
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+// Resources that were loaded from files and can rebuild themselves in place.
+// A failed reload must leave the resource as it was.
+pub trait HotReload {
+	type Error: fmt::Display;
+
+	fn source_files(&self) -> Vec<String>;
+	fn reload(&mut self) -> Result<(), Self::Error>;
+
+	// None when none of the changed files belong to this resource
+	fn reload_if_changed(&mut self, changed: &[String]) -> Option<Result<(), Self::Error>> {
+		let files = self.source_files();
+		if changed.iter().any(|file| files.contains(file)) {
+			Some(self.reload())
+		} else {
+			None
+		}
+	}
+}
+
+// Polls the modification times of watched files, call poll between frames
+// on the render thread and hand the result to HotReload::reload_if_changed.
+pub struct FileWatcher {
+	files: HashMap<String, Option<SystemTime>>,
+	interval: Duration,
+	last_poll: Instant,
+}
+
+impl FileWatcher {
+	pub fn new() -> FileWatcher {
+		FileWatcher::with_interval(Duration::from_millis(500))
+	}
+
+	pub fn with_interval(interval: Duration) -> FileWatcher {
+		FileWatcher {
+			files: HashMap::new(),
+			interval: interval,
+			last_poll: Instant::now(),
+		}
+	}
+
+	// Watching a file twice is fine
+	pub fn watch(&mut self, file_path: &str) {
+		if !self.files.contains_key(file_path) {
+			let modified = FileWatcher::modified(file_path);
+			self.files.insert(file_path.to_string(), modified);
+		}
+	}
+
+	pub fn watch_all(&mut self, file_paths: &[String]) {
+		for file_path in file_paths {
+			self.watch(file_path);
+		}
+	}
+
+	pub fn unwatch(&mut self, file_path: &str) {
+		self.files.remove(file_path);
+	}
+
+	// Files that changed since the last poll, empty until the interval has passed
+	pub fn poll(&mut self) -> Vec<String> {
+		let mut changed = Vec::new();
+		if self.last_poll.elapsed() < self.interval {
+			return changed;
+		}
+		self.last_poll = Instant::now();
+
+		for (file_path, last_modified) in self.files.iter_mut() {
+			let modified = FileWatcher::modified(file_path);
+			// Editors often delete and recreate files on save, wait until it's back
+			if modified.is_some() && modified != *last_modified {
+				*last_modified = modified;
+				changed.push(file_path.clone());
+			}
+		}
+
+		changed
+	}
+
+	fn modified(file_path: &str) -> Option<SystemTime> {
+		fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok()
+	}
+}
@@ -4,5 +4,8 @@ pub use self::camera::Camera;
 pub mod clock;
 pub use self::clock::Clock;
 
+pub mod hot_reload;
+pub use self::hot_reload::{FileWatcher, HotReload};
+
 pub mod transform;
 pub use self::transform::Transform;
\ No newline at end of file
@@ -0,0 +1,473 @@
+extern crate gl;
+extern crate std;
+
+use gl::types::*;
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+
+// How often the data of a buffer changes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BufferUsage {
+	Static,		// Uploaded once
+	Dynamic,	// Updated now and then
+	Stream,		// Updated every frame
+}
+
+impl BufferUsage {
+	pub fn gl_enum(&self) -> GLenum {
+		match *self {
+			BufferUsage::Static => gl::STATIC_DRAW,
+			BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+			BufferUsage::Stream => gl::STREAM_DRAW,
+		}
+	}
+}
+
+// Shared by vertex and index buffers. The element array binding is part of the
+// vertex array state, so vertex arrays are unbound while touching index buffers
+// and the caller's vertex array is bound again afterwards.
+struct Buffer {
+	id: GLuint,
+	target: GLenum,
+	usage: BufferUsage,
+	size: usize,	// Allocated bytes
+}
+
+impl Buffer {
+	fn new(target: GLenum, usage: BufferUsage, size: usize, data: *const GLvoid) -> Buffer {
+		let mut id = 0;
+		unsafe {
+			let vertex_array = unbind_vertex_array(target);
+			gl::GenBuffers(1, &mut id);
+			gl::BindBuffer(target, id);
+			gl::BufferData(target, size as GLsizeiptr, data, usage.gl_enum());
+			gl::BindBuffer(target, 0);
+			restore_vertex_array(vertex_array);
+		}
+
+		Buffer {
+			id: id,
+			target: target,
+			usage: usage,
+			size: size,
+		}
+	}
+
+	// Grows the buffer when needed. Streaming buffers are orphaned first so the
+	// driver doesn't have to wait until the previous frame is done with them.
+	fn update(&mut self, offset: usize, size: usize, data: *const GLvoid) {
+		unsafe {
+			let vertex_array = unbind_vertex_array(self.target);
+			gl::BindBuffer(self.target, self.id);
+
+			if offset + size > self.size {
+				if offset == 0 {
+					gl::BufferData(self.target, size as GLsizeiptr, data, self.usage.gl_enum());
+					self.size = size;
+					gl::BindBuffer(self.target, 0);
+					restore_vertex_array(vertex_array);
+					return;
+				}
+
+				// Keep the old contents in front of the new data
+				let mut old: Vec<u8> = vec![0; self.size];
+				gl::GetBufferSubData(self.target, 0, self.size as GLsizeiptr, old.as_mut_ptr() as *mut _);
+				gl::BufferData(self.target, (offset + size) as GLsizeiptr, ptr::null(), self.usage.gl_enum());
+				gl::BufferSubData(self.target, 0, old.len().min(offset) as GLsizeiptr, old.as_ptr() as *const _);
+				self.size = offset + size;
+			} else if self.usage == BufferUsage::Stream && offset == 0 {
+				gl::BufferData(self.target, self.size as GLsizeiptr, ptr::null(), self.usage.gl_enum());
+			}
+
+			gl::BufferSubData(self.target, offset as GLintptr, size as GLsizeiptr, data);
+			gl::BindBuffer(self.target, 0);
+			restore_vertex_array(vertex_array);
+		}
+	}
+}
+
+// Unbinds the current vertex array before an index buffer is bound, so its element
+// array binding stays as it was. Returns the vertex array to bind again, if any.
+unsafe fn unbind_vertex_array(target: GLenum) -> Option<GLuint> {
+	if target != gl::ELEMENT_ARRAY_BUFFER {
+		return None;
+	}
+
+	let mut current = 0;
+	gl::GetIntegerv(gl::VERTEX_ARRAY_BINDING, &mut current);
+	if current == 0 {
+		return None;
+	}
+	gl::BindVertexArray(0);
+	Some(current as GLuint)
+}
+
+unsafe fn restore_vertex_array(vertex_array: Option<GLuint>) {
+	if let Some(id) = vertex_array {
+		gl::BindVertexArray(id);
+	}
+}
+
+impl Drop for Buffer {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteBuffers(1, &self.id);
+		}
+	}
+}
+
+// Vertices of type T, usually a #[repr(C)] struct or plain f32s, described by a VertexLayout
+pub struct VertexBuffer<T> {
+	buffer: Buffer,
+	len: usize,
+	phantom: PhantomData<T>,
+}
+
+impl<T: Copy> VertexBuffer<T> {
+	pub fn new(data: &[T], usage: BufferUsage) -> VertexBuffer<T> {
+		VertexBuffer {
+			buffer: Buffer::new(gl::ARRAY_BUFFER, usage, data.len() * mem::size_of::<T>(),
+				data.as_ptr() as *const _),
+			len: data.len(),
+			phantom: PhantomData,
+		}
+	}
+
+	// Room for capacity vertices, filled later with update
+	pub fn with_capacity(capacity: usize, usage: BufferUsage) -> VertexBuffer<T> {
+		VertexBuffer {
+			buffer: Buffer::new(gl::ARRAY_BUFFER, usage, capacity * mem::size_of::<T>(), ptr::null()),
+			len: 0,
+			phantom: PhantomData,
+		}
+	}
+
+	pub fn id(&self) -> GLuint {
+		self.buffer.id
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn usage(&self) -> BufferUsage {
+		self.buffer.usage
+	}
+
+	// Replaces all vertices
+	pub fn update(&mut self, data: &[T]) {
+		self.buffer.update(0, data.len() * mem::size_of::<T>(), data.as_ptr() as *const _);
+		self.len = data.len();
+	}
+
+	// Overwrites vertices starting at index first, the buffer grows if needed
+	pub fn update_range(&mut self, first: usize, data: &[T]) {
+		let size = mem::size_of::<T>();
+		self.buffer.update(first * size, data.len() * size, data.as_ptr() as *const _);
+		self.len = self.len.max(first + data.len());
+	}
+
+	pub fn bind(&self) {
+		unsafe {
+			gl::BindBuffer(gl::ARRAY_BUFFER, self.buffer.id);
+		}
+	}
+
+	pub fn unbind(&self) {
+		unsafe {
+			gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+		}
+	}
+}
+
+// Index types accepted by IndexBuffer
+pub trait Index: Copy {
+	fn gl_type() -> GLenum;
+}
+
+impl Index for u16 {
+	fn gl_type() -> GLenum {
+		gl::UNSIGNED_SHORT
+	}
+}
+
+impl Index for u32 {
+	fn gl_type() -> GLenum {
+		gl::UNSIGNED_INT
+	}
+}
+
+// u16 or u32 indices, u16 halves the memory for meshes below 65536 vertices.
+// The type and count are shared with the vertex arrays it is attached to, so
+// they draw with the current ones after an update.
+pub struct IndexBuffer {
+	buffer: Buffer,
+	format: Rc<Cell<(GLenum, usize)>>,	// Type and count
+}
+
+impl IndexBuffer {
+	pub fn new<I: Index>(data: &[I], usage: BufferUsage) -> IndexBuffer {
+		IndexBuffer {
+			buffer: Buffer::new(gl::ELEMENT_ARRAY_BUFFER, usage, data.len() * mem::size_of::<I>(),
+				data.as_ptr() as *const _),
+			format: Rc::new(Cell::new((I::gl_type(), data.len()))),
+		}
+	}
+
+	pub fn id(&self) -> GLuint {
+		self.buffer.id
+	}
+
+	pub fn len(&self) -> usize {
+		self.format.get().1
+	}
+
+	// GL_UNSIGNED_SHORT or GL_UNSIGNED_INT
+	pub fn gl_type(&self) -> GLenum {
+		self.format.get().0
+	}
+
+	// Replaces all indices, the index type may change
+	pub fn update<I: Index>(&mut self, data: &[I]) {
+		self.buffer.update(0, data.len() * mem::size_of::<I>(), data.as_ptr() as *const _);
+		self.format.set((I::gl_type(), data.len()));
+	}
+}
+
+// Component types of vertex attributes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeType {
+	Float,
+	Byte,
+	UnsignedByte,
+	Short,
+	UnsignedShort,
+	Int,
+	UnsignedInt,
+}
+
+impl AttributeType {
+	pub fn gl_enum(&self) -> GLenum {
+		match *self {
+			AttributeType::Float => gl::FLOAT,
+			AttributeType::Byte => gl::BYTE,
+			AttributeType::UnsignedByte => gl::UNSIGNED_BYTE,
+			AttributeType::Short => gl::SHORT,
+			AttributeType::UnsignedShort => gl::UNSIGNED_SHORT,
+			AttributeType::Int => gl::INT,
+			AttributeType::UnsignedInt => gl::UNSIGNED_INT,
+		}
+	}
+
+	pub fn size(&self) -> usize {
+		match *self {
+			AttributeType::Byte | AttributeType::UnsignedByte => 1,
+			AttributeType::Short | AttributeType::UnsignedShort => 2,
+			AttributeType::Float | AttributeType::Int | AttributeType::UnsignedInt => 4,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+	pub location: GLuint,
+	pub components: GLint,
+	pub ty: AttributeType,
+	// Integers mapped to [0, 1] or [-1, 1], e.g. u8 colors
+	pub normalized: bool,
+	// Read as integers by ivec/uvec inputs instead of converted to float
+	pub integer: bool,
+	pub offset: usize,
+}
+
+// Attributes of one interleaved vertex in the order they are stored:
+// let mut layout = VertexLayout::new();
+// layout.float(0, 3)	// Position
+//     .float(1, 3)		// Color
+//     .float(2, 2);	// Texture Coordinates
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexLayout {
+	attributes: Vec<VertexAttribute>,
+	stride: usize,
+}
+
+impl VertexLayout {
+	pub fn new() -> VertexLayout {
+		VertexLayout {
+			attributes: Vec::new(),
+			stride: 0,
+		}
+	}
+
+	pub fn float(&mut self, location: GLuint, components: GLint) -> &mut VertexLayout {
+		self.attribute(location, components, AttributeType::Float, false, false)
+	}
+
+	pub fn normalized(&mut self, location: GLuint, components: GLint, ty: AttributeType) -> &mut VertexLayout {
+		self.attribute(location, components, ty, true, false)
+	}
+
+	pub fn integer(&mut self, location: GLuint, components: GLint, ty: AttributeType) -> &mut VertexLayout {
+		self.attribute(location, components, ty, false, true)
+	}
+
+	pub fn attribute(&mut self, location: GLuint, components: GLint, ty: AttributeType,
+		normalized: bool, integer: bool) -> &mut VertexLayout {
+		self.attributes.push(VertexAttribute {
+			location: location,
+			components: components,
+			ty: ty,
+			normalized: normalized,
+			integer: integer,
+			offset: self.stride,
+		});
+		self.stride += components as usize * ty.size();
+		self
+	}
+
+	// Unused bytes, e.g. to skip an attribute the shader doesn't read
+	pub fn padding(&mut self, bytes: usize) -> &mut VertexLayout {
+		self.stride += bytes;
+		self
+	}
+
+	pub fn attributes(&self) -> &[VertexAttribute] {
+		&self.attributes
+	}
+
+	// Size of one vertex in bytes
+	pub fn stride(&self) -> usize {
+		self.stride
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primitive {
+	Points,
+	Lines,
+	LineStrip,
+	Triangles,
+	TriangleStrip,
+	TriangleFan,
+}
+
+impl Primitive {
+	pub fn gl_enum(&self) -> GLenum {
+		match *self {
+			Primitive::Points => gl::POINTS,
+			Primitive::Lines => gl::LINES,
+			Primitive::LineStrip => gl::LINE_STRIP,
+			Primitive::Triangles => gl::TRIANGLES,
+			Primitive::TriangleStrip => gl::TRIANGLE_STRIP,
+			Primitive::TriangleFan => gl::TRIANGLE_FAN,
+		}
+	}
+}
+
+// Remembers which buffers feed which attributes. It doesn't own the buffers,
+// keep them alive for as long as the vertex array is drawn.
+pub struct VertexArray {
+	id: GLuint,
+	vertex_count: usize,
+	index: Option<Rc<Cell<(GLenum, usize)>>>,	// Type and count of the index buffer
+}
+
+impl VertexArray {
+	pub fn new() -> VertexArray {
+		let mut id = 0;
+		unsafe {
+			gl::GenVertexArrays(1, &mut id);
+		}
+
+		VertexArray {
+			id: id,
+			vertex_count: 0,
+			index: None,
+		}
+	}
+
+	pub fn id(&self) -> GLuint {
+		self.id
+	}
+
+	// Vertices are counted in strides of the layout, T can be smaller than a vertex
+	// (e.g. f32 for a buffer of interleaved floats).
+	pub fn add_vertex_buffer<T: Copy>(&mut self, buffer: &VertexBuffer<T>, layout: &VertexLayout) {
+		unsafe {
+			gl::BindVertexArray(self.id);
+			buffer.bind();
+
+			for attribute in layout.attributes() {
+				if attribute.integer {
+					gl::VertexAttribIPointer(attribute.location, attribute.components,
+						attribute.ty.gl_enum(), layout.stride() as GLsizei,
+						attribute.offset as *const _);
+				} else {
+					gl::VertexAttribPointer(attribute.location, attribute.components,
+						attribute.ty.gl_enum(),
+						if attribute.normalized { gl::TRUE } else { gl::FALSE },
+						layout.stride() as GLsizei, attribute.offset as *const _);
+				}
+				gl::EnableVertexAttribArray(attribute.location);
+			}
+
+			gl::BindVertexArray(0);
+			buffer.unbind();
+		}
+
+		if layout.stride() > 0 {
+			self.vertex_count = buffer.len() * mem::size_of::<T>() / layout.stride();
+		}
+	}
+
+	pub fn set_index_buffer(&mut self, buffer: &IndexBuffer) {
+		unsafe {
+			gl::BindVertexArray(self.id);
+			gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, buffer.id());
+			gl::BindVertexArray(0);
+		}
+
+		self.index = Some(buffer.format.clone());
+	}
+
+	// Call after the vertex buffers changed size, the index count follows its buffer
+	pub fn set_vertex_count(&mut self, vertex_count: usize) {
+		self.vertex_count = vertex_count;
+	}
+
+	pub fn bind(&self) {
+		unsafe {
+			gl::BindVertexArray(self.id);
+		}
+	}
+
+	pub fn unbind(&self) {
+		unsafe {
+			gl::BindVertexArray(0);
+		}
+	}
+
+	// Draws everything, indexed when there is an index buffer
+	pub fn draw(&self, primitive: Primitive) {
+		unsafe {
+			gl::BindVertexArray(self.id);
+			match self.index.as_ref().map(|format| format.get()) {
+				Some((ty, count)) => gl::DrawElements(primitive.gl_enum(), count as GLsizei, ty, ptr::null()),
+				None => gl::DrawArrays(primitive.gl_enum(), 0, self.vertex_count as GLsizei),
+			}
+			gl::BindVertexArray(0);
+		}
+	}
+}
+
+impl Drop for VertexArray {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteVertexArrays(1, &self.id);
+		}
+	}
+}
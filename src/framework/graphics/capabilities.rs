@@ -0,0 +1,40 @@
+extern crate gl;
+
+use gl::types::*;
+
+use std::ffi::CStr;
+
+// Queries about the current context, call after gl::load_with
+
+// Major and minor version of the context, e.g. (3, 3)
+pub fn gl_version() -> (i32, i32) {
+	let mut major = 0;
+	let mut minor = 0;
+	unsafe {
+		gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+		gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+	}
+
+	(major, minor)
+}
+
+pub fn gl_version_at_least(major: i32, minor: i32) -> bool {
+	gl_version() >= (major, minor)
+}
+
+pub fn has_extension(name: &str) -> bool {
+	unsafe {
+		let mut count = 0;
+		gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+
+		for index in 0..count {
+			let extension = gl::GetStringi(gl::EXTENSIONS, index as GLuint);
+			if !extension.is_null() &&
+				CStr::from_ptr(extension as *const _).to_bytes() == name.as_bytes() {
+				return true;
+			}
+		}
+	}
+
+	false
+}
@@ -0,0 +1,25 @@
+extern crate gl;
+
+use framework::graphics::RgbaImage;
+
+// Reads back the viewport of the framebuffer bound for reading, e.g. the window or a
+// headless context after drawing. Rows come out top to bottom like an image file.
+pub fn capture_framebuffer() -> RgbaImage {
+	let mut viewport = [0; 4];
+	unsafe {
+		gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+	}
+	read_pixels(viewport[0], viewport[1], viewport[2] as u32, viewport[3] as u32)
+}
+
+// Reads a rectangle of the read buffer, x and y are the bottom left corner like in GL
+pub fn read_pixels(x: i32, y: i32, width: u32, height: u32) -> RgbaImage {
+	let mut image = RgbaImage::new(width, height);
+	unsafe {
+		gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+		gl::ReadPixels(x, y, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE,
+			image.data.as_mut_ptr() as *mut _);
+	}
+	image.flip_vertically();
+	image
+}
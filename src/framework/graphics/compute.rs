@@ -0,0 +1,156 @@
+extern crate gl;
+
+use gl::types::*;
+
+use framework::graphics::capabilities;
+use framework::graphics::{InternalShader, ShaderBuilder, ShaderError, ShaderStorageBuffer};
+
+// Compute shaders need OpenGL 4.3 (or GL_ARB_compute_shader), our default context is 3.3,
+// so check ComputeShader::is_supported before relying on them.
+pub struct ComputeShader {
+	shader: InternalShader,
+	local_size: [u32; 3],
+}
+
+impl ComputeShader {
+	pub fn is_supported() -> bool {
+		(capabilities::gl_version_at_least(4, 3) || capabilities::has_extension("GL_ARB_compute_shader")) &&
+			gl::DispatchCompute::is_loaded()
+	}
+
+	pub fn new(file_path: &str) -> Result<ComputeShader, ShaderError> {
+		ComputeShader::from_builder(ShaderBuilder::new().compute(file_path))
+	}
+
+	// For compute shaders that need defines or the program cache
+	pub fn from_builder(builder: &ShaderBuilder) -> Result<ComputeShader, ShaderError> {
+		if !ComputeShader::is_supported() {
+			let (major, minor) = capabilities::gl_version();
+			return Err(ShaderError::Unsupported(
+				format!("compute shaders need OpenGL 4.3, the context is {}.{}", major, minor)));
+		}
+
+		let shader = try!(builder.build());
+
+		let mut local_size: [GLint; 3] = [0; 3];
+		unsafe {
+			gl::GetProgramiv(shader.id(), gl::COMPUTE_WORK_GROUP_SIZE, local_size.as_mut_ptr());
+		}
+
+		Ok(ComputeShader {
+			shader: shader,
+			local_size: [local_size[0] as u32, local_size[1] as u32, local_size[2] as u32],
+		})
+	}
+
+	// Uniforms and storage blocks are set through the program
+	pub fn shader(&self) -> &InternalShader {
+		&self.shader
+	}
+
+	pub fn shader_mut(&mut self) -> &mut InternalShader {
+		&mut self.shader
+	}
+
+	// The layout (local_size_x, ...) declared in the shader
+	pub fn local_size(&self) -> [u32; 3] {
+		self.local_size
+	}
+
+	// Number of work groups needed to cover a number of invocations in each dimension
+	pub fn work_groups(&self, invocations: [u32; 3]) -> [u32; 3] {
+		let mut groups = [1; 3];
+		for i in 0..3 {
+			groups[i] = group_count(invocations[i], self.local_size[i]);
+		}
+		groups
+	}
+
+	pub fn begin(&self) {
+		self.shader.begin();
+	}
+
+	pub fn end(&self) {
+		self.shader.end();
+	}
+
+	// Runs x * y * z work groups, call between begin and end
+	pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+		unsafe {
+			gl::DispatchCompute(x, y, z);
+		}
+	}
+
+	// Reads the group counts (three u32) from a buffer at offset, e.g. written by a culling pass
+	pub fn dispatch_indirect(&self, buffer: &ShaderStorageBuffer, offset: usize) {
+		unsafe {
+			gl::BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, buffer.id());
+			gl::DispatchComputeIndirect(offset as GLintptr);
+			gl::BindBuffer(gl::DISPATCH_INDIRECT_BUFFER, 0);
+		}
+	}
+}
+
+// Work groups of size covering the invocations, at least one. Rounds up without
+// overflowing for counts close to u32::MAX.
+fn group_count(invocations: u32, size: u32) -> u32 {
+	let size = size.max(1);
+	(invocations / size + (invocations % size != 0) as u32).max(1)
+}
+
+// What the results of a dispatch are going to be used for next
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Barrier {
+	ShaderStorage,		// Read by another shader through a storage block
+	VertexAttribArray,	// Used as vertex buffer, e.g. particles
+	ElementArray,		// Used as index buffer
+	Command,			// Used for indirect draws or dispatches
+	Uniform,			// Used as uniform buffer
+	TextureFetch,		// Sampled as texture
+	ShaderImageAccess,	// Read by another shader through an image
+	BufferUpdate,		// Read back or written with glBufferSubData
+	All,
+}
+
+impl Barrier {
+	pub fn gl_bits(&self) -> GLbitfield {
+		match *self {
+			Barrier::ShaderStorage => gl::SHADER_STORAGE_BARRIER_BIT,
+			Barrier::VertexAttribArray => gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT,
+			Barrier::ElementArray => gl::ELEMENT_ARRAY_BARRIER_BIT,
+			Barrier::Command => gl::COMMAND_BARRIER_BIT,
+			Barrier::Uniform => gl::UNIFORM_BARRIER_BIT,
+			Barrier::TextureFetch => gl::TEXTURE_FETCH_BARRIER_BIT,
+			Barrier::ShaderImageAccess => gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+			Barrier::BufferUpdate => gl::BUFFER_UPDATE_BARRIER_BIT,
+			Barrier::All => gl::ALL_BARRIER_BITS,
+		}
+	}
+}
+
+// Makes writes of earlier dispatches visible to the given kinds of access
+pub fn memory_barrier(barriers: &[Barrier]) {
+	let bits = barriers.iter().fold(0, |bits, barrier| bits | barrier.gl_bits());
+	if bits != 0 {
+		unsafe {
+			gl::MemoryBarrier(bits);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn group_counts() {
+		assert_eq!(group_count(0, 64), 1);
+		assert_eq!(group_count(1, 64), 1);
+		assert_eq!(group_count(64, 64), 1);
+		assert_eq!(group_count(65, 64), 2);
+		assert_eq!(group_count(1000, 0), 1000);
+		assert_eq!(group_count(::std::u32::MAX, 64), 67108864);
+		assert_eq!(group_count(::std::u32::MAX - 63, 64), 67108863);
+		assert_eq!(group_count(::std::u32::MAX, 1), ::std::u32::MAX);
+	}
+}
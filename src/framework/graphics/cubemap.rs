@@ -0,0 +1,58 @@
+extern crate gl;
+
+use framework::graphics::image;
+use framework::graphics::{SamplerCache, Texture, TextureDesc, TextureError};
+
+// A cube map texture for skyboxes and environment reflections. Seamless filtering is
+// switched on for the whole context when one is created, so lookups blend over the edges
+// between faces instead of clamping at them.
+pub struct CubemapTexture {
+	texture: Texture,
+}
+
+impl CubemapTexture {
+	// Six square images of the same size in the order +X, -X, +Y, -Y, +Z, -Z
+	pub fn from_files(face_paths: &[&str; 6], desc: &TextureDesc, samplers: &SamplerCache) -> Result<CubemapTexture, TextureError> {
+		let mut faces = Vec::with_capacity(6);
+		for path in face_paths.iter() {
+			faces.push(try!(image::load(path).map_err(|e| TextureError::from_io(path, e))));
+		}
+
+		let mut texture = Texture::new();
+		try!(texture.load_cubemap_images(&faces, desc, samplers).map_err(|e| TextureError::from_io(face_paths[0], e)));
+		CubemapTexture::from_texture(texture, face_paths[0])
+	}
+
+	// A dds file with all six faces, an array of cubemaps isn't accepted
+	pub fn from_dds(file_path: &str, desc: &TextureDesc, samplers: &SamplerCache) -> Result<CubemapTexture, TextureError> {
+		let desc = TextureDesc { fallback: false, ..*desc };
+		let (texture, _) = try!(Texture::from_file(file_path, &desc, samplers));
+		CubemapTexture::from_texture(texture, file_path)
+	}
+
+	fn from_texture(texture: Texture, path: &str) -> Result<CubemapTexture, TextureError> {
+		if texture.target() != gl::TEXTURE_CUBE_MAP {
+			return Err(TextureError::UnsupportedFormat {
+				path: path.to_string(),
+				message: String::from("not a cubemap"),
+			});
+		}
+
+		unsafe {
+			gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+		}
+		Ok(CubemapTexture { texture: texture })
+	}
+
+	pub fn texture(&self) -> &Texture {
+		&self.texture
+	}
+
+	pub fn bind(&self, unit: u32) {
+		self.texture.bind(unit);
+	}
+
+	pub fn unbind(&self, unit: u32) {
+		self.texture.unbind(unit);
+	}
+}
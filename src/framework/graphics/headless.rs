@@ -0,0 +1,207 @@
+extern crate gl;
+extern crate libc;
+extern crate std;
+
+use libc::{c_char, c_void};
+use std::error;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::mem;
+use std::ptr;
+
+// An OpenGL context without a window, rendering into an EGL pbuffer. Mesa's surfaceless
+// platform is used when available, so this runs on llvmpipe in CI without X or Wayland
+// (LIBGL_ALWAYS_SOFTWARE=1 forces the software renderer). Built with the headless feature.
+
+type EGLDisplay = *mut c_void;
+type EGLConfig = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLSurface = *mut c_void;
+type EGLint = i32;
+
+const EGL_NONE: EGLint = 0x3038;
+const EGL_ALPHA_SIZE: EGLint = 0x3021;
+const EGL_BLUE_SIZE: EGLint = 0x3022;
+const EGL_GREEN_SIZE: EGLint = 0x3023;
+const EGL_RED_SIZE: EGLint = 0x3024;
+const EGL_DEPTH_SIZE: EGLint = 0x3025;
+const EGL_STENCIL_SIZE: EGLint = 0x3026;
+const EGL_SURFACE_TYPE: EGLint = 0x3033;
+const EGL_PBUFFER_BIT: EGLint = 0x0001;
+const EGL_RENDERABLE_TYPE: EGLint = 0x3040;
+const EGL_OPENGL_BIT: EGLint = 0x0008;
+const EGL_HEIGHT: EGLint = 0x3056;
+const EGL_WIDTH: EGLint = 0x3057;
+const EGL_EXTENSIONS: EGLint = 0x3055;
+const EGL_OPENGL_API: u32 = 0x30A2;
+const EGL_CONTEXT_MAJOR_VERSION: EGLint = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: EGLint = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: EGLint = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EGLint = 0x0001;
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+
+type GetPlatformDisplay = extern "C" fn(platform: u32, native: *mut c_void, attributes: *const EGLint) -> EGLDisplay;
+
+#[link(name = "EGL")]
+extern "C" {
+	fn eglGetDisplay(native: *mut c_void) -> EGLDisplay;
+	fn eglInitialize(display: EGLDisplay, major: *mut EGLint, minor: *mut EGLint) -> u32;
+	fn eglQueryString(display: EGLDisplay, name: EGLint) -> *const c_char;
+	fn eglBindAPI(api: u32) -> u32;
+	fn eglChooseConfig(display: EGLDisplay, attributes: *const EGLint, configs: *mut EGLConfig,
+		size: EGLint, count: *mut EGLint) -> u32;
+	fn eglCreatePbufferSurface(display: EGLDisplay, config: EGLConfig, attributes: *const EGLint) -> EGLSurface;
+	fn eglCreateContext(display: EGLDisplay, config: EGLConfig, share: EGLContext, attributes: *const EGLint) -> EGLContext;
+	fn eglMakeCurrent(display: EGLDisplay, draw: EGLSurface, read: EGLSurface, context: EGLContext) -> u32;
+	fn eglDestroySurface(display: EGLDisplay, surface: EGLSurface) -> u32;
+	fn eglDestroyContext(display: EGLDisplay, context: EGLContext) -> u32;
+	fn eglGetProcAddress(name: *const c_char) -> *const c_void;
+	fn eglGetError() -> EGLint;
+}
+
+#[derive(Debug)]
+pub enum HeadlessError {
+	NoDisplay,
+	NoConfig,
+	Egl { call: &'static str, error: i32 },
+}
+
+impl fmt::Display for HeadlessError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			HeadlessError::NoDisplay => write!(f, "Could not open an EGL display"),
+			HeadlessError::NoConfig => write!(f, "No EGL config with OpenGL pbuffers and RGBA8, depth and stencil"),
+			HeadlessError::Egl { call, error } => write!(f, "{} failed with EGL error 0x{:x}", call, error),
+		}
+	}
+}
+
+impl error::Error for HeadlessError {}
+
+// The context is current on the creating thread and the GL functions are loaded, so it can be
+// used like the window context. Its default framebuffer is the pbuffer, read it back with
+// capture_framebuffer. Each test thread can have its own.
+pub struct HeadlessContext {
+	display: EGLDisplay,
+	surface: EGLSurface,
+	context: EGLContext,
+	width: u32,
+	height: u32,
+}
+
+impl HeadlessContext {
+	// An OpenGL 3.3 core context like the window asks for
+	pub fn new(width: u32, height: u32) -> Result<HeadlessContext, HeadlessError> {
+		HeadlessContext::with_version(width, height, 3, 3)
+	}
+
+	pub fn with_version(width: u32, height: u32, major: i32, minor: i32) -> Result<HeadlessContext, HeadlessError> {
+		unsafe {
+			let display = open_display();
+			if display.is_null() {
+				return Err(HeadlessError::NoDisplay);
+			}
+			let (mut egl_major, mut egl_minor) = (0, 0);
+			try!(check("eglInitialize", eglInitialize(display, &mut egl_major, &mut egl_minor)));
+			try!(check("eglBindAPI", eglBindAPI(EGL_OPENGL_API)));
+
+			let config_attributes = [
+				EGL_SURFACE_TYPE, EGL_PBUFFER_BIT,
+				EGL_RENDERABLE_TYPE, EGL_OPENGL_BIT,
+				EGL_RED_SIZE, 8, EGL_GREEN_SIZE, 8, EGL_BLUE_SIZE, 8, EGL_ALPHA_SIZE, 8,
+				EGL_DEPTH_SIZE, 24, EGL_STENCIL_SIZE, 8,
+				EGL_NONE];
+			let mut config = ptr::null_mut();
+			let mut count = 0;
+			try!(check("eglChooseConfig", eglChooseConfig(display, config_attributes.as_ptr(), &mut config, 1, &mut count)));
+			if count == 0 {
+				return Err(HeadlessError::NoConfig);
+			}
+
+			let surface_attributes = [EGL_WIDTH, width.max(1) as EGLint, EGL_HEIGHT, height.max(1) as EGLint, EGL_NONE];
+			let surface = eglCreatePbufferSurface(display, config, surface_attributes.as_ptr());
+			if surface.is_null() {
+				return Err(HeadlessError::Egl { call: "eglCreatePbufferSurface", error: eglGetError() });
+			}
+
+			let context_attributes = [
+				EGL_CONTEXT_MAJOR_VERSION, major,
+				EGL_CONTEXT_MINOR_VERSION, minor,
+				EGL_CONTEXT_OPENGL_PROFILE_MASK, EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+				EGL_NONE];
+			let context = eglCreateContext(display, config, ptr::null_mut(), context_attributes.as_ptr());
+			if context.is_null() {
+				let error = eglGetError();
+				eglDestroySurface(display, surface);
+				return Err(HeadlessError::Egl { call: "eglCreateContext", error: error });
+			}
+
+			let headless = HeadlessContext {
+				display: display,
+				surface: surface,
+				context: context,
+				width: width.max(1),
+				height: height.max(1),
+			};
+			try!(headless.make_current());
+
+			gl::load_with(|name| {
+				let name = CString::new(name).unwrap();
+				eglGetProcAddress(name.as_ptr())
+			});
+			gl::Viewport(0, 0, headless.width as i32, headless.height as i32);
+			Ok(headless)
+		}
+	}
+
+	pub fn width(&self) -> u32 {
+		self.width
+	}
+
+	pub fn height(&self) -> u32 {
+		self.height
+	}
+
+	// Makes the context current on the calling thread
+	pub fn make_current(&self) -> Result<(), HeadlessError> {
+		unsafe {
+			check("eglMakeCurrent", eglMakeCurrent(self.display, self.surface, self.surface, self.context))
+		}
+	}
+}
+
+impl Drop for HeadlessContext {
+	fn drop(&mut self) {
+		// The display is shared by every context in the process, so it isn't terminated
+		unsafe {
+			eglMakeCurrent(self.display, ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+			eglDestroyContext(self.display, self.context);
+			eglDestroySurface(self.display, self.surface);
+		}
+	}
+}
+
+// Mesa's surfaceless platform needs no window system, otherwise the default display
+unsafe fn open_display() -> EGLDisplay {
+	let extensions = eglQueryString(ptr::null_mut(), EGL_EXTENSIONS);
+	let surfaceless = !extensions.is_null() &&
+		CStr::from_ptr(extensions).to_string_lossy().split(' ').any(|name| name == "EGL_MESA_platform_surfaceless");
+
+	let get_platform_display = eglGetProcAddress(b"eglGetPlatformDisplayEXT\0".as_ptr() as *const c_char);
+	if surfaceless && !get_platform_display.is_null() {
+		let get_platform_display: GetPlatformDisplay = mem::transmute(get_platform_display);
+		let display = get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null());
+		if !display.is_null() {
+			return display;
+		}
+	}
+	eglGetDisplay(ptr::null_mut())
+}
+
+fn check(call: &'static str, result: u32) -> Result<(), HeadlessError> {
+	if result != 0 {
+		Ok(())
+	} else {
+		Err(HeadlessError::Egl { call: call, error: unsafe { eglGetError() } })
+	}
+}
@@ -0,0 +1,899 @@
+// Software decoders for BC1 to BC5 and BC7 blocks, used when the driver lacks the
+// compression extensions, and encoders for BC1 to BC5 used by the texture converter.
+// Blocks are 4x4 pixels, rows come out in the order they are stored.
+
+use std::io;
+
+use super::{invalid, truncated, RgbaImage};
+use super::dds::DdsFormat;
+
+pub fn can_decode(format: DdsFormat) -> bool {
+	match format {
+		DdsFormat::Bc6hUfloat | DdsFormat::Bc6hSfloat => false,
+		format => format.is_compressed(),
+	}
+}
+
+// Decodes a width by height surface to RGBA8. Single and two channel formats fill the
+// other channels like GL does, signed values are mapped from -1..1 to 0..255.
+pub fn decode(format: DdsFormat, width: u32, height: u32, data: &[u8]) -> io::Result<RgbaImage> {
+	if !can_decode(format) {
+		return Err(invalid("no software decoder for this format"));
+	}
+	if data.len() < format.surface_size(width, height) {
+		return Err(truncated());
+	}
+
+	let block_size = format.surface_size(4, 4);
+	let blocks_wide = (width as usize + 3) / 4;
+	let mut image = RgbaImage::new(width, height);
+	let mut pixels = [[0u8; 4]; 16];
+
+	for (index, block) in data.chunks(block_size).take(format.surface_size(width, height) / block_size).enumerate() {
+		decode_block(format, block, &mut pixels);
+		let (bx, by) = ((index % blocks_wide) as u32 * 4, (index / blocks_wide) as u32 * 4);
+		// Blocks on the right and bottom edges can hang over the surface
+		for (i, pixel) in pixels.iter().enumerate() {
+			let (x, y) = (bx + i as u32 % 4, by + i as u32 / 4);
+			if x < width && y < height {
+				image.set_pixel(x, y, *pixel);
+			}
+		}
+	}
+
+	Ok(image)
+}
+
+// Decodes one block to 16 pixels in row order
+pub fn decode_block(format: DdsFormat, block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+	match format {
+		DdsFormat::Bc1 | DdsFormat::Bc1Srgb => decode_color(block, true, pixels),
+		DdsFormat::Bc2 | DdsFormat::Bc2Srgb => {
+			decode_color(&block[8..], false, pixels);
+			for (i, pixel) in pixels.iter_mut().enumerate() {
+				pixel[3] = ((block[i / 2] >> (i % 2 * 4)) & 0x0f) * 17;
+			}
+		},
+		DdsFormat::Bc3 | DdsFormat::Bc3Srgb => {
+			decode_color(&block[8..], false, pixels);
+			decode_channel(block, false, pixels, 3);
+		},
+		DdsFormat::Bc4 | DdsFormat::Bc4Snorm => {
+			*pixels = [[0, 0, 0, 255]; 16];
+			decode_channel(block, format == DdsFormat::Bc4Snorm, pixels, 0);
+		},
+		DdsFormat::Bc5 | DdsFormat::Bc5Snorm => {
+			*pixels = [[0, 0, 0, 255]; 16];
+			decode_channel(block, format == DdsFormat::Bc5Snorm, pixels, 0);
+			decode_channel(&block[8..], format == DdsFormat::Bc5Snorm, pixels, 1);
+		},
+		DdsFormat::Bc7 | DdsFormat::Bc7Srgb => decode_bc7(block, pixels),
+		_ => *pixels = [[0; 4]; 16],
+	}
+}
+
+pub fn can_encode(format: DdsFormat) -> bool {
+	match format {
+		DdsFormat::Bc1 | DdsFormat::Bc1Srgb | DdsFormat::Bc2 | DdsFormat::Bc2Srgb | DdsFormat::Bc3 | DdsFormat::Bc3Srgb |
+		DdsFormat::Bc4 | DdsFormat::Bc5 => true,
+		_ => false,
+	}
+}
+
+// Compresses an image to blocks in row order. Edge blocks repeat the last row and column.
+pub fn encode(format: DdsFormat, image: &RgbaImage) -> io::Result<Vec<u8>> {
+	if !can_encode(format) {
+		return Err(invalid("no software encoder for this format"));
+	}
+
+	let mut data = Vec::with_capacity(format.surface_size(image.width, image.height));
+	let mut pixels = [[0u8; 4]; 16];
+	for by in 0..(image.height + 3) / 4 {
+		for bx in 0..(image.width + 3) / 4 {
+			for (i, pixel) in pixels.iter_mut().enumerate() {
+				let x = (bx * 4 + i as u32 % 4).min(image.width - 1);
+				let y = (by * 4 + i as u32 / 4).min(image.height - 1);
+				*pixel = image.pixel(x, y);
+			}
+			encode_block(format, &pixels, &mut data);
+		}
+	}
+
+	Ok(data)
+}
+
+// Compresses 16 pixels in row order and appends the block
+pub fn encode_block(format: DdsFormat, pixels: &[[u8; 4]; 16], data: &mut Vec<u8>) {
+	let channel = |channel: usize| {
+		let mut values = [0u8; 16];
+		for (value, pixel) in values.iter_mut().zip(pixels.iter()) {
+			*value = pixel[channel];
+		}
+		values
+	};
+
+	match format {
+		DdsFormat::Bc1 | DdsFormat::Bc1Srgb => data.extend_from_slice(&encode_color(pixels, true)),
+		DdsFormat::Bc2 | DdsFormat::Bc2Srgb => {
+			for pair in pixels.chunks(2) {
+				let (low, high) = (quantize(pair[0][3] as u32, 15), quantize(pair[1][3] as u32, 15));
+				data.push((low | high << 4) as u8);
+			}
+			data.extend_from_slice(&encode_color(pixels, false));
+		},
+		DdsFormat::Bc3 | DdsFormat::Bc3Srgb => {
+			data.extend_from_slice(&encode_channel(&channel(3)));
+			data.extend_from_slice(&encode_color(pixels, false));
+		},
+		DdsFormat::Bc4 => data.extend_from_slice(&encode_channel(&channel(0))),
+		DdsFormat::Bc5 => {
+			data.extend_from_slice(&encode_channel(&channel(0)));
+			data.extend_from_slice(&encode_channel(&channel(1)));
+		},
+		_ => panic!("no software encoder for {:?}", format),
+	}
+}
+
+// Rounds an 8 bit value to max levels
+fn quantize(value: u32, max: u32) -> u32 {
+	(value * max + 127) / 255
+}
+
+fn to_rgb565(color: [f32; 3]) -> u16 {
+	let channel = |value: f32, max: u32| quantize(value.max(0.0).min(255.0).round() as u32, max) as u16;
+	channel(color[0], 31) << 11 | channel(color[1], 63) << 5 | channel(color[2], 31)
+}
+
+fn distance(a: [u8; 4], b: [u8; 4]) -> u32 {
+	(0..3).map(|channel| {
+		let difference = a[channel] as i32 - b[channel] as i32;
+		(difference * difference) as u32
+	}).sum()
+}
+
+// Endpoints along the principal axis of the colors, refined by least squares against
+// the chosen indices. BC1 switches to three colors and transparent black when alpha is used.
+fn encode_color(pixels: &[[u8; 4]; 16], bc1: bool) -> [u8; 8] {
+	let transparent = bc1 && pixels.iter().any(|pixel| pixel[3] < 128);
+	let opaque: Vec<[u8; 4]> = pixels.iter().cloned().filter(|pixel| !transparent || pixel[3] >= 128).collect();
+	if opaque.is_empty() {
+		return [0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff];
+	}
+
+	let count = opaque.len() as f32;
+	let mut mean = [0.0f32; 3];
+	for pixel in &opaque {
+		for channel in 0..3 {
+			mean[channel] += pixel[channel] as f32 / count;
+		}
+	}
+	let mut covariance = [[0.0f32; 3]; 3];
+	for pixel in &opaque {
+		let d = [pixel[0] as f32 - mean[0], pixel[1] as f32 - mean[1], pixel[2] as f32 - mean[2]];
+		for i in 0..3 {
+			for j in 0..3 {
+				covariance[i][j] += d[i] * d[j];
+			}
+		}
+	}
+
+	// Power iteration for the axis of most variance
+	let mut axis = [1.0f32, 1.0, 1.0];
+	for _ in 0..8 {
+		let mut next = [0.0f32; 3];
+		for i in 0..3 {
+			next[i] = covariance[i][0] * axis[0] + covariance[i][1] * axis[1] + covariance[i][2] * axis[2];
+		}
+		let length = (next[0] * next[0] + next[1] * next[1] + next[2] * next[2]).sqrt();
+		if length < 1e-6 {
+			break;
+		}
+		axis = [next[0] / length, next[1] / length, next[2] / length];
+	}
+
+	let project = |pixel: &[u8; 4]| (0..3).map(|c| (pixel[c] as f32 - mean[c]) * axis[c]).sum::<f32>();
+	let (mut low, mut high) = (0.0f32, 0.0f32);
+	for pixel in &opaque {
+		let t = project(pixel);
+		low = low.min(t);
+		high = high.max(t);
+	}
+	let point = |t: f32| [mean[0] + axis[0] * t, mean[1] + axis[1] * t, mean[2] + axis[2] * t];
+
+	let mut best = build_color_block(pixels, to_rgb565(point(high)), to_rgb565(point(low)), transparent);
+	for _ in 0..2 {
+		match refine_endpoints(pixels, &best, transparent) {
+			Some((c0, c1)) => {
+				let candidate = build_color_block(pixels, c0, c1, transparent);
+				if candidate.1 < best.1 {
+					best = candidate;
+				} else {
+					break;
+				}
+			},
+			None => break,
+		}
+	}
+	best.0
+}
+
+// Picks the nearest palette entry for every pixel, returns the block and its error
+fn build_color_block(pixels: &[[u8; 4]; 16], c0: u16, c1: u16, transparent: bool) -> ([u8; 8], u32) {
+	// Four colors need c0 > c1, three colors and transparent black need c0 <= c1
+	let (c0, c1) = if (c0 < c1) != transparent && c0 != c1 { (c1, c0) } else { (c0, c1) };
+	let mut block = [c0 as u8, (c0 >> 8) as u8, c1 as u8, (c1 >> 8) as u8, 0, 0, 0, 0];
+	if !transparent && c0 == c1 {
+		// Equal endpoints decode as three colors in BC1, index 0 is the same color either way
+		let error = pixels.iter().map(|pixel| distance(*pixel, palette_of(&block, false)[0])).sum();
+		return (block, error);
+	}
+
+	let palette = palette_of(&block, true);
+	let mut error = 0;
+	for (i, pixel) in pixels.iter().enumerate() {
+		let index = if transparent && pixel[3] < 128 {
+			3
+		} else {
+			let candidates = if transparent { 3 } else { 4 };
+			let index = (0..candidates).min_by_key(|&index| distance(*pixel, palette[index])).unwrap_or(0);
+			error += distance(*pixel, palette[index]);
+			index
+		};
+		block[4 + i / 4] |= (index as u8) << (i % 4 * 2);
+	}
+	(block, error)
+}
+
+fn palette_of(block: &[u8; 8], bc1: bool) -> [[u8; 4]; 4] {
+	let mut pixels = [[0u8; 4]; 16];
+	let mut indexed = *block;
+	indexed[4] = 0b11100100;	// Pixels 0 to 3 get indices 0 to 3
+	decode_color(&indexed, bc1, &mut pixels);
+	[pixels[0], pixels[1], pixels[2], pixels[3]]
+}
+
+// Least squares endpoints for the indices of a block
+fn refine_endpoints(pixels: &[[u8; 4]; 16], block: &([u8; 8], u32), transparent: bool) -> Option<(u16, u16)> {
+	// Weight of c0 for each index
+	let weights: [f32; 4] = if transparent { [1.0, 0.0, 0.5, 0.0] } else { [1.0, 0.0, 2.0 / 3.0, 1.0 / 3.0] };
+	let (mut aa, mut ab, mut bb) = (0.0f32, 0.0f32, 0.0f32);
+	let (mut ax, mut bx) = ([0.0f32; 3], [0.0f32; 3]);
+
+	for (i, pixel) in pixels.iter().enumerate() {
+		let index = ((block.0[4 + i / 4] >> (i % 4 * 2)) & 3) as usize;
+		if transparent && index == 3 {
+			continue;
+		}
+		let (a, b) = (weights[index], 1.0 - weights[index]);
+		aa += a * a;
+		ab += a * b;
+		bb += b * b;
+		for channel in 0..3 {
+			ax[channel] += a * pixel[channel] as f32;
+			bx[channel] += b * pixel[channel] as f32;
+		}
+	}
+
+	let determinant = aa * bb - ab * ab;
+	if determinant.abs() < 1e-6 {
+		return None;
+	}
+	let mut e0 = [0.0f32; 3];
+	let mut e1 = [0.0f32; 3];
+	for channel in 0..3 {
+		e0[channel] = (ax[channel] * bb - bx[channel] * ab) / determinant;
+		e1[channel] = (bx[channel] * aa - ax[channel] * ab) / determinant;
+	}
+	Some((to_rgb565(e0), to_rgb565(e1)))
+}
+
+// BC3 alpha and BC4 / BC5 channels. Tries eight interpolated values, and six values plus
+// exact 0 and 255 when the block has those.
+fn encode_channel(values: &[u8; 16]) -> [u8; 8] {
+	let min = *values.iter().min().unwrap_or(&0);
+	let max = *values.iter().max().unwrap_or(&0);
+	let mut best = build_channel_block(values, max, min);
+
+	let inner = values.iter().cloned().filter(|&value| value != 0 && value != 255);
+	let (inner_min, inner_max) = inner.fold((255u8, 0u8), |(low, high), value| (low.min(value), high.max(value)));
+	if inner_min <= inner_max && (min == 0 || max == 255) {
+		let candidate = build_channel_block(values, inner_min, inner_max);
+		if candidate.1 < best.1 {
+			best = candidate;
+		}
+	}
+	best.0
+}
+
+fn build_channel_block(values: &[u8; 16], a0: u8, a1: u8) -> ([u8; 8], u32) {
+	let mut block = [a0, a1, 0, 0, 0, 0, 0, 0];
+	let mut palette = [[0u8; 4]; 16];
+	// Decode indices 0 to 7 to learn the eight values
+	block[2] = 0b10001000;
+	block[3] = 0b11000110;
+	block[4] = 0b11111010;
+	decode_channel(&block, false, &mut palette, 0);
+
+	let mut bits = 0u64;
+	let mut error = 0;
+	for (i, &value) in values.iter().enumerate() {
+		let index = (0..8).min_by_key(|&index| (palette[index][0] as i32 - value as i32).abs()).unwrap_or(0);
+		let difference = palette[index][0] as i32 - value as i32;
+		error += (difference * difference) as u32;
+		bits |= (index as u64) << (i * 3);
+	}
+	for i in 0..6 {
+		block[2 + i] = (bits >> (i * 8)) as u8;
+	}
+	(block, error)
+}
+
+fn rgb565(color: u16) -> [u32; 3] {
+	let (r, g, b) = ((color >> 11) as u32, ((color >> 5) & 0x3f) as u32, (color & 0x1f) as u32);
+	[r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+// Color part of BC1 to BC3. Only BC1 has the three color mode with transparent black.
+fn decode_color(block: &[u8], bc1: bool, pixels: &mut [[u8; 4]; 16]) {
+	let c0 = block[0] as u16 | (block[1] as u16) << 8;
+	let c1 = block[2] as u16 | (block[3] as u16) << 8;
+	let (a, b) = (rgb565(c0), rgb565(c1));
+
+	let mut palette = [[0u8; 4]; 4];
+	for channel in 0..3 {
+		palette[0][channel] = a[channel] as u8;
+		palette[1][channel] = b[channel] as u8;
+		if c0 > c1 || !bc1 {
+			palette[2][channel] = ((2 * a[channel] + b[channel]) / 3) as u8;
+			palette[3][channel] = ((a[channel] + 2 * b[channel]) / 3) as u8;
+		} else {
+			palette[2][channel] = ((a[channel] + b[channel]) / 2) as u8;
+		}
+	}
+	palette[0][3] = 255;
+	palette[1][3] = 255;
+	palette[2][3] = 255;
+	palette[3][3] = if c0 > c1 || !bc1 { 255 } else { 0 };
+
+	for (i, pixel) in pixels.iter_mut().enumerate() {
+		let index = (block[4 + i / 4] >> (i % 4 * 2)) & 3;
+		*pixel = palette[index as usize];
+	}
+}
+
+// BC3 alpha and BC4 / BC5 channels, two endpoints and 3 bit indices
+fn decode_channel(block: &[u8], signed: bool, pixels: &mut [[u8; 4]; 16], channel: usize) {
+	// Signed endpoints are interpolated as is, -128 is the same as -127
+	let endpoint = |byte: u8| if signed { (byte as i8).max(-127) as i32 } else { byte as i32 };
+	let (a, b) = (endpoint(block[0]), endpoint(block[1]));
+	let (min, max) = if signed { (-127, 127) } else { (0, 255) };
+
+	let mut values = [a, b, 0, 0, 0, 0, 0, 0];
+	if a > b {
+		for i in 1..7 {
+			values[i + 1] = divide_rounded((7 - i as i32) * a + i as i32 * b, 7);
+		}
+	} else {
+		for i in 1..5 {
+			values[i + 1] = divide_rounded((5 - i as i32) * a + i as i32 * b, 5);
+		}
+		values[6] = min;
+		values[7] = max;
+	}
+
+	let bits = (0..6).fold(0u64, |bits, i| bits | (block[2 + i] as u64) << (i * 8));
+	for (i, pixel) in pixels.iter_mut().enumerate() {
+		let value = values[((bits >> (i * 3)) & 7) as usize];
+		pixel[channel] = if signed { ((value + 127) * 255 / 254) as u8 } else { value as u8 };
+	}
+}
+
+fn divide_rounded(value: i32, divisor: i32) -> i32 {
+	if value < 0 { -((-value + divisor / 2) / divisor) } else { (value + divisor / 2) / divisor }
+}
+
+// Subset of each pixel for the 64 two subset partitions, one bit per pixel
+const PARTITIONS_2: [u16; 64] = [
+	0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+	0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+	0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+	0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+	0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+	0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+	0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+	0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22];
+
+// Subset of each pixel for the 64 three subset partitions, two bits per pixel
+const PARTITIONS_3: [u32; 64] = [
+	0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+	0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+	0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+	0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+	0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+	0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+	0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+	0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254];
+
+// Second subset anchor of the two subset partitions
+const ANCHORS_2: [u8; 64] = [
+	15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+	15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+	15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+	6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15];
+
+// Second and third subset anchors of the three subset partitions
+const ANCHORS_3: [[u8; 2]; 64] = [
+	[3, 15], [3, 8], [15, 8], [15, 3], [8, 15], [3, 15], [15, 3], [15, 8],
+	[8, 15], [8, 15], [6, 15], [6, 15], [6, 15], [5, 15], [3, 15], [3, 8],
+	[3, 15], [3, 8], [8, 15], [15, 3], [3, 15], [3, 8], [6, 15], [10, 8],
+	[5, 3], [8, 15], [8, 6], [6, 10], [8, 15], [5, 15], [15, 10], [15, 8],
+	[8, 15], [15, 3], [3, 15], [5, 10], [6, 10], [10, 8], [8, 9], [15, 10],
+	[15, 6], [3, 15], [15, 8], [5, 15], [15, 3], [15, 6], [15, 6], [15, 8],
+	[3, 15], [15, 3], [5, 15], [5, 15], [5, 15], [8, 15], [5, 15], [10, 15],
+	[5, 15], [10, 15], [8, 15], [13, 15], [15, 3], [12, 15], [3, 15], [3, 8]];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct Bc7Mode {
+	subsets: usize,
+	partition_bits: u32,
+	rotation_bits: u32,
+	index_selection_bits: u32,
+	color_bits: u32,
+	alpha_bits: u32,
+	endpoint_pbits: bool,	// One p-bit per endpoint
+	shared_pbits: bool,		// One p-bit per subset
+	index_bits: u32,
+	index_bits_2: u32,		// Second index set for alpha, 0 when there is none
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+	Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0,
+		endpoint_pbits: true, shared_pbits: false, index_bits: 3, index_bits_2: 0 },
+	Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0,
+		endpoint_pbits: false, shared_pbits: true, index_bits: 3, index_bits_2: 0 },
+	Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0,
+		endpoint_pbits: false, shared_pbits: false, index_bits: 2, index_bits_2: 0 },
+	Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0,
+		endpoint_pbits: true, shared_pbits: false, index_bits: 2, index_bits_2: 0 },
+	Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6,
+		endpoint_pbits: false, shared_pbits: false, index_bits: 2, index_bits_2: 3 },
+	Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8,
+		endpoint_pbits: false, shared_pbits: false, index_bits: 2, index_bits_2: 2 },
+	Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7,
+		endpoint_pbits: true, shared_pbits: false, index_bits: 4, index_bits_2: 0 },
+	Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5,
+		endpoint_pbits: true, shared_pbits: false, index_bits: 2, index_bits_2: 0 },
+];
+
+// Reads a 128 bit block from the least significant bit up
+struct BitReader {
+	bits: u128,
+}
+
+impl BitReader {
+	fn read(&mut self, count: u32) -> u32 {
+		let value = (self.bits & ((1u128 << count) - 1)) as u32;
+		self.bits >>= count;
+		value
+	}
+}
+
+fn weights(bits: u32) -> &'static [u32] {
+	match bits {
+		2 => &WEIGHTS_2,
+		3 => &WEIGHTS_3,
+		_ => &WEIGHTS_4,
+	}
+}
+
+fn decode_bc7(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+	let mut reader = BitReader { bits: (0..16).fold(0u128, |bits, i| bits | (block[i] as u128) << (i * 8)) };
+
+	// The mode is the number of zero bits before the first one
+	let mode = match (0..8).find(|&bit| block[0] & (1 << bit) != 0) {
+		Some(mode) => mode,
+		None => {
+			// Reserved mode, decoders must output transparent black
+			*pixels = [[0; 4]; 16];
+			return;
+		},
+	};
+	reader.read(mode as u32 + 1);
+	let m = &BC7_MODES[mode];
+
+	let partition = reader.read(m.partition_bits) as usize;
+	let rotation = reader.read(m.rotation_bits);
+	let index_selection = reader.read(m.index_selection_bits);
+
+	// Endpoints are stored channel by channel, two per subset
+	let endpoint_count = m.subsets * 2;
+	let mut endpoints = [[0u32; 4]; 6];
+	for channel in 0..4 {
+		let bits = if channel == 3 { m.alpha_bits } else { m.color_bits };
+		for endpoint in endpoints.iter_mut().take(endpoint_count) {
+			endpoint[channel] = reader.read(bits);
+		}
+	}
+
+	// P-bits add a shared lowest bit to every channel of an endpoint
+	let mut pbits = [0u32; 6];
+	if m.endpoint_pbits {
+		for pbit in pbits.iter_mut().take(endpoint_count) {
+			*pbit = reader.read(1);
+		}
+	} else if m.shared_pbits {
+		for subset in 0..m.subsets {
+			let pbit = reader.read(1);
+			pbits[subset * 2] = pbit;
+			pbits[subset * 2 + 1] = pbit;
+		}
+	}
+	let has_pbits = m.endpoint_pbits || m.shared_pbits;
+
+	for (endpoint, &pbit) in endpoints.iter_mut().zip(pbits.iter()).take(endpoint_count) {
+		for channel in 0..4 {
+			let mut bits = if channel == 3 { m.alpha_bits } else { m.color_bits };
+			if bits == 0 {
+				endpoint[channel] = 255;
+				continue;
+			}
+			let mut value = endpoint[channel];
+			if has_pbits {
+				value = value << 1 | pbit;
+				bits += 1;
+			}
+			// Replicate the high bits into the low ones
+			endpoint[channel] = (value << (8 - bits)) | (value >> (2 * bits - 8));
+		}
+	}
+
+	let subset_of = |pixel: usize| -> usize {
+		match m.subsets {
+			2 => ((PARTITIONS_2[partition] >> pixel) & 1) as usize,
+			3 => ((PARTITIONS_3[partition] >> (pixel * 2)) & 3) as usize,
+			_ => 0,
+		}
+	};
+	// Anchor pixels store their index with one bit less, the top bit is always zero
+	let is_anchor = |pixel: usize| -> bool {
+		pixel == 0 || match m.subsets {
+			2 => pixel == ANCHORS_2[partition] as usize,
+			3 => pixel == ANCHORS_3[partition][0] as usize || pixel == ANCHORS_3[partition][1] as usize,
+			_ => false,
+		}
+	};
+
+	let mut indices = [0u32; 16];
+	for (pixel, index) in indices.iter_mut().enumerate() {
+		*index = reader.read(if is_anchor(pixel) { m.index_bits - 1 } else { m.index_bits });
+	}
+	let mut indices_2 = [0u32; 16];
+	if m.index_bits_2 != 0 {
+		for (pixel, index) in indices_2.iter_mut().enumerate() {
+			*index = reader.read(if pixel == 0 { m.index_bits_2 - 1 } else { m.index_bits_2 });
+		}
+	}
+
+	for (pixel, rgba) in pixels.iter_mut().enumerate() {
+		let subset = subset_of(pixel);
+		let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+		let interpolate = |channel: usize, weight: u32| (((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6) as u8;
+
+		// Modes 4 and 5 have separate indices for color and alpha, mode 4 can swap them
+		let (color_weight, alpha_weight) = if m.index_bits_2 == 0 {
+			let weight = weights(m.index_bits)[indices[pixel] as usize];
+			(weight, weight)
+		} else if index_selection == 0 {
+			(weights(m.index_bits)[indices[pixel] as usize], weights(m.index_bits_2)[indices_2[pixel] as usize])
+		} else {
+			(weights(m.index_bits_2)[indices_2[pixel] as usize], weights(m.index_bits)[indices[pixel] as usize])
+		};
+
+		*rgba = [interpolate(0, color_weight), interpolate(1, color_weight), interpolate(2, color_weight),
+			interpolate(3, alpha_weight)];
+		match rotation {
+			1 => rgba.swap(0, 3),
+			2 => rgba.swap(1, 3),
+			3 => rgba.swap(2, 3),
+			_ => {},
+		}
+	}
+}
+
+// Reference blocks with their decoded texels. The BC7 texels match Mesa's decoder, the
+// others can be worked out by hand from the endpoints with truncating interpolation.
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use framework::graphics::image;
+
+	fn round_trip(format: DdsFormat, image: &RgbaImage) -> RgbaImage {
+		let data = encode(format, image).unwrap();
+		assert_eq!(data.len(), format.surface_size(image.width, image.height));
+		decode(format, image.width, image.height, &data).unwrap()
+	}
+
+	// Peak signal to noise ratio over the given channels in dB
+	fn psnr(a: &RgbaImage, b: &RgbaImage, channels: &[usize]) -> f64 {
+		let mut squared = 0.0;
+		for (p, q) in a.data.chunks(4).zip(b.data.chunks(4)) {
+			for &channel in channels {
+				let difference = p[channel] as f64 - q[channel] as f64;
+				squared += difference * difference;
+			}
+		}
+		let mean = squared / (a.data.len() / 4 * channels.len()) as f64;
+		10.0 * (255.0 * 255.0 / mean).log10()
+	}
+
+	fn max_error(a: &RgbaImage, b: &RgbaImage, channel: usize) -> i32 {
+		a.data.chunks(4).zip(b.data.chunks(4))
+			.map(|(p, q)| (p[channel] as i32 - q[channel] as i32).abs())
+			.max().unwrap_or(0)
+	}
+
+	// Smooth gradients in every channel, 19x11 so the edge blocks are partial
+	fn gradient() -> RgbaImage {
+		let mut image = RgbaImage::new(19, 11);
+		for y in 0..11 {
+			for x in 0..19 {
+				image.set_pixel(x, y, [x as u8 * 13, y as u8 * 23, (x + y) as u8 * 8, 255 - x as u8 * 12]);
+			}
+		}
+		image
+	}
+
+	fn check(format: DdsFormat, block: &[u8], expected: [[u8; 4]; 16]) {
+		let mut pixels = [[0u8; 4]; 16];
+		decode_block(format, block, &mut pixels);
+		for i in 0..16 {
+			assert_eq!(pixels[i], expected[i], "texel {} of a {:?} block", i, format);
+		}
+	}
+
+	#[test]
+	fn bc1_four_colors() {
+		let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+		check(DdsFormat::Bc1, &block, [
+			[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255],
+			[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255],
+			[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255],
+			[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255],
+		]);
+	}
+
+	#[test]
+	fn bc1_three_colors() {
+		let block = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0x1b, 0x4e, 0xb1];
+		check(DdsFormat::Bc1, &block, [
+			[0, 0, 255, 255], [255, 0, 0, 255], [127, 0, 127, 255], [0, 0, 0, 0],
+			[0, 0, 0, 0], [127, 0, 127, 255], [255, 0, 0, 255], [0, 0, 255, 255],
+			[127, 0, 127, 255], [0, 0, 0, 0], [0, 0, 255, 255], [255, 0, 0, 255],
+			[255, 0, 0, 255], [0, 0, 255, 255], [0, 0, 0, 0], [127, 0, 127, 255],
+		]);
+	}
+
+	#[test]
+	fn bc2() {
+		let block = [0x10, 0x32, 0x54, 0x76, 0x98, 0xba, 0xdc, 0xfe, 0xe0, 0x07, 0x1f, 0x00, 0x1b, 0x1b, 0xe4, 0xe4];
+		check(DdsFormat::Bc2, &block, [
+			[0, 85, 170, 0], [0, 170, 85, 17], [0, 0, 255, 34], [0, 255, 0, 51],
+			[0, 85, 170, 68], [0, 170, 85, 85], [0, 0, 255, 102], [0, 255, 0, 119],
+			[0, 255, 0, 136], [0, 0, 255, 153], [0, 170, 85, 170], [0, 85, 170, 187],
+			[0, 255, 0, 204], [0, 0, 255, 221], [0, 170, 85, 238], [0, 85, 170, 255],
+		]);
+	}
+
+	#[test]
+	fn bc3_eight_alphas() {
+		let block = [0xff, 0x00, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa, 0xff, 0xff, 0x00, 0x00, 0x00, 0x55, 0xaa, 0xff];
+		check(DdsFormat::Bc3, &block, [
+			[255, 255, 255, 255], [255, 255, 255, 0], [255, 255, 255, 219], [255, 255, 255, 182],
+			[0, 0, 0, 146], [0, 0, 0, 109], [0, 0, 0, 73], [0, 0, 0, 36],
+			[170, 170, 170, 255], [170, 170, 170, 0], [170, 170, 170, 219], [170, 170, 170, 182],
+			[85, 85, 85, 146], [85, 85, 85, 109], [85, 85, 85, 73], [85, 85, 85, 36],
+		]);
+	}
+
+	#[test]
+	fn bc3_six_alphas() {
+		let block = [0x20, 0xe0, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa, 0x00, 0xf8, 0xe0, 0x07, 0xe4, 0xe4, 0xe4, 0xe4];
+		check(DdsFormat::Bc3, &block, [
+			[255, 0, 0, 32], [0, 255, 0, 224], [170, 85, 0, 70], [85, 170, 0, 109],
+			[255, 0, 0, 147], [0, 255, 0, 186], [170, 85, 0, 0], [85, 170, 0, 255],
+			[255, 0, 0, 32], [0, 255, 0, 224], [170, 85, 0, 70], [85, 170, 0, 109],
+			[255, 0, 0, 147], [0, 255, 0, 186], [170, 85, 0, 0], [85, 170, 0, 255],
+		]);
+	}
+
+	#[test]
+	fn bc4() {
+		let block = [0xf0, 0x10, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa];
+		check(DdsFormat::Bc4, &block, [
+			[240, 0, 0, 255], [16, 0, 0, 255], [208, 0, 0, 255], [176, 0, 0, 255],
+			[144, 0, 0, 255], [112, 0, 0, 255], [80, 0, 0, 255], [48, 0, 0, 255],
+			[240, 0, 0, 255], [16, 0, 0, 255], [208, 0, 0, 255], [176, 0, 0, 255],
+			[144, 0, 0, 255], [112, 0, 0, 255], [80, 0, 0, 255], [48, 0, 0, 255],
+		]);
+	}
+
+	#[test]
+	fn bc4_snorm() {
+		let block = [0x80, 0x7f, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa];
+		check(DdsFormat::Bc4Snorm, &block, [
+			[0, 0, 0, 255], [255, 0, 0, 255], [51, 0, 0, 255], [102, 0, 0, 255],
+			[152, 0, 0, 255], [203, 0, 0, 255], [0, 0, 0, 255], [255, 0, 0, 255],
+			[0, 0, 0, 255], [255, 0, 0, 255], [51, 0, 0, 255], [102, 0, 0, 255],
+			[152, 0, 0, 255], [203, 0, 0, 255], [0, 0, 0, 255], [255, 0, 0, 255],
+		]);
+	}
+
+	#[test]
+	fn bc5() {
+		let block = [0x00, 0xff, 0x88, 0xc6, 0xfa, 0x88, 0xc6, 0xfa, 0xc0, 0x40, 0x1f, 0x2e, 0x3d, 0x4c, 0x5b, 0x6a];
+		check(DdsFormat::Bc5, &block, [
+			[0, 82, 0, 255], [255, 155, 0, 255], [51, 192, 0, 255], [102, 82, 0, 255],
+			[153, 174, 0, 255], [204, 174, 0, 255], [0, 82, 0, 255], [255, 64, 0, 255],
+			[0, 137, 0, 255], [255, 64, 0, 255], [51, 119, 0, 255], [102, 119, 0, 255],
+			[153, 119, 0, 255], [204, 137, 0, 255], [0, 174, 0, 255], [255, 155, 0, 255],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode0_three_subsets() {
+		let block = [0x51, 0xe0, 0x7b, 0x01, 0xe6, 0xf9, 0xba, 0xfc, 0xa8, 0xb5, 0x1a, 0xf9, 0x6d, 0x47, 0x0c, 0x43];
+		check(DdsFormat::Bc7, &block, [
+			[24, 25, 149, 255], [41, 8, 90, 255], [35, 14, 110, 255], [0, 49, 231, 255],
+			[0, 49, 231, 255], [6, 43, 211, 255], [6, 43, 211, 255], [6, 43, 211, 255],
+			[249, 247, 115, 255], [231, 222, 90, 255], [255, 255, 123, 255], [238, 231, 99, 255],
+			[189, 123, 222, 255], [109, 161, 198, 255], [189, 123, 222, 255], [162, 136, 214, 255],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode1_two_subsets() {
+		let block = [0xbe, 0xe8, 0xfd, 0x48, 0x1c, 0x55, 0xb4, 0x93, 0x1b, 0x66, 0x1e, 0x7b, 0xc6, 0xe5, 0xeb, 0xfe];
+		check(DdsFormat::Bc7, &block, [
+			[186, 99, 122, 255], [169, 108, 91, 255], [213, 85, 170, 255], [213, 85, 170, 255],
+			[186, 99, 122, 255], [72, 160, 107, 255], [62, 22, 135, 255], [213, 85, 170, 255],
+			[204, 89, 154, 255], [69, 115, 116, 255], [74, 183, 102, 255], [204, 89, 154, 255],
+			[213, 85, 170, 255], [204, 89, 154, 255], [221, 80, 185, 255], [221, 80, 185, 255],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode2() {
+		let block = [0x34, 0xb9, 0x89, 0xb2, 0x4d, 0xb8, 0xb3, 0xf0, 0x85, 0x68, 0xef, 0x29, 0xaa, 0xaa, 0x7e, 0xb3];
+		check(DdsFormat::Bc7, &block, [
+			[171, 162, 92, 255], [145, 78, 247, 255], [140, 109, 98, 255], [109, 192, 155, 255],
+			[140, 109, 98, 255], [109, 192, 155, 255], [145, 78, 247, 255], [140, 109, 98, 255],
+			[143, 144, 247, 255], [123, 179, 133, 255], [49, 222, 214, 255], [143, 144, 247, 255],
+			[49, 222, 214, 255], [140, 206, 247, 255], [156, 41, 66, 255], [109, 192, 155, 255],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode3() {
+		let block = [0x08, 0xce, 0x98, 0x5f, 0x13, 0x5e, 0x28, 0xba, 0x25, 0xd2, 0x1e, 0xff, 0xc7, 0x7f, 0xcc, 0x7f];
+		check(DdsFormat::Bc7, &block, [
+			[119, 206, 82, 255], [191, 69, 61, 255], [137, 168, 148, 255], [77, 111, 253, 255],
+			[153, 133, 211, 255], [77, 111, 253, 255], [153, 133, 211, 255], [191, 69, 61, 255],
+			[137, 168, 148, 255], [154, 83, 124, 255], [137, 168, 148, 255], [77, 111, 253, 255],
+			[153, 133, 211, 255], [77, 111, 253, 255], [153, 133, 211, 255], [191, 69, 61, 255],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode4_rotation() {
+		let block = [0x30, 0xb4, 0xf7, 0x82, 0x7b, 0xab, 0x57, 0x30, 0x30, 0x5f, 0xe8, 0x10, 0xf6, 0x93, 0x42, 0xa5];
+		check(DdsFormat::Bc7, &block, [
+			[182, 174, 211, 189], [220, 106, 226, 215], [204, 106, 226, 215], [182, 239, 198, 165],
+			[189, 239, 198, 165], [213, 106, 226, 215], [220, 174, 211, 189], [235, 239, 198, 165],
+			[204, 239, 198, 165], [197, 106, 226, 215], [197, 174, 211, 189], [189, 106, 226, 215],
+			[213, 41, 239, 239], [197, 41, 239, 239], [189, 106, 226, 215], [220, 239, 198, 165],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode4_index_selection() {
+		let block = [0x90, 0x0b, 0x23, 0x7a, 0xae, 0x7d, 0xc9, 0x35, 0x50, 0x98, 0x86, 0xdd, 0xb4, 0x77, 0x8b, 0x8f];
+		check(DdsFormat::Bc7, &block, [
+			[136, 108, 113, 219], [90, 66, 57, 178], [183, 151, 170, 134], [183, 151, 170, 93],
+			[168, 137, 152, 134], [105, 80, 76, 134], [168, 137, 152, 178], [168, 137, 152, 219],
+			[198, 165, 189, 219], [183, 151, 170, 134], [168, 137, 152, 134], [168, 137, 152, 219],
+			[90, 66, 57, 219], [198, 165, 189, 93], [136, 108, 113, 219], [152, 123, 133, 178],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode5_rotation() {
+		let block = [0x60, 0x0d, 0x0e, 0x7c, 0x10, 0x9f, 0x71, 0xb7, 0x85, 0xaf, 0xc7, 0x3e, 0xdf, 0x1c, 0xb8, 0x49];
+		check(DdsFormat::Bc7, &block, [
+			[184, 153, 186, 36], [109, 225, 227, 26], [184, 225, 227, 26], [109, 6, 102, 56],
+			[220, 6, 102, 56], [109, 153, 186, 36], [184, 153, 186, 36], [220, 6, 102, 56],
+			[220, 6, 102, 56], [145, 225, 227, 26], [109, 78, 143, 46], [145, 153, 186, 36],
+			[184, 6, 102, 56], [145, 6, 102, 56], [220, 153, 186, 36], [184, 78, 143, 46],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode6() {
+		let block = [0xc0, 0x60, 0x3e, 0x7d, 0x3d, 0x97, 0x3d, 0x1c, 0x3f, 0xcc, 0xbe, 0x03, 0x76, 0xb2, 0xaa, 0x10];
+		check(DdsFormat::Bc7, &block, [
+			[183, 194, 205, 59], [153, 203, 205, 59], [220, 182, 204, 58], [220, 182, 204, 58],
+			[236, 177, 203, 57], [213, 184, 204, 58], [153, 203, 205, 59], [130, 210, 206, 60],
+			[176, 196, 205, 59], [183, 194, 205, 59], [146, 205, 206, 60], [213, 184, 204, 58],
+			[206, 186, 204, 58], [206, 186, 204, 58], [130, 210, 206, 60], [137, 208, 206, 60],
+		]);
+	}
+
+	#[test]
+	fn bc7_mode7() {
+		let block = [0x80, 0x2d, 0xd4, 0x36, 0x8e, 0xe8, 0xbc, 0x43, 0x71, 0x32, 0x0f, 0x70, 0x56, 0xc6, 0x53, 0xeb];
+		check(DdsFormat::Bc7, &block, [
+			[159, 64, 100, 148], [186, 102, 82, 196], [154, 203, 151, 134], [178, 113, 138, 0],
+			[142, 247, 158, 199], [178, 113, 138, 0], [186, 102, 82, 196], [211, 138, 65, 243],
+			[166, 157, 145, 65], [154, 203, 151, 134], [186, 102, 82, 196], [186, 102, 82, 196],
+			[159, 64, 100, 148], [159, 64, 100, 148], [142, 247, 158, 199], [166, 157, 145, 65],
+		]);
+	}
+
+	#[test]
+	fn encode_bc1() {
+		// foo.png comes out at about 33.8 dB over RGB, 35 dB counting the exact alpha
+		let foo = image::load("./assets/textures/foo.png").unwrap();
+		let decoded = round_trip(DdsFormat::Bc1, &foo);
+		assert!(psnr(&foo, &decoded, &[0, 1, 2]) > 33.0, "{} dB", psnr(&foo, &decoded, &[0, 1, 2]));
+		assert!(decoded.is_opaque());
+
+		// One bit alpha, transparent texels are black
+		let source = gradient();
+		let decoded = round_trip(DdsFormat::Bc1, &source);
+		for (p, q) in source.data.chunks(4).zip(decoded.data.chunks(4)) {
+			if p[3] < 128 {
+				assert_eq!(q, &[0, 0, 0, 0][..]);
+			} else {
+				assert_eq!(q[3], 255);
+			}
+		}
+	}
+
+	#[test]
+	fn encode_bc2_bc3() {
+		let source = gradient();
+		// Four bit alpha is off by at most half a step
+		let decoded = round_trip(DdsFormat::Bc2, &source);
+		assert!(max_error(&source, &decoded, 3) <= 8, "BC2 alpha off by {}", max_error(&source, &decoded, 3));
+		let decoded = round_trip(DdsFormat::Bc3, &source);
+		assert!(max_error(&source, &decoded, 3) <= 3, "BC3 alpha off by {}", max_error(&source, &decoded, 3));
+
+		// Colors are BC1 blocks without the transparent mode
+		let foo = image::load("./assets/textures/foo.png").unwrap();
+		let bc1 = round_trip(DdsFormat::Bc1, &foo);
+		assert_eq!(round_trip(DdsFormat::Bc3, &foo).data, bc1.data);
+	}
+
+	#[test]
+	fn encode_bc4_bc5() {
+		let source = gradient();
+		let decoded = round_trip(DdsFormat::Bc4, &source);
+		assert!(max_error(&source, &decoded, 0) <= 3, "BC4 red off by {}", max_error(&source, &decoded, 0));
+		let decoded = round_trip(DdsFormat::Bc5, &source);
+		for channel in 0..2 {
+			assert!(max_error(&source, &decoded, channel) <= 3, "BC5 channel {} off by {}", channel, max_error(&source, &decoded, channel));
+		}
+	}
+
+	#[test]
+	fn encode_srgb_like_linear() {
+		// sRGB only changes how the texels are read, the blocks are the same
+		let source = gradient();
+		for &(srgb, linear) in &[(DdsFormat::Bc1Srgb, DdsFormat::Bc1), (DdsFormat::Bc2Srgb, DdsFormat::Bc2), (DdsFormat::Bc3Srgb, DdsFormat::Bc3)] {
+			assert_eq!(encode(srgb, &source).unwrap(), encode(linear, &source).unwrap());
+		}
+		assert!(encode(DdsFormat::Bc7, &source).is_err());
+	}
+}
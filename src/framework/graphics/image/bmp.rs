@@ -0,0 +1,306 @@
+// Windows and OS/2 bitmap decoder. Handles every header from BITMAPCOREHEADER to
+// BITMAPV5HEADER, 1 to 8 bit palettes, 16 and 32 bit bitfields, 24 bit BGR, RLE4, RLE8
+// and embedded PNG data. Color profiles are ignored.
+
+use std::io;
+
+use super::{check_dimensions, invalid, png, read_u16_le, read_u32_le, truncated, RgbaImage};
+
+pub const SIGNATURE: &'static [u8] = b"BM";
+
+const FILE_HEADER_SIZE: usize = 14;
+const CORE_HEADER_SIZE: usize = 12;
+const OS2_HEADER_SIZE: usize = 64;
+
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_RLE4: u32 = 2;
+const BI_BITFIELDS: u32 = 3;
+const BI_JPEG: u32 = 4;
+const BI_PNG: u32 = 5;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+// A color channel packed into a pixel value
+#[derive(Clone, Copy)]
+struct Mask {
+	mask: u32,
+	shift: u32,
+	max: u32,
+}
+
+impl Mask {
+	fn new(mask: u32) -> Mask {
+		let shift = if mask == 0 { 0 } else { mask.trailing_zeros() };
+		Mask {
+			mask: mask,
+			shift: shift,
+			max: mask >> shift,
+		}
+	}
+
+	fn extract(&self, value: u32, default: u8) -> u8 {
+		if self.mask == 0 {
+			return default;
+		}
+		((((value & self.mask) >> self.shift) as u64 * 255 + self.max as u64 / 2) / self.max as u64) as u8
+	}
+}
+
+struct Header {
+	width: u32,
+	height: u32,
+	top_down: bool,
+	bit_count: u16,
+	compression: u32,
+	colors_used: usize,
+	masks: Option<[u32; 4]>,	// Red, green, blue and alpha
+}
+
+pub fn decode(data: &[u8]) -> io::Result<RgbaImage> {
+	if !data.starts_with(SIGNATURE) {
+		return Err(invalid("not a bmp file"));
+	}
+	if data.len() < FILE_HEADER_SIZE + 4 {
+		return Err(truncated());
+	}
+	let data_offset = read_u32_le(data, 10) as usize;
+	let header_size = read_u32_le(data, FILE_HEADER_SIZE) as usize;
+	let header_end = FILE_HEADER_SIZE + header_size;
+	if header_size < CORE_HEADER_SIZE || header_size > 1024 {
+		return Err(invalid("unknown bmp header size"));
+	}
+	if header_end > data.len() {
+		return Err(truncated());
+	}
+
+	let header = try!(parse_header(data, header_size));
+	try!(check_dimensions(header.width, header.height));
+
+	// Bitfields of a BITMAPINFOHEADER follow it, later headers include them
+	let mut palette_start = header_end;
+	let mut masks = header.masks;
+	if masks.is_none() && (header.compression == BI_BITFIELDS || header.compression == BI_ALPHABITFIELDS) {
+		let count = if header.compression == BI_ALPHABITFIELDS { 4 } else { 3 };
+		if header_end + count * 4 > data.len() {
+			return Err(truncated());
+		}
+		let mut fields = [0u32; 4];
+		for i in 0..count {
+			fields[i] = read_u32_le(data, header_end + i * 4);
+		}
+		masks = Some(fields);
+		palette_start += count * 4;
+	}
+
+	// Some writers leave the offset at zero, the pixels then follow the palette
+	let entry_size = if header_size == CORE_HEADER_SIZE { 3 } else { 4 };
+	let palette = if header.bit_count <= 8 {
+		let mut count = if header.colors_used == 0 { 1 << header.bit_count } else { header.colors_used.min(256) };
+		if data_offset >= palette_start {
+			count = count.min((data_offset - palette_start) / entry_size);
+		}
+		let end = palette_start + count * entry_size;
+		if end > data.len() {
+			return Err(truncated());
+		}
+		data[palette_start..end].chunks(entry_size).map(|c| [c[2], c[1], c[0], 255]).collect()
+	} else {
+		Vec::new()
+	};
+	let pixels_start = if data_offset == 0 { palette_start + palette.len() * entry_size } else { data_offset };
+	if pixels_start > data.len() {
+		return Err(truncated());
+	}
+	let pixels = &data[pixels_start..];
+
+	match header.compression {
+		BI_RGB | BI_BITFIELDS | BI_ALPHABITFIELDS => decode_rows(&header, pixels, &palette, masks),
+		BI_RLE8 if header.bit_count == 8 => decode_rle(&header, pixels, &palette),
+		BI_RLE4 if header.bit_count == 4 => decode_rle(&header, pixels, &palette),
+		BI_PNG => {
+			let image = try!(png::decode(pixels));
+			if image.width != header.width || image.height != header.height {
+				return Err(invalid("bmp and embedded png sizes differ"));
+			}
+			Ok(image)
+		},
+		BI_JPEG => Err(invalid("bmp files with jpeg data are not supported")),
+		_ => Err(invalid("unsupported bmp compression")),
+	}
+}
+
+fn parse_header(data: &[u8], size: usize) -> io::Result<Header> {
+	let at = FILE_HEADER_SIZE;
+	let (width, height, bit_count) = if size == CORE_HEADER_SIZE {
+		// OS/2 1.x sizes are unsigned 16 bit and always bottom up
+		(read_u16_le(data, at + 4) as i32, read_u16_le(data, at + 6) as i32, read_u16_le(data, at + 10))
+	} else {
+		if size < 16 {
+			return Err(invalid("unknown bmp header size"));
+		}
+		(read_u32_le(data, at + 4) as i32, read_u32_le(data, at + 8) as i32, read_u16_le(data, at + 14))
+	};
+	// Fields past the end of a short header are zero
+	let field = |offset: usize| if offset + 4 <= size { read_u32_le(data, at + offset) } else { 0 };
+
+	let compression = field(16);
+	if size == OS2_HEADER_SIZE && (compression == BI_BITFIELDS || compression == BI_JPEG) {
+		// OS/2 2.x reuses these values for Huffman 1D and RLE24
+		return Err(invalid("unsupported os/2 bmp compression"));
+	}
+
+	// BITMAPV2INFOHEADER and later carry the masks in the header itself
+	let masks = if size >= 52 && size != OS2_HEADER_SIZE {
+		Some([field(40), field(44), field(48), field(52)])
+	} else {
+		None
+	};
+
+	if width <= 0 || height == 0 || height == i32::min_value() {
+		return Err(invalid("image has no pixels"));
+	}
+	if ![1, 2, 4, 8, 16, 24, 32].contains(&bit_count) && compression != BI_PNG && compression != BI_JPEG {
+		return Err(invalid("unsupported bmp bit count"));
+	}
+	if height < 0 && (compression == BI_RLE8 || compression == BI_RLE4) {
+		return Err(invalid("run length encoded bmp files can't be top down"));
+	}
+
+	Ok(Header {
+		width: width as u32,
+		height: height.abs() as u32,
+		top_down: height < 0,
+		bit_count: bit_count,
+		compression: compression,
+		colors_used: field(32) as usize,
+		masks: masks,
+	})
+}
+
+fn decode_rows(header: &Header, data: &[u8], palette: &[[u8; 4]], masks: Option<[u32; 4]>) -> io::Result<RgbaImage> {
+	let bit_count = header.bit_count as usize;
+	let width = header.width as usize;
+	// Rows are padded to 4 bytes
+	let stride = (width * bit_count + 31) / 32 * 4;
+	let height = header.height as usize;
+	if stride * (height - 1) + (width * bit_count + 7) / 8 > data.len() {
+		return Err(truncated());
+	}
+
+	// 32 bit BI_RGB has an unused fourth byte that is often alpha anyway
+	let (masks, guess_alpha) = match (masks, header.bit_count) {
+		(Some(masks), _) if header.compression != BI_RGB => (masks, false),
+		(_, 16) => ([0x7c00, 0x03e0, 0x001f, 0], false),
+		(_, 32) => ([0x00ff0000, 0x0000ff00, 0x000000ff, 0xff000000], true),
+		_ => ([0; 4], false),
+	};
+	let (red, green, blue, alpha) = (Mask::new(masks[0]), Mask::new(masks[1]), Mask::new(masks[2]), Mask::new(masks[3]));
+
+	let mut image = RgbaImage::new(header.width, header.height);
+	for y in 0..height {
+		let row = &data[y * stride..];
+		let target = (if header.top_down { y } else { height - 1 - y }) as u32;
+
+		for x in 0..width {
+			let rgba = match bit_count {
+				1 | 2 | 4 | 8 => {
+					let bit = x * bit_count;
+					let index = (row[bit / 8] >> (8 - bit_count - bit % 8)) & ((1u16 << bit_count) - 1) as u8;
+					// Out of range indices are an error in the file, show them black
+					palette.get(index as usize).cloned().unwrap_or([0, 0, 0, 255])
+				},
+				24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+				_ => {
+					let value = if bit_count == 16 { read_u16_le(row, x * 2) as u32 } else { read_u32_le(row, x * 4) };
+					[red.extract(value, 0), green.extract(value, 0), blue.extract(value, 0), alpha.extract(value, 255)]
+				},
+			};
+			image.set_pixel(x as u32, target, rgba);
+		}
+	}
+
+	// An alpha byte that is zero everywhere was never meant to be alpha
+	if guess_alpha && image.data.chunks(4).all(|pixel| pixel[3] == 0) {
+		for pixel in image.data.chunks_mut(4) {
+			pixel[3] = 255;
+		}
+	}
+
+	Ok(image)
+}
+
+// RLE8 and RLE4, always bottom up. Pixels skipped by deltas or an early end stay transparent.
+fn decode_rle(header: &Header, data: &[u8], palette: &[[u8; 4]]) -> io::Result<RgbaImage> {
+	let four_bit = header.compression == BI_RLE4;
+	let (width, height) = (header.width as usize, header.height as usize);
+	let mut image = RgbaImage::new(header.width, header.height);
+	let color = |index: u8| palette.get(index as usize).cloned().unwrap_or([0, 0, 0, 255]);
+
+	let (mut x, mut y) = (0usize, 0usize);
+	let mut offset = 0;
+	{
+		let mut put = |x: usize, y: usize, index: u8| {
+			// Runs past the end of a row are clipped
+			if x < width && y < height {
+				image.set_pixel(x as u32, (height - 1 - y) as u32, color(index));
+			}
+		};
+
+		loop {
+			if offset + 2 > data.len() {
+				return Err(truncated());
+			}
+			let (count, value) = (data[offset] as usize, data[offset + 1]);
+			offset += 2;
+
+			if count > 0 {
+				// Encoded run, RLE4 alternates between the two nibbles
+				for i in 0..count {
+					let index = if !four_bit { value } else if i % 2 == 0 { value >> 4 } else { value & 0x0f };
+					put(x + i, y, index);
+				}
+				x += count;
+				continue;
+			}
+
+			match value {
+				0 => { x = 0; y += 1; },
+				1 => break,
+				2 => {
+					if offset + 2 > data.len() {
+						return Err(truncated());
+					}
+					x += data[offset] as usize;
+					y += data[offset + 1] as usize;
+					offset += 2;
+				},
+				count => {
+					// Absolute run, padded to a 16 bit boundary
+					let count = count as usize;
+					let bytes = if four_bit { (count + 1) / 2 } else { count };
+					if offset + bytes > data.len() {
+						return Err(truncated());
+					}
+					for i in 0..count {
+						let index = if !four_bit {
+							data[offset + i]
+						} else if i % 2 == 0 {
+							data[offset + i / 2] >> 4
+						} else {
+							data[offset + i / 2] & 0x0f
+						};
+						put(x + i, y, index);
+					}
+					x += count;
+					offset += (bytes + 1) & !1;
+				},
+			}
+
+			if y >= height {
+				break;
+			}
+		}
+	}
+
+	Ok(image)
+}
@@ -0,0 +1,742 @@
+// DirectDraw Surface parser and writer. Reads legacy headers (FourCC or bit masks) and the DX10
+// extension, and keeps the surfaces in the file's layout so they can be uploaded as is.
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
+use super::{invalid, read_u32_le, truncated, MAX_DIMENSION};
+
+pub const SIGNATURE: &'static [u8] = b"DDS ";
+
+const HEADER_SIZE: usize = 124;
+const DX10_HEADER_SIZE: usize = 20;
+
+// Header flags
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDSD_DEPTH: u32 = 0x800000;
+
+// Pixel format flags
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xfc00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+// Direct3D 11 allows no more array elements than this
+const MAX_ARRAY_SIZE: u32 = 2048;
+
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+fn four_cc(code: &[u8; 4]) -> u32 {
+	read_u32_le(code, 0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DdsFormat {
+	Bc1,
+	Bc1Srgb,
+	Bc2,
+	Bc2Srgb,
+	Bc3,
+	Bc3Srgb,
+	Bc4,
+	Bc4Snorm,
+	Bc5,
+	Bc5Snorm,
+	Bc6hUfloat,
+	Bc6hSfloat,
+	Bc7,
+	Bc7Srgb,
+	Rgba8,
+	Rgba8Srgb,
+	Rgbx8,
+	Rgbx8Srgb,
+	Bgra8,
+	Bgra8Srgb,
+	Bgrx8,
+	Bgrx8Srgb,
+	Bgr8,
+	B5g6r5,
+	B5g5r5a1,
+	B4g4r4a4,
+	Rgb10a2,
+	R8,
+	Rg8,
+	Rgba16,
+	R16f,
+	Rg16f,
+	Rgba16f,
+	R11g11b10f,
+	R32f,
+	Rg32f,
+	Rgb32f,
+	Rgba32f,
+}
+
+impl DdsFormat {
+	pub fn is_compressed(&self) -> bool {
+		self.block_bytes() != 0
+	}
+
+	pub fn is_srgb(&self) -> bool {
+		match *self {
+			DdsFormat::Bc1Srgb | DdsFormat::Bc2Srgb | DdsFormat::Bc3Srgb | DdsFormat::Bc7Srgb |
+			DdsFormat::Rgba8Srgb | DdsFormat::Rgbx8Srgb | DdsFormat::Bgra8Srgb | DdsFormat::Bgrx8Srgb => true,
+			_ => false,
+		}
+	}
+
+	// Bytes in a 4x4 block of a compressed format, 0 otherwise
+	fn block_bytes(&self) -> usize {
+		match *self {
+			DdsFormat::Bc1 | DdsFormat::Bc1Srgb | DdsFormat::Bc4 | DdsFormat::Bc4Snorm => 8,
+			DdsFormat::Bc2 | DdsFormat::Bc2Srgb | DdsFormat::Bc3 | DdsFormat::Bc3Srgb |
+			DdsFormat::Bc5 | DdsFormat::Bc5Snorm | DdsFormat::Bc6hUfloat | DdsFormat::Bc6hSfloat |
+			DdsFormat::Bc7 | DdsFormat::Bc7Srgb => 16,
+			_ => 0,
+		}
+	}
+
+	// Bytes in a pixel of an uncompressed format, 0 otherwise
+	fn pixel_bytes(&self) -> usize {
+		match *self {
+			DdsFormat::R8 => 1,
+			DdsFormat::Rg8 | DdsFormat::B5g6r5 | DdsFormat::B5g5r5a1 | DdsFormat::B4g4r4a4 | DdsFormat::R16f => 2,
+			DdsFormat::Bgr8 => 3,
+			DdsFormat::Rgba8 | DdsFormat::Rgba8Srgb | DdsFormat::Rgbx8 | DdsFormat::Rgbx8Srgb |
+			DdsFormat::Bgra8 | DdsFormat::Bgra8Srgb | DdsFormat::Bgrx8 | DdsFormat::Bgrx8Srgb | DdsFormat::Rgb10a2 | DdsFormat::Rg16f |
+			DdsFormat::R11g11b10f | DdsFormat::R32f => 4,
+			DdsFormat::Rgba16 | DdsFormat::Rgba16f | DdsFormat::Rg32f => 8,
+			DdsFormat::Rgb32f => 12,
+			DdsFormat::Rgba32f => 16,
+			_ => 0,
+		}
+	}
+
+	// Size in bytes of one slice of the given size
+	pub fn surface_size(&self, width: u32, height: u32) -> usize {
+		if self.is_compressed() {
+			((width as usize + 3) / 4) * ((height as usize + 3) / 4) * self.block_bytes()
+		} else {
+			width as usize * height as usize * self.pixel_bytes()
+		}
+	}
+
+	fn from_dxgi(format: u32) -> Option<DdsFormat> {
+		Some(match format {
+			2 => DdsFormat::Rgba32f,
+			6 => DdsFormat::Rgb32f,
+			10 => DdsFormat::Rgba16f,
+			11 => DdsFormat::Rgba16,
+			16 => DdsFormat::Rg32f,
+			24 => DdsFormat::Rgb10a2,
+			26 => DdsFormat::R11g11b10f,
+			27 | 28 => DdsFormat::Rgba8,
+			29 => DdsFormat::Rgba8Srgb,
+			34 => DdsFormat::Rg16f,
+			41 => DdsFormat::R32f,
+			48 | 49 => DdsFormat::Rg8,
+			54 => DdsFormat::R16f,
+			60 | 61 => DdsFormat::R8,
+			70 | 71 => DdsFormat::Bc1,
+			72 => DdsFormat::Bc1Srgb,
+			73 | 74 => DdsFormat::Bc2,
+			75 => DdsFormat::Bc2Srgb,
+			76 | 77 => DdsFormat::Bc3,
+			78 => DdsFormat::Bc3Srgb,
+			79 | 80 => DdsFormat::Bc4,
+			81 => DdsFormat::Bc4Snorm,
+			82 | 83 => DdsFormat::Bc5,
+			84 => DdsFormat::Bc5Snorm,
+			85 => DdsFormat::B5g6r5,
+			86 => DdsFormat::B5g5r5a1,
+			87 | 90 => DdsFormat::Bgra8,
+			88 | 92 => DdsFormat::Bgrx8,
+			91 => DdsFormat::Bgra8Srgb,
+			93 => DdsFormat::Bgrx8Srgb,
+			94 | 95 => DdsFormat::Bc6hUfloat,
+			96 => DdsFormat::Bc6hSfloat,
+			97 | 98 => DdsFormat::Bc7,
+			99 => DdsFormat::Bc7Srgb,
+			115 => DdsFormat::B4g4r4a4,
+			_ => return None,
+		})
+	}
+
+	fn dxgi(&self) -> Option<u32> {
+		Some(match *self {
+			DdsFormat::Rgba32f => 2,
+			DdsFormat::Rgb32f => 6,
+			DdsFormat::Rgba16f => 10,
+			DdsFormat::Rgba16 => 11,
+			DdsFormat::Rg32f => 16,
+			DdsFormat::Rgb10a2 => 24,
+			DdsFormat::R11g11b10f => 26,
+			DdsFormat::Rgba8 => 28,
+			DdsFormat::Rgba8Srgb => 29,
+			DdsFormat::Rg16f => 34,
+			DdsFormat::R32f => 41,
+			DdsFormat::Rg8 => 49,
+			DdsFormat::R16f => 54,
+			DdsFormat::R8 => 61,
+			DdsFormat::Bc1 => 71,
+			DdsFormat::Bc1Srgb => 72,
+			DdsFormat::Bc2 => 74,
+			DdsFormat::Bc2Srgb => 75,
+			DdsFormat::Bc3 => 77,
+			DdsFormat::Bc3Srgb => 78,
+			DdsFormat::Bc4 => 80,
+			DdsFormat::Bc4Snorm => 81,
+			DdsFormat::Bc5 => 83,
+			DdsFormat::Bc5Snorm => 84,
+			DdsFormat::B5g6r5 => 85,
+			DdsFormat::B5g5r5a1 => 86,
+			DdsFormat::Bgra8 => 87,
+			DdsFormat::Bgrx8 => 88,
+			DdsFormat::Bgra8Srgb => 91,
+			DdsFormat::Bgrx8Srgb => 93,
+			DdsFormat::Bc6hUfloat => 95,
+			DdsFormat::Bc6hSfloat => 96,
+			DdsFormat::Bc7 => 98,
+			DdsFormat::Bc7Srgb => 99,
+			DdsFormat::B4g4r4a4 => 115,
+			DdsFormat::Rgbx8 | DdsFormat::Rgbx8Srgb | DdsFormat::Bgr8 => return None,
+		})
+	}
+
+	fn from_four_cc(code: u32) -> Option<DdsFormat> {
+		// DXT2 and DXT4 are premultiplied, the blocks are the same
+		Some(match code {
+			_ if code == four_cc(b"DXT1") => DdsFormat::Bc1,
+			_ if code == four_cc(b"DXT2") || code == four_cc(b"DXT3") => DdsFormat::Bc2,
+			_ if code == four_cc(b"DXT4") || code == four_cc(b"DXT5") => DdsFormat::Bc3,
+			_ if code == four_cc(b"ATI1") || code == four_cc(b"BC4U") => DdsFormat::Bc4,
+			_ if code == four_cc(b"BC4S") => DdsFormat::Bc4Snorm,
+			_ if code == four_cc(b"ATI2") || code == four_cc(b"BC5U") => DdsFormat::Bc5,
+			_ if code == four_cc(b"BC5S") => DdsFormat::Bc5Snorm,
+			// D3DFORMAT values stored in place of a FourCC
+			36 => DdsFormat::Rgba16,
+			111 => DdsFormat::R16f,
+			112 => DdsFormat::Rg16f,
+			113 => DdsFormat::Rgba16f,
+			114 => DdsFormat::R32f,
+			115 => DdsFormat::Rg32f,
+			116 => DdsFormat::Rgba32f,
+			_ => return None,
+		})
+	}
+
+	fn from_masks(bit_count: u32, red: u32, green: u32, blue: u32, alpha: u32) -> Option<DdsFormat> {
+		Some(match (bit_count, red, green, blue, alpha) {
+			(32, 0xff, 0xff00, 0xff0000, 0) => DdsFormat::Rgbx8,
+			(32, 0xff, 0xff00, 0xff0000, _) => DdsFormat::Rgba8,
+			(32, 0xff0000, 0xff00, 0xff, 0xff000000) => DdsFormat::Bgra8,
+			(32, 0xff0000, 0xff00, 0xff, 0) => DdsFormat::Bgrx8,
+			(32, 0x3ff, 0xffc00, 0x3ff00000, _) => DdsFormat::Rgb10a2,
+			(24, 0xff0000, 0xff00, 0xff, 0) => DdsFormat::Bgr8,
+			(16, 0xf800, 0x7e0, 0x1f, 0) => DdsFormat::B5g6r5,
+			(16, 0x7c00, 0x3e0, 0x1f, 0x8000) => DdsFormat::B5g5r5a1,
+			(16, 0xf00, 0xf0, 0xf, 0xf000) => DdsFormat::B4g4r4a4,
+			(16, 0xff, 0xff00, 0, 0) => DdsFormat::Rg8,
+			(8, 0xff, 0, 0, 0) => DdsFormat::R8,
+			_ => return None,
+		})
+	}
+
+	// Pixel format flags, FourCC, bit count and masks of a header without the DX10 extension.
+	// sRGB and the newer formats can only be written with the extension.
+	fn legacy(&self) -> Option<(u32, u32, [u32; 5])> {
+		let masks = |bit_count, red, green, blue, alpha| {
+			let flags = if alpha != 0 { DDPF_RGB | DDPF_ALPHAPIXELS } else { DDPF_RGB };
+			Some((flags, 0, [bit_count, red, green, blue, alpha]))
+		};
+		let code = |code: &[u8; 4]| Some((DDPF_FOURCC, four_cc(code), [0; 5]));
+
+		match *self {
+			DdsFormat::Bc1 => code(b"DXT1"),
+			DdsFormat::Bc2 => code(b"DXT3"),
+			DdsFormat::Bc3 => code(b"DXT5"),
+			DdsFormat::Bc4 => code(b"ATI1"),
+			DdsFormat::Bc4Snorm => code(b"BC4S"),
+			DdsFormat::Bc5 => code(b"ATI2"),
+			DdsFormat::Bc5Snorm => code(b"BC5S"),
+			DdsFormat::Rgba8 => masks(32, 0xff, 0xff00, 0xff0000, 0xff000000),
+			DdsFormat::Rgbx8 => masks(32, 0xff, 0xff00, 0xff0000, 0),
+			DdsFormat::Bgra8 => masks(32, 0xff0000, 0xff00, 0xff, 0xff000000),
+			DdsFormat::Bgrx8 => masks(32, 0xff0000, 0xff00, 0xff, 0),
+			DdsFormat::Bgr8 => masks(24, 0xff0000, 0xff00, 0xff, 0),
+			DdsFormat::B5g6r5 => masks(16, 0xf800, 0x7e0, 0x1f, 0),
+			DdsFormat::B5g5r5a1 => masks(16, 0x7c00, 0x3e0, 0x1f, 0x8000),
+			DdsFormat::B4g4r4a4 => masks(16, 0xf00, 0xf0, 0xf, 0xf000),
+			DdsFormat::Rg8 => masks(16, 0xff, 0xff00, 0, 0),
+			DdsFormat::R8 => masks(8, 0xff, 0, 0, 0),
+			_ => None,
+		}
+	}
+}
+
+// Every surface of a dds file. Surfaces are stored layer by layer, each layer with all its
+// mipmaps. Cubemaps have six layers per array element in the order +X, -X, +Y, -Y, +Z, -Z.
+pub struct DdsImage {
+	pub width: u32,
+	pub height: u32,
+	pub depth: u32,			// Slices of a volume texture, 1 otherwise
+	pub mipmap_count: u32,
+	pub array_size: u32,	// Array elements, a cubemap counts as one
+	pub cubemap: bool,
+	pub format: DdsFormat,
+	pub data: Vec<u8>,
+}
+
+impl DdsImage {
+	pub fn load(file_path: &str) -> io::Result<DdsImage> {
+		let mut file = try!(File::open(file_path));
+		let mut data = Vec::new();
+		try!(file.read_to_end(&mut data));
+		DdsImage::parse(&data)
+	}
+
+	pub fn parse(data: &[u8]) -> io::Result<DdsImage> {
+		if !data.starts_with(SIGNATURE) {
+			return Err(invalid("not a dds file"));
+		}
+		if data.len() < 4 + HEADER_SIZE {
+			return Err(truncated());
+		}
+		// Field offsets below are from the start of the file
+		let field = |offset: usize| read_u32_le(data, offset);
+		if field(4) != HEADER_SIZE as u32 || field(76) != 32 {
+			return Err(invalid("malformed dds header"));
+		}
+
+		let flags = field(8);
+		let height = field(12);
+		let width = field(16);
+		let depth = if flags & DDSD_DEPTH != 0 { field(24).max(1) } else { 1 };
+		let mipmap_count = if flags & DDSD_MIPMAPCOUNT != 0 { field(28).max(1) } else { 1 };
+		let format_flags = field(80);
+		let caps2 = field(112);
+
+		let mut offset = 4 + HEADER_SIZE;
+		let mut array_size = 1;
+		let mut cubemap = caps2 & DDSCAPS2_CUBEMAP != 0;
+		let mut volume = caps2 & DDSCAPS2_VOLUME != 0;
+
+		let format = if format_flags & DDPF_FOURCC != 0 && field(84) == four_cc(b"DX10") {
+			if data.len() < offset + DX10_HEADER_SIZE {
+				return Err(truncated());
+			}
+			let dxgi_format = field(offset);
+			volume = field(offset + 4) == D3D10_RESOURCE_DIMENSION_TEXTURE3D;
+			cubemap = field(offset + 8) & D3D10_RESOURCE_MISC_TEXTURECUBE != 0;
+			array_size = field(offset + 12).max(1);
+			offset += DX10_HEADER_SIZE;
+			match DdsFormat::from_dxgi(dxgi_format) {
+				Some(format) => format,
+				None => return Err(invalid("unsupported dxgi format")),
+			}
+		} else if format_flags & DDPF_FOURCC != 0 {
+			match DdsFormat::from_four_cc(field(84)) {
+				Some(format) => format,
+				None => return Err(invalid("unsupported dds fourcc")),
+			}
+		} else if format_flags & DDPF_RGB != 0 {
+			let alpha = if format_flags & DDPF_ALPHAPIXELS != 0 { field(104) } else { 0 };
+			match DdsFormat::from_masks(field(88), field(92), field(96), field(100), alpha) {
+				Some(format) => format,
+				None => return Err(invalid("unsupported dds pixel masks")),
+			}
+		} else {
+			return Err(invalid("unsupported dds pixel format"));
+		};
+
+		if cubemap && caps2 & DDSCAPS2_CUBEMAP != 0 && caps2 & DDSCAPS2_CUBEMAP_ALL_FACES != DDSCAPS2_CUBEMAP_ALL_FACES {
+			return Err(invalid("cubemaps without all six faces are not supported"));
+		}
+		if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION || depth > MAX_DIMENSION {
+			return Err(invalid("invalid dds dimensions"));
+		}
+		if array_size > MAX_ARRAY_SIZE {
+			return Err(invalid("dds texture array is too large"));
+		}
+		if (cubemap && (volume || width != height)) || (volume && array_size > 1) {
+			return Err(invalid("invalid dds texture layout"));
+		}
+
+		// Writers sometimes claim more levels than the chain has
+		let largest = width.max(height).max(if volume { depth } else { 1 });
+		let levels = 32 - largest.leading_zeros();
+
+		let mut image = DdsImage {
+			width: width,
+			height: height,
+			depth: if volume { depth } else { 1 },
+			mipmap_count: mipmap_count.min(levels),
+			array_size: array_size,
+			cubemap: cubemap,
+			format: format,
+			data: Vec::new(),
+		};
+
+		let size = image.layer_size() * image.layers() as usize;
+		if data.len() < offset + size {
+			return Err(truncated());
+		}
+		image.data = data[offset..offset + size].to_vec();
+		Ok(image)
+	}
+
+	// Writes the file parse() reads back. The legacy header is used when it can describe
+	// the image, so older tools can open the file too.
+	pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+		let legacy = if self.array_size == 1 { self.format.legacy() } else { None };
+		let dxgi_format = match (legacy, self.format.dxgi()) {
+			(Some(_), _) => None,
+			(None, Some(format)) => Some(format),
+			(None, None) => return Err(invalid("format can't be written to a dds file")),
+		};
+		if self.data.len() != self.layer_size() * self.layers() as usize {
+			return Err(invalid("dds image data doesn't match its size"));
+		}
+
+		let volume = self.depth > 1;
+		let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT;
+		flags |= if self.format.is_compressed() { DDSD_LINEARSIZE } else { DDSD_PITCH };
+		if volume {
+			flags |= DDSD_DEPTH;
+		}
+		// Pitch of a row for uncompressed formats, size of the top level otherwise
+		let pitch_or_size = if self.format.is_compressed() {
+			self.format.surface_size(self.width, self.height)
+		} else {
+			self.format.surface_size(self.width, 1)
+		};
+		let mut caps = DDSCAPS_TEXTURE;
+		if self.mipmap_count > 1 || self.cubemap || volume {
+			caps |= DDSCAPS_COMPLEX;
+		}
+		if self.mipmap_count > 1 {
+			caps |= DDSCAPS_MIPMAP;
+		}
+		let mut caps2 = 0;
+		if self.cubemap {
+			caps2 |= DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALL_FACES;
+		}
+		if volume {
+			caps2 |= DDSCAPS2_VOLUME;
+		}
+		let (format_flags, code, masks) = legacy.unwrap_or((DDPF_FOURCC, four_cc(b"DX10"), [0; 5]));
+
+		let mut data = Vec::with_capacity(4 + HEADER_SIZE + DX10_HEADER_SIZE + self.data.len());
+		data.extend_from_slice(SIGNATURE);
+		{
+			let mut put = |value: u32| data.extend_from_slice(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+			put(HEADER_SIZE as u32);
+			put(flags);
+			put(self.height);
+			put(self.width);
+			put(pitch_or_size as u32);
+			put(self.depth);
+			put(self.mipmap_count);
+			for _ in 0..11 {
+				put(0);
+			}
+			put(32);
+			put(format_flags);
+			put(code);
+			for &mask in masks.iter() {
+				put(mask);
+			}
+			put(caps);
+			put(caps2);
+			for _ in 0..3 {
+				put(0);
+			}
+
+			if let Some(dxgi_format) = dxgi_format {
+				put(dxgi_format);
+				put(if volume { D3D10_RESOURCE_DIMENSION_TEXTURE3D } else { D3D10_RESOURCE_DIMENSION_TEXTURE2D });
+				put(if self.cubemap { D3D10_RESOURCE_MISC_TEXTURECUBE } else { 0 });
+				put(self.array_size);
+				put(0);
+			}
+		}
+		data.extend_from_slice(&self.data);
+		Ok(data)
+	}
+
+	pub fn save(&self, file_path: &str) -> io::Result<()> {
+		let data = try!(self.to_bytes());
+		let mut file = try!(File::create(file_path));
+		file.write_all(&data)
+	}
+
+	// Array elements times cubemap faces
+	pub fn layers(&self) -> u32 {
+		if self.cubemap { self.array_size * 6 } else { self.array_size }
+	}
+
+	// Width, height and depth of a mipmap level
+	pub fn level_size(&self, level: u32) -> (u32, u32, u32) {
+		((self.width >> level).max(1), (self.height >> level).max(1), (self.depth >> level).max(1))
+	}
+
+	// Bytes of one mipmap level of one layer, all slices of a volume
+	pub fn level_bytes(&self, level: u32) -> usize {
+		let (width, height, depth) = self.level_size(level);
+		self.format.surface_size(width, height) * depth as usize
+	}
+
+	fn layer_size(&self) -> usize {
+		(0..self.mipmap_count).map(|level| self.level_bytes(level)).sum()
+	}
+
+	// Data of a mipmap level of a layer, see layers()
+	pub fn surface(&self, layer: u32, level: u32) -> &[u8] {
+		assert!(layer < self.layers() && level < self.mipmap_count);
+		let start = self.layer_size() * layer as usize +
+			(0..level).map(|level| self.level_bytes(level)).sum::<usize>();
+		&self.data[start..start + self.level_bytes(level)]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::ErrorKind;
+
+	use super::*;
+	use framework::graphics::image::{bcn, RgbaImage};
+
+	// A legacy header, the pixel format is the FourCC or, without one, the bit count and masks
+	fn header(width: u32, height: u32, depth: u32, mipmap_count: u32, code: Option<&[u8; 4]>,
+		masks: [u32; 5], caps2: u32) -> Vec<u8> {
+		let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT;
+		if depth > 1 {
+			flags |= DDSD_DEPTH;
+		}
+		let format_flags = match code {
+			Some(_) => DDPF_FOURCC,
+			None if masks[4] != 0 => DDPF_RGB | DDPF_ALPHAPIXELS,
+			None => DDPF_RGB,
+		};
+
+		let mut fields = vec![HEADER_SIZE as u32, flags, height, width, 0, depth, mipmap_count];
+		fields.extend_from_slice(&[0; 11]);
+		fields.extend_from_slice(&[32, format_flags, code.map_or(0, four_cc)]);
+		fields.extend_from_slice(&masks);
+		fields.extend_from_slice(&[DDSCAPS_TEXTURE, caps2, 0, 0, 0]);
+
+		let mut data = SIGNATURE.to_vec();
+		for field in fields {
+			data.extend_from_slice(&[field as u8, (field >> 8) as u8, (field >> 16) as u8, (field >> 24) as u8]);
+		}
+		data
+	}
+
+	// Appends the DX10 extension to a header with the DX10 FourCC
+	fn dx10(mut data: Vec<u8>, dxgi_format: u32, dimension: u32, misc: u32, array_size: u32) -> Vec<u8> {
+		for &field in &[dxgi_format, dimension, misc, array_size, 0] {
+			data.extend_from_slice(&[field as u8, (field >> 8) as u8, (field >> 16) as u8, (field >> 24) as u8]);
+		}
+		data
+	}
+
+	// Surface bytes counting up, so every surface can be told apart
+	fn with_surfaces(mut data: Vec<u8>, size: usize) -> Vec<u8> {
+		data.extend((0..size).map(|i| i as u8));
+		data
+	}
+
+	fn parse_error(data: &[u8]) -> ErrorKind {
+		match DdsImage::parse(data) {
+			Ok(_) => panic!("parsed an invalid dds file"),
+			Err(e) => e.kind(),
+		}
+	}
+
+	#[test]
+	fn legacy_four_cc_with_mipmaps() {
+		// 8x8, 4x4, 2x2 and 1x1 are 4, 1, 1 and 1 blocks
+		let data = with_surfaces(header(8, 8, 1, 4, Some(b"DXT5"), [0; 5], 0), 7 * 16);
+		let image = DdsImage::parse(&data).unwrap();
+		assert_eq!(image.format, DdsFormat::Bc3);
+		assert_eq!((image.width, image.height, image.depth), (8, 8, 1));
+		assert_eq!((image.mipmap_count, image.array_size, image.layers()), (4, 1, 1));
+		assert!(!image.cubemap);
+		assert_eq!(image.surface(0, 0).len(), 64);
+		assert_eq!(image.surface(0, 1)[0], 64);
+		assert_eq!(image.surface(0, 3)[0], 96);
+	}
+
+	#[test]
+	fn legacy_mipmap_count_is_clamped_to_the_chain() {
+		let data = with_surfaces(header(4, 2, 1, 10, Some(b"DXT1"), [0; 5], 0), 3 * 8);
+		assert_eq!(DdsImage::parse(&data).unwrap().mipmap_count, 3);
+	}
+
+	#[test]
+	fn legacy_masks() {
+		let formats = [
+			([32, 0xff0000, 0xff00, 0xff, 0xff000000], DdsFormat::Bgra8),
+			([32, 0xff0000, 0xff00, 0xff, 0], DdsFormat::Bgrx8),
+			([32, 0xff, 0xff00, 0xff0000, 0xff000000], DdsFormat::Rgba8),
+			([32, 0xff, 0xff00, 0xff0000, 0], DdsFormat::Rgbx8),
+			([24, 0xff0000, 0xff00, 0xff, 0], DdsFormat::Bgr8),
+			([16, 0xf800, 0x7e0, 0x1f, 0], DdsFormat::B5g6r5),
+			([8, 0xff, 0, 0, 0], DdsFormat::R8),
+		];
+		for &(masks, format) in formats.iter() {
+			let data = with_surfaces(header(2, 2, 1, 1, None, masks, 0), masks[0] as usize / 2);
+			assert_eq!(DdsImage::parse(&data).unwrap().format, format);
+		}
+	}
+
+	#[test]
+	fn legacy_rgbx_round_trips() {
+		let data = with_surfaces(header(2, 2, 1, 1, None, [32, 0xff, 0xff00, 0xff0000, 0], 0), 16);
+		let written = DdsImage::parse(&data).unwrap().to_bytes().unwrap();
+		assert_eq!(DdsImage::parse(&written).unwrap().format, DdsFormat::Rgbx8);
+	}
+
+	#[test]
+	fn legacy_cubemap() {
+		let caps2 = DDSCAPS2_CUBEMAP | DDSCAPS2_CUBEMAP_ALL_FACES;
+		let data = with_surfaces(header(4, 4, 1, 1, Some(b"DXT1"), [0; 5], caps2), 6 * 8);
+		let image = DdsImage::parse(&data).unwrap();
+		assert!(image.cubemap);
+		assert_eq!((image.array_size, image.layers()), (1, 6));
+		assert_eq!(image.surface(5, 0)[0], 40);
+	}
+
+	#[test]
+	fn legacy_cubemap_needs_every_face() {
+		let caps2 = DDSCAPS2_CUBEMAP | 0x400 | 0x800;
+		let data = with_surfaces(header(4, 4, 1, 1, Some(b"DXT1"), [0; 5], caps2), 6 * 8);
+		assert_eq!(parse_error(&data), ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn legacy_volume() {
+		// 4x4x4, 2x2x2 and 1x1x1 at one byte per pixel
+		let data = with_surfaces(header(4, 4, 4, 3, None, [8, 0xff, 0, 0, 0], DDSCAPS2_VOLUME), 64 + 8 + 1);
+		let image = DdsImage::parse(&data).unwrap();
+		assert_eq!((image.depth, image.mipmap_count, image.layers()), (4, 3, 1));
+		assert_eq!(image.level_size(1), (2, 2, 2));
+		assert_eq!(image.surface(0, 1).len(), 8);
+		assert_eq!(image.surface(0, 2), &[72]);
+	}
+
+	#[test]
+	fn dx10_array() {
+		let data = dx10(header(4, 4, 1, 1, Some(b"DX10"), [0; 5], 0), 99, D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, 3);
+		let data = with_surfaces(data, 3 * 16);
+		let image = DdsImage::parse(&data).unwrap();
+		assert_eq!(image.format, DdsFormat::Bc7Srgb);
+		assert_eq!((image.array_size, image.layers()), (3, 3));
+		assert_eq!(image.surface(2, 0)[0], 32);
+	}
+
+	#[test]
+	fn dx10_cubemap_array() {
+		let data = dx10(header(2, 2, 1, 2, Some(b"DX10"), [0; 5], 0), 28, D3D10_RESOURCE_DIMENSION_TEXTURE2D,
+			D3D10_RESOURCE_MISC_TEXTURECUBE, 2);
+		let data = with_surfaces(data, 12 * 20);
+		let image = DdsImage::parse(&data).unwrap();
+		assert_eq!(image.format, DdsFormat::Rgba8);
+		assert!(image.cubemap);
+		assert_eq!((image.array_size, image.layers(), image.mipmap_count), (2, 12, 2));
+		// Each face holds a 2x2 and a 1x1 level
+		assert_eq!(image.surface(1, 1)[0], 36);
+	}
+
+	#[test]
+	fn dx10_volume() {
+		// The depth is still read from the legacy header
+		let data = dx10(header(2, 2, 3, 1, Some(b"DX10"), [0; 5], 0), 61, D3D10_RESOURCE_DIMENSION_TEXTURE3D, 0, 1);
+		let image = DdsImage::parse(&with_surfaces(data, 12)).unwrap();
+		assert_eq!((image.format, image.depth, image.layers()), (DdsFormat::R8, 3, 1));
+		assert_eq!(image.level_bytes(0), 12);
+	}
+
+	#[test]
+	fn unknown_formats() {
+		let data = with_surfaces(header(4, 4, 1, 1, Some(b"ETC1"), [0; 5], 0), 8);
+		assert_eq!(parse_error(&data), ErrorKind::InvalidData);
+		let data = dx10(header(4, 4, 1, 1, Some(b"DX10"), [0; 5], 0), 1000, D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, 1);
+		let data = with_surfaces(data, 8);
+		assert_eq!(parse_error(&data), ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn truncated_input() {
+		let data = with_surfaces(header(8, 8, 1, 4, Some(b"DXT1"), [0; 5], 0), 7 * 8);
+		assert_eq!(parse_error(&data[..data.len() - 1]), ErrorKind::UnexpectedEof);
+		assert_eq!(parse_error(&data[..100]), ErrorKind::UnexpectedEof);
+
+		let data = dx10(header(4, 4, 1, 1, Some(b"DX10"), [0; 5], 0), 71, D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, 1);
+		assert_eq!(parse_error(&data[..4 + HEADER_SIZE + 10]), ErrorKind::UnexpectedEof);
+		assert_eq!(parse_error(b"DDS "), ErrorKind::UnexpectedEof);
+		assert_eq!(parse_error(b"PNG "), ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn save_and_load() {
+		// BC3 sRGB needs the DX10 header, the mipmaps are what nitrust-texconv writes
+		let mut source = RgbaImage::new(13, 7);
+		for y in 0..7 {
+			for x in 0..13 {
+				source.set_pixel(x, y, [x as u8 * 19, y as u8 * 36, 128, 255 - x as u8 * 16]);
+			}
+		}
+		let levels = source.mipmaps(true);
+		let mut data = Vec::new();
+		for level in &levels {
+			data.extend(bcn::encode(DdsFormat::Bc3Srgb, level).unwrap());
+		}
+		let image = DdsImage {
+			width: 13,
+			height: 7,
+			depth: 1,
+			mipmap_count: levels.len() as u32,
+			array_size: 1,
+			cubemap: false,
+			format: DdsFormat::Bc3Srgb,
+			data: data,
+		};
+
+		let path = ::std::env::temp_dir().join("nitrust-dds-save-and-load.dds");
+		let path = path.to_str().unwrap();
+		image.save(path).unwrap();
+		let loaded = DdsImage::load(path).unwrap();
+		let _ = ::std::fs::remove_file(path);
+
+		assert_eq!((loaded.width, loaded.height, loaded.depth), (13, 7, 1));
+		assert_eq!((loaded.mipmap_count, loaded.array_size, loaded.cubemap), (4, 1, false));
+		assert_eq!(loaded.format, DdsFormat::Bc3Srgb);
+		assert_eq!(loaded.data, image.data);
+		for (level, expected) in levels.iter().enumerate() {
+			let surface = loaded.surface(0, level as u32);
+			assert_eq!(surface.len(), DdsFormat::Bc3Srgb.surface_size(expected.width, expected.height));
+			let decoded = bcn::decode(loaded.format, expected.width, expected.height, surface).unwrap();
+			// Each level decodes back to its mipmap, BC3 alpha is within half a step of its block
+			for (p, q) in decoded.data.chunks(4).zip(expected.data.chunks(4)) {
+				assert!((p[3] as i32 - q[3] as i32).abs() <= 8, "level {}: alpha {} for {}", level, p[3], q[3]);
+			}
+		}
+	}
+}
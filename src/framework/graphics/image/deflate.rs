@@ -0,0 +1,141 @@
+// zlib / DEFLATE compression (RFC 1950 and 1951) for writing PNG files. Matches are found
+// with hash chains and written with the fixed Huffman codes, which is small and fast enough
+// for screenshots and test images.
+
+use super::inflate::{adler32, DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+const MAX_CHAIN: usize = 64;		// Candidates tried per position, trades speed for size
+
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+	// 32K window, default compression level, no dictionary
+	let mut writer = BitWriter { output: vec![0x78, 0x9c], bit_buffer: 0, bit_count: 0 };
+
+	// A single final block with fixed codes
+	writer.bits(1, 1);
+	writer.bits(1, 2);
+
+	let mut head = vec![usize::max_value(); 1 << HASH_BITS];
+	let mut previous = vec![usize::max_value(); data.len()];
+	let mut position = 0;
+	while position < data.len() {
+		let (length, distance) = longest_match(data, position, &head, &previous);
+		if length >= MIN_MATCH {
+			writer.length(length);
+			writer.distance(distance);
+		} else {
+			writer.literal(data[position] as u16);
+		}
+
+		for index in position..position + length.max(1) {
+			if index + MIN_MATCH <= data.len() {
+				let hash = hash(data, index);
+				previous[index] = head[hash];
+				head[hash] = index;
+			}
+		}
+		position += length.max(1);
+	}
+	writer.literal(256);
+	writer.flush();
+
+	let checksum = adler32(data);
+	writer.output.extend_from_slice(&[(checksum >> 24) as u8, (checksum >> 16) as u8,
+		(checksum >> 8) as u8, checksum as u8]);
+	writer.output
+}
+
+fn hash(data: &[u8], index: usize) -> usize {
+	let value = (data[index] as u32) << 16 | (data[index + 1] as u32) << 8 | data[index + 2] as u32;
+	(value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+// Length and distance of the longest earlier match, a length below MIN_MATCH means none
+fn longest_match(data: &[u8], position: usize, head: &[usize], previous: &[usize]) -> (usize, usize) {
+	if position + MIN_MATCH > data.len() {
+		return (0, 0);
+	}
+
+	let max_length = (data.len() - position).min(MAX_MATCH);
+	let (mut best_length, mut best_distance) = (0, 0);
+	let mut candidate = head[hash(data, position)];
+	let mut chain = 0;
+	while candidate != usize::max_value() && position - candidate <= WINDOW_SIZE && chain < MAX_CHAIN {
+		let mut length = 0;
+		while length < max_length && data[candidate + length] == data[position + length] {
+			length += 1;
+		}
+		if length > best_length {
+			best_length = length;
+			best_distance = position - candidate;
+			if length == max_length {
+				break;
+			}
+		}
+		candidate = previous[candidate];
+		chain += 1;
+	}
+	(best_length, best_distance)
+}
+
+struct BitWriter {
+	output: Vec<u8>,
+	bit_buffer: u32,
+	bit_count: u32,
+}
+
+impl BitWriter {
+	// Writes the lowest count bits of value, least significant first
+	fn bits(&mut self, value: u32, count: u32) {
+		self.bit_buffer |= value << self.bit_count;
+		self.bit_count += count;
+		while self.bit_count >= 8 {
+			self.output.push(self.bit_buffer as u8);
+			self.bit_buffer >>= 8;
+			self.bit_count -= 8;
+		}
+	}
+
+	// Huffman codes are stored most significant bit first
+	fn code(&mut self, code: u32, length: u32) {
+		let mut reversed = 0;
+		for bit in 0..length {
+			reversed |= ((code >> bit) & 1) << (length - 1 - bit);
+		}
+		self.bits(reversed, length);
+	}
+
+	// Literal or length symbol with the fixed literal/length code
+	fn literal(&mut self, symbol: u16) {
+		let symbol = symbol as u32;
+		match symbol {
+			0..=143 => self.code(0x30 + symbol, 8),
+			144..=255 => self.code(0x190 + symbol - 144, 9),
+			256..=279 => self.code(symbol - 256, 7),
+			_ => self.code(0xc0 + symbol - 280, 8),
+		}
+	}
+
+	fn length(&mut self, length: usize) {
+		let index = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+		self.literal(257 + index as u16);
+		self.bits((length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+	}
+
+	fn distance(&mut self, distance: usize) {
+		let index = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+		self.code(index as u32, 5);
+		self.bits((distance - DISTANCE_BASE[index] as usize) as u32, DISTANCE_EXTRA[index] as u32);
+	}
+
+	fn flush(&mut self) {
+		if self.bit_count > 0 {
+			self.output.push(self.bit_buffer as u8);
+			self.bit_buffer = 0;
+			self.bit_count = 0;
+		}
+	}
+}
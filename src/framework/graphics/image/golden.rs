@@ -0,0 +1,133 @@
+// Compares rendered images with reference ("golden") images, for renderer regression tests.
+// Set NITRUST_UPDATE_GOLDEN to write the current output as the new reference instead.
+
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+
+use super::{load, RgbaImage};
+
+pub const UPDATE_VARIABLE: &'static str = "NITRUST_UPDATE_GOLDEN";
+
+// Where two images of the same size differ by more than the tolerance
+#[derive(Clone, Debug)]
+pub struct ImageDiff {
+	pub mismatched: usize,			// Pixels with a channel off by more than the tolerance
+	pub max_difference: u8,			// Largest channel difference over all pixels
+	pub image: RgbaImage,			// Mismatches in red over a faded copy of the expected image
+}
+
+impl ImageDiff {
+	pub fn matches(&self) -> bool {
+		self.mismatched == 0
+	}
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+	Io { path: String, error: io::Error },
+	Missing { path: String },
+	SizeMismatch { path: String, expected: (u32, u32), actual: (u32, u32) },
+	Mismatch { path: String, mismatched: usize, max_difference: u8 },
+}
+
+impl fmt::Display for GoldenError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			GoldenError::Io { ref path, ref error } => {
+				write!(f, "Could not read golden image {}: {}", path, error)
+			},
+			GoldenError::Missing { ref path } => {
+				write!(f, "Golden image {} doesn't exist, rerun with {} set to create it", path, UPDATE_VARIABLE)
+			},
+			GoldenError::SizeMismatch { ref path, expected, actual } => {
+				write!(f, "Golden image {} is {}x{} but the output is {}x{}",
+					path, expected.0, expected.1, actual.0, actual.1)
+			},
+			GoldenError::Mismatch { ref path, mismatched, max_difference } => {
+				write!(f, "Output differs from golden image {} in {} pixels (by up to {}), see {} and {}",
+					path, mismatched, max_difference, sibling(path, "actual"), sibling(path, "diff"))
+			},
+		}
+	}
+}
+
+impl error::Error for GoldenError {}
+
+// Compares every channel, alpha included. None when the sizes differ.
+pub fn compare(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Option<ImageDiff> {
+	if actual.width != expected.width || actual.height != expected.height {
+		return None;
+	}
+
+	let mut diff = ImageDiff {
+		mismatched: 0,
+		max_difference: 0,
+		image: RgbaImage::new(expected.width, expected.height),
+	};
+	for (index, (a, e)) in actual.data.chunks(4).zip(expected.data.chunks(4)).enumerate() {
+		let difference = (0..4).map(|channel| (a[channel] as i16 - e[channel] as i16).abs() as u8).max().unwrap();
+		diff.max_difference = diff.max_difference.max(difference);
+
+		let pixel = if difference > tolerance {
+			diff.mismatched += 1;
+			[255, 0, 0, 255]
+		} else {
+			let gray = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 12) as u8;
+			[gray, gray, gray, 255]
+		};
+		diff.image.data[index * 4..index * 4 + 4].copy_from_slice(&pixel);
+	}
+	Some(diff)
+}
+
+// Checks an image against the png at golden_path. When they differ the output and the
+// difference are written next to it as name.actual.png and name.diff.png.
+pub fn check(actual: &RgbaImage, golden_path: &str, tolerance: u8) -> Result<(), GoldenError> {
+	let io_error = |error| GoldenError::Io { path: golden_path.to_string(), error: error };
+
+	if env::var_os(UPDATE_VARIABLE).is_some() {
+		return actual.save_png(golden_path).map_err(io_error);
+	}
+
+	let expected = match load(golden_path) {
+		Ok(image) => image,
+		Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+			try!(actual.save_png(&sibling(golden_path, "actual")).map_err(io_error));
+			return Err(GoldenError::Missing { path: golden_path.to_string() });
+		},
+		Err(error) => return Err(io_error(error)),
+	};
+
+	let diff = match compare(actual, &expected, tolerance) {
+		Some(diff) => diff,
+		None => return Err(GoldenError::SizeMismatch {
+			path: golden_path.to_string(),
+			expected: (expected.width, expected.height),
+			actual: (actual.width, actual.height),
+		}),
+	};
+
+	if diff.matches() {
+		// Leftovers from an earlier failure would be confusing
+		let _ = fs::remove_file(sibling(golden_path, "actual"));
+		let _ = fs::remove_file(sibling(golden_path, "diff"));
+		return Ok(());
+	}
+
+	try!(actual.save_png(&sibling(golden_path, "actual")).map_err(io_error));
+	try!(diff.image.save_png(&sibling(golden_path, "diff")).map_err(io_error));
+	Err(GoldenError::Mismatch {
+		path: golden_path.to_string(),
+		mismatched: diff.mismatched,
+		max_difference: diff.max_difference,
+	})
+}
+
+// quad.png becomes quad.actual.png
+fn sibling(path: &str, suffix: &str) -> String {
+	let stem = if path.to_lowercase().ends_with(".png") { &path[..path.len() - 4] } else { path };
+	format!("{}.{}.png", stem, suffix)
+}
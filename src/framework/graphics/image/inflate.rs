@@ -0,0 +1,348 @@
+// zlib / DEFLATE decompression (RFC 1950 and 1951) for PNG image data
+
+use std::io;
+
+use super::{invalid, truncated};
+
+// Decompresses a zlib stream and checks its Adler-32 checksum
+pub fn zlib_decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+	if data.len() < 6 {
+		return Err(truncated());
+	}
+	let (cmf, flags) = (data[0], data[1]);
+	if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+		return Err(invalid("zlib stream does not use deflate"));
+	}
+	if ((cmf as u16) << 8 | flags as u16) % 31 != 0 {
+		return Err(invalid("zlib header checksum mismatch"));
+	}
+	if flags & 0x20 != 0 {
+		return Err(invalid("zlib preset dictionaries are not supported"));
+	}
+
+	let mut inflater = Inflater {
+		input: &data[2..],
+		position: 0,
+		bit_buffer: 0,
+		bit_count: 0,
+		output: Vec::with_capacity(data.len() * 4),
+	};
+	try!(inflater.inflate());
+
+	// The checksum follows the deflate data on the next byte boundary
+	let end = 2 + inflater.position;
+	if end + 4 > data.len() {
+		return Err(truncated());
+	}
+	let expected = (data[end] as u32) << 24 | (data[end + 1] as u32) << 16 |
+		(data[end + 2] as u32) << 8 | data[end + 3] as u32;
+	if adler32(&inflater.output) != expected {
+		return Err(invalid("zlib checksum mismatch"));
+	}
+
+	Ok(inflater.output)
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+	let (mut a, mut b) = (1u32, 0u32);
+	// 5552 bytes is the most that can be summed before b overflows
+	for chunk in data.chunks(5552) {
+		for &byte in chunk {
+			a += byte as u32;
+			b += a;
+		}
+		a %= 65521;
+		b %= 65521;
+	}
+	b << 16 | a
+}
+
+pub const LENGTH_BASE: [u16; 29] = [
+	3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+	35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+pub const LENGTH_EXTRA: [u8; 29] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+	3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+pub const DISTANCE_BASE: [u16; 30] = [
+	1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+	257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+pub const DISTANCE_EXTRA: [u8; 30] = [
+	0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+	7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// Order in which code length code lengths are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const FAST_BITS: u32 = 9;
+
+// Canonical Huffman code. Codes up to FAST_BITS long are looked up in a table,
+// longer ones are decoded one bit at a time.
+struct Huffman {
+	fast: Vec<u16>,			// symbol << 4 | length, 0 when longer than FAST_BITS
+	counts: [u16; 16],		// Number of codes of each length
+	symbols: Vec<u16>,		// Symbols ordered by code
+}
+
+impl Huffman {
+	fn new(lengths: &[u8]) -> io::Result<Huffman> {
+		let mut counts = [0u16; 16];
+		for &length in lengths {
+			counts[length as usize] += 1;
+		}
+		counts[0] = 0;
+
+		// Over-subscribed codes can't be decoded, incomplete ones are allowed
+		let mut left: i32 = 1;
+		for length in 1..16 {
+			left = (left << 1) - counts[length] as i32;
+			if left < 0 {
+				return Err(invalid("over-subscribed huffman code"));
+			}
+		}
+
+		let mut offsets = [0u16; 16];
+		for length in 1..15 {
+			offsets[length + 1] = offsets[length] + counts[length];
+		}
+		let mut symbols = vec![0; lengths.len()];
+		for (symbol, &length) in lengths.iter().enumerate() {
+			if length != 0 {
+				symbols[offsets[length as usize] as usize] = symbol as u16;
+				offsets[length as usize] += 1;
+			}
+		}
+
+		// Fill the fast table with bit reversed codes, deflate sends codes MSB first
+		let mut fast = vec![0u16; 1 << FAST_BITS];
+		let mut code: u32 = 0;
+		let mut index = 0;
+		for length in 1..(FAST_BITS as usize + 1) {
+			for _ in 0..counts[length] {
+				let symbol = symbols[index];
+				let reversed = reverse(code, length as u32);
+				let mut entry = reversed as usize;
+				while entry < fast.len() {
+					fast[entry] = symbol << 4 | length as u16;
+					entry += 1 << length;
+				}
+				code += 1;
+				index += 1;
+			}
+			code <<= 1;
+		}
+
+		Ok(Huffman {
+			fast: fast,
+			counts: counts,
+			symbols: symbols,
+		})
+	}
+}
+
+fn reverse(code: u32, length: u32) -> u32 {
+	let mut reversed = 0;
+	for bit in 0..length {
+		reversed |= ((code >> bit) & 1) << (length - 1 - bit);
+	}
+	reversed
+}
+
+struct Inflater<'a> {
+	input: &'a [u8],
+	position: usize,
+	bit_buffer: u32,
+	bit_count: u32,
+	output: Vec<u8>,
+}
+
+impl<'a> Inflater<'a> {
+	fn inflate(&mut self) -> io::Result<()> {
+		loop {
+			let last = try!(self.bits(1)) == 1;
+			match try!(self.bits(2)) {
+				0 => try!(self.stored()),
+				1 => {
+					let (literals, distances) = try!(fixed_codes());
+					try!(self.codes(&literals, &distances));
+				},
+				2 => {
+					let (literals, distances) = try!(self.dynamic_codes());
+					try!(self.codes(&literals, &distances));
+				},
+				_ => return Err(invalid("invalid deflate block type")),
+			}
+			if last {
+				break;
+			}
+		}
+
+		// Give back whole bytes that were read ahead
+		self.position -= (self.bit_count / 8) as usize;
+		self.bit_buffer = 0;
+		self.bit_count = 0;
+		Ok(())
+	}
+
+	fn bits(&mut self, count: u32) -> io::Result<u32> {
+		while self.bit_count < count {
+			if self.position >= self.input.len() {
+				return Err(truncated());
+			}
+			self.bit_buffer |= (self.input[self.position] as u32) << self.bit_count;
+			self.position += 1;
+			self.bit_count += 8;
+		}
+
+		let value = self.bit_buffer & ((1u64 << count) - 1) as u32;
+		self.bit_buffer >>= count;
+		self.bit_count -= count;
+		Ok(value)
+	}
+
+	// Fills the bit buffer as far as the input allows, for table lookups
+	fn peek(&mut self, count: u32) -> u32 {
+		while self.bit_count < count && self.position < self.input.len() {
+			self.bit_buffer |= (self.input[self.position] as u32) << self.bit_count;
+			self.position += 1;
+			self.bit_count += 8;
+		}
+		self.bit_buffer & ((1 << count) - 1)
+	}
+
+	fn symbol(&mut self, huffman: &Huffman) -> io::Result<u16> {
+		let entry = huffman.fast[self.peek(FAST_BITS) as usize];
+		let length = (entry & 0x0f) as u32;
+		if length != 0 && length <= self.bit_count {
+			self.bit_buffer >>= length;
+			self.bit_count -= length;
+			return Ok(entry >> 4);
+		}
+
+		// Slow path, walk the code one bit at a time
+		let (mut code, mut first, mut index): (i32, i32, i32) = (0, 0, 0);
+		for length in 1..16 {
+			code |= try!(self.bits(1)) as i32;
+			let count = huffman.counts[length] as i32;
+			if code - count < first {
+				return Ok(huffman.symbols[(index + (code - first)) as usize]);
+			}
+			index += count;
+			first += count;
+			first <<= 1;
+			code <<= 1;
+		}
+
+		Err(invalid("invalid huffman code"))
+	}
+
+	fn stored(&mut self) -> io::Result<()> {
+		// Skip to the byte boundary
+		let skip = self.bit_count % 8;
+		try!(self.bits(skip));
+		let length = try!(self.bits(16));
+		let complement = try!(self.bits(16));
+		if length != !complement & 0xffff {
+			return Err(invalid("stored block length mismatch"));
+		}
+
+		// Whatever is left in the bit buffer is whole bytes now
+		for _ in 0..length {
+			let byte = try!(self.bits(8)) as u8;
+			self.output.push(byte);
+		}
+		Ok(())
+	}
+
+	fn dynamic_codes(&mut self) -> io::Result<(Huffman, Huffman)> {
+		let literal_count = try!(self.bits(5)) as usize + 257;
+		let distance_count = try!(self.bits(5)) as usize + 1;
+		let code_count = try!(self.bits(4)) as usize + 4;
+		if literal_count > 286 || distance_count > 30 {
+			return Err(invalid("too many huffman codes"));
+		}
+
+		let mut code_lengths = [0u8; 19];
+		for &index in CODE_LENGTH_ORDER.iter().take(code_count) {
+			code_lengths[index] = try!(self.bits(3)) as u8;
+		}
+		let code_huffman = try!(Huffman::new(&code_lengths));
+
+		let mut lengths = vec![0u8; literal_count + distance_count];
+		let mut index = 0;
+		while index < lengths.len() {
+			let symbol = try!(self.symbol(&code_huffman));
+			let (value, repeat) = match symbol {
+				0..=15 => (symbol as u8, 1),
+				16 => {
+					if index == 0 {
+						return Err(invalid("repeated code length without a previous length"));
+					}
+					(lengths[index - 1], 3 + try!(self.bits(2)) as usize)
+				},
+				17 => (0, 3 + try!(self.bits(3)) as usize),
+				_ => (0, 11 + try!(self.bits(7)) as usize),
+			};
+			if index + repeat > lengths.len() {
+				return Err(invalid("code lengths overflow"));
+			}
+			for _ in 0..repeat {
+				lengths[index] = value;
+				index += 1;
+			}
+		}
+		if lengths[256] == 0 {
+			return Err(invalid("missing end of block code"));
+		}
+
+		Ok((try!(Huffman::new(&lengths[..literal_count])), try!(Huffman::new(&lengths[literal_count..]))))
+	}
+
+	fn codes(&mut self, literals: &Huffman, distances: &Huffman) -> io::Result<()> {
+		loop {
+			let symbol = try!(self.symbol(literals)) as usize;
+			if symbol < 256 {
+				self.output.push(symbol as u8);
+				continue;
+			}
+			if symbol == 256 {
+				return Ok(());
+			}
+
+			let symbol = symbol - 257;
+			if symbol >= 29 {
+				return Err(invalid("invalid length symbol"));
+			}
+			let length = LENGTH_BASE[symbol] as usize + try!(self.bits(LENGTH_EXTRA[symbol] as u32)) as usize;
+
+			let symbol = try!(self.symbol(distances)) as usize;
+			if symbol >= 30 {
+				return Err(invalid("invalid distance symbol"));
+			}
+			let distance = DISTANCE_BASE[symbol] as usize + try!(self.bits(DISTANCE_EXTRA[symbol] as u32)) as usize;
+			if distance > self.output.len() {
+				return Err(invalid("distance too far back"));
+			}
+
+			// Copies may overlap themselves, so go byte by byte
+			let start = self.output.len() - distance;
+			self.output.reserve(length);
+			for i in 0..length {
+				let byte = self.output[start + i];
+				self.output.push(byte);
+			}
+		}
+	}
+}
+
+fn fixed_codes() -> io::Result<(Huffman, Huffman)> {
+	let mut lengths = [0u8; 288];
+	for (symbol, length) in lengths.iter_mut().enumerate() {
+		*length = match symbol {
+			0..=143 => 8,
+			144..=255 => 9,
+			256..=279 => 7,
+			_ => 8,
+		};
+	}
+
+	Ok((try!(Huffman::new(&lengths)), try!(Huffman::new(&[5; 30]))))
+}
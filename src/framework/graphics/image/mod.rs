@@ -0,0 +1,252 @@
+// Image decoders, a png encoder and golden image checks, none of which need a GL context.
+// Everything decodes to RgbaImage, except dds files that keep their GPU formats in DdsImage.
+
+use std::fs::File;
+use std::io;
+use std::io::{ Error, ErrorKind };
+use std::io::prelude::*;
+
+mod deflate;
+mod inflate;
+
+pub mod bcn;
+pub mod bmp;
+pub mod dds;
+pub mod golden;
+pub mod png;
+pub mod tga;
+
+pub use self::dds::{DdsFormat, DdsImage};
+pub use self::golden::{GoldenError, ImageDiff};
+
+// 8 bit RGBA pixels, rows from top to bottom
+#[derive(Clone, Debug, PartialEq)]
+pub struct RgbaImage {
+	pub width: u32,
+	pub height: u32,
+	pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+	// Transparent black
+	pub fn new(width: u32, height: u32) -> RgbaImage {
+		RgbaImage {
+			width: width,
+			height: height,
+			data: vec![0; width as usize * height as usize * 4],
+		}
+	}
+
+	pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+		let i = (y as usize * self.width as usize + x as usize) * 4;
+		[self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3]]
+	}
+
+	pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+		let i = (y as usize * self.width as usize + x as usize) * 4;
+		self.data[i..i + 4].copy_from_slice(&rgba);
+	}
+
+	pub fn flip_vertically(&mut self) {
+		let row = self.width as usize * 4;
+		let height = self.height as usize;
+		for y in 0..height / 2 {
+			let (top, bottom) = self.data.split_at_mut((height - 1 - y) * row);
+			top[y * row..(y + 1) * row].swap_with_slice(&mut bottom[..row]);
+		}
+	}
+
+	// Next mipmap level, half the size with a box filter. Odd sizes fold the last row or
+	// column into the one before. Colors are weighted by alpha so transparent pixels don't
+	// bleed in, and averaged in linear light when srgb is set.
+	pub fn downsample(&self, srgb: bool) -> RgbaImage {
+		let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+		let to_linear: Vec<f32> = (0..256).map(|value| {
+			let value = value as f32 / 255.0;
+			if !srgb {
+				value
+			} else if value <= 0.04045 {
+				value / 12.92
+			} else {
+				((value + 0.055) / 1.055).powf(2.4)
+			}
+		}).collect();
+
+		let mut image = RgbaImage::new(width, height);
+		for y in 0..height {
+			for x in 0..width {
+				// Source pixels covered by this one, three on the last row or column of odd sizes
+				let xs = 2 * x..if x == width - 1 { self.width } else { 2 * x + 2 };
+				let ys = 2 * y..if y == height - 1 { self.height } else { 2 * y + 2 };
+
+				let (mut color, mut alpha, mut count) = ([0.0f32; 3], 0.0f32, 0.0f32);
+				for sy in ys {
+					for sx in xs.clone() {
+						let pixel = self.pixel(sx, sy);
+						let weight = pixel[3] as f32 / 255.0;
+						for channel in 0..3 {
+							color[channel] += to_linear[pixel[channel] as usize] * weight;
+						}
+						alpha += weight;
+						count += 1.0;
+					}
+				}
+
+				let mut rgba = [0u8; 4];
+				for channel in 0..3 {
+					let value = if alpha > 0.0 { color[channel] / alpha } else { 0.0 };
+					let value = if !srgb {
+						value
+					} else if value <= 0.0031308 {
+						value * 12.92
+					} else {
+						1.055 * value.powf(1.0 / 2.4) - 0.055
+					};
+					rgba[channel] = (value * 255.0 + 0.5).max(0.0).min(255.0) as u8;
+				}
+				rgba[3] = (alpha / count * 255.0 + 0.5) as u8;
+				image.set_pixel(x, y, rgba);
+			}
+		}
+
+		image
+	}
+
+	// The image followed by every smaller level down to 1x1
+	pub fn mipmaps(&self, srgb: bool) -> Vec<RgbaImage> {
+		let mut levels = vec![self.clone()];
+		loop {
+			let next = {
+				let last = &levels[levels.len() - 1];
+				if last.width == 1 && last.height == 1 {
+					break;
+				}
+				last.downsample(srgb)
+			};
+			levels.push(next);
+		}
+		levels
+	}
+
+	pub fn is_opaque(&self) -> bool {
+		self.data.chunks(4).all(|pixel| pixel[3] == 255)
+	}
+
+	pub fn save_png(&self, file_path: &str) -> io::Result<()> {
+		png::save(file_path, self)
+	}
+}
+
+// Decodes a file by its extension, or by its signature when the extension is unknown
+pub fn load(file_path: &str) -> io::Result<RgbaImage> {
+	let mut file = try!(File::open(file_path));
+	let mut data = Vec::new();
+	try!(file.read_to_end(&mut data));
+
+	let lower = file_path.to_lowercase();
+	if lower.ends_with(".png") || data.starts_with(png::SIGNATURE) {
+		png::decode(&data)
+	} else if lower.ends_with(".bmp") || data.starts_with(bmp::SIGNATURE) {
+		bmp::decode(&data)
+	} else if lower.ends_with(".tga") {
+		tga::decode(&data)
+	} else {
+		Err(invalid("unsupported image format"))
+	}
+}
+
+// Malformed or unsupported data
+fn invalid(message: &str) -> Error {
+	Error::new(ErrorKind::InvalidData, message)
+}
+
+// Data that ends too early
+fn truncated() -> Error {
+	Error::new(ErrorKind::UnexpectedEof, "image data is truncated")
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> u16 {
+	data[offset] as u16 | (data[offset + 1] as u16) << 8
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> u32 {
+	read_u16_le(data, offset) as u32 | (read_u16_le(data, offset + 2) as u32) << 16
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> u32 {
+	(data[offset] as u32) << 24 | (data[offset + 1] as u32) << 16 |
+		(data[offset + 2] as u32) << 8 | data[offset + 3] as u32
+}
+
+// Images this large are rejected instead of risking absurd allocations
+const MAX_DIMENSION: u32 = 1 << 15;
+
+fn check_dimensions(width: u32, height: u32) -> io::Result<()> {
+	if width == 0 || height == 0 {
+		return Err(invalid("image has no pixels"));
+	}
+	if width > MAX_DIMENSION || height > MAX_DIMENSION {
+		return Err(invalid("image is too large"));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Two black and two white pixels
+	fn checker() -> RgbaImage {
+		let mut image = RgbaImage::new(2, 2);
+		image.set_pixel(0, 0, [255, 255, 255, 255]);
+		image.set_pixel(1, 0, [0, 0, 0, 255]);
+		image.set_pixel(0, 1, [0, 0, 0, 255]);
+		image.set_pixel(1, 1, [255, 255, 255, 255]);
+		image
+	}
+
+	#[test]
+	fn downsample_srgb() {
+		// Half the light is 188 in sRGB, averaging the stored values would give 128
+		assert_eq!(checker().downsample(true).pixel(0, 0), [188, 188, 188, 255]);
+		assert_eq!(checker().downsample(false).pixel(0, 0), [128, 128, 128, 255]);
+	}
+
+	#[test]
+	fn downsample_transparent() {
+		// Transparent pixels don't darken the color, only the alpha
+		let mut image = RgbaImage::new(2, 1);
+		image.set_pixel(0, 0, [200, 100, 50, 255]);
+		image.set_pixel(1, 0, [0, 0, 0, 0]);
+		assert_eq!(image.downsample(true).pixel(0, 0), [200, 100, 50, 128]);
+		assert_eq!(RgbaImage::new(2, 2).downsample(true).pixel(0, 0), [0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn mipmaps_odd_size() {
+		let mut image = RgbaImage::new(13, 7);
+		for y in 0..7 {
+			for x in 0..13 {
+				image.set_pixel(x, y, [x as u8 * 10, y as u8 * 30, 0, 255]);
+			}
+		}
+		let levels = image.mipmaps(false);
+		let sizes: Vec<(u32, u32)> = levels.iter().map(|level| (level.width, level.height)).collect();
+		assert_eq!(sizes, vec![(13, 7), (6, 3), (3, 1), (1, 1)]);
+		assert_eq!(levels[0], image);
+
+		// The last column and row of an odd level fold into the pixel before
+		assert_eq!(levels[1].pixel(5, 2), [110, 150, 0, 255]);
+		// The single row of 3x1 covers all three rows of 6x3
+		let reds: Vec<u8> = levels[1].data.chunks(4).take(6).map(|pixel| pixel[0]).collect();
+		assert_eq!(reds, vec![5, 25, 45, 65, 85, 110]);
+		assert_eq!(levels[2].data, vec![15, 80, 0, 255, 55, 80, 0, 255, 98, 80, 0, 255]);
+		assert_eq!(levels[3].data, vec![56, 80, 0, 255]);
+	}
+
+	#[test]
+	fn mipmaps_of_one_pixel() {
+		let image = RgbaImage::new(1, 1);
+		assert_eq!(image.mipmaps(true), vec![image]);
+	}
+}
@@ -0,0 +1,403 @@
+// PNG decoder for every color type and bit depth, Adam7 interlacing and tRNS transparency.
+// 16 bit channels are reduced to 8 bits, gamma and color profile chunks are ignored.
+// The encoder always writes 8 bit RGBA.
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
+use super::{check_dimensions, invalid, read_u32_be, truncated, RgbaImage};
+use super::deflate::zlib_compress;
+use super::inflate::zlib_decompress;
+
+pub const SIGNATURE: &'static [u8] = b"\x89PNG\r\n\x1a\n";
+
+const GRAYSCALE: u8 = 0;
+const RGB: u8 = 2;
+const PALETTE: u8 = 3;
+const GRAYSCALE_ALPHA: u8 = 4;
+const RGBA: u8 = 6;
+
+// Column start, row start, column step and row step of the Adam7 passes
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+	(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4),
+	(0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+struct Header {
+	width: u32,
+	height: u32,
+	bit_depth: u8,
+	color_type: u8,
+	interlaced: bool,
+}
+
+impl Header {
+	fn channels(&self) -> usize {
+		match self.color_type {
+			GRAYSCALE | PALETTE => 1,
+			GRAYSCALE_ALPHA => 2,
+			RGB => 3,
+			_ => 4,
+		}
+	}
+
+	fn bits_per_pixel(&self) -> usize {
+		self.channels() * self.bit_depth as usize
+	}
+
+	fn row_bytes(&self, width: usize) -> usize {
+		(width * self.bits_per_pixel() + 7) / 8
+	}
+}
+
+pub fn decode(data: &[u8]) -> io::Result<RgbaImage> {
+	if !data.starts_with(SIGNATURE) {
+		return Err(invalid("not a png file"));
+	}
+
+	let mut header = None;
+	let mut palette: Vec<[u8; 4]> = Vec::new();
+	let mut transparency: Option<Vec<u8>> = None;
+	let mut compressed = Vec::new();
+	let mut offset = SIGNATURE.len();
+	let mut ended = false;
+
+	while offset + 12 <= data.len() {
+		let length = read_u32_be(data, offset) as usize;
+		let kind = &data[offset + 4..offset + 8];
+		let start = offset + 8;
+		if length > data.len() || start + length + 4 > data.len() {
+			return Err(truncated());
+		}
+		let body = &data[start..start + length];
+		if crc32(&data[offset + 4..start + length]) != read_u32_be(data, start + length) {
+			return Err(invalid("png chunk checksum mismatch"));
+		}
+		offset = start + length + 4;
+
+		match kind {
+			b"IHDR" => header = Some(try!(parse_header(body))),
+			b"PLTE" => {
+				if length % 3 != 0 || length / 3 > 256 {
+					return Err(invalid("malformed png palette"));
+				}
+				palette = body.chunks(3).map(|c| [c[0], c[1], c[2], 255]).collect();
+			},
+			b"tRNS" => transparency = Some(body.to_vec()),
+			b"IDAT" => compressed.extend_from_slice(body),
+			b"IEND" => { ended = true; break; },
+			// Ancillary chunks have a lower case first letter and may be skipped
+			_ if kind[0] & 0x20 != 0 => {},
+			_ => return Err(invalid("unknown critical png chunk")),
+		}
+	}
+
+	let header = match header {
+		Some(header) => header,
+		None => return Err(invalid("png has no header")),
+	};
+	if !ended && compressed.is_empty() {
+		return Err(truncated());
+	}
+	if header.color_type == PALETTE && palette.is_empty() {
+		return Err(invalid("png has no palette"));
+	}
+
+	// Alpha values for the palette or the transparent color key
+	let mut color_key = None;
+	if let Some(ref trns) = transparency {
+		match header.color_type {
+			PALETTE => for (entry, &alpha) in palette.iter_mut().zip(trns.iter()) {
+				entry[3] = alpha;
+			},
+			GRAYSCALE if trns.len() >= 2 => {
+				let gray = (trns[0] as u16) << 8 | trns[1] as u16;
+				color_key = Some([gray, gray, gray]);
+			},
+			RGB if trns.len() >= 6 => {
+				color_key = Some([
+					(trns[0] as u16) << 8 | trns[1] as u16,
+					(trns[2] as u16) << 8 | trns[3] as u16,
+					(trns[4] as u16) << 8 | trns[5] as u16,
+				]);
+			},
+			_ => {},
+		}
+	}
+
+	let raw = try!(zlib_decompress(&compressed));
+	let mut image = RgbaImage::new(header.width, header.height);
+	let (width, height) = (header.width as usize, header.height as usize);
+
+	if header.interlaced {
+		let mut offset = 0;
+		for &(x0, y0, dx, dy) in ADAM7.iter() {
+			if x0 >= width || y0 >= height {
+				continue;
+			}
+			let pass_width = (width - x0 + dx - 1) / dx;
+			let pass_height = (height - y0 + dy - 1) / dy;
+			let size = (header.row_bytes(pass_width) + 1) * pass_height;
+			if offset + size > raw.len() {
+				return Err(truncated());
+			}
+
+			let pixels = try!(unfilter(&header, &raw[offset..offset + size], pass_width, pass_height));
+			for y in 0..pass_height {
+				for x in 0..pass_width {
+					let rgba = pixel(&header, &pixels, pass_width, x, y, &palette, color_key);
+					image.set_pixel((x0 + x * dx) as u32, (y0 + y * dy) as u32, rgba);
+				}
+			}
+			offset += size;
+		}
+	} else {
+		let size = (header.row_bytes(width) + 1) * height;
+		if size > raw.len() {
+			return Err(truncated());
+		}
+
+		let pixels = try!(unfilter(&header, &raw[..size], width, height));
+		for y in 0..height {
+			for x in 0..width {
+				let rgba = pixel(&header, &pixels, width, x, y, &palette, color_key);
+				image.set_pixel(x as u32, y as u32, rgba);
+			}
+		}
+	}
+
+	Ok(image)
+}
+
+pub fn encode(image: &RgbaImage) -> Vec<u8> {
+	// Each row gets the filter that leaves the smallest values, which compress best
+	let row = image.width as usize * 4;
+	let mut filtered = Vec::with_capacity((row + 1) * image.height as usize);
+	let zeros = vec![0u8; row];
+	let mut candidate = vec![0u8; row];
+	let mut best = vec![0u8; row];
+	for y in 0..image.height as usize {
+		let current = &image.data[y * row..(y + 1) * row];
+		let above = if y == 0 { &zeros[..] } else { &image.data[(y - 1) * row..y * row] };
+
+		let mut best_filter = 0;
+		let mut best_cost = u64::max_value();
+		for filter in 0..5 {
+			for x in 0..row {
+				let left = if x >= 4 { current[x - 4] } else { 0 };
+				let upper_left = if x >= 4 { above[x - 4] } else { 0 };
+				let predicted = match filter {
+					0 => 0,
+					1 => left,
+					2 => above[x],
+					3 => ((left as u16 + above[x] as u16) / 2) as u8,
+					_ => paeth(left, above[x], upper_left),
+				};
+				candidate[x] = current[x].wrapping_sub(predicted);
+			}
+			let cost = candidate.iter().map(|&value| (value as i8 as i16).abs() as u64).sum();
+			if cost < best_cost {
+				best_cost = cost;
+				best_filter = filter;
+				best.copy_from_slice(&candidate);
+			}
+		}
+		filtered.push(best_filter);
+		filtered.extend_from_slice(&best);
+	}
+
+	let mut header = Vec::with_capacity(13);
+	header.extend_from_slice(&be_bytes(image.width));
+	header.extend_from_slice(&be_bytes(image.height));
+	header.extend_from_slice(&[8, RGBA, 0, 0, 0]);
+
+	let mut data = SIGNATURE.to_vec();
+	write_chunk(&mut data, b"IHDR", &header);
+	write_chunk(&mut data, b"IDAT", &zlib_compress(&filtered));
+	write_chunk(&mut data, b"IEND", &[]);
+	data
+}
+
+pub fn save(file_path: &str, image: &RgbaImage) -> io::Result<()> {
+	let mut file = try!(File::create(file_path));
+	file.write_all(&encode(image))
+}
+
+fn write_chunk(data: &mut Vec<u8>, kind: &[u8], body: &[u8]) {
+	data.extend_from_slice(&be_bytes(body.len() as u32));
+	let start = data.len();
+	data.extend_from_slice(kind);
+	data.extend_from_slice(body);
+	let crc = crc32(&data[start..]);
+	data.extend_from_slice(&be_bytes(crc));
+}
+
+// Whichever of left, above and upper left is closest to left + above - upper left
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+	let (a, b, c) = (a as i16, b as i16, c as i16);
+	let p = a + b - c;
+	let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+	(if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }) as u8
+}
+
+fn be_bytes(value: u32) -> [u8; 4] {
+	[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+fn parse_header(body: &[u8]) -> io::Result<Header> {
+	if body.len() != 13 {
+		return Err(invalid("malformed png header"));
+	}
+
+	let header = Header {
+		width: read_u32_be(body, 0),
+		height: read_u32_be(body, 4),
+		bit_depth: body[8],
+		color_type: body[9],
+		interlaced: body[12] == 1,
+	};
+	try!(check_dimensions(header.width, header.height));
+
+	let valid_depth = match header.color_type {
+		GRAYSCALE => [1, 2, 4, 8, 16].contains(&header.bit_depth),
+		PALETTE => [1, 2, 4, 8].contains(&header.bit_depth),
+		RGB | GRAYSCALE_ALPHA | RGBA => header.bit_depth == 8 || header.bit_depth == 16,
+		_ => return Err(invalid("unknown png color type")),
+	};
+	if !valid_depth {
+		return Err(invalid("invalid png bit depth for the color type"));
+	}
+	if body[10] != 0 || body[11] != 0 || body[12] > 1 {
+		return Err(invalid("unknown png compression, filter or interlace method"));
+	}
+
+	Ok(header)
+}
+
+// Undoes the per row filters, returns the rows without filter bytes
+fn unfilter(header: &Header, data: &[u8], width: usize, height: usize) -> io::Result<Vec<u8>> {
+	let row = header.row_bytes(width);
+	// Filters work on whole bytes, the left neighbour is at least one byte back
+	let bpp = ((header.bits_per_pixel() + 7) / 8).max(1);
+	let mut pixels = vec![0u8; row * height];
+
+	for y in 0..height {
+		let filter = data[y * (row + 1)];
+		let source = &data[y * (row + 1) + 1..(y + 1) * (row + 1)];
+		let (previous, current) = pixels.split_at_mut(y * row);
+		let current = &mut current[..row];
+		let above = if y > 0 { &previous[(y - 1) * row..] } else { &[][..] };
+
+		for x in 0..row {
+			let a = if x >= bpp { current[x - bpp] as i16 } else { 0 };
+			let b = if y > 0 { above[x] as i16 } else { 0 };
+			let c = if x >= bpp && y > 0 { above[x - bpp] as i16 } else { 0 };
+
+			let predicted = match filter {
+				0 => 0,
+				1 => a,
+				2 => b,
+				3 => (a + b) / 2,
+				4 => paeth(a as u8, b as u8, c as u8) as i16,
+				_ => return Err(invalid("unknown png filter type")),
+			};
+			current[x] = source[x].wrapping_add(predicted as u8);
+		}
+	}
+
+	Ok(pixels)
+}
+
+// Sample number index of a row, any bit depth, not scaled
+fn sample(header: &Header, row: &[u8], index: usize) -> u16 {
+	match header.bit_depth {
+		16 => (row[index * 2] as u16) << 8 | row[index * 2 + 1] as u16,
+		8 => row[index] as u16,
+		depth => {
+			let depth = depth as usize;
+			let bit = index * depth;
+			let shift = 8 - depth - bit % 8;
+			((row[bit / 8] >> shift) & ((1 << depth) - 1) as u8) as u16
+		},
+	}
+}
+
+fn pixel(header: &Header, pixels: &[u8], width: usize, x: usize, y: usize,
+	palette: &[[u8; 4]], color_key: Option<[u16; 3]>) -> [u8; 4] {
+
+	let row_bytes = header.row_bytes(width);
+	let row = &pixels[y * row_bytes..(y + 1) * row_bytes];
+	let channels = header.channels();
+	let max = (1u32 << header.bit_depth) - 1;
+	let scale = |value: u16| (value as u32 * 255 / max) as u8;
+	let s = |channel: usize| sample(header, row, x * channels + channel);
+
+	match header.color_type {
+		PALETTE => {
+			// Out of range indices are an error in the file, show them black
+			palette.get(s(0) as usize).cloned().unwrap_or([0, 0, 0, 255])
+		},
+		GRAYSCALE => {
+			let gray = s(0);
+			let alpha = if color_key.map_or(false, |key| key[0] == gray) { 0 } else { 255 };
+			let gray = scale(gray);
+			[gray, gray, gray, alpha]
+		},
+		GRAYSCALE_ALPHA => {
+			let gray = scale(s(0));
+			[gray, gray, gray, scale(s(1))]
+		},
+		RGB => {
+			let (r, g, b) = (s(0), s(1), s(2));
+			let alpha = if color_key == Some([r, g, b]) { 0 } else { 255 };
+			[scale(r), scale(g), scale(b), alpha]
+		},
+		_ => [scale(s(0)), scale(s(1)), scale(s(2)), scale(s(3))],
+	}
+}
+
+fn crc32(data: &[u8]) -> u32 {
+	let mut crc = 0xffffffffu32;
+	for &byte in data {
+		crc ^= byte as u32;
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+		}
+	}
+	!crc
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Pseudo random pixels, so every filter gets to produce bytes around 0x80
+	fn noise(width: u32, height: u32) -> RgbaImage {
+		let mut image = RgbaImage::new(width, height);
+		let mut state = 0x2545f491u32;
+		for value in image.data.iter_mut() {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			*value = (state >> 24) as u8;
+		}
+		image
+	}
+
+	#[test]
+	fn encode_round_trips_noise() {
+		let image = noise(37, 23);
+		assert_eq!(decode(&encode(&image)).unwrap(), image);
+	}
+
+	#[test]
+	fn encode_round_trips_gradient() {
+		let mut image = RgbaImage::new(64, 8);
+		for y in 0..8 {
+			for x in 0..64 {
+				image.set_pixel(x, y, [(x * 4) as u8, 0x80, (y * 32) as u8, 255 - x as u8]);
+			}
+		}
+		assert_eq!(decode(&encode(&image)).unwrap(), image);
+	}
+}
@@ -0,0 +1,148 @@
+// Truevision TGA decoder for color mapped, true color and grayscale images,
+// uncompressed or run length encoded.
+
+use std::io;
+
+use super::{check_dimensions, invalid, read_u16_le, truncated, RgbaImage};
+
+const COLOR_MAPPED: u8 = 1;
+const TRUE_COLOR: u8 = 2;
+const GRAYSCALE: u8 = 3;
+const RLE: u8 = 8;		// Added to the types above
+
+pub fn decode(data: &[u8]) -> io::Result<RgbaImage> {
+	if data.len() < 18 {
+		return Err(truncated());
+	}
+
+	let id_length = data[0] as usize;
+	let has_color_map = data[1] == 1;
+	let image_type = data[2];
+	let map_first = read_u16_le(data, 3) as usize;
+	let map_length = read_u16_le(data, 5) as usize;
+	let map_depth = data[7];
+	let width = read_u16_le(data, 12) as u32;
+	let height = read_u16_le(data, 14) as u32;
+	let depth = data[16];
+	let descriptor = data[17];
+	try!(check_dimensions(width, height));
+
+	let kind = image_type & !RLE;
+	let valid = match kind {
+		COLOR_MAPPED => has_color_map && (depth == 8 || depth == 16),
+		TRUE_COLOR => [15, 16, 24, 32].contains(&depth),
+		GRAYSCALE => depth == 8 || depth == 16,
+		_ => false,
+	};
+	if !valid || data[1] > 1 || image_type & !(RLE | 3) != 0 {
+		return Err(invalid("unsupported tga image type or pixel depth"));
+	}
+
+	// The color map may be present even when the image doesn't use it
+	let mut offset = 18 + id_length;
+	let mut color_map = Vec::new();
+	if has_color_map {
+		if ![15, 16, 24, 32].contains(&map_depth) {
+			return Err(invalid("unsupported tga color map depth"));
+		}
+		let entry = (map_depth as usize + 7) / 8;
+		let end = offset + map_length * entry;
+		if end > data.len() {
+			return Err(truncated());
+		}
+		color_map = data[offset..end].chunks(entry).map(|c| color(c, map_depth)).collect();
+		offset = end;
+	}
+
+	// Decode into file order first, RLE packets may run across rows
+	let pixel_size = (depth as usize + 7) / 8;
+	let count = width as usize * height as usize;
+	let mut raw = Vec::with_capacity(count * pixel_size);
+	if image_type & RLE != 0 {
+		while raw.len() < count * pixel_size {
+			if offset >= data.len() {
+				return Err(truncated());
+			}
+			let packet = data[offset];
+			offset += 1;
+			let repeat = (packet & 0x7f) as usize + 1;
+
+			if packet & 0x80 != 0 {
+				if offset + pixel_size > data.len() {
+					return Err(truncated());
+				}
+				for _ in 0..repeat {
+					raw.extend_from_slice(&data[offset..offset + pixel_size]);
+				}
+				offset += pixel_size;
+			} else {
+				let end = offset + repeat * pixel_size;
+				if end > data.len() {
+					return Err(truncated());
+				}
+				raw.extend_from_slice(&data[offset..end]);
+				offset = end;
+			}
+		}
+		raw.truncate(count * pixel_size);
+	} else {
+		let end = offset + count * pixel_size;
+		if end > data.len() {
+			return Err(truncated());
+		}
+		raw.extend_from_slice(&data[offset..end]);
+	}
+
+	let mut pixels: Vec<[u8; 4]> = Vec::with_capacity(count);
+	for bytes in raw.chunks(pixel_size) {
+		pixels.push(match kind {
+			COLOR_MAPPED => {
+				let index = if depth == 8 { bytes[0] as usize } else { read_u16_le(bytes, 0) as usize };
+				match index.checked_sub(map_first).and_then(|index| color_map.get(index)) {
+					Some(&rgba) => rgba,
+					None => return Err(invalid("tga color map index out of range")),
+				}
+			},
+			GRAYSCALE => [bytes[0], bytes[0], bytes[0], if depth == 16 { bytes[1] } else { 255 }],
+			_ => color(bytes, depth),
+		});
+	}
+
+	// Plenty of writers store 32 bit images with an alpha channel that is all zero
+	// while the descriptor claims no alpha bits, those are meant to be opaque.
+	let alpha_bits = descriptor & 0x0f;
+	if alpha_bits == 0 && pixels.iter().all(|pixel| pixel[3] == 0) {
+		for pixel in &mut pixels {
+			pixel[3] = 255;
+		}
+	}
+
+	// Rows are stored bottom up unless bit 5 is set, right to left when bit 4 is set
+	let top_down = descriptor & 0x20 != 0;
+	let right_to_left = descriptor & 0x10 != 0;
+	let mut image = RgbaImage::new(width, height);
+	for y in 0..height {
+		for x in 0..width {
+			let source_x = if right_to_left { width - 1 - x } else { x };
+			let source_y = if top_down { y } else { height - 1 - y };
+			image.set_pixel(x, y, pixels[(source_y * width + source_x) as usize]);
+		}
+	}
+
+	Ok(image)
+}
+
+// BGR(A) color of a pixel or color map entry
+fn color(bytes: &[u8], depth: u8) -> [u8; 4] {
+	match depth {
+		15 | 16 => {
+			// A RRRRR GGGGG BBBBB, the attribute bit is only alpha at 16 bits
+			let value = read_u16_le(bytes, 0);
+			let expand = |v: u16| ((v as u32 * 255 + 15) / 31) as u8;
+			let alpha = if depth == 16 && value & 0x8000 == 0 { 0 } else { 255 };
+			[expand((value >> 10) & 0x1f), expand((value >> 5) & 0x1f), expand(value & 0x1f), alpha]
+		},
+		24 => [bytes[2], bytes[1], bytes[0], 255],
+		_ => [bytes[2], bytes[1], bytes[0], bytes[3]],
+	}
+}
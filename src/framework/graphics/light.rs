@@ -0,0 +1,232 @@
+use std::cmp::Ordering;
+use std::f32;
+
+use framework::core::Transform;
+use framework::math::{Quaternion, Vec3};
+
+// Lights sent to one draw call by default, the lit shader is compiled with arrays of this size
+pub const MAX_LIGHTS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+	// Shines along the forward direction of the transform from infinitely far away
+	Directional,
+	// Shines from the position in every direction and fades out to nothing at range
+	Point { range: f32 },
+	// A point light limited to a cone around the forward direction. The angles are in
+	// degrees from the axis, the light fades out between the inner and the outer angle.
+	Spot { range: f32, inner_angle: f32, outer_angle: f32 },
+}
+
+// Only the position and orientation of the transform are used, the scale is ignored
+pub struct Light {
+	pub kind: LightKind,
+	pub transform: Transform,
+	pub color: Vec3,		// Linear RGB
+	pub intensity: f32,
+	pub enabled: bool,
+}
+
+impl Light {
+	pub fn directional(orientation: Quaternion, color: Vec3, intensity: f32) -> Light {
+		Light::new(LightKind::Directional, Vec3::new(), orientation, color, intensity)
+	}
+
+	pub fn point(position: Vec3, range: f32, color: Vec3, intensity: f32) -> Light {
+		Light::new(LightKind::Point { range: range }, position, Quaternion::new(), color, intensity)
+	}
+
+	pub fn spot(position: Vec3, orientation: Quaternion, range: f32, inner_angle: f32, outer_angle: f32,
+		color: Vec3, intensity: f32) -> Light {
+		let kind = LightKind::Spot { range: range, inner_angle: inner_angle, outer_angle: outer_angle };
+		Light::new(kind, position, orientation, color, intensity)
+	}
+
+	fn new(kind: LightKind, position: Vec3, orientation: Quaternion, color: Vec3, intensity: f32) -> Light {
+		Light {
+			kind: kind,
+			transform: Transform {
+				position: position,
+				scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
+				orientation: orientation,
+			},
+			color: color,
+			intensity: intensity,
+			enabled: true,
+		}
+	}
+
+	pub fn position(&self) -> Vec3 {
+		self.transform.position
+	}
+
+	// The direction the light travels in
+	pub fn direction(&self) -> Vec3 {
+		self.transform.orientation.forward().normalized()
+	}
+
+	// None for directional lights, they reach everything
+	pub fn range(&self) -> Option<f32> {
+		match self.kind {
+			LightKind::Directional => None,
+			LightKind::Point { range } | LightKind::Spot { range, .. } => Some(range),
+		}
+	}
+
+	// Fraction of the light left at a distance, the same falloff as in the lit shader.
+	// It is inverse square and reaches 0 at the range instead of never.
+	pub fn attenuation(&self, distance: f32) -> f32 {
+		match self.range() {
+			Some(range) => attenuation(distance, range),
+			None => 1.0,
+		}
+	}
+
+	// How much this light adds at a point, ignoring surfaces and spot cones.
+	// Directional lights come before everything else.
+	pub fn influence(&self, position: &Vec3) -> f32 {
+		if !self.enabled {
+			return 0.0;
+		}
+		match self.kind {
+			LightKind::Directional => f32::INFINITY,
+			_ => {
+				let brightness = self.color.x.max(self.color.y).max(self.color.z) * self.intensity;
+				brightness * self.attenuation(Vec3::distance(&self.transform.position, position))
+			},
+		}
+	}
+}
+
+pub fn attenuation(distance: f32, range: f32) -> f32 {
+	if range <= 0.0 {
+		return 0.0;
+	}
+	let ratio = distance / range;
+	let window = (1.0 - ratio * ratio * ratio * ratio).max(0.0).min(1.0);
+	window * window / (distance * distance + 1.0)
+}
+
+// The lights of a scene, lights can be added, changed and removed between frames
+pub struct LightSet {
+	pub ambient: Vec3,		// Added to every lit surface, times the ambient color of its material
+	pub lights: Vec<Light>,
+}
+
+impl LightSet {
+	pub fn new() -> LightSet {
+		LightSet {
+			ambient: Vec3 { x: 0.05, y: 0.05, z: 0.05 },
+			lights: Vec::new(),
+		}
+	}
+
+	// Returns the index of the light
+	pub fn add(&mut self, light: Light) -> usize {
+		self.lights.push(light);
+		self.lights.len() - 1
+	}
+
+	// The lights that matter most for an object at position, brightest first and at most max of them.
+	// Disabled lights and lights that are out of range are left out.
+	pub fn select(&self, position: &Vec3, max: usize) -> Vec<&Light> {
+		let mut selected: Vec<(f32, &Light)> = self.lights.iter()
+			.map(|light| (light.influence(position), light))
+			.filter(|&(influence, _)| influence > 0.0)
+			.collect();
+		selected.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+		selected.truncate(max);
+		selected.into_iter().map(|(_, light)| light).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+		Vec3 { x: x, y: y, z: z }
+	}
+
+	fn white() -> Vec3 {
+		vec3(1.0, 1.0, 1.0)
+	}
+
+	#[test]
+	fn attenuation_falloff() {
+		// Inverse square with the +1 near the light, where the window is still 1
+		assert_eq!(attenuation(0.0, 10.0), 1.0);
+		assert!((attenuation(1.0, 1000.0) - 0.5).abs() < 1e-6);
+		assert!((attenuation(3.0, 1000.0) - 0.1).abs() < 1e-6);
+
+		// Nothing at and beyond the range, or without a range
+		assert_eq!(attenuation(10.0, 10.0), 0.0);
+		assert_eq!(attenuation(20.0, 10.0), 0.0);
+		assert_eq!(attenuation(1.0, 0.0), 0.0);
+
+		let samples: Vec<f32> = (0..11).map(|d| attenuation(d as f32, 10.0)).collect();
+		assert!(samples.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", samples);
+	}
+
+	#[test]
+	fn directional_lights_are_not_attenuated() {
+		let light = Light::directional(Quaternion::new(), white(), 1.0);
+		assert_eq!(light.range(), None);
+		assert_eq!(light.attenuation(1e6), 1.0);
+		assert_eq!(Light::point(Vec3::new(), 5.0, white(), 1.0).attenuation(5.0), 0.0);
+	}
+
+	#[test]
+	fn select_drops_disabled_and_out_of_range_lights() {
+		let mut lights = LightSet::new();
+		lights.add(Light::point(vec3(0.0, 0.0, 0.0), 10.0, white(), 1.0));
+		let disabled = lights.add(Light::point(vec3(1.0, 0.0, 0.0), 10.0, white(), 2.0));
+		lights.lights[disabled].enabled = false;
+		lights.add(Light::point(vec3(50.0, 0.0, 0.0), 10.0, white(), 3.0));
+		lights.add(Light::spot(vec3(0.0, 9.0, 0.0), Quaternion::new(), 10.0, 20.0, 30.0, white(), 4.0));
+		lights.add(Light::spot(vec3(0.0, 20.0, 0.0), Quaternion::new(), 10.0, 20.0, 30.0, white(), 5.0));
+
+		let selected: Vec<f32> = lights.select(&Vec3::new(), MAX_LIGHTS).iter().map(|light| light.intensity).collect();
+		assert_eq!(selected, vec![1.0, 4.0]);
+	}
+
+	#[test]
+	fn select_sorts_directional_lights_first() {
+		let mut lights = LightSet::new();
+		lights.add(Light::point(vec3(1.0, 0.0, 0.0), 10.0, white(), 100.0));
+		lights.add(Light::point(vec3(2.0, 0.0, 0.0), 10.0, white(), 1.0));
+		lights.add(Light::directional(Quaternion::new(), white(), 0.1));
+		// The same intensity further away and in a dimmer color counts for less
+		lights.add(Light::point(vec3(5.0, 0.0, 0.0), 10.0, white(), 1.0));
+		lights.add(Light::point(vec3(2.0, 0.0, 0.0), 10.0, vec3(0.5, 0.2, 0.1), 1.0));
+
+		let selected: Vec<(LightKind, f32)> = lights.select(&Vec3::new(), MAX_LIGHTS).iter()
+			.map(|light| (light.kind, light.position().x))
+			.collect();
+		assert_eq!(selected, vec![
+			(LightKind::Directional, 0.0),
+			(LightKind::Point { range: 10.0 }, 1.0),
+			(LightKind::Point { range: 10.0 }, 2.0),
+			(LightKind::Point { range: 10.0 }, 2.0),
+			(LightKind::Point { range: 10.0 }, 5.0),
+		]);
+		assert_eq!(lights.select(&Vec3::new(), MAX_LIGHTS)[3].color, vec3(0.5, 0.2, 0.1));
+	}
+
+	#[test]
+	fn select_caps_at_max() {
+		let mut lights = LightSet::new();
+		for i in 0..12 {
+			lights.add(Light::point(vec3(i as f32, 0.0, 0.0), 100.0, white(), 1.0));
+		}
+		lights.add(Light::directional(Quaternion::new(), white(), 1.0));
+
+		let selected = lights.select(&Vec3::new(), MAX_LIGHTS);
+		assert_eq!(selected.len(), MAX_LIGHTS);
+		assert_eq!(selected[0].kind, LightKind::Directional);
+		// The closest point lights win
+		let distances: Vec<f32> = selected[1..].iter().map(|light| light.position().x).collect();
+		assert_eq!(distances, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+		assert!(lights.select(&Vec3::new(), 0).is_empty());
+	}
+}
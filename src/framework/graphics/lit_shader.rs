@@ -0,0 +1,158 @@
+use std::f32::consts::PI;
+
+use framework::core::{Camera, HotReload, Transform};
+use framework::graphics::{InternalShader, LightKind, LightSet, Material, Shader, ShaderBuilder, ShaderError, Texture};
+use framework::graphics::light::MAX_LIGHTS;
+use framework::math::{Mat3x3, Vec2, Vec3};
+
+pub const VERTEX_SHADER: &'static str = "./assets/shaders/lit.vs.glsl";
+pub const FRAGMENT_SHADER: &'static str = "./assets/shaders/lit.fs.glsl";
+
+// Values of light_types in the shader
+const DIRECTIONAL: i32 = 0;
+const POINT: i32 = 1;
+const SPOT: i32 = 2;
+
+// Forward Blinn-Phong lighting with ambient, diffuse and specular terms. Meshes need
+// normals, a draw goes like:
+// shader.begin();
+// try!(shader.update_uniforms(&transform, &camera, dt));
+// try!(shader.set_material(&material));
+// try!(shader.set_lights(&lights, &transform.position));
+// mesh.draw();
+// shader.end();
+pub struct LitShader {
+	shader: InternalShader,
+	max_lights: usize,
+}
+
+impl LitShader {
+	pub fn new() -> Result<LitShader, ShaderError> {
+		LitShader::with_max_lights(MAX_LIGHTS)
+	}
+
+	// Every draw gets the max_lights lights with the most influence on it
+	pub fn with_max_lights(max_lights: usize) -> Result<LitShader, ShaderError> {
+		let max_lights = max_lights.max(1);
+		let shader = try!(ShaderBuilder::new()
+			.vertex(VERTEX_SHADER)
+			.fragment(FRAGMENT_SHADER)
+			.define("MAX_LIGHTS", &max_lights.to_string())
+			.build());
+
+		Ok(LitShader {
+			shader: shader,
+			max_lights: max_lights,
+		})
+	}
+
+	pub fn max_lights(&self) -> usize {
+		self.max_lights
+	}
+
+	pub fn shader(&self) -> &InternalShader {
+		&self.shader
+	}
+
+	pub fn set_material(&self, material: &Material) -> Result<(), ShaderError> {
+		let shader = &self.shader;
+		try!(shader.set_vec3(&try!(shader.uniform("material_ambient")), material.ambient));
+		try!(shader.set_vec3(&try!(shader.uniform("material_diffuse")), material.diffuse));
+		try!(shader.set_vec3(&try!(shader.uniform("material_specular")), material.specular));
+		try!(shader.set_vec3(&try!(shader.uniform("material_emissive")), material.emissive));
+		try!(shader.set_f32(&try!(shader.uniform("material_shininess")), material.shininess.max(1.0)));
+		shader.set_f32(&try!(shader.uniform("material_opacity")), material.opacity)
+	}
+
+	// Multiplies the diffuse and ambient colors, None to go without
+	pub fn set_diffuse_texture(&self, texture: Option<&Texture>) -> Result<(), ShaderError> {
+		let uniform = try!(self.shader.uniform("use_diffuse_texture"));
+		if let Some(texture) = texture {
+			try!(self.shader.bind_texture("diffuse_texture", texture));
+		}
+		self.shader.set_bool(&uniform, texture.is_some())
+	}
+
+	// Uploads the lights that matter most for an object at position, see LightSet::select
+	pub fn set_lights(&self, lights: &LightSet, position: &Vec3) -> Result<(), ShaderError> {
+		let selected = lights.select(position, self.max_lights);
+		try!(self.shader.set_vec3(&try!(self.shader.uniform("ambient_light")), lights.ambient));
+		try!(self.shader.set_i32(&try!(self.shader.uniform("light_count")), selected.len() as i32));
+		if selected.is_empty() {
+			return Ok(());
+		}
+
+		let mut types = Vec::with_capacity(selected.len());
+		let mut positions = Vec::with_capacity(selected.len());
+		let mut directions = Vec::with_capacity(selected.len());
+		let mut colors = Vec::with_capacity(selected.len());
+		let mut ranges = Vec::with_capacity(selected.len());
+		let mut cones = Vec::with_capacity(selected.len());
+		for light in selected {
+			let (ty, range, cone) = match light.kind {
+				LightKind::Directional => (DIRECTIONAL, 0.0, Vec2 { x: -1.0, y: -1.0 }),
+				LightKind::Point { range } => (POINT, range, Vec2 { x: -1.0, y: -1.0 }),
+				LightKind::Spot { range, inner_angle, outer_angle } => (SPOT, range, cone_cosines(inner_angle, outer_angle)),
+			};
+			types.push(ty);
+			positions.push(light.position());
+			directions.push(light.direction());
+			colors.push(&light.color * light.intensity);
+			ranges.push(range);
+			cones.push(cone);
+		}
+
+		let shader = &self.shader;
+		try!(shader.set_i32_array(&try!(shader.uniform("light_types")), &types));
+		try!(shader.set_vec3_array(&try!(shader.uniform("light_positions")), &positions));
+		try!(shader.set_vec3_array(&try!(shader.uniform("light_directions")), &directions));
+		try!(shader.set_vec3_array(&try!(shader.uniform("light_colors")), &colors));
+		try!(shader.set_f32_array(&try!(shader.uniform("light_ranges")), &ranges));
+		shader.set_vec2_array(&try!(shader.uniform("light_cones")), &cones)
+	}
+}
+
+// Cosines of the outer and the inner angle, kept apart so the shader's smoothstep has an edge to fade over
+fn cone_cosines(inner_angle: f32, outer_angle: f32) -> Vec2 {
+	let outer = outer_angle.max(0.0).min(180.0) * PI / 180.0;
+	let inner = inner_angle.max(0.0).min(outer_angle) * PI / 180.0;
+	let outer_cos = outer.cos();
+	Vec2 { x: outer_cos, y: inner.cos().max(outer_cos + 1.0e-4) }
+}
+
+impl Shader for LitShader {
+	fn init(&mut self) -> Result<(), ShaderError> {
+		Ok(())
+	}
+
+	fn begin(&self) {
+		self.shader.begin();
+	}
+
+	fn end(&self) {
+		self.shader.end();
+	}
+
+	// The matrices of the object and the camera position for the specular term
+	fn update_uniforms(&self, transform: &Transform, camera: &Camera, _dt: f32) -> Result<(), ShaderError> {
+		let model = transform.model();
+		let shader = &self.shader;
+		try!(shader.set_mat4x4(&try!(shader.uniform("mvp")), &transform.mvp(camera)));
+		try!(shader.set_mat4x4(&try!(shader.uniform("model")), &model));
+		try!(shader.set_mat3x3(&try!(shader.uniform("normal_matrix")), &Mat3x3::normal_matrix(&model)));
+		shader.set_vec3(&try!(shader.uniform("camera_position")), camera.position())
+	}
+}
+
+impl HotReload for LitShader {
+	type Error = ShaderError;
+
+	fn source_files(&self) -> Vec<String> {
+		self.shader.source_files()
+	}
+
+	fn reload(&mut self) -> Result<(), ShaderError> {
+		self.shader.reload()
+	}
+}
+
@@ -0,0 +1,1173 @@
+// glTF 2.0 importer for .gltf files with external or embedded buffers and binary .glb files.
+// Importing never touches GL, so assets can be validated without a context. Call
+// GltfAsset::upload once a context is current to create the vertex buffers.
+
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use framework::core::Transform;
+use framework::graphics::{Mesh, MeshError};
+use framework::graphics::loaders::json::{Json, JsonError};
+use framework::graphics::mesh::MAX_UV_CHANNELS;
+use framework::math::{Mat4x4, Quaternion, Vec2, Vec3, Vec4};
+
+#[derive(Debug)]
+pub enum GltfError {
+	Io { path: String, error: io::Error },
+	Json { path: String, error: JsonError },
+	Invalid { path: String, message: String },
+	Unsupported { path: String, message: String },
+}
+
+impl fmt::Display for GltfError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			GltfError::Io { ref path, ref error } => {
+				write!(f, "Could not read glTF asset {}: {}", path, error)
+			},
+			GltfError::Json { ref path, ref error } => {
+				write!(f, "Could not parse glTF asset {}:{}", path, error)
+			},
+			GltfError::Invalid { ref path, ref message } => {
+				write!(f, "Invalid glTF asset {}: {}", path, message)
+			},
+			GltfError::Unsupported { ref path, ref message } => {
+				write!(f, "Unsupported glTF asset {}: {}", path, message)
+			},
+		}
+	}
+}
+
+impl error::Error for GltfError {}
+
+// Mesh data of one draw call. V is flipped to the bottom left origin of the
+// other loaders. Joints and weights are only filled for skinned meshes.
+pub struct GltfPrimitive {
+	pub mesh: Mesh,
+	pub material: Option<usize>,
+	pub joints: Vec<[u16; 4]>,
+	pub weights: Vec<Vec4>,
+}
+
+pub struct GltfMesh {
+	pub name: String,
+	pub primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+	Opaque,
+	Mask,
+	Blend,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TextureRef {
+	pub texture: usize,		// Index into GltfAsset::textures
+	pub uv_channel: usize,
+}
+
+// Metallic-roughness material, factors multiply their textures
+#[derive(Clone, Debug)]
+pub struct GltfMaterial {
+	pub name: String,
+	pub base_color: Vec4,
+	pub base_color_texture: Option<TextureRef>,
+	pub metallic: f32,
+	pub roughness: f32,
+	// Roughness in G, metalness in B
+	pub metallic_roughness_texture: Option<TextureRef>,
+	pub normal_texture: Option<TextureRef>,
+	pub normal_scale: f32,
+	pub occlusion_texture: Option<TextureRef>,
+	pub occlusion_strength: f32,
+	pub emissive: Vec3,
+	pub emissive_texture: Option<TextureRef>,
+	pub alpha_mode: AlphaMode,
+	pub alpha_cutoff: f32,
+	pub double_sided: bool,
+}
+
+impl GltfMaterial {
+	// The default material of primitives without one
+	pub fn new(name: &str) -> GltfMaterial {
+		GltfMaterial {
+			name: name.to_string(),
+			base_color: Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 },
+			base_color_texture: None,
+			metallic: 1.0,
+			roughness: 1.0,
+			metallic_roughness_texture: None,
+			normal_texture: None,
+			normal_scale: 1.0,
+			occlusion_texture: None,
+			occlusion_strength: 1.0,
+			emissive: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+			emissive_texture: None,
+			alpha_mode: AlphaMode::Opaque,
+			alpha_cutoff: 0.5,
+			double_sided: false,
+		}
+	}
+}
+
+pub struct GltfTexture {
+	pub name: String,
+	pub image: Option<usize>,
+	pub sampler: Option<usize>,
+}
+
+// Filters and wrap modes are GL enums, None lets the renderer choose
+#[derive(Clone, Copy, Debug)]
+pub struct GltfSampler {
+	pub mag_filter: Option<u32>,
+	pub min_filter: Option<u32>,
+	pub wrap_s: u32,
+	pub wrap_t: u32,
+}
+
+pub enum ImageSource {
+	File(String),	// Relative to the working directory
+	Embedded { mime_type: String, data: Vec<u8> },
+}
+
+pub struct GltfImage {
+	pub name: String,
+	pub source: ImageSource,
+}
+
+pub struct GltfNode {
+	pub name: String,
+	pub parent: Option<usize>,
+	pub children: Vec<usize>,
+	pub transform: Transform,	// Relative to the parent
+	pub mesh: Option<usize>,
+	pub skin: Option<usize>,
+	pub weights: Vec<f32>,		// Morph target weights
+}
+
+pub struct GltfScene {
+	pub name: String,
+	pub nodes: Vec<usize>,
+}
+
+pub struct GltfSkin {
+	pub name: String,
+	pub joints: Vec<usize>,
+	pub inverse_bind_matrices: Vec<Mat4x4>,
+	pub skeleton: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationProperty {
+	Translation,
+	Rotation,
+	Scale,
+	Weights,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+	Linear,
+	Step,
+	CubicSpline,	// Values are in-tangent, value, out-tangent triplets
+}
+
+// Keyframes of one node property. Rotations are converted to the
+// conjugate convention of math::Quaternion like node transforms.
+pub struct GltfChannel {
+	pub node: usize,
+	pub property: AnimationProperty,
+	pub interpolation: Interpolation,
+	pub times: Vec<f32>,
+	pub values: Vec<f32>,
+}
+
+impl GltfChannel {
+	// Floats per keyframe value, e.g. 4 for rotations
+	pub fn components(&self) -> usize {
+		let per_key = if self.interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+		if self.times.is_empty() { 0 } else { self.values.len() / (self.times.len() * per_key) }
+	}
+}
+
+pub struct GltfAnimation {
+	pub name: String,
+	pub channels: Vec<GltfChannel>,
+}
+
+impl GltfAnimation {
+	// Time of the last keyframe in seconds
+	pub fn duration(&self) -> f32 {
+		self.channels.iter()
+			.filter_map(|channel| channel.times.last().cloned())
+			.fold(0.0, f32::max)
+	}
+}
+
+pub struct GltfAsset {
+	pub meshes: Vec<GltfMesh>,
+	pub materials: Vec<GltfMaterial>,
+	pub textures: Vec<GltfTexture>,
+	pub images: Vec<GltfImage>,
+	pub samplers: Vec<GltfSampler>,
+	pub nodes: Vec<GltfNode>,
+	pub scenes: Vec<GltfScene>,
+	pub scene: Option<usize>,	// Scene to show by default
+	pub skins: Vec<GltfSkin>,
+	pub animations: Vec<GltfAnimation>,
+}
+
+impl GltfAsset {
+	// Uploads every primitive, needs a current GL context
+	pub fn upload(&mut self) -> Result<(), MeshError> {
+		for mesh in &mut self.meshes {
+			for primitive in &mut mesh.primitives {
+				try!(primitive.mesh.upload());
+			}
+		}
+		Ok(())
+	}
+
+	// Nodes of the default scene, or every node without a parent when there are no scenes
+	pub fn root_nodes(&self) -> Vec<usize> {
+		match self.scene.or(if self.scenes.is_empty() { None } else { Some(0) }) {
+			Some(scene) => self.scenes[scene].nodes.clone(),
+			None => (0..self.nodes.len()).filter(|&node| self.nodes[node].parent.is_none()).collect(),
+		}
+	}
+
+	// Model matrix of a node including all of its parents
+	pub fn world_matrix(&self, node: usize) -> Mat4x4 {
+		let model = self.nodes[node].transform.model();
+		match self.nodes[node].parent {
+			Some(parent) => &self.world_matrix(parent) * &model,
+			None => model,
+		}
+	}
+}
+
+// Loads a .gltf or .glb file, buffers and images are resolved relative to it
+pub fn load(path: &str) -> Result<GltfAsset, GltfError> {
+	let data = try!(read(path));
+	parse(path, &data)
+}
+
+// Same as load for a file that is already in memory
+pub fn parse(path: &str, data: &[u8]) -> Result<GltfAsset, GltfError> {
+	let (text, binary) = if data.starts_with(b"glTF") {
+		try!(split_glb(path, data))
+	} else {
+		(data, None)
+	};
+
+	let text = match ::std::str::from_utf8(text) {
+		Ok(text) => text.trim_start_matches('\u{feff}'),
+		Err(_) => return Err(invalid(path, "JSON is not valid UTF-8".to_string())),
+	};
+	let json = try!(Json::parse(text).map_err(|error| GltfError::Json { path: path.to_string(), error: error }));
+
+	let mut importer = Importer {
+		path: path,
+		json: &json,
+		buffers: Vec::new(),
+	};
+	importer.import(binary)
+}
+
+fn read(path: &str) -> Result<Vec<u8>, GltfError> {
+	let to_error = |error| GltfError::Io { path: path.to_string(), error: error };
+
+	let mut file = try!(File::open(path).map_err(&to_error));
+	let mut data = Vec::new();
+	try!(file.read_to_end(&mut data).map_err(&to_error));
+
+	Ok(data)
+}
+
+fn invalid(path: &str, message: String) -> GltfError {
+	GltfError::Invalid { path: path.to_string(), message: message }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+	(data[offset] as u32) | ((data[offset + 1] as u32) << 8) |
+		((data[offset + 2] as u32) << 16) | ((data[offset + 3] as u32) << 24)
+}
+
+// JSON chunk and optional BIN chunk of a .glb file
+fn split_glb<'a>(path: &str, data: &'a [u8]) -> Result<(&'a [u8], Option<&'a [u8]>), GltfError> {
+	if data.len() < 20 {
+		return Err(invalid(path, "GLB header is truncated".to_string()));
+	}
+	let version = read_u32(data, 4);
+	if version != 2 {
+		return Err(GltfError::Unsupported { path: path.to_string(), message: format!("GLB version {}", version) });
+	}
+	let length = read_u32(data, 8) as usize;
+	if length > data.len() {
+		return Err(invalid(path, format!("GLB is {} bytes, header says {}", data.len(), length)));
+	}
+
+	let mut json = None;
+	let mut binary = None;
+	let mut offset = 12;
+	while offset + 8 <= length {
+		let chunk_length = read_u32(data, offset) as usize;
+		let chunk_type = read_u32(data, offset + 4);
+		let start = offset + 8;
+		if start + chunk_length > length {
+			return Err(invalid(path, "GLB chunk is truncated".to_string()));
+		}
+
+		let chunk = &data[start..start + chunk_length];
+		match chunk_type {
+			0x4e4f534a if json.is_none() => json = Some(chunk),
+			0x004e4942 if binary.is_none() => binary = Some(chunk),
+			// Unknown chunks must be ignored
+			_ => {},
+		}
+		// Chunks are padded to 4 bytes
+		offset = start + (chunk_length + 3) / 4 * 4;
+	}
+
+	match json {
+		Some(json) => Ok((json, binary)),
+		None => Err(invalid(path, "GLB has no JSON chunk".to_string())),
+	}
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+	let mut data = Vec::with_capacity(text.len() * 3 / 4);
+	let mut bits: u32 = 0;
+	let mut count = 0;
+
+	for byte in text.bytes() {
+		let value = match byte {
+			b'A'..=b'Z' => byte - b'A',
+			b'a'..=b'z' => byte - b'a' + 26,
+			b'0'..=b'9' => byte - b'0' + 52,
+			b'+' | b'-' => 62,
+			b'/' | b'_' => 63,
+			b'=' => break,
+			b' ' | b'\t' | b'\r' | b'\n' => continue,
+			_ => return None,
+		};
+
+		bits = (bits << 6) | value as u32;
+		count += 6;
+		if count >= 8 {
+			count -= 8;
+			data.push((bits >> count) as u8);
+		}
+	}
+
+	Some(data)
+}
+
+fn decode_percent(uri: &str) -> String {
+	let bytes = uri.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+
+	while i < bytes.len() {
+		if bytes[i] == b'%' && i + 2 < bytes.len() {
+			let hex = ::std::str::from_utf8(&bytes[i + 1..i + 3]).ok()
+				.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+			if let Some(byte) = hex {
+				decoded.push(byte);
+				i += 3;
+				continue;
+			}
+		}
+		decoded.push(bytes[i]);
+		i += 1;
+	}
+
+	String::from_utf8_lossy(&decoded).into_owned()
+}
+
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+fn component_size(ty: u32) -> usize {
+	match ty {
+		BYTE | UNSIGNED_BYTE => 1,
+		SHORT | UNSIGNED_SHORT => 2,
+		_ => 4,
+	}
+}
+
+fn component_f32(ty: u32, data: &[u8], normalized: bool) -> f32 {
+	match ty {
+		BYTE if normalized => (data[0] as i8 as f32 / 127.0).max(-1.0),
+		BYTE => data[0] as i8 as f32,
+		UNSIGNED_BYTE if normalized => data[0] as f32 / 255.0,
+		UNSIGNED_BYTE => data[0] as f32,
+		SHORT | UNSIGNED_SHORT => {
+			let value = (data[0] as u16) | ((data[1] as u16) << 8);
+			match (ty, normalized) {
+				(SHORT, true) => (value as i16 as f32 / 32767.0).max(-1.0),
+				(SHORT, false) => value as i16 as f32,
+				(_, true) => value as f32 / 65535.0,
+				_ => value as f32,
+			}
+		},
+		UNSIGNED_INT => read_u32(data, 0) as f32,
+		_ => f32::from_bits(read_u32(data, 0)),
+	}
+}
+
+fn component_u32(ty: u32, data: &[u8], _: bool) -> u32 {
+	match ty {
+		BYTE | UNSIGNED_BYTE => data[0] as u32,
+		SHORT | UNSIGNED_SHORT => (data[0] as u32) | ((data[1] as u32) << 8),
+		UNSIGNED_INT => read_u32(data, 0),
+		_ => f32::from_bits(read_u32(data, 0)) as u32,
+	}
+}
+
+struct Accessor {
+	view: Option<usize>,
+	offset: usize,
+	ty: u32,
+	normalized: bool,
+	count: usize,
+	components: usize,
+}
+
+struct Importer<'a> {
+	path: &'a str,
+	json: &'a Json,
+	buffers: Vec<Vec<u8>>,
+}
+
+impl<'a> Importer<'a> {
+	fn import(&mut self, binary: Option<&[u8]>) -> Result<GltfAsset, GltfError> {
+		let version = self.json.get("asset").and_then(|asset| asset.get("version")).and_then(Json::as_str);
+		match version {
+			Some(version) if version.starts_with("2.") => {},
+			Some(version) => return Err(self.unsupported(format!("glTF version {}", version))),
+			None => return Err(self.invalid("asset.version is missing".to_string())),
+		}
+		if let Some(extension) = self.list("extensionsRequired").iter().filter_map(Json::as_str).next() {
+			return Err(self.unsupported(format!("required extension {}", extension)));
+		}
+
+		try!(self.load_buffers(binary));
+
+		let mut meshes = Vec::new();
+		for mesh in self.list("meshes") {
+			meshes.push(try!(self.mesh(mesh)));
+		}
+		let mut materials = Vec::new();
+		for material in self.list("materials") {
+			materials.push(try!(self.material(material)));
+		}
+		let mut textures = Vec::new();
+		for texture in self.list("textures") {
+			textures.push(GltfTexture {
+				name: name(texture),
+				image: try!(self.optional_index(texture, "source", "images")),
+				sampler: try!(self.optional_index(texture, "sampler", "samplers")),
+			});
+		}
+		let mut images = Vec::new();
+		for image in self.list("images") {
+			images.push(try!(self.image(image)));
+		}
+		let mut samplers = Vec::new();
+		for sampler in self.list("samplers") {
+			samplers.push(GltfSampler {
+				mag_filter: sampler.get("magFilter").and_then(Json::as_usize).map(|filter| filter as u32),
+				min_filter: sampler.get("minFilter").and_then(Json::as_usize).map(|filter| filter as u32),
+				wrap_s: sampler.get("wrapS").and_then(Json::as_usize).unwrap_or(10497) as u32,
+				wrap_t: sampler.get("wrapT").and_then(Json::as_usize).unwrap_or(10497) as u32,
+			});
+		}
+		let nodes = try!(self.nodes());
+		let mut scenes = Vec::new();
+		for scene in self.list("scenes") {
+			scenes.push(GltfScene {
+				name: name(scene),
+				nodes: try!(self.indices(scene, "nodes", "nodes")),
+			});
+		}
+		let mut skins = Vec::new();
+		for skin in self.list("skins") {
+			skins.push(try!(self.skin(skin)));
+		}
+		let mut animations = Vec::new();
+		for animation in self.list("animations") {
+			animations.push(try!(self.animation(animation)));
+		}
+
+		Ok(GltfAsset {
+			meshes: meshes,
+			materials: materials,
+			textures: textures,
+			images: images,
+			samplers: samplers,
+			nodes: nodes,
+			scenes: scenes,
+			scene: try!(self.optional_index(self.json, "scene", "scenes")),
+			skins: skins,
+			animations: animations,
+		})
+	}
+
+	fn load_buffers(&mut self, binary: Option<&[u8]>) -> Result<(), GltfError> {
+		for (index, buffer) in self.list("buffers").iter().enumerate() {
+			let length = try!(self.required_usize(buffer, "byteLength"));
+			let data = match buffer.get("uri").and_then(Json::as_str) {
+				Some(uri) => try!(self.resolve_uri(uri)),
+				// Only the first buffer of a .glb may leave out the uri
+				None => match binary {
+					Some(binary) if index == 0 => binary.to_vec(),
+					_ => return Err(self.invalid(format!("buffer {} has no uri", index))),
+				},
+			};
+			if data.len() < length {
+				return Err(self.invalid(format!("buffer {} has {} bytes, expected {}", index, data.len(), length)));
+			}
+			self.buffers.push(data);
+		}
+
+		Ok(())
+	}
+
+	// data: URIs with base64 payloads or paths relative to the asset
+	fn resolve_uri(&self, uri: &str) -> Result<Vec<u8>, GltfError> {
+		if uri.starts_with("data:") {
+			let comma = match uri.find(',') {
+				Some(comma) => comma,
+				None => return Err(self.invalid("malformed data URI".to_string())),
+			};
+			if !uri[..comma].ends_with(";base64") {
+				return Err(self.unsupported("data URIs without base64 encoding".to_string()));
+			}
+			return decode_base64(&uri[comma + 1..]).ok_or_else(|| self.invalid("malformed base64 data".to_string()));
+		}
+
+		read(&self.relative(uri))
+	}
+
+	fn relative(&self, uri: &str) -> String {
+		let name = decode_percent(uri);
+		match Path::new(self.path).parent() {
+			Some(directory) => directory.join(name).to_string_lossy().into_owned(),
+			None => name,
+		}
+	}
+
+	fn image(&self, image: &Json) -> Result<GltfImage, GltfError> {
+		let source = match image.get("uri").and_then(Json::as_str) {
+			Some(uri) if uri.starts_with("data:") => {
+				let mime_type = uri["data:".len()..].split(|c| c == ';' || c == ',').next().unwrap_or("");
+				ImageSource::Embedded {
+					mime_type: mime_type.to_string(),
+					data: try!(self.resolve_uri(uri)),
+				}
+			},
+			Some(uri) => ImageSource::File(self.relative(uri)),
+			None => {
+				let view = try!(self.required_index(image, "bufferView", "bufferViews"));
+				let (data, _) = try!(self.buffer_view(view));
+				ImageSource::Embedded {
+					mime_type: image.get("mimeType").and_then(Json::as_str).unwrap_or("").to_string(),
+					data: data.to_vec(),
+				}
+			},
+		};
+
+		Ok(GltfImage {
+			name: name(image),
+			source: source,
+		})
+	}
+
+	fn mesh(&self, mesh: &Json) -> Result<GltfMesh, GltfError> {
+		let mut primitives = Vec::new();
+		for primitive in self.array(mesh, "primitives") {
+			primitives.push(try!(self.primitive(primitive)));
+		}
+
+		Ok(GltfMesh {
+			name: name(mesh),
+			primitives: primitives,
+		})
+	}
+
+	fn primitive(&self, primitive: &Json) -> Result<GltfPrimitive, GltfError> {
+		let attributes = match primitive.get("attributes") {
+			Some(attributes) => attributes,
+			None => return Err(self.invalid("primitive without attributes".to_string())),
+		};
+		let attribute = |name: &str| -> Result<Option<usize>, GltfError> {
+			self.optional_index(attributes, name, "accessors")
+		};
+
+		let mut mesh = Mesh::new();
+
+		let positions = match try!(attribute("POSITION")) {
+			Some(accessor) => try!(self.floats(accessor, &[3])),
+			None => return Err(self.unsupported("primitives without positions".to_string())),
+		};
+		mesh.positions = positions.chunks(3).map(|p| Vec3 { x: p[0], y: p[1], z: p[2] }).collect();
+		let count = mesh.positions.len();
+
+		if let Some(accessor) = try!(attribute("NORMAL")) {
+			let normals = try!(self.vertex_floats(accessor, &[3], count));
+			mesh.normals = normals.chunks(3).map(|n| Vec3 { x: n[0], y: n[1], z: n[2] }).collect();
+		}
+		if let Some(accessor) = try!(attribute("TANGENT")) {
+			let tangents = try!(self.vertex_floats(accessor, &[4], count));
+			// Flipping V flips the bitangent
+			mesh.tangents = tangents.chunks(4).map(|t| Vec4 { x: t[0], y: t[1], z: t[2], w: -t[3] }).collect();
+		}
+		for channel in 0..MAX_UV_CHANNELS {
+			let uvs = match try!(attribute(&format!("TEXCOORD_{}", channel))) {
+				Some(accessor) => try!(self.vertex_floats(accessor, &[2], count)),
+				None => continue,
+			};
+			while mesh.uvs.len() < channel {
+				mesh.uvs.push(Vec::new());
+			}
+			mesh.uvs.push(uvs.chunks(2).map(|uv| Vec2 { x: uv[0], y: 1.0 - uv[1] }).collect());
+		}
+		if let Some(accessor) = try!(attribute("COLOR_0")) {
+			let components = try!(self.accessor(accessor)).components;
+			let colors = try!(self.vertex_floats(accessor, &[3, 4], count));
+			mesh.colors = colors.chunks(components).map(|c| {
+				Vec4 { x: c[0], y: c[1], z: c[2], w: if components == 4 { c[3] } else { 1.0 } }
+			}).collect();
+		}
+
+		let mut joints = Vec::new();
+		let mut weights = Vec::new();
+		if let Some(accessor) = try!(attribute("JOINTS_0")) {
+			let values = try!(self.read(accessor, &[4], component_u32));
+			if values.len() != count * 4 {
+				return Err(self.invalid(format!("accessor {} has {} joints for {} vertices", accessor, values.len() / 4, count)));
+			}
+			joints = values.chunks(4).map(|j| [j[0] as u16, j[1] as u16, j[2] as u16, j[3] as u16]).collect();
+		}
+		if let Some(accessor) = try!(attribute("WEIGHTS_0")) {
+			let values = try!(self.vertex_floats(accessor, &[4], count));
+			weights = values.chunks(4).map(|w| Vec4 { x: w[0], y: w[1], z: w[2], w: w[3] }).collect();
+		}
+
+		let indices = match try!(self.optional_index(primitive, "indices", "accessors")) {
+			Some(accessor) => try!(self.read(accessor, &[1], component_u32)),
+			None => (0..count as u32).collect(),
+		};
+		if let Some(&index) = indices.iter().find(|&&index| index as usize >= count) {
+			return Err(self.invalid(format!("vertex index {} out of range, {} vertices", index, count)));
+		}
+
+		mesh.indices = match primitive.get("mode").and_then(Json::as_usize).unwrap_or(4) {
+			4 => indices,
+			// Strips alternate their winding
+			5 => (2..indices.len()).flat_map(|i| if i % 2 == 0 {
+				vec![indices[i - 2], indices[i - 1], indices[i]]
+			} else {
+				vec![indices[i - 1], indices[i - 2], indices[i]]
+			}).collect(),
+			6 => (2..indices.len()).flat_map(|i| vec![indices[0], indices[i - 1], indices[i]]).collect(),
+			mode => return Err(self.unsupported(format!("primitive mode {}, only triangles are supported", mode))),
+		};
+
+		if mesh.normals.is_empty() {
+			mesh.compute_normals();
+		}
+		if mesh.tangents.is_empty() {
+			mesh.compute_tangents();
+		}
+
+		Ok(GltfPrimitive {
+			mesh: mesh,
+			material: try!(self.optional_index(primitive, "material", "materials")),
+			joints: joints,
+			weights: weights,
+		})
+	}
+
+	fn material(&self, material: &Json) -> Result<GltfMaterial, GltfError> {
+		let mut result = GltfMaterial::new(&name(material));
+
+		if let Some(pbr) = material.get("pbrMetallicRoughness") {
+			let color = try!(self.numbers(pbr, "baseColorFactor", &[1.0, 1.0, 1.0, 1.0]));
+			result.base_color = Vec4 { x: color[0], y: color[1], z: color[2], w: color[3] };
+			result.base_color_texture = try!(self.texture_ref(pbr, "baseColorTexture"));
+			result.metallic = try!(self.number(pbr, "metallicFactor", 1.0));
+			result.roughness = try!(self.number(pbr, "roughnessFactor", 1.0));
+			result.metallic_roughness_texture = try!(self.texture_ref(pbr, "metallicRoughnessTexture"));
+		}
+
+		result.normal_texture = try!(self.texture_ref(material, "normalTexture"));
+		if let Some(normal) = material.get("normalTexture") {
+			result.normal_scale = try!(self.number(normal, "scale", 1.0));
+		}
+		result.occlusion_texture = try!(self.texture_ref(material, "occlusionTexture"));
+		if let Some(occlusion) = material.get("occlusionTexture") {
+			result.occlusion_strength = try!(self.number(occlusion, "strength", 1.0));
+		}
+		let emissive = try!(self.numbers(material, "emissiveFactor", &[0.0, 0.0, 0.0]));
+		result.emissive = Vec3 { x: emissive[0], y: emissive[1], z: emissive[2] };
+		result.emissive_texture = try!(self.texture_ref(material, "emissiveTexture"));
+
+		result.alpha_mode = match material.get("alphaMode").and_then(Json::as_str) {
+			None | Some("OPAQUE") => AlphaMode::Opaque,
+			Some("MASK") => AlphaMode::Mask,
+			Some("BLEND") => AlphaMode::Blend,
+			Some(mode) => return Err(self.invalid(format!("unknown alpha mode {}", mode))),
+		};
+		result.alpha_cutoff = try!(self.number(material, "alphaCutoff", 0.5));
+		result.double_sided = material.get("doubleSided").and_then(Json::as_bool).unwrap_or(false);
+
+		Ok(result)
+	}
+
+	fn texture_ref(&self, json: &Json, key: &str) -> Result<Option<TextureRef>, GltfError> {
+		let info = match json.get(key) {
+			Some(info) => info,
+			None => return Ok(None),
+		};
+
+		Ok(Some(TextureRef {
+			texture: try!(self.required_index(info, "index", "textures")),
+			uv_channel: info.get("texCoord").and_then(Json::as_usize).unwrap_or(0),
+		}))
+	}
+
+	fn nodes(&self) -> Result<Vec<GltfNode>, GltfError> {
+		let mut nodes = Vec::new();
+		for node in self.list("nodes") {
+			nodes.push(GltfNode {
+				name: name(node),
+				parent: None,
+				children: try!(self.indices(node, "children", "nodes")),
+				transform: try!(self.transform(node)),
+				mesh: try!(self.optional_index(node, "mesh", "meshes")),
+				skin: try!(self.optional_index(node, "skin", "skins")),
+				weights: try!(self.numbers(node, "weights", &[])),
+			});
+		}
+
+		for index in 0..nodes.len() {
+			for child in nodes[index].children.clone() {
+				if nodes[child].parent.is_some() {
+					return Err(self.invalid(format!("node {} has more than one parent", child)));
+				}
+				nodes[child].parent = Some(index);
+			}
+		}
+
+		// A node that is its own ancestor would make world_matrix recurse forever
+		for index in 0..nodes.len() {
+			let mut current = nodes[index].parent;
+			let mut depth = 0;
+			while let Some(parent) = current {
+				depth += 1;
+				if parent == index || depth > nodes.len() {
+					return Err(self.invalid(format!("node {} is its own ancestor", index)));
+				}
+				current = nodes[parent].parent;
+			}
+		}
+
+		Ok(nodes)
+	}
+
+	// glTF quaternions rotate the other way than math::Quaternion, hence the negated axis
+	fn transform(&self, node: &Json) -> Result<Transform, GltfError> {
+		if node.get("matrix").is_some() {
+			let m = try!(self.numbers(node, "matrix", &[]));
+			if m.len() != 16 {
+				return Err(self.invalid("node matrix needs 16 numbers".to_string()));
+			}
+			return Ok(decompose(&m));
+		}
+
+		let t = try!(self.numbers(node, "translation", &[0.0, 0.0, 0.0]));
+		let r = try!(self.numbers(node, "rotation", &[0.0, 0.0, 0.0, 1.0]));
+		let s = try!(self.numbers(node, "scale", &[1.0, 1.0, 1.0]));
+		if t.len() != 3 || r.len() != 4 || s.len() != 3 {
+			return Err(self.invalid("node translation, rotation or scale has the wrong size".to_string()));
+		}
+
+		Ok(Transform {
+			position: Vec3 { x: t[0], y: t[1], z: t[2] },
+			scale: Vec3 { x: s[0], y: s[1], z: s[2] },
+			orientation: Quaternion { x: -r[0], y: -r[1], z: -r[2], w: r[3] },
+		})
+	}
+
+	fn skin(&self, skin: &Json) -> Result<GltfSkin, GltfError> {
+		let joints = try!(self.indices(skin, "joints", "nodes"));
+		if joints.is_empty() {
+			return Err(self.invalid("skin without joints".to_string()));
+		}
+
+		let inverse_bind_matrices = match try!(self.optional_index(skin, "inverseBindMatrices", "accessors")) {
+			Some(accessor) => {
+				let values = try!(self.floats(accessor, &[16]));
+				if values.len() != joints.len() * 16 {
+					return Err(self.invalid(format!("skin has {} joints but {} inverse bind matrices", joints.len(), values.len() / 16)));
+				}
+				// Column major in the file, row major in Mat4x4
+				values.chunks(16).map(|c| Mat4x4 { m: [
+					c[0], c[4], c[8], c[12],
+					c[1], c[5], c[9], c[13],
+					c[2], c[6], c[10], c[14],
+					c[3], c[7], c[11], c[15],
+				]}).collect()
+			},
+			None => joints.iter().map(|_| Mat4x4::identity()).collect(),
+		};
+
+		Ok(GltfSkin {
+			name: name(skin),
+			joints: joints,
+			inverse_bind_matrices: inverse_bind_matrices,
+			skeleton: try!(self.optional_index(skin, "skeleton", "nodes")),
+		})
+	}
+
+	fn animation(&self, animation: &Json) -> Result<GltfAnimation, GltfError> {
+		let samplers = self.array(animation, "samplers");
+		let mut channels = Vec::new();
+
+		for channel in self.array(animation, "channels") {
+			let target = match channel.get("target") {
+				Some(target) => target,
+				None => return Err(self.invalid("animation channel without target".to_string())),
+			};
+			// Channels without a node are meant for extensions
+			let node = match try!(self.optional_index(target, "node", "nodes")) {
+				Some(node) => node,
+				None => continue,
+			};
+			let property = match target.get("path").and_then(Json::as_str) {
+				Some("translation") => AnimationProperty::Translation,
+				Some("rotation") => AnimationProperty::Rotation,
+				Some("scale") => AnimationProperty::Scale,
+				Some("weights") => AnimationProperty::Weights,
+				Some(path) => return Err(self.unsupported(format!("animation path {}", path))),
+				None => return Err(self.invalid("animation target without path".to_string())),
+			};
+
+			let sampler_index = match channel.get("sampler").and_then(Json::as_usize) {
+				Some(index) if index < samplers.len() => index,
+				_ => return Err(self.invalid("animation channel with invalid sampler".to_string())),
+			};
+			let sampler = &samplers[sampler_index];
+			let interpolation = match sampler.get("interpolation").and_then(Json::as_str) {
+				None | Some("LINEAR") => Interpolation::Linear,
+				Some("STEP") => Interpolation::Step,
+				Some("CUBICSPLINE") => Interpolation::CubicSpline,
+				Some(interpolation) => return Err(self.invalid(format!("unknown interpolation {}", interpolation))),
+			};
+
+			let input = try!(self.required_index(sampler, "input", "accessors"));
+			let output = try!(self.required_index(sampler, "output", "accessors"));
+			let times = try!(self.floats(input, &[1]));
+			let components: &[usize] = match property {
+				AnimationProperty::Translation | AnimationProperty::Scale => &[3],
+				AnimationProperty::Rotation => &[4],
+				AnimationProperty::Weights => &[1],
+			};
+			let mut values = try!(self.floats(output, components));
+
+			let per_key = if interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+			if times.is_empty() || values.len() % (times.len() * per_key) != 0 {
+				return Err(self.invalid(format!("animation sampler {} has {} values for {} keyframes",
+					sampler_index, values.len(), times.len())));
+			}
+			if property == AnimationProperty::Rotation {
+				for rotation in values.chunks_mut(4) {
+					rotation[0] = -rotation[0];
+					rotation[1] = -rotation[1];
+					rotation[2] = -rotation[2];
+				}
+			}
+
+			channels.push(GltfChannel {
+				node: node,
+				property: property,
+				interpolation: interpolation,
+				times: times,
+				values: values,
+			});
+		}
+
+		Ok(GltfAnimation {
+			name: name(animation),
+			channels: channels,
+		})
+	}
+
+	fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), GltfError> {
+		let view = &self.list("bufferViews")[index];
+		let buffer = try!(self.required_index(view, "buffer", "buffers"));
+		let offset = view.get("byteOffset").and_then(Json::as_usize).unwrap_or(0);
+		let length = try!(self.required_usize(view, "byteLength"));
+		let data = &self.buffers[buffer];
+		if offset + length > data.len() {
+			return Err(self.invalid(format!("buffer view {} exceeds buffer {}", index, buffer)));
+		}
+
+		Ok((&data[offset..offset + length], view.get("byteStride").and_then(Json::as_usize)))
+	}
+
+	fn accessor(&self, index: usize) -> Result<Accessor, GltfError> {
+		let accessor = &self.list("accessors")[index];
+		let ty = try!(self.required_usize(accessor, "componentType")) as u32;
+		if ty < BYTE || ty > FLOAT || ty == 5124 {
+			return Err(self.invalid(format!("accessor {} has unknown component type {}", index, ty)));
+		}
+		let components = match accessor.get("type").and_then(Json::as_str) {
+			Some("SCALAR") => 1,
+			Some("VEC2") => 2,
+			Some("VEC3") => 3,
+			Some("VEC4") | Some("MAT2") => 4,
+			Some("MAT3") => 9,
+			Some("MAT4") => 16,
+			_ => return Err(self.invalid(format!("accessor {} has an unknown type", index))),
+		};
+
+		Ok(Accessor {
+			view: try!(self.optional_index(accessor, "bufferView", "bufferViews")),
+			offset: accessor.get("byteOffset").and_then(Json::as_usize).unwrap_or(0),
+			ty: ty,
+			normalized: accessor.get("normalized").and_then(Json::as_bool).unwrap_or(false),
+			count: try!(self.required_usize(accessor, "count")),
+			components: components,
+		})
+	}
+
+	// Every component of an accessor, sparse substitution applied
+	fn read<T: Copy>(&self, index: usize, components: &[usize],
+		convert: fn(u32, &[u8], bool) -> T) -> Result<Vec<T>, GltfError> {
+
+		let accessor = try!(self.accessor(index));
+		if !components.contains(&accessor.components) {
+			return Err(self.invalid(format!("accessor {} has {} components, expected {:?}",
+				index, accessor.components, components)));
+		}
+
+		let size = component_size(accessor.ty);
+		let element = size * accessor.components;
+		let zero = [0u8; 4];
+		let mut values = Vec::with_capacity(accessor.count * accessor.components);
+
+		match accessor.view {
+			Some(view) => {
+				let (data, stride) = try!(self.buffer_view(view));
+				let stride = stride.unwrap_or(element);
+				if accessor.count > 0 && accessor.offset + stride * (accessor.count - 1) + element > data.len() {
+					return Err(self.invalid(format!("accessor {} exceeds buffer view {}", index, view)));
+				}
+				for i in 0..accessor.count {
+					let start = accessor.offset + i * stride;
+					for c in 0..accessor.components {
+						values.push(convert(accessor.ty, &data[start + c * size..], accessor.normalized));
+					}
+				}
+			},
+			// Without a view everything is zero, usually overridden by sparse values
+			None => {
+				let value = convert(accessor.ty, &zero, accessor.normalized);
+				values.resize(accessor.count * accessor.components, value);
+			},
+		}
+
+		if let Some(sparse) = self.list("accessors")[index].get("sparse") {
+			try!(self.apply_sparse(index, sparse, &accessor, &mut values, convert));
+		}
+
+		Ok(values)
+	}
+
+	fn apply_sparse<T: Copy>(&self, index: usize, sparse: &Json, accessor: &Accessor,
+		values: &mut Vec<T>, convert: fn(u32, &[u8], bool) -> T) -> Result<(), GltfError> {
+
+		let count = try!(self.required_usize(sparse, "count"));
+		let (indices, values_json) = match (sparse.get("indices"), sparse.get("values")) {
+			(Some(indices), Some(values)) => (indices, values),
+			_ => return Err(self.invalid(format!("sparse accessor {} lacks indices or values", index))),
+		};
+
+		let index_type = try!(self.required_usize(indices, "componentType")) as u32;
+		let index_view = try!(self.required_index(indices, "bufferView", "bufferViews"));
+		let index_offset = indices.get("byteOffset").and_then(Json::as_usize).unwrap_or(0);
+		let (index_data, _) = try!(self.buffer_view(index_view));
+
+		let value_view = try!(self.required_index(values_json, "bufferView", "bufferViews"));
+		let value_offset = values_json.get("byteOffset").and_then(Json::as_usize).unwrap_or(0);
+		let (value_data, _) = try!(self.buffer_view(value_view));
+
+		let index_size = component_size(index_type);
+		let size = component_size(accessor.ty);
+		let element = size * accessor.components;
+		if index_offset + count * index_size > index_data.len() || value_offset + count * element > value_data.len() {
+			return Err(self.invalid(format!("sparse accessor {} exceeds its buffer views", index)));
+		}
+
+		for i in 0..count {
+			let target = component_u32(index_type, &index_data[index_offset + i * index_size..], false) as usize;
+			if target >= accessor.count {
+				return Err(self.invalid(format!("sparse accessor {} replaces element {} of {}", index, target, accessor.count)));
+			}
+			for c in 0..accessor.components {
+				let start = value_offset + i * element + c * size;
+				values[target * accessor.components + c] = convert(accessor.ty, &value_data[start..], accessor.normalized);
+			}
+		}
+
+		Ok(())
+	}
+
+	fn floats(&self, index: usize, components: &[usize]) -> Result<Vec<f32>, GltfError> {
+		self.read(index, components, component_f32)
+	}
+
+	// Floats of a vertex attribute, one element per vertex
+	fn vertex_floats(&self, index: usize, components: &[usize], count: usize) -> Result<Vec<f32>, GltfError> {
+		let values = try!(self.floats(index, components));
+		let accessor = try!(self.accessor(index));
+		if accessor.count != count {
+			return Err(self.invalid(format!("accessor {} has {} elements for {} vertices", index, accessor.count, count)));
+		}
+		Ok(values)
+	}
+
+	// Top level array such as "meshes", empty when missing
+	fn list(&self, key: &str) -> &'a [Json] {
+		self.array(self.json, key)
+	}
+
+	fn array(&self, json: &'a Json, key: &str) -> &'a [Json] {
+		json.get(key).and_then(Json::as_array).unwrap_or(&[])
+	}
+
+	fn required_usize(&self, json: &Json, key: &str) -> Result<usize, GltfError> {
+		json.get(key).and_then(Json::as_usize)
+			.ok_or_else(|| self.invalid(format!("{} is missing or not a whole number", key)))
+	}
+
+	// Index into the top level array named list, checked against its length
+	fn optional_index(&self, json: &Json, key: &str, list: &str) -> Result<Option<usize>, GltfError> {
+		match json.get(key) {
+			None => Ok(None),
+			Some(value) => match value.as_usize() {
+				Some(index) if index < self.list(list).len() => Ok(Some(index)),
+				_ => Err(self.invalid(format!("{} does not refer to one of the {} {}", key, self.list(list).len(), list))),
+			},
+		}
+	}
+
+	fn required_index(&self, json: &Json, key: &str, list: &str) -> Result<usize, GltfError> {
+		match try!(self.optional_index(json, key, list)) {
+			Some(index) => Ok(index),
+			None => Err(self.invalid(format!("{} is missing", key))),
+		}
+	}
+
+	fn indices(&self, json: &Json, key: &str, list: &str) -> Result<Vec<usize>, GltfError> {
+		let count = self.list(list).len();
+		self.array(json, key).iter().map(|value| match value.as_usize() {
+			Some(index) if index < count => Ok(index),
+			_ => Err(self.invalid(format!("{} does not refer to one of the {} {}", key, count, list))),
+		}).collect()
+	}
+
+	fn number(&self, json: &Json, key: &str, default: f32) -> Result<f32, GltfError> {
+		match json.get(key) {
+			None => Ok(default),
+			Some(value) => value.as_f64().map(|value| value as f32)
+				.ok_or_else(|| self.invalid(format!("{} is not a number", key))),
+		}
+	}
+
+	fn numbers(&self, json: &Json, key: &str, default: &[f32]) -> Result<Vec<f32>, GltfError> {
+		let values = match json.get(key) {
+			None => return Ok(default.to_vec()),
+			Some(values) => values,
+		};
+
+		let numbers: Option<Vec<f32>> = values.as_array()
+			.map(|values| values.iter().map(|value| value.as_f64().map(|value| value as f32)).collect())
+			.unwrap_or(None);
+		match numbers {
+			Some(ref numbers) if default.is_empty() || numbers.len() == default.len() => Ok(numbers.clone()),
+			_ => Err(self.invalid(format!("{} must be an array of {} numbers", key, default.len()))),
+		}
+	}
+
+	fn invalid(&self, message: String) -> GltfError {
+		invalid(self.path, message)
+	}
+
+	fn unsupported(&self, message: String) -> GltfError {
+		GltfError::Unsupported { path: self.path.to_string(), message: message }
+	}
+}
+
+fn name(json: &Json) -> String {
+	json.get("name").and_then(Json::as_str).unwrap_or("").to_string()
+}
+
+// Splits a column major matrix into translation, rotation and scale, shear is lost
+fn decompose(m: &[f32]) -> Transform {
+	let length = |x: f32, y: f32, z: f32| (x * x + y * y + z * z).sqrt();
+	let mut scale = Vec3 {
+		x: length(m[0], m[1], m[2]),
+		y: length(m[4], m[5], m[6]),
+		z: length(m[8], m[9], m[10]),
+	};
+
+	// A negative determinant means one axis is mirrored
+	let determinant = m[0] * (m[5] * m[10] - m[6] * m[9]) -
+		m[4] * (m[1] * m[10] - m[2] * m[9]) +
+		m[8] * (m[1] * m[6] - m[2] * m[5]);
+	if determinant < 0.0 {
+		scale.x = -scale.x;
+	}
+
+	// Rotation part, r[row][column]
+	let divide = |value: f32, s: f32| if s != 0.0 { value / s } else { 0.0 };
+	let r = [
+		[divide(m[0], scale.x), divide(m[4], scale.y), divide(m[8], scale.z)],
+		[divide(m[1], scale.x), divide(m[5], scale.y), divide(m[9], scale.z)],
+		[divide(m[2], scale.x), divide(m[6], scale.y), divide(m[10], scale.z)],
+	];
+
+	let trace = r[0][0] + r[1][1] + r[2][2];
+	let (x, y, z, w) = if trace > 0.0 {
+		let s = (trace + 1.0).sqrt() * 2.0;
+		((r[2][1] - r[1][2]) / s, (r[0][2] - r[2][0]) / s, (r[1][0] - r[0][1]) / s, 0.25 * s)
+	} else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+		let s = (1.0 + r[0][0] - r[1][1] - r[2][2]).sqrt() * 2.0;
+		(0.25 * s, (r[0][1] + r[1][0]) / s, (r[0][2] + r[2][0]) / s, (r[2][1] - r[1][2]) / s)
+	} else if r[1][1] > r[2][2] {
+		let s = (1.0 + r[1][1] - r[0][0] - r[2][2]).sqrt() * 2.0;
+		((r[0][1] + r[1][0]) / s, 0.25 * s, (r[1][2] + r[2][1]) / s, (r[0][2] - r[2][0]) / s)
+	} else {
+		let s = (1.0 + r[2][2] - r[0][0] - r[1][1]).sqrt() * 2.0;
+		((r[0][2] + r[2][0]) / s, (r[1][2] + r[2][1]) / s, 0.25 * s, (r[1][0] - r[0][1]) / s)
+	};
+
+	Transform {
+		position: Vec3 { x: m[12], y: m[13], z: m[14] },
+		scale: scale,
+		orientation: Quaternion { x: -x, y: -y, z: -z, w: w }.normalized(),
+	}
+}
@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+
+// Just enough JSON for the model formats, numbers are kept as f64
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(BTreeMap<String, Json>),
+}
+
+#[derive(Debug)]
+pub struct JsonError {
+	pub line: usize,
+	pub column: usize,
+	pub message: String,
+}
+
+impl fmt::Display for JsonError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:{}: {}", self.line, self.column, self.message)
+	}
+}
+
+impl error::Error for JsonError {}
+
+impl Json {
+	pub fn parse(text: &str) -> Result<Json, JsonError> {
+		let mut parser = Parser { text: text.as_bytes(), position: 0, depth: 0 };
+		parser.skip_whitespace();
+		let value = try!(parser.value());
+		parser.skip_whitespace();
+		if parser.position < parser.text.len() {
+			return Err(parser.error("unexpected data after the document"));
+		}
+		Ok(value)
+	}
+
+	// Member of an object, None for missing members and other types
+	pub fn get(&self, key: &str) -> Option<&Json> {
+		match *self {
+			Json::Object(ref members) => members.get(key),
+			_ => None,
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match *self {
+			Json::Bool(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match *self {
+			Json::Number(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	// Non negative whole numbers only
+	pub fn as_usize(&self) -> Option<usize> {
+		match *self {
+			Json::Number(value) if value >= 0.0 && value.fract() == 0.0 => Some(value as usize),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match *self {
+			Json::String(ref value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&[Json]> {
+		match *self {
+			Json::Array(ref values) => Some(values),
+			_ => None,
+		}
+	}
+
+	pub fn as_object(&self) -> Option<&BTreeMap<String, Json>> {
+		match *self {
+			Json::Object(ref members) => Some(members),
+			_ => None,
+		}
+	}
+
+	pub fn is_null(&self) -> bool {
+		*self == Json::Null
+	}
+}
+
+// Deeper nesting than this is treated as an attack on the stack
+const MAX_DEPTH: usize = 512;
+
+struct Parser<'a> {
+	text: &'a [u8],
+	position: usize,
+	depth: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn value(&mut self) -> Result<Json, JsonError> {
+		match self.peek() {
+			Some(b'{') => self.object(),
+			Some(b'[') => self.array(),
+			Some(b'"') => self.string().map(Json::String),
+			Some(b't') => self.literal("true", Json::Bool(true)),
+			Some(b'f') => self.literal("false", Json::Bool(false)),
+			Some(b'n') => self.literal("null", Json::Null),
+			Some(b'-') | Some(b'0'..=b'9') => self.number(),
+			Some(_) => Err(self.error("expected a value")),
+			None => Err(self.error("unexpected end of document")),
+		}
+	}
+
+	fn object(&mut self) -> Result<Json, JsonError> {
+		try!(self.enter());
+		self.position += 1;
+		let mut members = BTreeMap::new();
+
+		self.skip_whitespace();
+		if self.peek() == Some(b'}') {
+			self.position += 1;
+			self.depth -= 1;
+			return Ok(Json::Object(members));
+		}
+
+		loop {
+			self.skip_whitespace();
+			if self.peek() != Some(b'"') {
+				return Err(self.error("expected a member name"));
+			}
+			let key = try!(self.string());
+			self.skip_whitespace();
+			try!(self.expect(b':'));
+			self.skip_whitespace();
+			let value = try!(self.value());
+			members.insert(key, value);
+
+			self.skip_whitespace();
+			match self.peek() {
+				Some(b',') => self.position += 1,
+				Some(b'}') => { self.position += 1; break; },
+				_ => return Err(self.error("expected , or }")),
+			}
+		}
+
+		self.depth -= 1;
+		Ok(Json::Object(members))
+	}
+
+	fn array(&mut self) -> Result<Json, JsonError> {
+		try!(self.enter());
+		self.position += 1;
+		let mut values = Vec::new();
+
+		self.skip_whitespace();
+		if self.peek() == Some(b']') {
+			self.position += 1;
+			self.depth -= 1;
+			return Ok(Json::Array(values));
+		}
+
+		loop {
+			self.skip_whitespace();
+			values.push(try!(self.value()));
+
+			self.skip_whitespace();
+			match self.peek() {
+				Some(b',') => self.position += 1,
+				Some(b']') => { self.position += 1; break; },
+				_ => return Err(self.error("expected , or ]")),
+			}
+		}
+
+		self.depth -= 1;
+		Ok(Json::Array(values))
+	}
+
+	fn string(&mut self) -> Result<String, JsonError> {
+		self.position += 1;
+		let mut bytes = Vec::new();
+
+		loop {
+			let byte = match self.peek() {
+				Some(byte) => byte,
+				None => return Err(self.error("unterminated string")),
+			};
+			self.position += 1;
+
+			match byte {
+				b'"' => break,
+				b'\\' => {
+					let escape = match self.peek() {
+						Some(escape) => escape,
+						None => return Err(self.error("unterminated string")),
+					};
+					self.position += 1;
+					match escape {
+						b'"' => bytes.push(b'"'),
+						b'\\' => bytes.push(b'\\'),
+						b'/' => bytes.push(b'/'),
+						b'b' => bytes.push(8),
+						b'f' => bytes.push(12),
+						b'n' => bytes.push(b'\n'),
+						b'r' => bytes.push(b'\r'),
+						b't' => bytes.push(b'\t'),
+						b'u' => {
+							let c = try!(self.unicode_escape());
+							let mut buffer = [0; 4];
+							bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+						},
+						_ => return Err(self.error("invalid escape sequence")),
+					}
+				},
+				0..=0x1f => return Err(self.error("control character in string")),
+				_ => bytes.push(byte),
+			}
+		}
+
+		String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+	}
+
+	// \uXXXX, surrogate pairs take two escapes
+	fn unicode_escape(&mut self) -> Result<char, JsonError> {
+		let high = try!(self.hex4());
+		let code = if high >= 0xd800 && high < 0xdc00 {
+			if self.text[self.position..].starts_with(b"\\u") {
+				self.position += 2;
+				let low = try!(self.hex4());
+				if low < 0xdc00 || low >= 0xe000 {
+					return Err(self.error("invalid surrogate pair"));
+				}
+				0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+			} else {
+				return Err(self.error("unpaired surrogate"));
+			}
+		} else {
+			high
+		};
+
+		::std::char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+	}
+
+	fn hex4(&mut self) -> Result<u32, JsonError> {
+		if self.position + 4 > self.text.len() {
+			return Err(self.error("truncated unicode escape"));
+		}
+
+		let mut value = 0;
+		for &byte in &self.text[self.position..self.position + 4] {
+			let digit = match (byte as char).to_digit(16) {
+				Some(digit) => digit,
+				None => return Err(self.error("invalid unicode escape")),
+			};
+			value = value * 16 + digit;
+		}
+		self.position += 4;
+		Ok(value)
+	}
+
+	fn number(&mut self) -> Result<Json, JsonError> {
+		let start = self.position;
+		if self.peek() == Some(b'-') {
+			self.position += 1;
+		}
+
+		match self.peek() {
+			Some(b'0') => self.position += 1,
+			Some(b'1'..=b'9') => self.digits(),
+			_ => return Err(self.error("expected a digit")),
+		}
+		if self.peek() == Some(b'.') {
+			self.position += 1;
+			if !self.peek().map_or(false, |b| b.is_ascii_digit()) {
+				return Err(self.error("expected a digit after the decimal point"));
+			}
+			self.digits();
+		}
+		if self.peek() == Some(b'e') || self.peek() == Some(b'E') {
+			self.position += 1;
+			if self.peek() == Some(b'+') || self.peek() == Some(b'-') {
+				self.position += 1;
+			}
+			if !self.peek().map_or(false, |b| b.is_ascii_digit()) {
+				return Err(self.error("expected a digit in the exponent"));
+			}
+			self.digits();
+		}
+
+		// Only ASCII was consumed
+		let text = ::std::str::from_utf8(&self.text[start..self.position]).unwrap();
+		text.parse().map(Json::Number).map_err(|_| self.error("invalid number"))
+	}
+
+	fn digits(&mut self) {
+		while self.peek().map_or(false, |b| b.is_ascii_digit()) {
+			self.position += 1;
+		}
+	}
+
+	fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+		if self.text[self.position..].starts_with(word.as_bytes()) {
+			self.position += word.len();
+			Ok(value)
+		} else {
+			Err(self.error("expected a value"))
+		}
+	}
+
+	fn enter(&mut self) -> Result<(), JsonError> {
+		self.depth += 1;
+		if self.depth > MAX_DEPTH {
+			return Err(self.error("nested too deeply"));
+		}
+		Ok(())
+	}
+
+	fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+		if self.peek() == Some(byte) {
+			self.position += 1;
+			Ok(())
+		} else {
+			Err(self.error(&format!("expected {}", byte as char)))
+		}
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.text.get(self.position).cloned()
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+			self.position += 1;
+		}
+	}
+
+	fn error(&self, message: &str) -> JsonError {
+		let consumed = &self.text[..self.position.min(self.text.len())];
+		let line = consumed.iter().filter(|&&b| b == b'\n').count() + 1;
+		let column = consumed.iter().rev().take_while(|&&b| b != b'\n').count() + 1;
+
+		JsonError {
+			line: line,
+			column: column,
+			message: message.to_string(),
+		}
+	}
+}
@@ -0,0 +1,8 @@
+pub mod gltf;
+pub use self::gltf::{GltfAsset, GltfError};
+
+pub mod json;
+pub use self::json::{Json, JsonError};
+
+pub mod obj;
+pub use self::obj::{ObjError, ObjMesh, ObjModel};
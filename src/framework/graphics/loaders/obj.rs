@@ -0,0 +1,832 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use framework::graphics::{Material, Mesh, MeshError};
+use framework::math::{Vec2, Vec3, Vec4};
+
+#[derive(Debug)]
+pub enum ObjError {
+	Io { path: String, error: io::Error },
+	Parse { path: String, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ObjError::Io { ref path, ref error } => {
+				write!(f, "Could not read model {}: {}", path, error)
+			},
+			ObjError::Parse { ref path, line, ref message } => {
+				write!(f, "Could not parse model {}:{}: {}", path, line, message)
+			},
+		}
+	}
+}
+
+impl error::Error for ObjError {}
+
+// One mesh per object, group and material combination
+pub struct ObjMesh {
+	pub object: String,
+	pub group: String,
+	pub mesh: Mesh,
+	pub material: Option<usize>,	// Index into ObjModel::materials
+}
+
+pub struct ObjModel {
+	pub meshes: Vec<ObjMesh>,
+	pub materials: Vec<Material>,
+	// Missing material libraries and unknown materials, the model loads without them
+	pub warnings: Vec<ObjError>,
+}
+
+impl ObjModel {
+	pub fn material(&self, mesh: &ObjMesh) -> Option<&Material> {
+		mesh.material.and_then(|index| self.materials.get(index))
+	}
+
+	// Uploads every mesh, see Mesh::upload
+	pub fn upload(&mut self) -> Result<(), MeshError> {
+		for mesh in &mut self.meshes {
+			try!(mesh.mesh.upload());
+		}
+		Ok(())
+	}
+}
+
+// Loads an .obj file and the .mtl files it refers to
+pub fn load(path: &str) -> Result<ObjModel, ObjError> {
+	let source = try!(read(path));
+	parse(path, &source)
+}
+
+// Same as load for a file that is already in memory, material
+// libraries are still read relative to path.
+pub fn parse(path: &str, source: &str) -> Result<ObjModel, ObjError> {
+	Parser::new(path).parse(source)
+}
+
+pub fn load_mtl(path: &str) -> Result<Vec<Material>, ObjError> {
+	let source = try!(read(path));
+	parse_mtl(path, &source)
+}
+
+pub fn parse_mtl(path: &str, source: &str) -> Result<Vec<Material>, ObjError> {
+	let mut materials: Vec<Material> = Vec::new();
+
+	for (index, line) in source.lines().enumerate() {
+		let number = index + 1;
+		let mut tokens = Tokens::new(path, number, line);
+		let keyword = match tokens.next() {
+			Some(keyword) => keyword,
+			None => continue,
+		};
+
+		if keyword == "newmtl" {
+			let name = try!(tokens.rest("material name"));
+			materials.push(Material::new(name));
+			continue;
+		}
+
+		let material = match materials.last_mut() {
+			Some(material) => material,
+			None => return Err(tokens.error(format!("{} before the first newmtl", keyword))),
+		};
+
+		match keyword {
+			"Ka" => material.ambient = try!(tokens.color()),
+			"Kd" => material.diffuse = try!(tokens.color()),
+			"Ks" => material.specular = try!(tokens.color()),
+			"Ke" => material.emissive = try!(tokens.color()),
+			"Ns" => material.shininess = try!(tokens.float()),
+			"d" => material.opacity = try!(tokens.float()),
+			"Tr" => material.opacity = 1.0 - try!(tokens.float()),
+			"map_Kd" => material.diffuse_texture = Some(try!(tokens.texture())),
+			"map_Ks" => material.specular_texture = Some(try!(tokens.texture())),
+			"map_Ke" => material.emissive_texture = Some(try!(tokens.texture())),
+			"map_d" => material.opacity_texture = Some(try!(tokens.texture())),
+			"map_Bump" | "map_bump" | "bump" | "norm" => {
+				material.normal_texture = Some(try!(tokens.texture()));
+			},
+			// Illumination models, reflection maps, PBR extensions etc. aren't used
+			_ => {},
+		}
+	}
+
+	Ok(materials)
+}
+
+fn read(path: &str) -> Result<String, ObjError> {
+	let to_error = |error| ObjError::Io { path: path.to_string(), error: error };
+
+	let mut file = try!(File::open(path).map_err(&to_error));
+	let mut contents = String::new();
+	try!(file.read_to_string(&mut contents).map_err(&to_error));
+
+	Ok(contents)
+}
+
+// Whitespace separated tokens of one line, comments stripped
+struct Tokens<'a> {
+	path: &'a str,
+	line: usize,
+	rest: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+	fn new(path: &'a str, line: usize, code: &'a str) -> Tokens<'a> {
+		let code = match code.find('#') {
+			Some(comment) => &code[..comment],
+			None => code,
+		};
+
+		Tokens {
+			path: path,
+			line: line,
+			rest: code.trim(),
+		}
+	}
+
+	fn next(&mut self) -> Option<&'a str> {
+		if self.rest.is_empty() {
+			return None;
+		}
+
+		let end = self.rest.find(char::is_whitespace).unwrap_or(self.rest.len());
+		let token = &self.rest[..end];
+		self.rest = self.rest[end..].trim_start();
+		Some(token)
+	}
+
+	fn peek(&self) -> Option<&'a str> {
+		self.rest.split_whitespace().next()
+	}
+
+	// Everything up to the end of the line, for names containing spaces
+	fn rest(&mut self, what: &str) -> Result<&'a str, ObjError> {
+		let rest = self.rest;
+		self.rest = "";
+		if rest.is_empty() {
+			return Err(self.error(format!("expected {}", what)));
+		}
+		Ok(rest)
+	}
+
+	fn float(&mut self) -> Result<f32, ObjError> {
+		match self.next() {
+			Some(token) => token.parse().map_err(|_| self.error(format!("expected a number, found {}", token))),
+			None => Err(self.error("expected a number".to_string())),
+		}
+	}
+
+	fn optional_float(&mut self, default: f32) -> Result<f32, ObjError> {
+		if self.rest.is_empty() {
+			Ok(default)
+		} else {
+			self.float()
+		}
+	}
+
+	fn color(&mut self) -> Result<Vec3, ObjError> {
+		if self.rest.starts_with("spectral") || self.rest.starts_with("xyz") {
+			return Err(self.error("only RGB colors are supported".to_string()));
+		}
+
+		let r = try!(self.float());
+		// A single value means gray
+		let g = try!(self.optional_float(r));
+		let b = try!(self.optional_float(r));
+		Ok(Vec3 { x: r, y: g, z: b })
+	}
+
+	// Options such as -bm 0.5 come first, the file name is the rest of the line
+	// and may contain spaces
+	fn texture(&mut self) -> Result<String, ObjError> {
+		while self.rest.starts_with('-') {
+			let option = self.next().unwrap_or("");
+			match option {
+				"-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-type" => {
+					try!(self.argument(option));
+				},
+				"-mm" => {
+					try!(self.argument(option));
+					try!(self.argument(option));
+				},
+				// u with optional v and w
+				"-o" | "-s" | "-t" => {
+					try!(self.argument(option));
+					for _ in 0..2 {
+						if self.peek().map_or(false, |token| token.parse::<f32>().is_ok()) {
+							self.next();
+						}
+					}
+				},
+				_ => return Err(self.error(format!("unknown texture option {}", option))),
+			}
+		}
+
+		let name = try!(self.rest("texture file"));
+		Ok(relative_to(self.path, name))
+	}
+
+	fn argument(&mut self, option: &str) -> Result<&'a str, ObjError> {
+		match self.next() {
+			Some(token) => Ok(token),
+			None => Err(self.error(format!("expected a value for {}", option))),
+		}
+	}
+
+	fn end(&self) -> Result<(), ObjError> {
+		if self.rest.is_empty() {
+			Ok(())
+		} else {
+			Err(self.error(format!("unexpected {}", self.rest)))
+		}
+	}
+
+	fn error(&self, message: String) -> ObjError {
+		ObjError::Parse {
+			path: self.path.to_string(),
+			line: self.line,
+			message: message,
+		}
+	}
+}
+
+fn relative_to(file_path: &str, name: &str) -> String {
+	let name = name.replace('\\', "/");
+	match Path::new(file_path).parent() {
+		Some(directory) => directory.join(name).to_string_lossy().into_owned(),
+		None => name,
+	}
+}
+
+// Attribute indices of one face corner, welded into a single vertex
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct MeshBuilder {
+	object: String,
+	group: String,
+	material: Option<usize>,
+	mesh: Mesh,
+	vertices: HashMap<Corner, u32>,
+	has_uvs: bool,
+	missing_normals: bool,
+}
+
+impl MeshBuilder {
+	fn finish(mut self, has_colors: bool) -> ObjMesh {
+		if !self.has_uvs {
+			self.mesh.uvs.clear();
+		}
+		if !has_colors {
+			self.mesh.colors.clear();
+		}
+		if self.missing_normals {
+			self.mesh.compute_normals();
+		}
+		self.mesh.compute_tangents();
+
+		ObjMesh {
+			object: self.object,
+			group: self.group,
+			mesh: self.mesh,
+			material: self.material,
+		}
+	}
+}
+
+struct Parser<'a> {
+	path: &'a str,
+	positions: Vec<Vec3>,
+	colors: Vec<Vec4>,
+	has_colors: bool,
+	uvs: Vec<Vec2>,
+	normals: Vec<Vec3>,
+	materials: Vec<Material>,
+	warnings: Vec<ObjError>,
+	builders: Vec<MeshBuilder>,
+	lookup: HashMap<(String, String, Option<usize>), usize>,
+	object: String,
+	group: String,
+	material: Option<usize>,
+}
+
+impl<'a> Parser<'a> {
+	fn new(path: &'a str) -> Parser<'a> {
+		Parser {
+			path: path,
+			positions: Vec::new(),
+			colors: Vec::new(),
+			has_colors: false,
+			uvs: Vec::new(),
+			normals: Vec::new(),
+			materials: Vec::new(),
+			warnings: Vec::new(),
+			builders: Vec::new(),
+			lookup: HashMap::new(),
+			object: String::new(),
+			group: String::new(),
+			material: None,
+		}
+	}
+
+	fn parse(mut self, source: &str) -> Result<ObjModel, ObjError> {
+		// Lines ending in a backslash continue on the next line
+		let mut pending = String::new();
+		let mut first_line = 0;
+
+		for (index, line) in source.lines().enumerate() {
+			if pending.is_empty() {
+				first_line = index + 1;
+			}
+			if line.ends_with('\\') {
+				pending.push_str(&line[..line.len() - 1]);
+				pending.push(' ');
+				continue;
+			}
+			pending.push_str(line);
+			try!(self.parse_line(first_line, &pending));
+			pending.clear();
+		}
+		if !pending.is_empty() {
+			try!(self.parse_line(first_line, &pending));
+		}
+
+		let has_colors = self.has_colors;
+		Ok(ObjModel {
+			meshes: self.builders.into_iter()
+				.filter(|builder| !builder.mesh.indices.is_empty())
+				.map(|builder| builder.finish(has_colors))
+				.collect(),
+			materials: self.materials,
+			warnings: self.warnings,
+		})
+	}
+
+	fn parse_line(&mut self, number: usize, line: &str) -> Result<(), ObjError> {
+		let mut tokens = Tokens::new(self.path, number, line);
+		let keyword = match tokens.next() {
+			Some(keyword) => keyword,
+			None => return Ok(()),
+		};
+
+		match keyword {
+			"v" => {
+				let x = try!(tokens.float());
+				let y = try!(tokens.float());
+				let z = try!(tokens.float());
+				self.positions.push(Vec3 { x: x, y: y, z: z });
+
+				// Either a w component or the common x y z r g b extension
+				let extra: Vec<&str> = tokens.rest.split_whitespace().collect();
+				let color = match extra.len() {
+					0 | 1 => Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 },
+					3 | 4 => {
+						let r = try!(tokens.float());
+						let g = try!(tokens.float());
+						let b = try!(tokens.float());
+						let a = try!(tokens.optional_float(1.0));
+						self.has_colors = true;
+						Vec4 { x: r, y: g, z: b, w: a }
+					},
+					_ => return Err(tokens.error(format!("expected 3, 4, 6 or 7 numbers, found {}", 3 + extra.len()))),
+				};
+				if extra.len() == 1 {
+					try!(tokens.float());
+				}
+				self.colors.push(color);
+			},
+			"vt" => {
+				let u = try!(tokens.float());
+				let v = try!(tokens.optional_float(0.0));
+				try!(tokens.optional_float(0.0));
+				self.uvs.push(Vec2 { x: u, y: v });
+			},
+			"vn" => {
+				let x = try!(tokens.float());
+				let y = try!(tokens.float());
+				let z = try!(tokens.float());
+				self.normals.push(Vec3 { x: x, y: y, z: z });
+			},
+			"f" => return self.face(&mut tokens),
+			"o" => {
+				self.object = tokens.rest.to_string();
+				self.group.clear();
+				return Ok(());
+			},
+			// A face can be in several groups, use them together as the name
+			"g" => {
+				self.group = tokens.rest.split_whitespace().collect::<Vec<_>>().join(" ");
+				return Ok(());
+			},
+			"usemtl" => {
+				let name = try!(tokens.rest("material name"));
+				self.material = match self.materials.iter().position(|material| material.name == name) {
+					Some(index) => Some(index),
+					// Its library may be missing, see mtllib
+					None => {
+						self.warnings.push(tokens.error(format!("unknown material {}", name)));
+						None
+					},
+				};
+			},
+			"mtllib" => {
+				while let Some(name) = tokens.next() {
+					// A missing library shouldn't lose the geometry, a broken one is still an error
+					let materials = match load_mtl(&relative_to(self.path, name)) {
+						Ok(materials) => materials,
+						Err(e) => {
+							let missing = match e {
+								ObjError::Io { ref error, .. } => error.kind() == io::ErrorKind::NotFound,
+								_ => false,
+							};
+							if !missing {
+								return Err(e);
+							}
+							self.warnings.push(e);
+							continue;
+						},
+					};
+					for material in materials {
+						match self.materials.iter().position(|m| m.name == material.name) {
+							Some(index) => self.materials[index] = material,
+							None => self.materials.push(material),
+						}
+					}
+				}
+			},
+			// Smoothing groups are implied by the normals, lines, points, free
+			// form geometry etc. aren't supported and skipped
+			_ => return Ok(()),
+		}
+
+		tokens.end()
+	}
+
+	fn face(&mut self, tokens: &mut Tokens) -> Result<(), ObjError> {
+		let mut corners = Vec::new();
+		while let Some(token) = tokens.next() {
+			corners.push(try!(self.corner(tokens, token)));
+		}
+		if corners.len() < 3 {
+			return Err(tokens.error(format!("a face needs at least 3 vertices, found {}", corners.len())));
+		}
+
+		let builder = self.builder();
+		let polygon: Vec<u32> = corners.iter().map(|corner| self.vertex(builder, corner)).collect();
+
+		let builder = &mut self.builders[builder];
+		for triangle in triangulate(&builder.mesh.positions, &polygon) {
+			builder.mesh.indices.extend_from_slice(&triangle);
+		}
+
+		Ok(())
+	}
+
+	// v, v/vt, v//vn or v/vt/vn
+	fn corner(&self, tokens: &Tokens, token: &str) -> Result<Corner, ObjError> {
+		let mut parts = token.split('/');
+		let position = try!(self.index(tokens, parts.next(), self.positions.len(), "vertex"));
+		let uv = match parts.next() {
+			Some("") | None => None,
+			part => Some(try!(self.index(tokens, part, self.uvs.len(), "texture coordinate"))),
+		};
+		let normal = match parts.next() {
+			Some("") | None => None,
+			part => Some(try!(self.index(tokens, part, self.normals.len(), "normal"))),
+		};
+		if parts.next().is_some() {
+			return Err(tokens.error(format!("malformed face vertex {}", token)));
+		}
+
+		Ok((position, uv, normal))
+	}
+
+	// 1-based, negative indices count back from the last element read so far
+	fn index(&self, tokens: &Tokens, part: Option<&str>, count: usize, what: &str) -> Result<usize, ObjError> {
+		let part = part.unwrap_or("");
+		let index: i64 = match part.parse() {
+			Ok(index) => index,
+			Err(_) => return Err(tokens.error(format!("expected a {} index, found \"{}\"", what, part))),
+		};
+
+		let resolved = if index > 0 { index - 1 } else { count as i64 + index };
+		if index == 0 || resolved < 0 || resolved >= count as i64 {
+			return Err(tokens.error(format!("{} index {} out of range, {} defined", what, index, count)));
+		}
+
+		Ok(resolved as usize)
+	}
+
+	fn builder(&mut self) -> usize {
+		let key = (self.object.clone(), self.group.clone(), self.material);
+		if let Some(&index) = self.lookup.get(&key) {
+			return index;
+		}
+
+		let mut mesh = Mesh::new();
+		mesh.uvs.push(Vec::new());
+		self.builders.push(MeshBuilder {
+			object: self.object.clone(),
+			group: self.group.clone(),
+			material: self.material,
+			mesh: mesh,
+			vertices: HashMap::new(),
+			has_uvs: false,
+			missing_normals: false,
+		});
+		self.lookup.insert(key, self.builders.len() - 1);
+		self.builders.len() - 1
+	}
+
+	// Corners with the same attribute indices share one vertex
+	fn vertex(&mut self, builder: usize, corner: &Corner) -> u32 {
+		let builder = &mut self.builders[builder];
+		if let Some(&index) = builder.vertices.get(corner) {
+			return index;
+		}
+
+		let (position, uv, normal) = *corner;
+		let mesh = &mut builder.mesh;
+		mesh.positions.push(self.positions[position]);
+		mesh.colors.push(self.colors[position]);
+		mesh.uvs[0].push(match uv { Some(uv) => self.uvs[uv], None => Vec2::new() });
+		mesh.normals.push(match normal { Some(normal) => self.normals[normal], None => Vec3::new() });
+		builder.has_uvs |= uv.is_some();
+		builder.missing_normals |= normal.is_none();
+
+		let index = mesh.positions.len() as u32 - 1;
+		builder.vertices.insert(*corner, index);
+		index
+	}
+}
+
+// Ear clipping in the plane the polygon faces most, so concave polygons work too.
+// Falls back to a fan for whatever is left when no ear can be found.
+fn triangulate(positions: &[Vec3], polygon: &[u32]) -> Vec<[u32; 3]> {
+	if polygon.len() == 3 {
+		return vec![[polygon[0], polygon[1], polygon[2]]];
+	}
+
+	// Newell's method, robust for slightly non planar polygons
+	let mut normal = Vec3::new();
+	for i in 0..polygon.len() {
+		let a = &positions[polygon[i] as usize];
+		let b = &positions[polygon[(i + 1) % polygon.len()] as usize];
+		normal.x += (a.y - b.y) * (a.z + b.z);
+		normal.y += (a.z - b.z) * (a.x + b.x);
+		normal.z += (a.x - b.x) * (a.y + b.y);
+	}
+
+	// Drop the dominant axis, flip so the polygon is counter clockwise in 2D
+	let (ax, ay, az) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+	let points: Vec<(f32, f32)> = polygon.iter().map(|&index| {
+		let p = &positions[index as usize];
+		if ax >= ay && ax >= az {
+			if normal.x > 0.0 { (p.y, p.z) } else { (p.z, p.y) }
+		} else if ay >= az {
+			if normal.y > 0.0 { (p.z, p.x) } else { (p.x, p.z) }
+		} else {
+			if normal.z > 0.0 { (p.x, p.y) } else { (p.y, p.x) }
+		}
+	}).collect();
+
+	let cross = |o: (f32, f32), a: (f32, f32), b: (f32, f32)| {
+		(a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+	};
+
+	let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+	let mut triangles = Vec::with_capacity(polygon.len() - 2);
+
+	while remaining.len() > 3 {
+		let count = remaining.len();
+		let ear = (0..count).find(|&i| {
+			let (prev, current, next) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+			let (a, b, c) = (points[prev], points[current], points[next]);
+			if cross(a, b, c) <= 0.0 {
+				return false;
+			}
+			// No other corner may lie inside the ear
+			remaining.iter().all(|&other| {
+				other == prev || other == current || other == next || {
+					let p = points[other];
+					cross(a, b, p) < 0.0 || cross(b, c, p) < 0.0 || cross(c, a, p) < 0.0
+				}
+			})
+		});
+
+		match ear {
+			Some(i) => {
+				let (prev, current, next) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+				triangles.push([polygon[prev], polygon[current], polygon[next]]);
+				remaining.remove(i);
+			},
+			None => break,
+		}
+	}
+
+	for i in 1..remaining.len() - 1 {
+		triangles.push([polygon[remaining[0]], polygon[remaining[i]], polygon[remaining[i + 1]]]);
+	}
+
+	triangles
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn textures(source: &str) -> Material {
+		match parse_mtl("models/crate.mtl", source) {
+			Ok(mut materials) => materials.remove(0),
+			Err(e) => panic!("{}", e),
+		}
+	}
+
+	#[test]
+	fn texture_path_with_spaces() {
+		let material = textures("newmtl crate\nmap_Kd textures/old crate.png\nmap_Ks  spec map.png # shiny\n");
+		assert_eq!(material.diffuse_texture, Some("models/textures/old crate.png".to_string()));
+		assert_eq!(material.specular_texture, Some("models/spec map.png".to_string()));
+	}
+
+	#[test]
+	fn texture_options() {
+		let material = textures("newmtl crate\n\
+			map_Kd -blendu off -o 0.5 0.5 -s 2 crate diffuse.png\n\
+			map_Bump -bm 0.5 -mm 0 1 normal.png\n\
+			map_d -t 1 2 3 -clamp on 4.png\n");
+		assert_eq!(material.diffuse_texture, Some("models/crate diffuse.png".to_string()));
+		assert_eq!(material.normal_texture, Some("models/normal.png".to_string()));
+		assert_eq!(material.opacity_texture, Some("models/4.png".to_string()));
+	}
+
+	#[test]
+	fn texture_without_file() {
+		match parse_mtl("crate.mtl", "newmtl crate\nmap_Kd -bm 0.5\n") {
+			Err(ObjError::Parse { line, .. }) => assert_eq!(line, 2),
+			_ => panic!("parsed a texture without a file"),
+		}
+	}
+
+	fn parsed(source: &str) -> ObjModel {
+		match parse("models/model.obj", source) {
+			Ok(model) => model,
+			Err(e) => panic!("{}", e),
+		}
+	}
+
+	fn parse_error(source: &str) -> (usize, String) {
+		match parse("models/model.obj", source) {
+			Err(ObjError::Parse { line, message, .. }) => (line, message),
+			Err(e) => panic!("unexpected error: {}", e),
+			Ok(_) => panic!("parsed a malformed model"),
+		}
+	}
+
+	// A fresh directory in the system temp directory for .obj and .mtl files
+	fn temp_dir(name: &str) -> String {
+		let directory = ::std::env::temp_dir().join(format!("nitrust-obj-{}", name));
+		let _ = ::std::fs::remove_dir_all(&directory);
+		::std::fs::create_dir_all(&directory).unwrap();
+		directory.to_string_lossy().into_owned()
+	}
+
+	fn area(mesh: &Mesh, triangle: &[u32]) -> f32 {
+		let (a, b, c) = (mesh.positions[triangle[0] as usize], mesh.positions[triangle[1] as usize], mesh.positions[triangle[2] as usize]);
+		((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)) / 2.0
+	}
+
+	#[test]
+	fn negative_indices() {
+		let model = parsed("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf -4//-1 -3//-1 -2//-1\n");
+		let mesh = &model.meshes[0].mesh;
+		assert_eq!(mesh.positions, vec![Vec3 { x: 0.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 0.0, z: 0.0 }, Vec3 { x: 1.0, y: 1.0, z: 0.0 }]);
+		assert_eq!(mesh.normals, vec![Vec3 { x: 0.0, y: 0.0, z: 1.0 }; 3]);
+		assert_eq!(mesh.indices, vec![0, 1, 2]);
+
+		// Relative to the vertices read so far, not the whole file
+		assert_eq!(parse_error("v 0 0 0\nv 1 0 0\nf -1 -2 -3\nv 0 1 0\n"), (3, "vertex index -3 out of range, 2 defined".to_string()));
+	}
+
+	#[test]
+	fn convex_polygon() {
+		let model = parsed("v 0 0 0\nv 2 0 0\nv 3 1 0\nv 1 2 0\nv -1 1 0\nf 1 2 3 4 5\n");
+		let mesh = &model.meshes[0].mesh;
+		assert_eq!(mesh.positions.len(), 5);
+		assert_eq!(mesh.indices.len(), 9);
+		let total: f32 = mesh.indices.chunks(3).map(|triangle| area(mesh, triangle)).sum();
+		assert!((total - 5.0).abs() < 1e-5, "area {}", total);
+	}
+
+	#[test]
+	fn concave_polygon() {
+		// A chevron with its reflex corner at (2, 1), a fan from the first corner would fold over
+		let model = parsed("v 0 0 0\nv 2 1 0\nv 4 0 0\nv 2 3 0\nf 1 2 3 4\n");
+		let mesh = &model.meshes[0].mesh;
+		assert_eq!(mesh.indices.len(), 6);
+		for triangle in mesh.indices.chunks(3) {
+			assert!(area(mesh, triangle) > 0.0, "triangle {:?} is folded", triangle);
+		}
+		let total: f32 = mesh.indices.chunks(3).map(|triangle| area(mesh, triangle)).sum();
+		assert!((total - 4.0).abs() < 1e-5, "area {}", total);
+	}
+
+	#[test]
+	fn welds_identical_corners() {
+		let source = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 1\nvn 0 0 1\n";
+		let model = parsed(&format!("{}f 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n", source));
+		let mesh = &model.meshes[0].mesh;
+		assert_eq!(mesh.positions.len(), 4);
+		assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+
+		// The same position with another texture coordinate is a separate vertex
+		let model = parsed(&format!("{}f 1/1/1 2/1/1 3/1/1\nf 1/2/1 3/1/1 4/1/1\n", source));
+		let mesh = &model.meshes[0].mesh;
+		assert_eq!(mesh.positions.len(), 5);
+		assert_eq!(mesh.indices, vec![0, 1, 2, 3, 2, 4]);
+	}
+
+	#[test]
+	fn splits_by_object_and_group() {
+		let model = parsed("v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+			o box\nf 1 2 3\n\
+			g lid top\nf 1 2 3\n\
+			g sides\nf 1 2 3\n\
+			g lid   top\nf 3 2 1\n\
+			o ball\nf 1 2 3\n");
+		let names: Vec<(&str, &str, usize)> = model.meshes.iter()
+			.map(|mesh| (&mesh.object[..], &mesh.group[..], mesh.mesh.indices.len()))
+			.collect();
+		assert_eq!(names, vec![("box", "", 3), ("box", "lid top", 6), ("box", "sides", 3), ("ball", "", 3)]);
+	}
+
+	#[test]
+	fn bad_face_line() {
+		assert_eq!(parse_error("v 0 0 0\nv 1 0 0\nv 0 1 0\n\n# comment\nf 1 2 x\n"), (6, "expected a vertex index, found \"x\"".to_string()));
+		assert_eq!(parse_error("v 0 0 0\nv 1 0 0\nf 1 2\n"), (3, "a face needs at least 3 vertices, found 2".to_string()));
+		// Continued lines report the line they start on
+		assert_eq!(parse_error("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 \\\n2 4\n").0, 4);
+	}
+
+	#[test]
+	fn missing_material_library() {
+		let directory = temp_dir("missing");
+		let source = "mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl crate\nf 1 2 3\n";
+		let model = match parse(&format!("{}/model.obj", directory), source) {
+			Ok(model) => model,
+			Err(e) => panic!("{}", e),
+		};
+		assert!(model.materials.is_empty());
+		assert_eq!(model.meshes.len(), 1);
+		assert_eq!(model.meshes[0].material, None);
+		assert_eq!(model.meshes[0].mesh.indices, vec![0, 1, 2]);
+
+		assert_eq!(model.warnings.len(), 2);
+		match model.warnings[0] {
+			ObjError::Io { ref path, ref error } => {
+				assert_eq!(path, &format!("{}/missing.mtl", directory));
+				assert_eq!(error.kind(), io::ErrorKind::NotFound);
+			},
+			ref e => panic!("unexpected warning: {}", e),
+		}
+		match model.warnings[1] {
+			ObjError::Parse { line, ref message, .. } => assert_eq!((line, &message[..]), (5, "unknown material crate")),
+			ref e => panic!("unexpected warning: {}", e),
+		}
+	}
+
+	#[test]
+	fn malformed_material_library() {
+		let directory = temp_dir("malformed");
+		::std::fs::write(format!("{}/broken.mtl", directory), "newmtl crate\nKd 1 0 0\nNs shiny\n").unwrap();
+		match parse(&format!("{}/model.obj", directory), "mtllib broken.mtl\n") {
+			Err(ObjError::Parse { ref path, line, .. }) => {
+				assert_eq!(path, &format!("{}/broken.mtl", directory));
+				assert_eq!(line, 3);
+			},
+			Err(e) => panic!("unexpected error: {}", e),
+			Ok(_) => panic!("loaded a model with a malformed material library"),
+		}
+	}
+
+	#[test]
+	fn material_library() {
+		let directory = temp_dir("library");
+		::std::fs::write(format!("{}/crate.mtl", directory), "newmtl crate\nKd 1 0 0\nmap_Kd crate.png\n").unwrap();
+		let model = match parse(&format!("{}/model.obj", directory), "mtllib crate.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl crate\nf 1 2 3\n") {
+			Ok(model) => model,
+			Err(e) => panic!("{}", e),
+		};
+		assert!(model.warnings.is_empty());
+		let material = model.material(&model.meshes[0]).expect("material");
+		assert_eq!(material.diffuse, Vec3 { x: 1.0, y: 0.0, z: 0.0 });
+		assert_eq!(material.diffuse_texture, Some(format!("{}/crate.png", directory)));
+	}
+}
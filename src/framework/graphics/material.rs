@@ -0,0 +1,43 @@
+use framework::math::Vec3;
+
+// Surface description shared by the model loaders. Colors are linear RGB,
+// texture paths are resolved relative to the working directory.
+#[derive(Clone, Debug)]
+pub struct Material {
+	pub name: String,
+	pub ambient: Vec3,
+	pub diffuse: Vec3,
+	pub specular: Vec3,
+	pub emissive: Vec3,
+	pub shininess: f32,		// Specular exponent
+	pub opacity: f32,		// 1 is opaque
+	pub diffuse_texture: Option<String>,
+	pub specular_texture: Option<String>,
+	pub normal_texture: Option<String>,
+	pub emissive_texture: Option<String>,
+	pub opacity_texture: Option<String>,
+}
+
+impl Material {
+	// White, dull and opaque
+	pub fn new(name: &str) -> Material {
+		Material {
+			name: name.to_string(),
+			ambient: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+			diffuse: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
+			specular: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+			emissive: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+			shininess: 1.0,
+			opacity: 1.0,
+			diffuse_texture: None,
+			specular_texture: None,
+			normal_texture: None,
+			emissive_texture: None,
+			opacity_texture: None,
+		}
+	}
+
+	pub fn is_transparent(&self) -> bool {
+		self.opacity < 1.0 || self.opacity_texture.is_some()
+	}
+}
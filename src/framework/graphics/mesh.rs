@@ -0,0 +1,640 @@
+extern crate gl;
+extern crate std;
+
+use gl::types::*;
+
+use std::error;
+use std::f32;
+use std::fmt;
+
+use framework::graphics::{BufferUsage, IndexBuffer, Primitive, VertexArray, VertexBuffer, VertexLayout};
+use framework::math::{Vec2, Vec3, Vec4};
+
+// Attribute locations used when a mesh is uploaded. Color and the first UV channel
+// keep the locations of the original quad, so existing shaders keep working.
+pub const POSITION_LOCATION: GLuint = 0;
+pub const COLOR_LOCATION: GLuint = 1;
+pub const NORMAL_LOCATION: GLuint = 3;
+pub const TANGENT_LOCATION: GLuint = 4;
+pub const MAX_UV_CHANNELS: usize = 4;
+
+// UV channel 0 at location 2, further channels from location 5 on
+pub fn uv_location(channel: usize) -> GLuint {
+	if channel == 0 { 2 } else { 4 + channel as GLuint }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MeshError {
+	// A channel that is neither empty nor has one entry per position
+	ChannelLength { channel: String, len: usize, positions: usize },
+	TooManyUvChannels { channels: usize },
+}
+
+impl fmt::Display for MeshError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			MeshError::ChannelLength { ref channel, len, positions } => {
+				write!(f, "Mesh has {} {} for {} positions", len, channel, positions)
+			},
+			MeshError::TooManyUvChannels { channels } => {
+				write!(f, "Mesh has {} UV channels, at most {} are supported", channels, MAX_UV_CHANNELS)
+			},
+		}
+	}
+}
+
+impl error::Error for MeshError {}
+
+// Buffers of an uploaded mesh, the vertex array refers to the other two
+struct MeshBuffers {
+	vertex_array: VertexArray,
+	layout: VertexLayout,
+	vertices: VertexBuffer<f32>,
+	indices: IndexBuffer,
+}
+
+// Triangle mesh on the CPU, counter clockwise triangles are front faces.
+// Every channel is either empty or has one entry per position. Tangents
+// store the handedness of the bitangent in w.
+pub struct Mesh {
+	pub positions: Vec<Vec3>,
+	pub normals: Vec<Vec3>,
+	pub tangents: Vec<Vec4>,
+	pub colors: Vec<Vec4>,
+	pub uvs: Vec<Vec<Vec2>>,
+	pub indices: Vec<u32>,
+	buffers: Option<MeshBuffers>,
+}
+
+impl Mesh {
+	pub fn new() -> Mesh {
+		Mesh {
+			positions: Vec::new(),
+			normals: Vec::new(),
+			tangents: Vec::new(),
+			colors: Vec::new(),
+			uvs: Vec::new(),
+			indices: Vec::new(),
+			buffers: None,
+		}
+	}
+
+	// Quad in the XY plane facing +Z
+	pub fn quad(width: f32, height: f32) -> Mesh {
+		let mut mesh = Mesh::new();
+		mesh.uvs.push(Vec::new());
+		mesh.add_face(&vec3(0.0, 0.0, 1.0), &vec3(width * 0.5, 0.0, 0.0), &vec3(0.0, height * 0.5, 0.0), 0.0);
+		mesh.compute_tangents();
+		mesh
+	}
+
+	// Axis aligned cube centered at the origin, every face has its own vertices
+	// so normals and UVs don't get shared over the edges
+	pub fn cube(size: f32) -> Mesh {
+		let mut mesh = Mesh::new();
+		mesh.uvs.push(Vec::new());
+
+		let half = size * 0.5;
+		// Normal, right and up of each face, right x up = normal
+		let faces = [
+			(vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)),
+			(vec3(0.0, 0.0, -1.0), vec3(-1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)),
+			(vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0), vec3(0.0, 1.0, 0.0)),
+			(vec3(-1.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 0.0)),
+			(vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, -1.0)),
+			(vec3(0.0, -1.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0)),
+		];
+		for &(ref normal, ref right, ref up) in faces.iter() {
+			mesh.add_face(normal, &(right * half), &(up * half), half);
+		}
+
+		mesh.compute_tangents();
+		mesh
+	}
+
+	// Sphere centered at the origin, segments around the Y axis and rings from pole to pole
+	pub fn sphere(radius: f32, segments: usize, rings: usize) -> Mesh {
+		let segments = segments.max(3);
+		let rings = rings.max(2);
+
+		let profile: Vec<Ring> = (0..rings + 1).map(|ring| {
+			let phi = f32::consts::PI * ring as f32 / rings as f32;
+			Ring {
+				y: radius * phi.cos(),
+				radius: radius * phi.sin(),
+				normal: (phi.sin(), phi.cos()),
+				v: 1.0 - ring as f32 / rings as f32,
+			}
+		}).collect();
+
+		let mut mesh = Mesh::new();
+		mesh.uvs.push(Vec::new());
+		mesh.add_lathe(&profile, segments);
+		mesh.compute_tangents();
+		mesh
+	}
+
+	// Grid in the XZ plane facing +Y, handy for terrain and floors
+	pub fn plane(width: f32, depth: f32, x_segments: usize, z_segments: usize) -> Mesh {
+		let x_segments = x_segments.max(1);
+		let z_segments = z_segments.max(1);
+
+		let mut mesh = Mesh::new();
+		mesh.uvs.push(Vec::new());
+
+		for z in 0..z_segments + 1 {
+			for x in 0..x_segments + 1 {
+				let u = x as f32 / x_segments as f32;
+				let v = z as f32 / z_segments as f32;
+				mesh.positions.push(vec3(width * (u - 0.5), 0.0, depth * (v - 0.5)));
+				mesh.normals.push(vec3(0.0, 1.0, 0.0));
+				mesh.uvs[0].push(Vec2 { x: u, y: 1.0 - v });
+			}
+		}
+
+		let columns = (x_segments + 1) as u32;
+		for z in 0..z_segments as u32 {
+			for x in 0..x_segments as u32 {
+				let top_left = z * columns + x;
+				let bottom_left = top_left + columns;
+				mesh.indices.extend_from_slice(&[
+					top_left, bottom_left, bottom_left + 1,
+					bottom_left + 1, top_left + 1, top_left,
+				]);
+			}
+		}
+
+		mesh.compute_tangents();
+		mesh
+	}
+
+	// Cylinder along the Y axis centered at the origin, with caps
+	pub fn cylinder(radius: f32, height: f32, segments: usize) -> Mesh {
+		let segments = segments.max(3);
+		let half = height * 0.5;
+
+		let mut mesh = Mesh::new();
+		mesh.uvs.push(Vec::new());
+		mesh.add_lathe(&[
+			Ring { y: half, radius: radius, normal: (1.0, 0.0), v: 1.0 },
+			Ring { y: -half, radius: radius, normal: (1.0, 0.0), v: 0.0 },
+		], segments);
+		mesh.add_cap(radius, half, 1.0, segments);
+		mesh.add_cap(radius, -half, -1.0, segments);
+		mesh.compute_tangents();
+		mesh
+	}
+
+	// Cylinder with hemispheres on both ends along the Y axis. The height includes
+	// the hemispheres, the rings are per hemisphere.
+	pub fn capsule(radius: f32, height: f32, segments: usize, rings: usize) -> Mesh {
+		let segments = segments.max(3);
+		let rings = rings.max(1);
+		let half = (height * 0.5 - radius).max(0.0);
+		let total = 2.0 * (half + radius);
+
+		let mut profile = Vec::new();
+		for &(center, first) in [(half, 0.0), (-half, f32::consts::PI * 0.5)].iter() {
+			for ring in 0..rings + 1 {
+				let phi = first + f32::consts::PI * 0.5 * ring as f32 / rings as f32;
+				let y = center + radius * phi.cos();
+				profile.push(Ring {
+					y: y,
+					radius: radius * phi.sin(),
+					normal: (phi.sin(), phi.cos()),
+					v: y / total + 0.5,
+				});
+			}
+		}
+
+		let mut mesh = Mesh::new();
+		mesh.uvs.push(Vec::new());
+		mesh.add_lathe(&profile, segments);
+		mesh.compute_tangents();
+		mesh
+	}
+
+	pub fn vertex_count(&self) -> usize {
+		self.positions.len()
+	}
+
+	pub fn triangle_count(&self) -> usize {
+		self.indices.len() / 3
+	}
+
+	// Gives every vertex the same color
+	pub fn set_color(&mut self, color: &Vec4) {
+		self.colors = vec![*color; self.positions.len()];
+	}
+
+	// Smooth normals, faces are weighted by their area. Vertices that are
+	// duplicated for seams get the normals of their own faces only.
+	pub fn compute_normals(&mut self) {
+		let mut normals = vec![Vec3::new(); self.positions.len()];
+
+		for triangle in self.indices.chunks(3) {
+			if triangle.len() < 3 {
+				break;
+			}
+			let (a, b, c) = (triangle[0] as usize, triangle[1] as usize, triangle[2] as usize);
+			// Not normalized, the length is twice the area
+			let normal = Vec3::cross(&(&self.positions[b] - &self.positions[a]),
+				&(&self.positions[c] - &self.positions[a]));
+			for &index in &[a, b, c] {
+				normals[index] = &normals[index] + &normal;
+			}
+		}
+
+		self.normals = normals.iter().map(|normal| normalize_or(normal, &vec3(0.0, 1.0, 0.0))).collect();
+	}
+
+	// Tangents along +U of the first UV channel, orthogonalized against the normals.
+	// Needs normals and UVs, computes the normals when they are missing.
+	pub fn compute_tangents(&mut self) {
+		if self.uvs.is_empty() || self.uvs[0].len() != self.positions.len() {
+			self.tangents.clear();
+			return;
+		}
+		if self.normals.len() != self.positions.len() {
+			self.compute_normals();
+		}
+
+		let mut tangents = vec![Vec3::new(); self.positions.len()];
+		let mut bitangents = vec![Vec3::new(); self.positions.len()];
+		let uvs = &self.uvs[0];
+
+		for triangle in self.indices.chunks(3) {
+			if triangle.len() < 3 {
+				break;
+			}
+			let (a, b, c) = (triangle[0] as usize, triangle[1] as usize, triangle[2] as usize);
+			let edge1 = &self.positions[b] - &self.positions[a];
+			let edge2 = &self.positions[c] - &self.positions[a];
+			let delta1 = &uvs[b] - &uvs[a];
+			let delta2 = &uvs[c] - &uvs[a];
+
+			let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+			if determinant.abs() < 1e-12 {
+				continue;
+			}
+			let r = 1.0 / determinant;
+			let tangent = (&edge1 * delta2.y - &edge2 * delta1.y) * r;
+			let bitangent = (&edge2 * delta1.x - &edge1 * delta2.x) * r;
+
+			for &index in &[a, b, c] {
+				tangents[index] = &tangents[index] + &tangent;
+				bitangents[index] = &bitangents[index] + &bitangent;
+			}
+		}
+
+		self.tangents = (0..self.positions.len()).map(|index| {
+			let normal = &self.normals[index];
+			let tangent = &tangents[index] - &(normal * Vec3::dot(normal, &tangents[index]));
+			let tangent = normalize_or(&tangent, &perpendicular(normal));
+			let handedness = if Vec3::dot(&Vec3::cross(normal, &tangent), &bitangents[index]) < 0.0 {
+				-1.0
+			} else {
+				1.0
+			};
+			Vec4 { x: tangent.x, y: tangent.y, z: tangent.z, w: handedness }
+		}).collect();
+	}
+
+	// Interleaves every non empty channel into one vertex buffer. Call again after
+	// changing the mesh, the buffers are updated in place while the channels stay the same.
+	// Nothing is uploaded when a channel doesn't match the positions.
+	pub fn upload(&mut self) -> Result<(), MeshError> {
+		let count = self.positions.len();
+		if self.uvs.len() > MAX_UV_CHANNELS {
+			return Err(MeshError::TooManyUvChannels { channels: self.uvs.len() });
+		}
+		let normals = try!(used(self.normals.len(), count, "normals"));
+		let tangents = try!(used(self.tangents.len(), count, "tangents"));
+		let colors = try!(used(self.colors.len(), count, "colors"));
+		let mut uv_channels = Vec::with_capacity(self.uvs.len());
+		for (channel, uvs) in self.uvs.iter().enumerate() {
+			uv_channels.push(try!(used(uvs.len(), count, &format!("UVs in channel {}", channel))));
+		}
+
+		let mut layout = VertexLayout::new();
+		layout.float(POSITION_LOCATION, 3);
+		if normals {
+			layout.float(NORMAL_LOCATION, 3);
+		}
+		if tangents {
+			layout.float(TANGENT_LOCATION, 4);
+		}
+		if colors {
+			layout.float(COLOR_LOCATION, 4);
+		}
+		for (channel, &used) in uv_channels.iter().enumerate() {
+			if used {
+				layout.float(uv_location(channel), 2);
+			}
+		}
+
+		let mut data: Vec<f32> = Vec::with_capacity(count * layout.stride() / 4);
+		for index in 0..count {
+			let position = &self.positions[index];
+			data.extend_from_slice(&[position.x, position.y, position.z]);
+			if normals {
+				let normal = &self.normals[index];
+				data.extend_from_slice(&[normal.x, normal.y, normal.z]);
+			}
+			if tangents {
+				let tangent = &self.tangents[index];
+				data.extend_from_slice(&[tangent.x, tangent.y, tangent.z, tangent.w]);
+			}
+			if colors {
+				let color = &self.colors[index];
+				data.extend_from_slice(&[color.x, color.y, color.z, color.w]);
+			}
+			for (uvs, &used) in self.uvs.iter().zip(&uv_channels) {
+				if used {
+					data.extend_from_slice(&[uvs[index].x, uvs[index].y]);
+				}
+			}
+		}
+
+		let small_indices: Option<Vec<u16>> = if count <= 65536 {
+			Some(self.indices.iter().map(|&index| index as u16).collect())
+		} else {
+			None
+		};
+
+		if let Some(ref mut buffers) = self.buffers {
+			if buffers.layout == layout {
+				buffers.vertices.update(&data);
+				match small_indices {
+					Some(ref indices) => buffers.indices.update(indices),
+					None => buffers.indices.update(&self.indices),
+				}
+				buffers.vertex_array.set_vertex_count(count);
+				return Ok(());
+			}
+		}
+
+		let vertices = VertexBuffer::new(&data, BufferUsage::Static);
+		let indices = match small_indices {
+			Some(ref indices) => IndexBuffer::new(indices, BufferUsage::Static),
+			None => IndexBuffer::new(&self.indices, BufferUsage::Static),
+		};
+
+		let mut vertex_array = VertexArray::new();
+		vertex_array.add_vertex_buffer(&vertices, &layout);
+		vertex_array.set_index_buffer(&indices);
+
+		self.buffers = Some(MeshBuffers {
+			vertex_array: vertex_array,
+			layout: layout,
+			vertices: vertices,
+			indices: indices,
+		});
+		Ok(())
+	}
+
+	pub fn is_uploaded(&self) -> bool {
+		self.buffers.is_some()
+	}
+
+	// Frees the GPU buffers, the CPU data stays
+	pub fn release(&mut self) {
+		self.buffers = None;
+	}
+
+	// Draws the uploaded mesh with the bound shader, nothing happens before upload
+	pub fn draw(&self) {
+		if let Some(ref buffers) = self.buffers {
+			buffers.vertex_array.draw(Primitive::Triangles);
+		}
+	}
+
+	// Two triangles spanning center +- right +- up, UV (0, 0) in the lower left
+	fn add_face(&mut self, normal: &Vec3, right: &Vec3, up: &Vec3, distance: f32) {
+		let first = self.positions.len() as u32;
+		let center = normal * distance;
+		let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+		for &(x, y) in corners.iter() {
+			self.positions.push(&(&center + &(right * x)) + &(up * y));
+			self.normals.push(*normal);
+			self.uvs[0].push(Vec2 { x: (x + 1.0) * 0.5, y: (y + 1.0) * 0.5 });
+		}
+
+		self.indices.extend_from_slice(&[first, first + 1, first + 2, first + 2, first + 3, first]);
+	}
+
+	// Revolves a profile from top to bottom around the Y axis. The seam gets
+	// duplicate vertices so U runs from 0 to 1.
+	fn add_lathe(&mut self, profile: &[Ring], segments: usize) {
+		let first = self.positions.len() as u32;
+		let columns = (segments + 1) as u32;
+
+		for ring in profile {
+			for segment in 0..segments + 1 {
+				let u = segment as f32 / segments as f32;
+				let (sin, cos) = (u * 2.0 * f32::consts::PI).sin_cos();
+				self.positions.push(vec3(ring.radius * sin, ring.y, ring.radius * cos));
+				self.normals.push(normalize_or(&vec3(ring.normal.0 * sin, ring.normal.1, ring.normal.0 * cos),
+					&vec3(0.0, ring.y.signum(), 0.0)));
+				self.uvs[0].push(Vec2 { x: u, y: ring.v });
+			}
+		}
+
+		for row in 0..profile.len().saturating_sub(1) {
+			for segment in 0..segments as u32 {
+				let top = first + row as u32 * columns + segment;
+				let bottom = top + columns;
+				// Rings of radius zero are poles, skip the triangles that collapse there
+				if profile[row + 1].radius > 0.0 {
+					self.indices.extend_from_slice(&[top, bottom, bottom + 1]);
+				}
+				if profile[row].radius > 0.0 {
+					self.indices.extend_from_slice(&[bottom + 1, top + 1, top]);
+				}
+			}
+		}
+	}
+
+	// Disc at height y facing up (direction 1) or down (direction -1)
+	fn add_cap(&mut self, radius: f32, y: f32, direction: f32, segments: usize) {
+		let center = self.positions.len() as u32;
+		self.positions.push(vec3(0.0, y, 0.0));
+		self.normals.push(vec3(0.0, direction, 0.0));
+		self.uvs[0].push(Vec2 { x: 0.5, y: 0.5 });
+
+		for segment in 0..segments + 1 {
+			let (sin, cos) = (segment as f32 / segments as f32 * 2.0 * f32::consts::PI).sin_cos();
+			self.positions.push(vec3(radius * sin, y, radius * cos));
+			self.normals.push(vec3(0.0, direction, 0.0));
+			// Mirrored on the bottom so the texture isn't flipped when seen from below
+			self.uvs[0].push(Vec2 { x: 0.5 + sin * 0.5 * direction, y: 0.5 - cos * 0.5 });
+		}
+
+		for segment in 0..segments as u32 {
+			let (a, b) = (center + 1 + segment, center + 2 + segment);
+			if direction > 0.0 {
+				self.indices.extend_from_slice(&[center, a, b]);
+			} else {
+				self.indices.extend_from_slice(&[center, b, a]);
+			}
+		}
+	}
+}
+
+// One ring of a lathe profile, the normal is given as (radial, y)
+struct Ring {
+	y: f32,
+	radius: f32,
+	normal: (f32, f32),
+	v: f32,
+}
+
+fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+	Vec3 { x: x, y: y, z: z }
+}
+
+fn normalize_or(v: &Vec3, fallback: &Vec3) -> Vec3 {
+	if v.length_squared() > 1e-12 { v.normalized() } else { *fallback }
+}
+
+// Any unit vector perpendicular to v
+fn perpendicular(v: &Vec3) -> Vec3 {
+	let axis = if v.x.abs() < 0.9 { vec3(1.0, 0.0, 0.0) } else { vec3(0.0, 1.0, 0.0) };
+	normalize_or(&Vec3::cross(v, &axis), &vec3(0.0, 0.0, 1.0))
+}
+
+// Whether a channel with len entries goes into the vertex buffer, empty channels are left out
+fn used(len: usize, count: usize, channel: &str) -> Result<bool, MeshError> {
+	if len != 0 && len != count {
+		return Err(MeshError::ChannelLength { channel: channel.to_string(), len: len, positions: count });
+	}
+	Ok(len != 0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn generated() -> Vec<(&'static str, Mesh)> {
+		vec![
+			("quad", Mesh::quad(2.0, 1.0)),
+			("cube", Mesh::cube(2.0)),
+			("sphere", Mesh::sphere(1.5, 8, 4)),
+			("plane", Mesh::plane(2.0, 3.0, 3, 2)),
+			("cylinder", Mesh::cylinder(1.0, 2.0, 8)),
+			("capsule", Mesh::capsule(0.5, 2.0, 8, 2)),
+		]
+	}
+
+	fn assert_unit(v: &Vec3, what: &str) {
+		assert!((v.length() - 1.0).abs() < 1e-4, "{} {:?} isn't unit length", what, v);
+	}
+
+	#[test]
+	fn generator_counts() {
+		// Vertices and indices in the order of generated()
+		let counts = [(4, 6), (24, 36), (45, 144), (12, 36), (38, 96), (54, 192)];
+		for (&(name, ref mesh), &(vertices, indices)) in generated().iter().zip(counts.iter()) {
+			assert_eq!((mesh.vertex_count(), mesh.indices.len()), (vertices, indices), "{}", name);
+			assert!(mesh.indices.iter().all(|&index| (index as usize) < vertices), "{} has an index out of range", name);
+			assert_eq!((mesh.normals.len(), mesh.tangents.len(), mesh.uvs.len(), mesh.uvs[0].len()), (vertices, vertices, 1, vertices), "{}", name);
+		}
+	}
+
+	#[test]
+	fn generator_normals_and_tangents() {
+		for &(name, ref mesh) in generated().iter() {
+			for (normal, tangent) in mesh.normals.iter().zip(&mesh.tangents) {
+				assert_unit(normal, name);
+				let direction = vec3(tangent.x, tangent.y, tangent.z);
+				assert_unit(&direction, name);
+				assert!(Vec3::dot(normal, &direction).abs() < 1e-4, "{} tangent {:?} isn't orthogonal to {:?}", name, tangent, normal);
+				assert!(tangent.w == 1.0 || tangent.w == -1.0, "{} handedness {}", name, tangent.w);
+			}
+		}
+	}
+
+	#[test]
+	fn generator_winding() {
+		// Counter clockwise triangles face along the normals of their vertices
+		for &(name, ref mesh) in generated().iter() {
+			for triangle in mesh.indices.chunks(3) {
+				let (a, b, c) = (&mesh.positions[triangle[0] as usize], &mesh.positions[triangle[1] as usize], &mesh.positions[triangle[2] as usize]);
+				let face = Vec3::cross(&(b - a), &(c - a));
+				assert!(Vec3::dot(&face, &mesh.normals[triangle[0] as usize]) > 0.0, "{} triangle {:?} faces inwards", name, triangle);
+			}
+		}
+	}
+
+	#[test]
+	fn computed_normals() {
+		// Cube faces have their own vertices, so the computed normals are the face normals
+		let mut cube = Mesh::cube(2.0);
+		let expected = cube.normals.clone();
+		cube.normals.clear();
+		cube.compute_normals();
+		assert_eq!(cube.normals, expected);
+
+		let mut plane = Mesh::plane(2.0, 2.0, 4, 4);
+		plane.normals.clear();
+		plane.compute_normals();
+		assert_eq!(plane.normals, vec![vec3(0.0, 1.0, 0.0); plane.vertex_count()]);
+
+		// A shared vertex averages its faces weighted by their area
+		let mut mesh = Mesh::new();
+		mesh.positions = vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 3.0)];
+		mesh.indices = vec![0, 1, 2, 0, 2, 3];
+		mesh.compute_normals();
+		let shared = vec3(1.5, 0.0, 0.5).normalized();
+		assert!((mesh.normals[0] - shared).length() < 1e-5, "{:?}", mesh.normals[0]);
+		assert_eq!(mesh.normals[1], vec3(0.0, 0.0, 1.0));
+		assert_eq!(mesh.normals[3], vec3(1.0, 0.0, 0.0));
+	}
+
+	#[test]
+	fn computed_tangents() {
+		// U runs along +X on the quad and along -Z on the +X face of the cube
+		let quad = Mesh::quad(1.0, 1.0);
+		assert_eq!(quad.tangents, vec![Vec4 { x: 1.0, y: 0.0, z: 0.0, w: 1.0 }; 4]);
+		let cube = Mesh::cube(1.0);
+		assert_eq!(cube.tangents[8], Vec4 { x: 0.0, y: 0.0, z: -1.0, w: 1.0 });
+
+		// Mirrored UVs flip the handedness
+		let mut mirrored = Mesh::quad(1.0, 1.0);
+		for uv in &mut mirrored.uvs[0] {
+			uv.y = 1.0 - uv.y;
+		}
+		mirrored.compute_tangents();
+		assert!(mirrored.tangents.iter().all(|tangent| tangent.w == -1.0));
+
+		// Without UVs there are no tangents
+		let mut mesh = Mesh::quad(1.0, 1.0);
+		mesh.uvs.clear();
+		mesh.compute_tangents();
+		assert!(mesh.tangents.is_empty());
+	}
+
+	#[test]
+	fn upload_rejects_mismatched_channels() {
+		// The channels are checked before anything is sent to GL
+		let mut mesh = Mesh::quad(1.0, 1.0);
+		mesh.normals.pop();
+		assert_eq!(mesh.upload(), Err(MeshError::ChannelLength { channel: "normals".to_string(), len: 3, positions: 4 }));
+		assert!(!mesh.is_uploaded());
+
+		let mut mesh = Mesh::quad(1.0, 1.0);
+		mesh.uvs.push(vec![Vec2::new(); 5]);
+		assert_eq!(mesh.upload(), Err(MeshError::ChannelLength { channel: "UVs in channel 1".to_string(), len: 5, positions: 4 }));
+
+		let mut mesh = Mesh::quad(1.0, 1.0);
+		mesh.set_color(&Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 });
+		mesh.colors.push(Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 });
+		assert_eq!(mesh.upload(), Err(MeshError::ChannelLength { channel: "colors".to_string(), len: 5, positions: 4 }));
+
+		let mut mesh = Mesh::quad(1.0, 1.0);
+		let uvs = mesh.uvs[0].clone();
+		mesh.uvs = vec![uvs; MAX_UV_CHANNELS + 1];
+		assert_eq!(mesh.upload(), Err(MeshError::TooManyUvChannels { channels: MAX_UV_CHANNELS + 1 }));
+	}
+}
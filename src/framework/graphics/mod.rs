@@ -1,5 +1,68 @@
 pub mod texture;
-pub use self::texture::Texture;
+pub use self::texture::{Texture, TextureDesc, TextureError};
+
+pub mod sampler;
+pub use self::sampler::{CompareFunc, Filter, MipFilter, Sampler, SamplerCache, SamplerDesc, Wrap};
 
 pub mod shader;
-pub use self::shader::{InternalShader, Shader, Uniform};
\ No newline at end of file
+pub use self::shader::{InternalShader, Shader, ShaderBuilder, ShaderDiagnostic, ShaderError, ShaderStage, ShaderVariants, Uniform};
+
+pub mod buffer;
+pub use self::buffer::{BufferUsage, IndexBuffer, Primitive, VertexArray, VertexBuffer, VertexLayout};
+
+pub mod capabilities;
+
+pub mod capture;
+pub use self::capture::capture_framebuffer;
+
+pub mod compute;
+pub use self::compute::{Barrier, ComputeShader, memory_barrier};
+
+pub mod cubemap;
+pub use self::cubemap::CubemapTexture;
+
+#[cfg(feature = "headless")]
+pub mod headless;
+#[cfg(feature = "headless")]
+pub use self::headless::{HeadlessContext, HeadlessError};
+
+pub mod image;
+pub use self::image::{DdsImage, RgbaImage};
+
+pub mod light;
+pub use self::light::{Light, LightKind, LightSet};
+
+pub mod lit_shader;
+pub use self::lit_shader::LitShader;
+
+pub mod loaders;
+
+pub mod material;
+pub use self::material::Material;
+
+pub mod mesh;
+pub use self::mesh::{Mesh, MeshError};
+
+pub mod post_process;
+pub use self::post_process::{Antialiasing, Bloom, Param, PostEffect, PostProcessError, PostProcessStack, ShaderPass, Tonemapper};
+
+pub mod preprocessor;
+pub use self::preprocessor::{Preprocessor, ShaderSource};
+
+pub mod program_cache;
+pub use self::program_cache::ProgramCache;
+
+pub mod reflection;
+pub use self::reflection::{AttributeInfo, GlslType, UniformBlockInfo, UniformInfo};
+
+pub mod render_target;
+pub use self::render_target::{RenderTarget, RenderTargetDesc, RenderTargetError};
+
+pub mod skybox;
+pub use self::skybox::Skybox;
+
+pub mod storage_buffer;
+pub use self::storage_buffer::ShaderStorageBuffer;
+
+pub mod uniform_buffer;
+pub use self::uniform_buffer::{Std140Writer, UniformBuffer};
\ No newline at end of file
@@ -0,0 +1,198 @@
+extern crate gl;
+
+use framework::core::HotReload;
+use framework::graphics::{InternalShader, RenderTarget, RenderTargetDesc, SamplerCache, ShaderBuilder, ShaderError, Texture};
+use framework::graphics::post_process::{upload_param, FullscreenTriangle, Param, PostEffect, PostProcessError};
+use framework::graphics::post_process::{FULLSCREEN_VERTEX_SHADER, HDR_FORMAT};
+
+pub const EXTRACT_SHADER: &'static str = "./assets/shaders/post/bloom_extract.fs.glsl";
+pub const DOWNSAMPLE_SHADER: &'static str = "./assets/shaders/post/bloom_downsample.fs.glsl";
+pub const UPSAMPLE_SHADER: &'static str = "./assets/shaders/post/bloom_upsample.fs.glsl";
+pub const COMBINE_SHADER: &'static str = "./assets/shaders/post/bloom_combine.fs.glsl";
+
+// Glow around bright HDR colors, put it before tonemapping. The bright parts are copied
+// into a chain of ever smaller targets, blurred on the way back up and added to the image.
+pub struct Bloom {
+	enabled: bool,
+	threshold: f32,
+	knee: f32,
+	intensity: f32,
+	levels: u32,
+	extract: InternalShader,
+	downsample: InternalShader,
+	upsample: InternalShader,
+	combine: InternalShader,
+	chain: Vec<RenderTarget>,		// Half the input size and smaller
+	chain_size: (u32, u32),
+}
+
+impl Bloom {
+	pub fn new() -> Result<Bloom, ShaderError> {
+		Ok(Bloom {
+			enabled: true,
+			threshold: 1.0,
+			knee: 0.5,
+			intensity: 0.1,
+			levels: 5,
+			extract: try!(fullscreen_shader(EXTRACT_SHADER)),
+			downsample: try!(fullscreen_shader(DOWNSAMPLE_SHADER)),
+			upsample: try!(fullscreen_shader(UPSAMPLE_SHADER)),
+			combine: try!(fullscreen_shader(COMBINE_SHADER)),
+			chain: Vec::new(),
+			chain_size: (0, 0),
+		})
+	}
+
+	// Rebuilds the chain when the input size or the number of levels changed
+	fn update_chain(&mut self, width: u32, height: u32, samplers: &SamplerCache) -> Result<(), PostProcessError> {
+		let mut levels = 0;
+		let (mut level_width, mut level_height) = (width / 2, height / 2);
+		while levels < self.levels.max(1) && level_width >= 2 && level_height >= 2 {
+			levels += 1;
+			level_width /= 2;
+			level_height /= 2;
+		}
+
+		if self.chain_size == (width, height) && self.chain.len() == levels as usize {
+			return Ok(());
+		}
+
+		self.chain.clear();
+		let desc = RenderTargetDesc { depth_format: None, ..RenderTargetDesc::with_color(&[HDR_FORMAT]) };
+		for level in 0..levels {
+			let (level_width, level_height) = ((width >> (level + 1)).max(1), (height >> (level + 1)).max(1));
+			self.chain.push(try!(RenderTarget::new(level_width, level_height, &desc, samplers)
+				.map_err(PostProcessError::render_target)));
+		}
+		self.chain_size = (width, height);
+		Ok(())
+	}
+
+	fn render_chain(&self, input: &Texture, triangle: &FullscreenTriangle) -> Result<(), ShaderError> {
+		if self.chain.is_empty() {
+			return Ok(());
+		}
+
+		self.chain[0].begin();
+		self.extract.begin();
+		let result = upload_param(&self.extract, "threshold", Param::Float(self.threshold))
+			.and_then(|_| upload_param(&self.extract, "knee", Param::Float(self.knee)))
+			.and_then(|_| triangle.draw(&self.extract, &[("input_texture", input)]));
+		self.extract.end();
+		try!(result);
+
+		self.downsample.begin();
+		for level in 1..self.chain.len() {
+			self.chain[level].begin();
+			let result = triangle.draw(&self.downsample, &[("input_texture", self.chain[level - 1].color_texture(0))]);
+			if result.is_err() {
+				self.downsample.end();
+				return result;
+			}
+		}
+		self.downsample.end();
+
+		// Each level gets the blurred level below it added on top
+		unsafe {
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::ONE, gl::ONE);
+		}
+		self.upsample.begin();
+		let mut result = Ok(());
+		for level in (0..self.chain.len() - 1).rev() {
+			self.chain[level].begin();
+			result = triangle.draw(&self.upsample, &[("input_texture", self.chain[level + 1].color_texture(0))]);
+			if result.is_err() {
+				break;
+			}
+		}
+		self.upsample.end();
+		unsafe {
+			gl::Disable(gl::BLEND);
+		}
+		result
+	}
+}
+
+impl PostEffect for Bloom {
+	fn name(&self) -> &str {
+		"bloom"
+	}
+
+	fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+	}
+
+	// threshold, knee and intensity are floats, levels is an int
+	fn param(&self, name: &str) -> Option<Param> {
+		match name {
+			"threshold" => Some(Param::Float(self.threshold)),
+			"knee" => Some(Param::Float(self.knee)),
+			"intensity" => Some(Param::Float(self.intensity)),
+			"levels" => Some(Param::Int(self.levels as i32)),
+			_ => None,
+		}
+	}
+
+	fn set_param(&mut self, name: &str, value: Param) -> bool {
+		match (name, value) {
+			("threshold", Param::Float(value)) => self.threshold = value,
+			("knee", Param::Float(value)) => self.knee = value.max(0.0),
+			("intensity", Param::Float(value)) => self.intensity = value,
+			("levels", Param::Int(value)) => self.levels = value.max(1) as u32,
+			_ => return false,
+		}
+		true
+	}
+
+	fn prepare(&mut self, input: &Texture, width: u32, height: u32, triangle: &FullscreenTriangle,
+		samplers: &SamplerCache) -> Result<(), PostProcessError> {
+		try!(self.update_chain(width, height, samplers));
+		self.render_chain(input, triangle).map_err(|error| PostProcessError::shader("bloom", error))
+	}
+
+	fn apply(&self, input: &Texture, triangle: &FullscreenTriangle) -> Result<(), PostProcessError> {
+		self.combine.begin();
+		let result = match self.chain.first() {
+			Some(bloom) => upload_param(&self.combine, "intensity", Param::Float(self.intensity))
+				.and_then(|_| triangle.draw(&self.combine, &[("input_texture", input), ("bloom_texture", bloom.color_texture(0))])),
+			// Too small to bloom, the input is still copied through
+			None => upload_param(&self.combine, "intensity", Param::Float(0.0))
+				.and_then(|_| triangle.draw(&self.combine, &[("input_texture", input), ("bloom_texture", input)])),
+		};
+		self.combine.end();
+		result.map_err(|error| PostProcessError::shader("bloom", error))
+	}
+}
+
+impl HotReload for Bloom {
+	type Error = ShaderError;
+
+	fn source_files(&self) -> Vec<String> {
+		let mut files = self.extract.source_files();
+		files.extend(self.downsample.source_files());
+		files.extend(self.upsample.source_files());
+		files.extend(self.combine.source_files());
+		files.sort();
+		files.dedup();
+		files
+	}
+
+	fn reload(&mut self) -> Result<(), ShaderError> {
+		try!(self.extract.reload());
+		try!(self.downsample.reload());
+		try!(self.upsample.reload());
+		self.combine.reload()
+	}
+}
+
+fn fullscreen_shader(fragment_path: &str) -> Result<InternalShader, ShaderError> {
+	ShaderBuilder::new()
+		.vertex(FULLSCREEN_VERTEX_SHADER)
+		.fragment(fragment_path)
+		.build()
+}
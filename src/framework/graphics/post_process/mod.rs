@@ -0,0 +1,388 @@
+extern crate gl;
+
+use gl::types::*;
+
+use std::error;
+use std::fmt;
+
+use framework::core::HotReload;
+use framework::graphics::{InternalShader, Primitive, RenderTarget, RenderTargetDesc, RenderTargetError};
+use framework::graphics::{SamplerCache, ShaderError, Texture, VertexArray};
+use framework::math::{Vec2, Vec3, Vec4};
+
+pub mod bloom;
+pub use self::bloom::Bloom;
+
+pub mod shader_pass;
+pub use self::shader_pass::{identity_lut, load_lut, ShaderPass, Tonemapper};
+
+pub const FULLSCREEN_VERTEX_SHADER: &'static str = "./assets/shaders/post/fullscreen.vs.glsl";
+
+// The scene and everything between the passes stays HDR until it is tonemapped
+const HDR_FORMAT: GLenum = gl::RGBA16F;
+
+// Value of an effect parameter, uploaded to the uniform of the same name
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param {
+	Bool(bool),
+	Int(i32),
+	Float(f32),
+	Vec2(Vec2),
+	Vec3(Vec3),
+	Vec4(Vec4),
+}
+
+// MSAA renders the scene target multisampled, FXAA is a pass near the end of the stack
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Antialiasing {
+	None,
+	Msaa(u32),
+	Fxaa,
+}
+
+impl Antialiasing {
+	pub fn samples(&self) -> u32 {
+		match *self {
+			Antialiasing::Msaa(samples) => samples.max(1),
+			_ => 1,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum PostProcessError {
+	Shader { effect: String, error: ShaderError },
+	RenderTarget { error: RenderTargetError },
+}
+
+impl PostProcessError {
+	pub fn shader(effect: &str, error: ShaderError) -> PostProcessError {
+		PostProcessError::Shader { effect: effect.to_string(), error: error }
+	}
+
+	pub fn render_target(error: RenderTargetError) -> PostProcessError {
+		PostProcessError::RenderTarget { error: error }
+	}
+}
+
+impl fmt::Display for PostProcessError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			PostProcessError::Shader { ref effect, ref error } => {
+				write!(f, "Post-process effect {} failed: {}", effect, error)
+			},
+			PostProcessError::RenderTarget { ref error } => {
+				write!(f, "Could not create post-process targets: {}", error)
+			},
+		}
+	}
+}
+
+impl error::Error for PostProcessError {}
+
+// A triangle that covers the viewport, the vertex shader makes it from gl_VertexID
+pub struct FullscreenTriangle {
+	vertex_array: VertexArray,
+}
+
+impl FullscreenTriangle {
+	pub fn new() -> FullscreenTriangle {
+		let mut vertex_array = VertexArray::new();
+		vertex_array.set_vertex_count(3);
+		FullscreenTriangle { vertex_array: vertex_array }
+	}
+
+	// Binds the textures to their samplers and draws, call between shader.begin and end
+	pub fn draw(&self, shader: &InternalShader, textures: &[(&str, &Texture)]) -> Result<(), ShaderError> {
+		let mut units = Vec::with_capacity(textures.len());
+		let mut result = Ok(());
+		for &(name, texture) in textures {
+			match shader.bind_texture(name, texture) {
+				Ok(unit) => units.push((unit, texture)),
+				Err(error) => {
+					result = Err(error);
+					break;
+				},
+			}
+		}
+
+		if result.is_ok() {
+			self.vertex_array.draw(Primitive::Triangles);
+		}
+		for (unit, texture) in units {
+			texture.unbind(unit);
+		}
+		result
+	}
+}
+
+// Uploads a parameter to the uniform of the same name, the shader has to be in use
+fn upload_param(shader: &InternalShader, name: &str, value: Param) -> Result<(), ShaderError> {
+	let uniform = try!(shader.uniform(name));
+	match value {
+		Param::Bool(value) => shader.set_bool(&uniform, value),
+		Param::Int(value) => shader.set_i32(&uniform, value),
+		Param::Float(value) => shader.set_f32(&uniform, value),
+		Param::Vec2(value) => shader.set_vec2(&uniform, value),
+		Param::Vec3(value) => shader.set_vec3(&uniform, value),
+		Param::Vec4(value) => shader.set_vec4(&uniform, value),
+	}
+}
+
+// One fullscreen step of the stack. Effects read the previous output as a texture and
+// draw into whatever the stack bound, only the last enabled one draws to the screen.
+pub trait PostEffect: HotReload<Error = ShaderError> {
+	fn name(&self) -> &str;
+	fn is_enabled(&self) -> bool;
+	fn set_enabled(&mut self, enabled: bool);
+
+	fn param(&self, name: &str) -> Option<Param>;
+	// False when the effect has no parameter with that name
+	fn set_param(&mut self, name: &str, value: Param) -> bool;
+
+	// Renders into the effect's own targets before the output is bound, input is width x height
+	fn prepare(&mut self, _input: &Texture, _width: u32, _height: u32, _triangle: &FullscreenTriangle,
+		_samplers: &SamplerCache) -> Result<(), PostProcessError> {
+		Ok(())
+	}
+
+	fn apply(&self, input: &Texture, triangle: &FullscreenTriangle) -> Result<(), PostProcessError>;
+}
+
+// Renders the scene into an HDR target and runs it through an ordered list of effects:
+// post_process.begin();
+// ... draw the scene ...
+// try!(post_process.end(&samplers));
+pub struct PostProcessStack {
+	effects: Vec<Box<dyn PostEffect>>,
+	scene: RenderTarget,
+	targets: Vec<RenderTarget>,		// Passes draw into these in turn
+	triangle: FullscreenTriangle,
+	antialiasing: Antialiasing,
+}
+
+impl PostProcessStack {
+	// An empty stack, the scene goes to the screen as it is
+	pub fn new(width: u32, height: u32, antialiasing: Antialiasing, samplers: &SamplerCache) -> Result<PostProcessStack, PostProcessError> {
+		let scene = try!(scene_target(width, height, antialiasing, samplers));
+		let mut targets = Vec::with_capacity(2);
+		for _ in 0..2 {
+			let desc = RenderTargetDesc { depth_format: None, ..RenderTargetDesc::with_color(&[HDR_FORMAT]) };
+			targets.push(try!(RenderTarget::new(width, height, &desc, samplers).map_err(PostProcessError::render_target)));
+		}
+
+		Ok(PostProcessStack {
+			effects: Vec::new(),
+			scene: scene,
+			targets: targets,
+			triangle: FullscreenTriangle::new(),
+			antialiasing: antialiasing,
+		})
+	}
+
+	// Bloom, ACES tonemapping, gamma correction, FXAA (enabled when chosen as antialiasing)
+	// and a vignette that starts disabled
+	pub fn with_defaults(width: u32, height: u32, antialiasing: Antialiasing, samplers: &SamplerCache) -> Result<PostProcessStack, PostProcessError> {
+		let mut stack = try!(PostProcessStack::new(width, height, antialiasing, samplers));
+
+		stack.push(Box::new(try!(Bloom::new().map_err(|e| PostProcessError::shader("bloom", e)))));
+		stack.push(Box::new(try!(ShaderPass::tonemap(Tonemapper::Aces, 1.0).map_err(|e| PostProcessError::shader("tonemap", e)))));
+		stack.push(Box::new(try!(ShaderPass::gamma(2.2).map_err(|e| PostProcessError::shader("gamma", e)))));
+
+		let mut fxaa = try!(ShaderPass::fxaa().map_err(|e| PostProcessError::shader("fxaa", e)));
+		fxaa.set_enabled(antialiasing == Antialiasing::Fxaa);
+		stack.push(Box::new(fxaa));
+
+		let mut vignette = try!(ShaderPass::vignette().map_err(|e| PostProcessError::shader("vignette", e)));
+		vignette.set_enabled(false);
+		stack.push(Box::new(vignette));
+		Ok(stack)
+	}
+
+	pub fn push(&mut self, effect: Box<dyn PostEffect>) {
+		self.effects.push(effect);
+	}
+
+	pub fn insert(&mut self, index: usize, effect: Box<dyn PostEffect>) {
+		self.effects.insert(index, effect);
+	}
+
+	pub fn remove(&mut self, name: &str) -> Option<Box<dyn PostEffect>> {
+		self.position(name).map(|index| self.effects.remove(index))
+	}
+
+	pub fn position(&self, name: &str) -> Option<usize> {
+		self.effects.iter().position(|effect| effect.name() == name)
+	}
+
+	pub fn effects(&self) -> &[Box<dyn PostEffect>] {
+		&self.effects
+	}
+
+	pub fn effect(&self, name: &str) -> Option<&dyn PostEffect> {
+		self.effects.iter().find(|effect| effect.name() == name).map(|effect| &**effect)
+	}
+
+	pub fn effect_mut(&mut self, name: &str) -> Option<&mut dyn PostEffect> {
+		match self.effects.iter_mut().find(|effect| effect.name() == name) {
+			Some(effect) => Some(&mut **effect),
+			None => None,
+		}
+	}
+
+	// False when there is no effect with that name
+	pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+		match self.effect_mut(name) {
+			Some(effect) => {
+				effect.set_enabled(enabled);
+				true
+			},
+			None => false,
+		}
+	}
+
+	pub fn set_param(&mut self, effect: &str, name: &str, value: Param) -> bool {
+		match self.effect_mut(effect) {
+			Some(effect) => effect.set_param(name, value),
+			None => false,
+		}
+	}
+
+	// The HDR target the scene is drawn into, its depth can be used by later passes
+	pub fn scene(&self) -> &RenderTarget {
+		&self.scene
+	}
+
+	pub fn antialiasing(&self) -> Antialiasing {
+		self.antialiasing
+	}
+
+	// Recreates the scene target for MSAA and switches the fxaa effect on or off
+	pub fn set_antialiasing(&mut self, antialiasing: Antialiasing, samplers: &SamplerCache) -> Result<(), PostProcessError> {
+		if antialiasing.samples() != self.antialiasing.samples() {
+			self.scene = try!(scene_target(self.scene.width(), self.scene.height(), antialiasing, samplers));
+		}
+		self.set_enabled("fxaa", antialiasing == Antialiasing::Fxaa);
+		self.antialiasing = antialiasing;
+		Ok(())
+	}
+
+	pub fn resize(&mut self, width: u32, height: u32, samplers: &SamplerCache) -> Result<(), PostProcessError> {
+		try!(self.scene.resize(width, height, samplers).map_err(PostProcessError::render_target));
+		for target in &mut self.targets {
+			try!(target.resize(width, height, samplers).map_err(PostProcessError::render_target));
+		}
+		Ok(())
+	}
+
+	// Binds the scene target, clear it and draw the scene after this
+	pub fn begin(&self) {
+		self.scene.begin();
+	}
+
+	// Runs the enabled effects and draws the result to the screen, whose viewport is set
+	// to the size of the stack
+	pub fn end(&mut self, samplers: &SamplerCache) -> Result<(), PostProcessError> {
+		self.run(None, samplers)
+	}
+
+	// Like end, but draws the result into the first color attachment of a render target
+	pub fn end_to(&mut self, output: &RenderTarget, samplers: &SamplerCache) -> Result<(), PostProcessError> {
+		self.run(Some(output), samplers)
+	}
+
+	fn run(&mut self, output: Option<&RenderTarget>, samplers: &SamplerCache) -> Result<(), PostProcessError> {
+		self.scene.end();
+		let (width, height) = (self.scene.width(), self.scene.height());
+
+		let enabled: Vec<usize> = (0..self.effects.len()).filter(|&index| self.effects[index].is_enabled()).collect();
+		if enabled.is_empty() {
+			match output {
+				Some(target) => self.scene.blit_to(0, target),
+				None => self.scene.blit_to_screen(0, width, height),
+			}
+			return Ok(());
+		}
+
+		let (depth_test, blend, cull_face) = unsafe {
+			(gl::IsEnabled(gl::DEPTH_TEST), gl::IsEnabled(gl::BLEND), gl::IsEnabled(gl::CULL_FACE))
+		};
+		unsafe {
+			gl::Disable(gl::DEPTH_TEST);
+			gl::Disable(gl::BLEND);
+			gl::Disable(gl::CULL_FACE);
+		}
+
+		let mut result = Ok(());
+		for (step, &index) in enabled.iter().enumerate() {
+			let input = if step == 0 {
+				self.scene.color_texture(0)
+			} else {
+				self.targets[(step - 1) % 2].color_texture(0)
+			};
+
+			let effect = &mut self.effects[index];
+			result = effect.prepare(input, width, height, &self.triangle, samplers);
+			if result.is_err() {
+				break;
+			}
+
+			if step + 1 < enabled.len() {
+				self.targets[step % 2].begin();
+			} else {
+				match output {
+					Some(target) => target.begin(),
+					None => unsafe {
+						gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+						gl::Viewport(0, 0, width as i32, height as i32);
+					},
+				}
+			}
+
+			result = effect.apply(input, &self.triangle);
+			if result.is_err() {
+				break;
+			}
+		}
+
+		unsafe {
+			gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+			if depth_test == gl::TRUE {
+				gl::Enable(gl::DEPTH_TEST);
+			}
+			if blend == gl::TRUE {
+				gl::Enable(gl::BLEND);
+			}
+			if cull_face == gl::TRUE {
+				gl::Enable(gl::CULL_FACE);
+			}
+		}
+		result
+	}
+}
+
+impl HotReload for PostProcessStack {
+	type Error = PostProcessError;
+
+	fn source_files(&self) -> Vec<String> {
+		self.effects.iter().flat_map(|effect| effect.source_files()).collect()
+	}
+
+	// Every effect is reloaded, the first failure is reported
+	fn reload(&mut self) -> Result<(), PostProcessError> {
+		let mut result = Ok(());
+		for effect in &mut self.effects {
+			if let Err(error) = effect.reload() {
+				if result.is_ok() {
+					result = Err(PostProcessError::shader(effect.name(), error));
+				}
+			}
+		}
+		result
+	}
+}
+
+fn scene_target(width: u32, height: u32, antialiasing: Antialiasing, samplers: &SamplerCache) -> Result<RenderTarget, PostProcessError> {
+	let desc = RenderTargetDesc { samples: antialiasing.samples(), ..RenderTargetDesc::with_color(&[HDR_FORMAT]) };
+	RenderTarget::new(width, height, &desc, samplers).map_err(PostProcessError::render_target)
+}
@@ -0,0 +1,215 @@
+use framework::core::HotReload;
+use framework::graphics::image;
+use framework::graphics::{InternalShader, MipFilter, RgbaImage, SamplerCache, SamplerDesc, ShaderBuilder, ShaderError};
+use framework::graphics::{Texture, TextureDesc, TextureError, Wrap};
+use framework::graphics::post_process::{upload_param, FullscreenTriangle, Param, PostEffect, PostProcessError};
+use framework::graphics::post_process::FULLSCREEN_VERTEX_SHADER;
+
+pub const TONEMAP_SHADER: &'static str = "./assets/shaders/post/tonemap.fs.glsl";
+pub const GAMMA_SHADER: &'static str = "./assets/shaders/post/gamma.fs.glsl";
+pub const FXAA_SHADER: &'static str = "./assets/shaders/post/fxaa.fs.glsl";
+pub const VIGNETTE_SHADER: &'static str = "./assets/shaders/post/vignette.fs.glsl";
+pub const COLOR_GRADE_SHADER: &'static str = "./assets/shaders/post/color_grade.fs.glsl";
+
+// Curve that maps HDR colors into 0..1, the tonemapper parameter of the tonemap pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tonemapper {
+	Reinhard = 0,
+	Aces = 1,
+}
+
+// A fragment shader over the whole screen. It reads the previous output from
+// input_texture at frag_uv, every parameter is a uniform of the same name.
+pub struct ShaderPass {
+	name: String,
+	enabled: bool,
+	shader: InternalShader,
+	params: Vec<(String, Param)>,
+	textures: Vec<(String, Texture)>,
+}
+
+impl ShaderPass {
+	pub fn new(name: &str, fragment_path: &str) -> Result<ShaderPass, ShaderError> {
+		ShaderPass::from_builder(name, ShaderBuilder::new()
+			.vertex(FULLSCREEN_VERTEX_SHADER)
+			.fragment(fragment_path))
+	}
+
+	// For passes that need defines or the program cache, the builder needs the
+	// fullscreen vertex shader too
+	pub fn from_builder(name: &str, builder: &ShaderBuilder) -> Result<ShaderPass, ShaderError> {
+		let shader = try!(builder.build());
+		Ok(ShaderPass {
+			name: name.to_string(),
+			enabled: true,
+			shader: shader,
+			params: Vec::new(),
+			textures: Vec::new(),
+		})
+	}
+
+	// HDR to 0..1, multiplied by exposure first
+	pub fn tonemap(tonemapper: Tonemapper, exposure: f32) -> Result<ShaderPass, ShaderError> {
+		let mut pass = try!(ShaderPass::new("tonemap", TONEMAP_SHADER));
+		pass.add_param("exposure", Param::Float(exposure));
+		pass.add_param("tonemapper", Param::Int(tonemapper as i32));
+		Ok(pass)
+	}
+
+	// Linear to display colors, put it after tonemapping
+	pub fn gamma(gamma: f32) -> Result<ShaderPass, ShaderError> {
+		let mut pass = try!(ShaderPass::new("gamma", GAMMA_SHADER));
+		pass.add_param("gamma", Param::Float(gamma));
+		Ok(pass)
+	}
+
+	// Works on display colors, put it after gamma correction
+	pub fn fxaa() -> Result<ShaderPass, ShaderError> {
+		let mut pass = try!(ShaderPass::new("fxaa", FXAA_SHADER));
+		pass.add_param("span_max", Param::Float(8.0));
+		pass.add_param("reduce_mul", Param::Float(1.0 / 8.0));
+		pass.add_param("reduce_min", Param::Float(1.0 / 128.0));
+		Ok(pass)
+	}
+
+	pub fn vignette() -> Result<ShaderPass, ShaderError> {
+		let mut pass = try!(ShaderPass::new("vignette", VIGNETTE_SHADER));
+		pass.add_param("strength", Param::Float(0.5));
+		pass.add_param("radius", Param::Float(0.4));
+		pass.add_param("softness", Param::Float(0.35));
+		Ok(pass)
+	}
+
+	// Looks colors up in a 3D texture made by load_lut. The table is authored on display
+	// colors, so the pass goes after gamma correction.
+	pub fn color_grade(lut: Texture, strength: f32) -> Result<ShaderPass, ShaderError> {
+		let mut pass = try!(ShaderPass::new("color_grade", COLOR_GRADE_SHADER));
+		pass.add_param("strength", Param::Float(strength));
+		pass.set_texture("lut", lut);
+		Ok(pass)
+	}
+
+	// Adds a parameter or replaces its value, unlike set_param which only changes existing ones
+	pub fn add_param(&mut self, name: &str, value: Param) {
+		match self.params.iter().position(|&(ref param, _)| param == name) {
+			Some(index) => self.params[index].1 = value,
+			None => self.params.push((name.to_string(), value)),
+		}
+	}
+
+	// An extra texture bound to the sampler uniform of that name
+	pub fn set_texture(&mut self, name: &str, texture: Texture) {
+		match self.textures.iter().position(|&(ref sampler, _)| sampler == name) {
+			Some(index) => self.textures[index].1 = texture,
+			None => self.textures.push((name.to_string(), texture)),
+		}
+	}
+
+	pub fn shader(&self) -> &InternalShader {
+		&self.shader
+	}
+}
+
+impl PostEffect for ShaderPass {
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled;
+	}
+
+	fn param(&self, name: &str) -> Option<Param> {
+		self.params.iter().find(|&&(ref param, _)| param == name).map(|&(_, value)| value)
+	}
+
+	fn set_param(&mut self, name: &str, value: Param) -> bool {
+		match self.params.iter_mut().find(|&&mut (ref param, _)| param == name) {
+			Some(param) => {
+				param.1 = value;
+				true
+			},
+			None => false,
+		}
+	}
+
+	fn apply(&self, input: &Texture, triangle: &FullscreenTriangle) -> Result<(), PostProcessError> {
+		self.shader.begin();
+		let mut result = Ok(());
+		for &(ref name, value) in &self.params {
+			result = upload_param(&self.shader, name, value);
+			if result.is_err() {
+				break;
+			}
+		}
+		if result.is_ok() {
+			let mut textures = vec![("input_texture", input)];
+			textures.extend(self.textures.iter().map(|&(ref name, ref texture)| (&name[..], texture)));
+			result = triangle.draw(&self.shader, &textures);
+		}
+		self.shader.end();
+		result.map_err(|error| PostProcessError::shader(&self.name, error))
+	}
+}
+
+impl HotReload for ShaderPass {
+	type Error = ShaderError;
+
+	fn source_files(&self) -> Vec<String> {
+		self.shader.source_files()
+	}
+
+	fn reload(&mut self) -> Result<(), ShaderError> {
+		self.shader.reload()
+	}
+}
+
+// Loads a color grading table stored as a strip of size x size tiles, one per blue value,
+// e.g. 256x16 for 16 entries per channel. Red goes right and green down within a tile.
+// Start from identity_lut saved as png and grade it in an image editor.
+pub fn load_lut(file_path: &str, samplers: &SamplerCache) -> Result<Texture, TextureError> {
+	let strip = try!(image::load(file_path).map_err(|e| TextureError::from_io(file_path, e)));
+	let size = strip.height;
+	if size < 2 || strip.width != size * size {
+		return Err(TextureError::UnsupportedFormat {
+			path: file_path.to_string(),
+			message: format!("a lut strip is size * size by size pixels, not {}x{}", strip.width, strip.height),
+		});
+	}
+
+	let mut data = Vec::with_capacity(strip.data.len());
+	for blue in 0..size {
+		for green in 0..size {
+			for red in 0..size {
+				data.extend_from_slice(&strip.pixel(blue * size + red, green));
+			}
+		}
+	}
+
+	let mut sampler = SamplerDesc::with_wrap(Wrap::ClampToEdge);
+	sampler.mip_filter = MipFilter::None;
+	let desc = TextureDesc { generate_mipmaps: false, sampler: sampler, fallback: false, ..TextureDesc::new() };
+
+	let mut texture = Texture::new();
+	try!(texture.load_volume((size, size, size), &data, &desc, samplers).map_err(|e| TextureError::from_io(file_path, e)));
+	Ok(texture)
+}
+
+// The table that leaves colors as they are, in the strip layout load_lut reads
+pub fn identity_lut(size: u32) -> RgbaImage {
+	let mut strip = RgbaImage::new(size * size, size);
+	let scale = 255.0 / (size - 1).max(1) as f32;
+	for blue in 0..size {
+		for green in 0..size {
+			for red in 0..size {
+				let value = |channel: u32| (channel as f32 * scale + 0.5) as u8;
+				strip.set_pixel(blue * size + red, green, [value(red), value(green), value(blue), 255]);
+			}
+		}
+	}
+	strip
+}
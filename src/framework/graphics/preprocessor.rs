@@ -0,0 +1,409 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use framework::graphics::{ShaderError, ShaderStage};
+
+// Preprocessed GLSL code that remembers where every line came from,
+// so driver errors can be reported against the original files.
+#[derive(Clone, Debug)]
+pub struct ShaderSource {
+	pub path: String,
+	pub code: String,
+	// Every file that was read, the root file first
+	pub files: Vec<String>,
+	// For each line of code: index into files and 1-based line number,
+	// None for lines that were injected such as defines
+	lines: Vec<Option<(usize, usize)>>,
+}
+
+impl ShaderSource {
+	// Code that didn't come from the preprocessor maps to itself
+	pub fn from_code(path: &str, code: &str) -> ShaderSource {
+		ShaderSource {
+			path: path.to_string(),
+			code: code.to_string(),
+			files: vec![path.to_string()],
+			lines: (1..code.lines().count() + 1).map(|line| Some((0, line))).collect(),
+		}
+	}
+
+	// File and line number of a 1-based line of the preprocessed code
+	pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+		if line == 0 {
+			return None;
+		}
+
+		match self.lines.get(line - 1) {
+			Some(&Some((file, line))) => Some((&self.files[file], line)),
+			_ => None,
+		}
+	}
+
+	pub fn line(&self, line: usize) -> Option<&str> {
+		if line == 0 {
+			return None;
+		}
+
+		self.code.lines().nth(line - 1)
+	}
+
+	// Splits code with #pragma stage sections into one source per stage.
+	// Lines before the first section are shared by every stage, the
+	// line mapping still points into the original files.
+	pub fn split_stages(&self) -> Result<Vec<(ShaderStage, ShaderSource)>, ShaderError> {
+		let mut shared = self.empty();
+		let mut stages: Vec<(ShaderStage, ShaderSource)> = Vec::new();
+
+		for (index, line) in self.code.lines().enumerate() {
+			let origin = self.lines.get(index).cloned().unwrap_or(None);
+			let trimmed = line.trim();
+
+			if trimmed.starts_with("#pragma") && trimmed[7..].trim().starts_with("stage") {
+				let name = trimmed[7..].trim()["stage".len()..].trim();
+				let stage = match ShaderStage::from_name(name) {
+					Some(stage) => stage,
+					None => return Err(self.error(index + 1, format!("unknown shader stage {}", name))),
+				};
+				if stages.iter().any(|&(s, _)| s == stage) {
+					return Err(self.error(index + 1, format!("{} stage defined twice", stage)));
+				}
+
+				stages.push((stage, shared.clone()));
+			} else {
+				match stages.last_mut() {
+					Some(&mut (_, ref mut section)) => section.push_line(line, origin),
+					None => shared.push_line(line, origin),
+				}
+			}
+		}
+
+		if stages.is_empty() {
+			return Err(self.error(1, "no #pragma stage sections found".to_string()));
+		}
+
+		Ok(stages)
+	}
+
+	fn empty(&self) -> ShaderSource {
+		ShaderSource {
+			path: self.path.clone(),
+			code: String::new(),
+			files: self.files.clone(),
+			lines: Vec::new(),
+		}
+	}
+
+	// Error at a line of the preprocessed code, reported at its origin
+	fn error(&self, line: usize, message: String) -> ShaderError {
+		let (path, line) = match self.origin(line) {
+			Some((path, line)) => (path.to_string(), line),
+			None => (self.path.clone(), line),
+		};
+
+		ShaderError::Preprocess {
+			path: path,
+			line: line,
+			message: message,
+		}
+	}
+
+	fn push_line(&mut self, code: &str, origin: Option<(usize, usize)>) {
+		self.code.push_str(code);
+		self.code.push('\n');
+		self.lines.push(origin);
+	}
+}
+
+// Resolves #include "file.glsl" relative to the including file and injects
+// #define lines right after the #version directive, so one source file can
+// be built in several variants. Files containing #pragma once are only
+// included the first time.
+#[derive(Clone, Debug)]
+pub struct Preprocessor {
+	defines: Vec<(String, String)>,
+}
+
+impl Preprocessor {
+	pub fn new() -> Preprocessor {
+		Preprocessor {
+			defines: Vec::new(),
+		}
+	}
+
+	pub fn define(&mut self, name: &str, value: &str) -> &mut Preprocessor {
+		self.defines.retain(|&(ref n, _)| n != name);
+		self.defines.push((name.to_string(), value.to_string()));
+		self
+	}
+
+	pub fn defines(&self) -> &[(String, String)] {
+		&self.defines
+	}
+
+	pub fn process(&self, file_path: &str) -> Result<ShaderSource, ShaderError> {
+		let code = try!(Preprocessor::read(file_path));
+		self.process_code(file_path, &code)
+	}
+
+	// Same as process for code that is already in memory, includes are
+	// still resolved relative to file_path.
+	pub fn process_code(&self, file_path: &str, code: &str) -> Result<ShaderSource, ShaderError> {
+		let mut source = ShaderSource {
+			path: file_path.to_string(),
+			code: String::new(),
+			files: Vec::new(),
+			lines: Vec::new(),
+		};
+		let mut stack = Vec::new();
+		let mut once = Vec::new();
+
+		// Without a #version directive the defines go on top
+		if !code.lines().any(|line| line.trim().starts_with("#version")) {
+			for &(ref name, ref value) in &self.defines {
+				source.push_line(format!("#define {} {}", name, value).trim_end(), None);
+			}
+		}
+
+		try!(self.include(&mut source, &mut stack, &mut once, file_path, code));
+
+		Ok(source)
+	}
+
+	fn read(file_path: &str) -> Result<String, ShaderError> {
+		let to_error = |error| ShaderError::Io { path: file_path.to_string(), error: error };
+
+		let mut file = try!(File::open(file_path).map_err(&to_error));
+		let mut contents = String::new();
+		try!(file.read_to_string(&mut contents).map_err(&to_error));
+
+		Ok(contents)
+	}
+
+	fn include(&self, source: &mut ShaderSource, stack: &mut Vec<String>, once: &mut Vec<String>,
+		file_path: &str, code: &str) -> Result<(), ShaderError> {
+
+		let file = source.files.len();
+		source.files.push(file_path.to_string());
+		stack.push(file_path.to_string());
+
+		for (index, line) in code.lines().enumerate() {
+			let number = index + 1;
+			let trimmed = line.trim();
+
+			if trimmed.starts_with("#include") {
+				let included = try!(Preprocessor::include_path(file_path, number, trimmed));
+				let key = Preprocessor::normalize(&included);
+
+				if stack.iter().any(|f| Preprocessor::normalize(f) == key) {
+					let mut chain = stack.clone();
+					chain.push(included);
+					return Err(ShaderError::Preprocess {
+						path: file_path.to_string(),
+						line: number,
+						message: format!("include cycle {}", chain.join(" -> ")),
+					});
+				}
+
+				if once.contains(&key) {
+					continue;
+				}
+
+				let included_code = try!(Preprocessor::read(&included));
+				try!(self.include(source, stack, once, &included, &included_code));
+			} else if trimmed.starts_with("#pragma") && trimmed[7..].trim() == "once" {
+				once.push(Preprocessor::normalize(file_path));
+			} else {
+				source.push_line(line, Some((file, number)));
+
+				// Defines have to follow the #version directive of the root file
+				if stack.len() == 1 && trimmed.starts_with("#version") {
+					for &(ref name, ref value) in &self.defines {
+						source.push_line(format!("#define {} {}", name, value).trim_end(), None);
+					}
+				}
+			}
+		}
+
+		stack.pop();
+		Ok(())
+	}
+
+	fn include_path(file_path: &str, line: usize, directive: &str) -> Result<String, ShaderError> {
+		let argument = directive["#include".len()..].trim();
+		if argument.len() < 2 || !argument.starts_with('"') || !argument.ends_with('"') {
+			return Err(ShaderError::Preprocess {
+				path: file_path.to_string(),
+				line: line,
+				message: format!("expected #include \"file\", found {}", directive),
+			});
+		}
+
+		let name = &argument[1..argument.len() - 1];
+		let path = match Path::new(file_path).parent() {
+			Some(directory) => directory.join(name),
+			None => Path::new(name).to_path_buf(),
+		};
+
+		Ok(path.to_string_lossy().into_owned())
+	}
+
+	// Compare includes by their canonical path when the file exists
+	fn normalize(file_path: &str) -> String {
+		match Path::new(file_path).canonicalize() {
+			Ok(path) => path.to_string_lossy().into_owned(),
+			Err(_) => file_path.to_string(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	// Writes the files into a fresh directory in the system temp directory and returns its path
+	fn fixture(name: &str, files: &[(&str, &str)]) -> String {
+		let directory = ::std::env::temp_dir().join(format!("nitrust-preprocessor-{}", name));
+		let _ = fs::remove_dir_all(&directory);
+		for &(file, code) in files {
+			let path = directory.join(file);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(&path, code).unwrap();
+		}
+		directory.to_string_lossy().into_owned()
+	}
+
+	fn process(path: &str) -> ShaderSource {
+		match Preprocessor::new().process(path) {
+			Ok(source) => source,
+			Err(e) => panic!("{}", e),
+		}
+	}
+
+	#[test]
+	fn includes_relative_to_the_including_file() {
+		let directory = fixture("relative", &[
+			("main.glsl", "#include \"lib/common.glsl\"\nvoid main() {}\n"),
+			("lib/common.glsl", "#include \"math.glsl\"\nfloat common;\n"),
+			("lib/math.glsl", "float math;\n"),
+		]);
+		let source = process(&format!("{}/main.glsl", directory));
+		assert_eq!(source.code, "float math;\nfloat common;\nvoid main() {}\n");
+		assert_eq!(source.files, vec![
+			format!("{}/main.glsl", directory),
+			format!("{}/lib/common.glsl", directory),
+			format!("{}/lib/math.glsl", directory),
+		]);
+	}
+
+	#[test]
+	fn include_cycle() {
+		let directory = fixture("cycle", &[
+			("a.glsl", "#include \"b.glsl\"\n"),
+			("b.glsl", "float b;\n#include \"c.glsl\"\n"),
+			("c.glsl", "#include \"a.glsl\"\n"),
+		]);
+		let (a, b, c) = (format!("{}/a.glsl", directory), format!("{}/b.glsl", directory), format!("{}/c.glsl", directory));
+		match Preprocessor::new().process(&a) {
+			Err(ShaderError::Preprocess { path, line, message }) => {
+				assert_eq!((path, line), (c.clone(), 1));
+				assert_eq!(message, format!("include cycle {} -> {} -> {} -> {}", a, b, c, a));
+			},
+			Err(e) => panic!("unexpected error: {}", e),
+			Ok(_) => panic!("processed an include cycle"),
+		}
+	}
+
+	#[test]
+	fn pragma_once() {
+		let directory = fixture("once", &[
+			("main.glsl", "#include \"once.glsl\"\n#include \"twice.glsl\"\n#include \"lib.glsl\"\n#include \"twice.glsl\"\n"),
+			("lib.glsl", "#include \"once.glsl\"\n"),
+			("once.glsl", "#pragma once\nfloat once;\n"),
+			("twice.glsl", "float twice;\n"),
+		]);
+		let source = process(&format!("{}/main.glsl", directory));
+		assert_eq!(source.code, "float once;\nfloat twice;\nfloat twice;\n");
+	}
+
+	#[test]
+	fn defines_after_version() {
+		let mut preprocessor = Preprocessor::new();
+		preprocessor.define("SKINNED", "").define("ALPHA_TEST", "0.5");
+		let code = "// Lit surface\n#version 330 core\nvoid main() {}\n";
+		let source = preprocessor.process_code("lit.glsl", code).unwrap();
+		assert_eq!(source.code, "// Lit surface\n#version 330 core\n#define SKINNED\n#define ALPHA_TEST 0.5\nvoid main() {}\n");
+		// Injected lines have no origin, the lines after them keep theirs
+		assert_eq!(source.origin(3), None);
+		assert_eq!(source.origin(4), None);
+		assert_eq!(source.origin(5), Some(("lit.glsl", 3)));
+
+		// Redefining replaces the value
+		preprocessor.define("ALPHA_TEST", "0.25");
+		assert_eq!(preprocessor.defines().to_vec(), vec![
+			("SKINNED".to_string(), String::new()),
+			("ALPHA_TEST".to_string(), "0.25".to_string()),
+		]);
+	}
+
+	#[test]
+	fn defines_without_version() {
+		let mut preprocessor = Preprocessor::new();
+		preprocessor.define("SKINNED", "1");
+		let source = preprocessor.process_code("lit.glsl", "void main() {}\n").unwrap();
+		assert_eq!(source.code, "#define SKINNED 1\nvoid main() {}\n");
+		assert_eq!(source.origin(2), Some(("lit.glsl", 1)));
+	}
+
+	#[test]
+	fn lines_map_to_included_files() {
+		let directory = fixture("origin", &[
+			("main.glsl", "#version 330 core\n#include \"common.glsl\"\nvoid main() {}\n"),
+			("common.glsl", "// Shared code\nuniform float time;\n"),
+		]);
+		let (main, common) = (format!("{}/main.glsl", directory), format!("{}/common.glsl", directory));
+		let mut preprocessor = Preprocessor::new();
+		preprocessor.define("SKINNED", "1");
+		let source = preprocessor.process(&main).unwrap();
+
+		assert_eq!(source.line(4), Some("uniform float time;"));
+		assert_eq!(source.origin(1), Some((&main[..], 1)));
+		assert_eq!(source.origin(3), Some((&common[..], 1)));
+		assert_eq!(source.origin(4), Some((&common[..], 2)));
+		assert_eq!(source.origin(5), Some((&main[..], 3)));
+		assert_eq!(source.origin(6), None);
+	}
+
+	#[test]
+	fn errors_map_to_included_files() {
+		let directory = fixture("errors", &[
+			("main.glsl", "#version 330 core\n#include \"stages.glsl\"\n"),
+			("stages.glsl", "#pragma stage vertex\nvoid main() {}\n#pragma stage geometry shader\n"),
+			("broken.glsl", "float a;\n#include <missing.glsl>\n"),
+		]);
+		let source = process(&format!("{}/main.glsl", directory));
+		match source.split_stages() {
+			Err(ShaderError::Preprocess { path, line, .. }) => assert_eq!((path, line), (format!("{}/stages.glsl", directory), 3)),
+			Err(e) => panic!("unexpected error: {}", e),
+			Ok(_) => panic!("split an unknown stage"),
+		}
+
+		match Preprocessor::new().process(&format!("{}/broken.glsl", directory)) {
+			Err(ShaderError::Preprocess { path, line, .. }) => assert_eq!((path, line), (format!("{}/broken.glsl", directory), 2)),
+			Err(e) => panic!("unexpected error: {}", e),
+			Ok(_) => panic!("processed a malformed include"),
+		}
+	}
+
+	#[test]
+	fn missing_include() {
+		let directory = fixture("missing", &[("main.glsl", "#include \"missing.glsl\"\n")]);
+		match Preprocessor::new().process(&format!("{}/main.glsl", directory)) {
+			Err(ShaderError::Io { path, .. }) => assert_eq!(path, format!("{}/missing.glsl", directory)),
+			Err(e) => panic!("unexpected error: {}", e),
+			Ok(_) => panic!("processed a missing include"),
+		}
+	}
+}
@@ -0,0 +1,169 @@
+extern crate gl;
+
+use gl::types::*;
+
+use std::env;
+use std::ffi::CStr;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::ptr;
+
+use framework::graphics::{ShaderSource, ShaderStage};
+
+const MAGIC: &'static [u8; 4] = b"NOPB";
+
+// Linked program binaries on disk, so programs don't have to be compiled at every launch.
+// Binaries are keyed by a hash of the preprocessed sources and the driver vendor, renderer
+// and version, a driver update simply misses the cache. Drivers may still reject a binary,
+// callers then compile from source and store the new binary.
+#[derive(Clone, Debug)]
+pub struct ProgramCache {
+	directory: PathBuf,
+	driver: String,
+	supported: bool,
+}
+
+impl ProgramCache {
+	// Needs a current GL context to query the driver
+	pub fn new() -> ProgramCache {
+		let directory = ProgramCache::default_directory()
+			.unwrap_or_else(|| PathBuf::from("./cache/shaders"));
+		ProgramCache::with_directory(directory)
+	}
+
+	pub fn with_directory(directory: PathBuf) -> ProgramCache {
+		let (driver, formats) = unsafe {
+			let driver = format!("{}\n{}\n{}",
+				ProgramCache::gl_string(gl::VENDOR),
+				ProgramCache::gl_string(gl::RENDERER),
+				ProgramCache::gl_string(gl::VERSION));
+
+			// GL 4.1 or ARB_get_program_binary, without it the entry points aren't loaded
+			let mut formats = 0;
+			if gl::GetProgramBinary::is_loaded() && gl::ProgramBinary::is_loaded() {
+				gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+			}
+			(driver, formats)
+		};
+
+		ProgramCache {
+			directory: directory,
+			driver: driver,
+			supported: formats > 0,
+		}
+	}
+
+	// %LOCALAPPDATA% on Windows, ~/Library/Caches on OS X, $XDG_CACHE_HOME or ~/.cache otherwise
+	pub fn default_directory() -> Option<PathBuf> {
+		let base = if cfg!(target_os = "windows") {
+			env::var_os("LOCALAPPDATA").map(PathBuf::from)
+		} else if cfg!(target_os = "macos") {
+			env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Caches"))
+		} else {
+			env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+				.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+		};
+
+		base.map(|base| base.join("nitrust-oxide").join("shaders"))
+	}
+
+	pub fn directory(&self) -> &PathBuf {
+		&self.directory
+	}
+
+	// False when the driver offers no binary formats, loading then always misses
+	pub fn is_supported(&self) -> bool {
+		self.supported
+	}
+
+	pub fn key(&self, sources: &[(ShaderStage, ShaderSource)]) -> String {
+		// 64 bit FNV-1a, stable between runs and Rust versions
+		let mut hash: u64 = 0xcbf29ce484222325;
+		{
+			let mut feed = |bytes: &[u8]| {
+				for byte in bytes {
+					hash ^= *byte as u64;
+					hash = hash.wrapping_mul(0x100000001b3);
+				}
+				// Separator so "ab" + "c" differs from "a" + "bc"
+				hash ^= 0xff;
+				hash = hash.wrapping_mul(0x100000001b3);
+			};
+
+			feed(self.driver.as_bytes());
+			for &(stage, ref source) in sources {
+				feed(stage.name().as_bytes());
+				feed(source.code.as_bytes());
+			}
+		}
+
+		format!("{:016x}", hash)
+	}
+
+	fn path(&self, key: &str) -> PathBuf {
+		self.directory.join(format!("{}.bin", key))
+	}
+
+	// Binary format and data, None when missing or unreadable
+	pub fn load(&self, key: &str) -> Option<(GLenum, Vec<u8>)> {
+		if !self.supported {
+			return None;
+		}
+
+		let mut data = Vec::new();
+		match File::open(self.path(key)).and_then(|mut file| file.read_to_end(&mut data)) {
+			Ok(_) => {},
+			Err(_) => return None,
+		}
+
+		if data.len() < 8 || &data[0..4] != MAGIC {
+			return None;
+		}
+
+		let format = (data[4] as u32) | ((data[5] as u32) << 8) |
+			((data[6] as u32) << 16) | ((data[7] as u32) << 24);
+
+		Some((format, data[8..].to_vec()))
+	}
+
+	pub fn store(&self, key: &str, format: GLenum, binary: &[u8]) -> io::Result<()> {
+		if !self.supported {
+			return Ok(());
+		}
+
+		try!(fs::create_dir_all(&self.directory));
+
+		// Write next to the target and rename, so a crash never leaves half a binary
+		let path = self.path(key);
+		let temporary = self.path(&format!("{}.tmp", key));
+		{
+			let mut file = try!(File::create(&temporary));
+			try!(file.write_all(MAGIC));
+			try!(file.write_all(&[format as u8, (format >> 8) as u8,
+				(format >> 16) as u8, (format >> 24) as u8]));
+			try!(file.write_all(binary));
+		}
+
+		fs::rename(&temporary, &path)
+	}
+
+	// Removes every cached binary
+	pub fn clear(&self) -> io::Result<()> {
+		match fs::remove_dir_all(&self.directory) {
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+			result => result,
+		}
+	}
+
+	unsafe fn gl_string(name: GLenum) -> String {
+		let string = gl::GetString(name);
+		if string == ptr::null() {
+			return String::new();
+		}
+
+		CStr::from_ptr(string as *const _).to_string_lossy().into_owned()
+	}
+}
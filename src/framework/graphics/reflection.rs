@@ -0,0 +1,268 @@
+extern crate gl;
+
+use gl::types::*;
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+
+// GLSL types as reported by glGetActiveUniform and glGetActiveAttrib
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GlslType {
+	Float, Vec2, Vec3, Vec4,
+	Int, IVec2, IVec3, IVec4,
+	UInt, UVec2, UVec3, UVec4,
+	Bool, BVec2, BVec3, BVec4,
+	Mat2, Mat3, Mat4,
+	Mat2x3, Mat2x4, Mat3x2, Mat3x4, Mat4x2, Mat4x3,
+	Sampler1D, Sampler2D, Sampler3D, SamplerCube,
+	Sampler2DShadow, SamplerCubeShadow,
+	Sampler2DArray, Sampler2DArrayShadow, Sampler2DMultisample, SamplerBuffer,
+	ISampler2D, USampler2D,
+	Other(GLenum),
+}
+
+impl GlslType {
+	pub fn from_gl(ty: GLenum) -> GlslType {
+		match ty {
+			gl::FLOAT => GlslType::Float,
+			gl::FLOAT_VEC2 => GlslType::Vec2,
+			gl::FLOAT_VEC3 => GlslType::Vec3,
+			gl::FLOAT_VEC4 => GlslType::Vec4,
+			gl::INT => GlslType::Int,
+			gl::INT_VEC2 => GlslType::IVec2,
+			gl::INT_VEC3 => GlslType::IVec3,
+			gl::INT_VEC4 => GlslType::IVec4,
+			gl::UNSIGNED_INT => GlslType::UInt,
+			gl::UNSIGNED_INT_VEC2 => GlslType::UVec2,
+			gl::UNSIGNED_INT_VEC3 => GlslType::UVec3,
+			gl::UNSIGNED_INT_VEC4 => GlslType::UVec4,
+			gl::BOOL => GlslType::Bool,
+			gl::BOOL_VEC2 => GlslType::BVec2,
+			gl::BOOL_VEC3 => GlslType::BVec3,
+			gl::BOOL_VEC4 => GlslType::BVec4,
+			gl::FLOAT_MAT2 => GlslType::Mat2,
+			gl::FLOAT_MAT3 => GlslType::Mat3,
+			gl::FLOAT_MAT4 => GlslType::Mat4,
+			gl::FLOAT_MAT2x3 => GlslType::Mat2x3,
+			gl::FLOAT_MAT2x4 => GlslType::Mat2x4,
+			gl::FLOAT_MAT3x2 => GlslType::Mat3x2,
+			gl::FLOAT_MAT3x4 => GlslType::Mat3x4,
+			gl::FLOAT_MAT4x2 => GlslType::Mat4x2,
+			gl::FLOAT_MAT4x3 => GlslType::Mat4x3,
+			gl::SAMPLER_1D => GlslType::Sampler1D,
+			gl::SAMPLER_2D => GlslType::Sampler2D,
+			gl::SAMPLER_3D => GlslType::Sampler3D,
+			gl::SAMPLER_CUBE => GlslType::SamplerCube,
+			gl::SAMPLER_2D_SHADOW => GlslType::Sampler2DShadow,
+			gl::SAMPLER_CUBE_SHADOW => GlslType::SamplerCubeShadow,
+			gl::SAMPLER_2D_ARRAY => GlslType::Sampler2DArray,
+			gl::SAMPLER_2D_ARRAY_SHADOW => GlslType::Sampler2DArrayShadow,
+			gl::SAMPLER_2D_MULTISAMPLE => GlslType::Sampler2DMultisample,
+			gl::SAMPLER_BUFFER => GlslType::SamplerBuffer,
+			gl::INT_SAMPLER_2D => GlslType::ISampler2D,
+			gl::UNSIGNED_INT_SAMPLER_2D => GlslType::USampler2D,
+			other => GlslType::Other(other),
+		}
+	}
+
+	pub fn is_sampler(&self) -> bool {
+		match *self {
+			GlslType::Sampler1D | GlslType::Sampler2D | GlslType::Sampler3D |
+			GlslType::SamplerCube | GlslType::Sampler2DShadow | GlslType::SamplerCubeShadow |
+			GlslType::Sampler2DArray | GlslType::Sampler2DArrayShadow |
+			GlslType::Sampler2DMultisample | GlslType::SamplerBuffer |
+			GlslType::ISampler2D | GlslType::USampler2D => true,
+			_ => false,
+		}
+	}
+
+	// Whether a setter written for `requested` may upload to a uniform of this type.
+	// Booleans and samplers are set through glUniform1i as well.
+	pub fn accepts(&self, requested: GlslType) -> bool {
+		if *self == requested {
+			return true;
+		}
+
+		match requested {
+			GlslType::Int => *self == GlslType::Bool || self.is_sampler(),
+			GlslType::Bool => *self == GlslType::Int,
+			_ => false,
+		}
+	}
+}
+
+impl fmt::Display for GlslType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let name = match *self {
+			GlslType::Float => "float",
+			GlslType::Vec2 => "vec2",
+			GlslType::Vec3 => "vec3",
+			GlslType::Vec4 => "vec4",
+			GlslType::Int => "int",
+			GlslType::IVec2 => "ivec2",
+			GlslType::IVec3 => "ivec3",
+			GlslType::IVec4 => "ivec4",
+			GlslType::UInt => "uint",
+			GlslType::UVec2 => "uvec2",
+			GlslType::UVec3 => "uvec3",
+			GlslType::UVec4 => "uvec4",
+			GlslType::Bool => "bool",
+			GlslType::BVec2 => "bvec2",
+			GlslType::BVec3 => "bvec3",
+			GlslType::BVec4 => "bvec4",
+			GlslType::Mat2 => "mat2",
+			GlslType::Mat3 => "mat3",
+			GlslType::Mat4 => "mat4",
+			GlslType::Mat2x3 => "mat2x3",
+			GlslType::Mat2x4 => "mat2x4",
+			GlslType::Mat3x2 => "mat3x2",
+			GlslType::Mat3x4 => "mat3x4",
+			GlslType::Mat4x2 => "mat4x2",
+			GlslType::Mat4x3 => "mat4x3",
+			GlslType::Sampler1D => "sampler1D",
+			GlslType::Sampler2D => "sampler2D",
+			GlslType::Sampler3D => "sampler3D",
+			GlslType::SamplerCube => "samplerCube",
+			GlslType::Sampler2DShadow => "sampler2DShadow",
+			GlslType::SamplerCubeShadow => "samplerCubeShadow",
+			GlslType::Sampler2DArray => "sampler2DArray",
+			GlslType::Sampler2DArrayShadow => "sampler2DArrayShadow",
+			GlslType::Sampler2DMultisample => "sampler2DMS",
+			GlslType::SamplerBuffer => "samplerBuffer",
+			GlslType::ISampler2D => "isampler2D",
+			GlslType::USampler2D => "usampler2D",
+			GlslType::Other(ty) => return write!(f, "GL type 0x{:X}", ty),
+		};
+
+		write!(f, "{}", name)
+	}
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UniformInfo {
+	pub location: GLint,
+	pub ty: GlslType,
+	pub size: GLint,	// Number of elements for arrays, 1 otherwise
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AttributeInfo {
+	pub location: GLint,
+	pub ty: GlslType,
+	pub size: GLint,
+}
+
+// Arrays are reported as "lights[0]", store them under "lights"
+fn base_name(name: &str) -> &str {
+	if name.ends_with("[0]") {
+		&name[..name.len() - 3]
+	} else {
+		name
+	}
+}
+
+// Uniforms inside uniform blocks have no location and are left out.
+pub unsafe fn active_uniforms(program: GLuint) -> HashMap<String, UniformInfo> {
+	let mut uniforms = HashMap::new();
+
+	let mut count = 0;
+	let mut max_length = 0;
+	gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+	gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_length);
+
+	for index in 0..count {
+		let mut name: Vec<u8> = vec![0; max_length.max(1) as usize];
+		let mut length = 0;
+		let mut size = 0;
+		let mut ty = 0;
+		gl::GetActiveUniform(program, index as GLuint, max_length, &mut length,
+			&mut size, &mut ty, name.as_mut_ptr() as *mut GLchar);
+		name.truncate(length as usize);
+
+		let c_str = CString::new(name.clone()).unwrap();
+		let location = gl::GetUniformLocation(program, c_str.as_ptr());
+		if location == -1 {
+			continue;
+		}
+
+		let name = String::from_utf8_lossy(&name).into_owned();
+		uniforms.insert(base_name(&name).to_string(), UniformInfo {
+			location: location,
+			ty: GlslType::from_gl(ty),
+			size: size,
+		});
+	}
+
+	uniforms
+}
+
+// Built-in attributes such as gl_VertexID have no location and are left out.
+pub unsafe fn active_attributes(program: GLuint) -> HashMap<String, AttributeInfo> {
+	let mut attributes = HashMap::new();
+
+	let mut count = 0;
+	let mut max_length = 0;
+	gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTES, &mut count);
+	gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_length);
+
+	for index in 0..count {
+		let mut name: Vec<u8> = vec![0; max_length.max(1) as usize];
+		let mut length = 0;
+		let mut size = 0;
+		let mut ty = 0;
+		gl::GetActiveAttrib(program, index as GLuint, max_length, &mut length,
+			&mut size, &mut ty, name.as_mut_ptr() as *mut GLchar);
+		name.truncate(length as usize);
+
+		let c_str = CString::new(name.clone()).unwrap();
+		let location = gl::GetAttribLocation(program, c_str.as_ptr());
+		if location == -1 {
+			continue;
+		}
+
+		let name = String::from_utf8_lossy(&name).into_owned();
+		attributes.insert(base_name(&name).to_string(), AttributeInfo {
+			location: location,
+			ty: GlslType::from_gl(ty),
+			size: size,
+		});
+	}
+
+	attributes
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UniformBlockInfo {
+	pub index: GLuint,
+	pub size: GLint,	// Data size in bytes
+}
+
+pub unsafe fn active_uniform_blocks(program: GLuint) -> HashMap<String, UniformBlockInfo> {
+	let mut blocks = HashMap::new();
+
+	let mut count = 0;
+	let mut max_length = 0;
+	gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+	gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH, &mut max_length);
+
+	for index in 0..count {
+		let index = index as GLuint;
+		let mut name: Vec<u8> = vec![0; max_length.max(1) as usize];
+		let mut length = 0;
+		gl::GetActiveUniformBlockName(program, index, max_length, &mut length,
+			name.as_mut_ptr() as *mut GLchar);
+		name.truncate(length as usize);
+
+		let mut size = 0;
+		gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+
+		let name = String::from_utf8_lossy(&name).into_owned();
+		blocks.insert(name, UniformBlockInfo {
+			index: index,
+			size: size,
+		});
+	}
+
+	blocks
+}
@@ -0,0 +1,400 @@
+extern crate gl;
+extern crate std;
+
+use gl::types::*;
+
+use std::error;
+use std::fmt;
+
+use framework::graphics::capture;
+use framework::graphics::{MipFilter, RgbaImage, SamplerCache, SamplerDesc, Texture, TextureDesc, Wrap};
+
+// How the attachments of a render target are created
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderTargetDesc {
+	pub color_formats: Vec<GLenum>,			// One texture each, at COLOR_ATTACHMENT0 and up
+	pub depth_format: Option<GLenum>,		// DEPTH_COMPONENT24, DEPTH24_STENCIL8, STENCIL_INDEX8 and so on
+	pub color_texture: TextureDesc,			// Sampler and mipmaps of the color textures
+	pub depth_texture: Option<TextureDesc>,	// Makes the depth a texture too, e.g. for shadow maps
+	pub samples: u32,						// Above 1 renders multisampled and resolves into the textures
+}
+
+impl RenderTargetDesc {
+	// One RGBA8 color texture and a depth/stencil renderbuffer
+	pub fn new() -> RenderTargetDesc {
+		RenderTargetDesc::with_color(&[gl::RGBA8])
+	}
+
+	pub fn with_color(formats: &[GLenum]) -> RenderTargetDesc {
+		let mut sampler = SamplerDesc::with_wrap(Wrap::ClampToEdge);
+		sampler.mip_filter = MipFilter::None;
+
+		RenderTargetDesc {
+			color_formats: formats.to_vec(),
+			depth_format: Some(gl::DEPTH24_STENCIL8),
+			color_texture: TextureDesc {
+				generate_mipmaps: false,
+				sampler: sampler,
+				fallback: false,
+				..TextureDesc::new()
+			},
+			depth_texture: None,
+			samples: 1,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum RenderTargetError {
+	TooManyAttachments { count: usize, max: u32 },
+	TooManySamples { samples: u32, max: u32 },
+	Incomplete { status: GLenum },
+}
+
+impl fmt::Display for RenderTargetError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			RenderTargetError::TooManyAttachments { count, max } => {
+				write!(f, "Render target has {} color attachments, the driver allows {}", count, max)
+			},
+			RenderTargetError::TooManySamples { samples, max } => {
+				write!(f, "Render target asks for {} samples, the driver allows {}", samples, max)
+			},
+			RenderTargetError::Incomplete { status } => {
+				write!(f, "Render target is incomplete: framebuffer status 0x{:x}", status)
+			},
+		}
+	}
+}
+
+impl error::Error for RenderTargetError {}
+
+// A framebuffer object to render into. The color attachments are textures that can be
+// sampled afterwards. With multisampling the scene goes to multisampled renderbuffers
+// first and end (or resolve) blits them into the textures.
+pub struct RenderTarget {
+	desc: RenderTargetDesc,
+	width: u32,
+	height: u32,
+	framebuffer: GLuint,				// Holds the textures
+	multisample_framebuffer: GLuint,	// 0 without multisampling
+	renderbuffers: Vec<GLuint>,
+	color_textures: Vec<Texture>,
+	depth_texture: Option<Texture>,
+}
+
+impl RenderTarget {
+	pub fn new(width: u32, height: u32, desc: &RenderTargetDesc, samplers: &SamplerCache) -> Result<RenderTarget, RenderTargetError> {
+		let mut target = RenderTarget {
+			desc: desc.clone(),
+			width: width.max(1),
+			height: height.max(1),
+			framebuffer: 0,
+			multisample_framebuffer: 0,
+			renderbuffers: Vec::new(),
+			color_textures: Vec::new(),
+			depth_texture: None,
+		};
+		try!(target.create(samplers));
+		Ok(target)
+	}
+
+	pub fn width(&self) -> u32 {
+		self.width
+	}
+
+	pub fn height(&self) -> u32 {
+		self.height
+	}
+
+	pub fn desc(&self) -> &RenderTargetDesc {
+		&self.desc
+	}
+
+	pub fn is_multisampled(&self) -> bool {
+		self.multisample_framebuffer != 0
+	}
+
+	// The framebuffer to read the resolved pixels from
+	pub fn id(&self) -> GLuint {
+		self.framebuffer
+	}
+
+	pub fn color_texture(&self, index: usize) -> &Texture {
+		&self.color_textures[index]
+	}
+
+	pub fn color_textures(&self) -> &[Texture] {
+		&self.color_textures
+	}
+
+	pub fn depth_texture(&self) -> Option<&Texture> {
+		self.depth_texture.as_ref()
+	}
+
+	// Recreates the attachments at the new size, e.g. when the window is resized.
+	// The old contents are lost.
+	pub fn resize(&mut self, width: u32, height: u32, samplers: &SamplerCache) -> Result<(), RenderTargetError> {
+		let (width, height) = (width.max(1), height.max(1));
+		if width == self.width && height == self.height {
+			return Ok(());
+		}
+
+		self.width = width;
+		self.height = height;
+		self.create(samplers)
+	}
+
+	// Binds the framebuffer for drawing and sets the viewport to cover it
+	pub fn begin(&self) {
+		unsafe {
+			gl::BindFramebuffer(gl::FRAMEBUFFER, self.draw_framebuffer());
+			gl::Viewport(0, 0, self.width as i32, self.height as i32);
+		}
+	}
+
+	// Resolves multisampling and rebinds the default framebuffer. The viewport is left
+	// as it is, set it back to the window size before drawing there.
+	pub fn end(&self) {
+		self.resolve();
+		unsafe {
+			gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+		}
+		if self.desc.color_texture.generate_mipmaps {
+			for texture in &self.color_textures {
+				texture.generate_mipmaps();
+			}
+		}
+	}
+
+	// Copies the multisampled attachments into the textures, does nothing without multisampling
+	pub fn resolve(&self) {
+		if !self.is_multisampled() {
+			return;
+		}
+
+		unsafe {
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.multisample_framebuffer);
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.framebuffer);
+
+			// A blit writes every draw buffer, so they are enabled one at a time
+			for index in 0..self.color_textures.len() {
+				let mut buffers = vec![gl::NONE; self.color_textures.len()];
+				buffers[index] = gl::COLOR_ATTACHMENT0 + index as GLenum;
+				gl::ReadBuffer(buffers[index]);
+				gl::DrawBuffers(buffers.len() as i32, buffers.as_ptr());
+				gl::BlitFramebuffer(0, 0, self.width as i32, self.height as i32,
+					0, 0, self.width as i32, self.height as i32, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+			}
+			set_draw_buffers(self.color_textures.len());
+
+			if let (Some(format), Some(_)) = (self.desc.depth_format, self.depth_texture.as_ref()) {
+				let mask = match depth_attachment(format) {
+					gl::DEPTH_STENCIL_ATTACHMENT => gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT,
+					gl::STENCIL_ATTACHMENT => gl::STENCIL_BUFFER_BIT,
+					_ => gl::DEPTH_BUFFER_BIT,
+				};
+				gl::BlitFramebuffer(0, 0, self.width as i32, self.height as i32,
+					0, 0, self.width as i32, self.height as i32, mask, gl::NEAREST);
+			}
+
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+		}
+	}
+
+	// Stretches a resolved color attachment over the default framebuffer
+	pub fn blit_to_screen(&self, index: usize, width: u32, height: u32) {
+		unsafe {
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+			gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index as GLenum);
+			gl::BlitFramebuffer(0, 0, self.width as i32, self.height as i32,
+				0, 0, width as i32, height as i32, gl::COLOR_BUFFER_BIT, gl::LINEAR);
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+		}
+	}
+
+	// Stretches a resolved color attachment over the first color attachment of another target
+	pub fn blit_to(&self, index: usize, target: &RenderTarget) {
+		unsafe {
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.draw_framebuffer());
+			gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index as GLenum);
+			gl::BlitFramebuffer(0, 0, self.width as i32, self.height as i32,
+				0, 0, target.width as i32, target.height as i32, gl::COLOR_BUFFER_BIT, gl::LINEAR);
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+		}
+	}
+
+	// Reads back a resolved color attachment, rows top to bottom
+	pub fn capture(&self, index: usize) -> RgbaImage {
+		let mut framebuffer = 0;
+		unsafe {
+			gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut framebuffer);
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+			gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index as GLenum);
+		}
+		let image = capture::read_pixels(0, 0, self.width, self.height);
+		unsafe {
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer as GLuint);
+		}
+		image
+	}
+
+	fn draw_framebuffer(&self) -> GLuint {
+		if self.is_multisampled() {
+			self.multisample_framebuffer
+		} else {
+			self.framebuffer
+		}
+	}
+
+	fn create(&mut self, samplers: &SamplerCache) -> Result<(), RenderTargetError> {
+		self.delete();
+
+		let (mut max_attachments, mut max_buffers, mut max_samples) = (0, 0, 0);
+		unsafe {
+			gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_attachments);
+			gl::GetIntegerv(gl::MAX_DRAW_BUFFERS, &mut max_buffers);
+			gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+		}
+		let max = max_attachments.min(max_buffers) as u32;
+		if self.desc.color_formats.len() > max as usize {
+			return Err(RenderTargetError::TooManyAttachments { count: self.desc.color_formats.len(), max: max });
+		}
+		if self.desc.samples > max_samples as u32 {
+			return Err(RenderTargetError::TooManySamples { samples: self.desc.samples, max: max_samples as u32 });
+		}
+
+		let (width, height) = (self.width, self.height);
+		for &format in &self.desc.color_formats {
+			let mut texture = Texture::new();
+			texture.allocate(width, height, format, &self.desc.color_texture, samplers);
+			self.color_textures.push(texture);
+		}
+		if let (Some(format), Some(desc)) = (self.desc.depth_format, self.desc.depth_texture) {
+			let mut texture = Texture::new();
+			texture.allocate(width, height, format, &desc, samplers);
+			self.depth_texture = Some(texture);
+		}
+
+		unsafe {
+			gl::GenFramebuffers(1, &mut self.framebuffer);
+			gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+			for (index, texture) in self.color_textures.iter().enumerate() {
+				gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + index as GLenum,
+					gl::TEXTURE_2D, texture.id(), 0);
+			}
+			match (self.desc.depth_format, self.depth_texture.as_ref()) {
+				(Some(format), Some(texture)) => {
+					gl::FramebufferTexture2D(gl::FRAMEBUFFER, depth_attachment(format), gl::TEXTURE_2D, texture.id(), 0);
+				},
+				(Some(format), None) if self.desc.samples <= 1 => {
+					let renderbuffer = create_renderbuffer(format, 1, width, height);
+					gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, depth_attachment(format), gl::RENDERBUFFER, renderbuffer);
+					self.renderbuffers.push(renderbuffer);
+				},
+				_ => {},
+			}
+			set_draw_buffers(self.color_textures.len());
+			try!(check_status());
+
+			if self.desc.samples > 1 {
+				gl::GenFramebuffers(1, &mut self.multisample_framebuffer);
+				gl::BindFramebuffer(gl::FRAMEBUFFER, self.multisample_framebuffer);
+				for (index, &format) in self.desc.color_formats.iter().enumerate() {
+					let renderbuffer = create_renderbuffer(format, self.desc.samples, width, height);
+					gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + index as GLenum,
+						gl::RENDERBUFFER, renderbuffer);
+					self.renderbuffers.push(renderbuffer);
+				}
+				if let Some(format) = self.desc.depth_format {
+					let renderbuffer = create_renderbuffer(format, self.desc.samples, width, height);
+					gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, depth_attachment(format), gl::RENDERBUFFER, renderbuffer);
+					self.renderbuffers.push(renderbuffer);
+				}
+				set_draw_buffers(self.desc.color_formats.len());
+				try!(check_status());
+			}
+
+			gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+		}
+		Ok(())
+	}
+
+	fn delete(&mut self) {
+		unsafe {
+			if self.framebuffer != 0 {
+				gl::DeleteFramebuffers(1, &self.framebuffer);
+			}
+			if self.multisample_framebuffer != 0 {
+				gl::DeleteFramebuffers(1, &self.multisample_framebuffer);
+			}
+			if !self.renderbuffers.is_empty() {
+				gl::DeleteRenderbuffers(self.renderbuffers.len() as i32, self.renderbuffers.as_ptr());
+			}
+		}
+		self.framebuffer = 0;
+		self.multisample_framebuffer = 0;
+		self.renderbuffers.clear();
+		self.color_textures.clear();
+		self.depth_texture = None;
+	}
+}
+
+impl Drop for RenderTarget {
+	fn drop(&mut self) {
+		self.delete();
+	}
+}
+
+fn depth_attachment(format: GLenum) -> GLenum {
+	match format {
+		gl::DEPTH24_STENCIL8 | gl::DEPTH32F_STENCIL8 => gl::DEPTH_STENCIL_ATTACHMENT,
+		gl::STENCIL_INDEX8 => gl::STENCIL_ATTACHMENT,
+		_ => gl::DEPTH_ATTACHMENT,
+	}
+}
+
+fn create_renderbuffer(format: GLenum, samples: u32, width: u32, height: u32) -> GLuint {
+	let mut id = 0;
+	unsafe {
+		gl::GenRenderbuffers(1, &mut id);
+		gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+		if samples > 1 {
+			gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples as i32, format, width as i32, height as i32);
+		} else {
+			gl::RenderbufferStorage(gl::RENDERBUFFER, format, width as i32, height as i32);
+		}
+		gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+	}
+	id
+}
+
+// Enables every color attachment of the bound framebuffer, a depth only target draws none
+fn set_draw_buffers(count: usize) {
+	unsafe {
+		if count == 0 {
+			gl::DrawBuffer(gl::NONE);
+			gl::ReadBuffer(gl::NONE);
+		} else {
+			let buffers: Vec<GLenum> = (0..count as GLenum).map(|index| gl::COLOR_ATTACHMENT0 + index).collect();
+			gl::DrawBuffers(count as i32, buffers.as_ptr());
+			gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+		}
+	}
+}
+
+fn check_status() -> Result<(), RenderTargetError> {
+	match unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) } {
+		gl::FRAMEBUFFER_COMPLETE => Ok(()),
+		status => {
+			unsafe {
+				gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+			}
+			Err(RenderTargetError::Incomplete { status: status })
+		},
+	}
+}
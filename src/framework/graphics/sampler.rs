@@ -0,0 +1,249 @@
+extern crate gl;
+
+use gl::types::*;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use framework::graphics::capabilities;
+
+// From GL_EXT_texture_filter_anisotropic, core since GL 4.6
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Wrap {
+	Repeat,
+	MirroredRepeat,
+	ClampToEdge,
+	ClampToBorder,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Filter {
+	Nearest,
+	Linear,
+}
+
+// Filter between mipmap levels, None samples the base level only
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MipFilter {
+	None,
+	Nearest,
+	Linear,
+}
+
+// Depth comparison for shadow samplers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompareFunc {
+	Never,
+	Less,
+	Equal,
+	LessEqual,
+	Greater,
+	NotEqual,
+	GreaterEqual,
+	Always,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDesc {
+	pub wrap_s: Wrap,
+	pub wrap_t: Wrap,
+	pub wrap_r: Wrap,
+	pub min_filter: Filter,
+	pub mag_filter: Filter,
+	pub mip_filter: MipFilter,
+	pub max_anisotropy: f32,		// 1 is off, clamped to what the driver allows
+	pub lod_bias: f32,
+	pub border_color: [f32; 4],		// Used with ClampToBorder
+	pub compare: Option<CompareFunc>,
+}
+
+impl SamplerDesc {
+	// Repeating trilinear filtering
+	pub fn new() -> SamplerDesc {
+		SamplerDesc::with_wrap(Wrap::Repeat)
+	}
+
+	pub fn with_wrap(wrap: Wrap) -> SamplerDesc {
+		SamplerDesc {
+			wrap_s: wrap,
+			wrap_t: wrap,
+			wrap_r: wrap,
+			min_filter: Filter::Linear,
+			mag_filter: Filter::Linear,
+			mip_filter: MipFilter::Linear,
+			max_anisotropy: 1.0,
+			lod_bias: 0.0,
+			border_color: [0.0, 0.0, 0.0, 0.0],
+			compare: None,
+		}
+	}
+
+	// Equal descriptions have equal keys, floats are compared by their bits
+	fn key(&self) -> [u32; 13] {
+		let compare = match self.compare {
+			Some(func) => func as u32 + 1,
+			None => 0,
+		};
+		[self.wrap_s as u32, self.wrap_t as u32, self.wrap_r as u32,
+			self.min_filter as u32, self.mag_filter as u32, self.mip_filter as u32,
+			self.max_anisotropy.to_bits(), self.lod_bias.to_bits(),
+			self.border_color[0].to_bits(), self.border_color[1].to_bits(),
+			self.border_color[2].to_bits(), self.border_color[3].to_bits(), compare]
+	}
+}
+
+fn gl_wrap(wrap: Wrap) -> GLenum {
+	match wrap {
+		Wrap::Repeat => gl::REPEAT,
+		Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+		Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+		Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+	}
+}
+
+fn gl_min_filter(filter: Filter, mip_filter: MipFilter) -> GLenum {
+	match (filter, mip_filter) {
+		(Filter::Nearest, MipFilter::None) => gl::NEAREST,
+		(Filter::Linear, MipFilter::None) => gl::LINEAR,
+		(Filter::Nearest, MipFilter::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+		(Filter::Linear, MipFilter::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+		(Filter::Nearest, MipFilter::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+		(Filter::Linear, MipFilter::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+	}
+}
+
+fn gl_filter(filter: Filter) -> GLenum {
+	match filter {
+		Filter::Nearest => gl::NEAREST,
+		Filter::Linear => gl::LINEAR,
+	}
+}
+
+fn gl_compare_func(func: CompareFunc) -> GLenum {
+	match func {
+		CompareFunc::Never => gl::NEVER,
+		CompareFunc::Less => gl::LESS,
+		CompareFunc::Equal => gl::EQUAL,
+		CompareFunc::LessEqual => gl::LEQUAL,
+		CompareFunc::Greater => gl::GREATER,
+		CompareFunc::NotEqual => gl::NOTEQUAL,
+		CompareFunc::GreaterEqual => gl::GEQUAL,
+		CompareFunc::Always => gl::ALWAYS,
+	}
+}
+
+// A GL sampler object, deleted when the last Rc to it is dropped. SamplerCache keeps one
+// until purge() finds no texture using the sampler anymore.
+#[derive(Debug)]
+pub struct Sampler {
+	id: GLuint,
+	desc: SamplerDesc,
+}
+
+impl Sampler {
+	pub fn new(desc: &SamplerDesc) -> Sampler {
+		let mut id = 0;
+		unsafe {
+			gl::GenSamplers(1, &mut id);
+			gl::SamplerParameteri(id, gl::TEXTURE_WRAP_S, gl_wrap(desc.wrap_s) as i32);
+			gl::SamplerParameteri(id, gl::TEXTURE_WRAP_T, gl_wrap(desc.wrap_t) as i32);
+			gl::SamplerParameteri(id, gl::TEXTURE_WRAP_R, gl_wrap(desc.wrap_r) as i32);
+			gl::SamplerParameteri(id, gl::TEXTURE_MIN_FILTER, gl_min_filter(desc.min_filter, desc.mip_filter) as i32);
+			gl::SamplerParameteri(id, gl::TEXTURE_MAG_FILTER, gl_filter(desc.mag_filter) as i32);
+			gl::SamplerParameterf(id, gl::TEXTURE_LOD_BIAS, desc.lod_bias);
+			gl::SamplerParameterfv(id, gl::TEXTURE_BORDER_COLOR, desc.border_color.as_ptr());
+
+			match desc.compare {
+				Some(func) => {
+					gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as i32);
+					gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_FUNC, gl_compare_func(func) as i32);
+				},
+				None => gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_MODE, gl::NONE as i32),
+			}
+
+			if desc.max_anisotropy > 1.0 && anisotropy_supported() {
+				let mut max = 1.0;
+				gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+				gl::SamplerParameterf(id, TEXTURE_MAX_ANISOTROPY, desc.max_anisotropy.min(max));
+			}
+		}
+
+		Sampler {
+			id: id,
+			desc: *desc,
+		}
+	}
+
+	pub fn id(&self) -> GLuint {
+		self.id
+	}
+
+	pub fn desc(&self) -> &SamplerDesc {
+		&self.desc
+	}
+
+	pub fn bind(&self, unit: u32) {
+		unsafe {
+			gl::BindSampler(unit, self.id);
+		}
+	}
+
+	pub fn unbind(unit: u32) {
+		unsafe {
+			gl::BindSampler(unit, 0);
+		}
+	}
+}
+
+impl Drop for Sampler {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteSamplers(1, &self.id);
+		}
+	}
+}
+
+fn anisotropy_supported() -> bool {
+	capabilities::gl_version_at_least(4, 6) ||
+		capabilities::has_extension("GL_EXT_texture_filter_anisotropic") ||
+		capabilities::has_extension("GL_ARB_texture_filter_anisotropic")
+}
+
+// Hands out one sampler object per distinct description, so textures with the same
+// sampler state share it. Samplers are created on first use, which needs a current context.
+#[derive(Debug)]
+pub struct SamplerCache {
+	samplers: RefCell<HashMap<[u32; 13], Rc<Sampler>>>,
+}
+
+impl SamplerCache {
+	pub fn new() -> SamplerCache {
+		SamplerCache {
+			samplers: RefCell::new(HashMap::new()),
+		}
+	}
+
+	pub fn get(&self, desc: &SamplerDesc) -> Rc<Sampler> {
+		self.samplers.borrow_mut()
+			.entry(desc.key())
+			.or_insert_with(|| Rc::new(Sampler::new(desc)))
+			.clone()
+	}
+
+	pub fn len(&self) -> usize {
+		self.samplers.borrow().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.samplers.borrow().is_empty()
+	}
+
+	// Forgets samplers no texture uses anymore, which deletes them
+	pub fn purge(&self) {
+		self.samplers.borrow_mut().retain(|_, sampler| Rc::strong_count(sampler) > 1);
+	}
+}
@@ -3,40 +3,404 @@ extern crate std;
 
 use gl::types::*;
 
+use std::collections::{BTreeMap, HashMap};
+use std::error;
 use std::ffi::CString;
-use std::fs::File;
-use std::io::prelude::*;
+use std::fmt;
+use std::io;
+use std::mem;
 use std::ptr;
 
-use framework::math::{Mat4x4, Vec4};
-use framework::core::{Camera, Transform};
+use framework::math::{Mat3x3, Mat4x4, Vec2, Vec3, Vec4};
+use framework::core::{Camera, HotReload, Transform};
+use framework::graphics::{Preprocessor, ProgramCache, ShaderSource, Texture};
+use framework::graphics::capabilities;
+use framework::graphics::reflection;
+use framework::graphics::reflection::{AttributeInfo, GlslType, UniformBlockInfo, UniformInfo};
 
 pub trait Shader {
-	fn init(&mut self);
+	fn init(&mut self) -> Result<(), ShaderError>;
 	fn begin(&self);
 	fn end(&self);
-	fn update_uniforms(&self, transform: &Transform, camera: &Camera, dt: f32);
+	fn update_uniforms(&self, transform: &Transform, camera: &Camera, dt: f32) -> Result<(), ShaderError>;
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+	Vertex,
+	Geometry,
+	Fragment,
+	Compute,	// OpenGL 4.3, see ComputeShader
+}
+
+impl ShaderStage {
+	pub fn gl_enum(&self) -> GLenum {
+		match *self {
+			ShaderStage::Vertex => gl::VERTEX_SHADER,
+			ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+			ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+			ShaderStage::Compute => gl::COMPUTE_SHADER,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			ShaderStage::Vertex => "vertex",
+			ShaderStage::Geometry => "geometry",
+			ShaderStage::Fragment => "fragment",
+			ShaderStage::Compute => "compute",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<ShaderStage> {
+		match name {
+			"vertex" => Some(ShaderStage::Vertex),
+			"geometry" => Some(ShaderStage::Geometry),
+			"fragment" => Some(ShaderStage::Fragment),
+			"compute" => Some(ShaderStage::Compute),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for ShaderStage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.name())
+	}
+}
+
+// One line of a driver info log, with the file and line it refers to (if any)
+// and the offending line of source code.
+#[derive(Clone, Debug)]
+pub struct ShaderDiagnostic {
+	pub file: Option<String>,
+	pub line: Option<usize>,
+	pub code: Option<String>,
+	pub message: String,
+}
+
+impl ShaderDiagnostic {
+	// Drivers don't agree on a log format, the common ones are:
+	// Mesa:        0:12(5): error: ...
+	// NVIDIA:      0(12) : error C0000: ...
+	// AMD / Intel: ERROR: 0:12: ...
+	// Line numbers in the log refer to the preprocessed code and are mapped
+	// back to the file they came from.
+	fn parse(log_line: &str, source: &ShaderSource) -> ShaderDiagnostic {
+		let line = ShaderDiagnostic::parse_line_number(log_line);
+		let code = line.and_then(|n| source.line(n)).map(|s| s.trim().to_string());
+		let (file, line) = match line.and_then(|n| source.origin(n)) {
+			Some((file, line)) => (Some(file.to_string()), Some(line)),
+			None => (None, None),
+		};
+
+		ShaderDiagnostic {
+			file: file,
+			line: line,
+			code: code,
+			message: log_line.trim().to_string(),
+		}
+	}
+
+	fn parse_line_number(log_line: &str) -> Option<usize> {
+		let mut rest = log_line.trim_start();
+		for prefix in &["ERROR:", "WARNING:"] {
+			if rest.starts_with(prefix) {
+				rest = rest[prefix.len()..].trim_start();
+			}
+		}
+
+		// Skip the source string index
+		let digits = rest.chars().take_while(|c| c.is_digit(10)).count();
+		if digits == 0 {
+			return None;
+		}
+		let rest = &rest[digits..];
+
+		let rest = if rest.starts_with(':') || rest.starts_with('(') {
+			&rest[1..]
+		} else {
+			return None;
+		};
+
+		let digits = rest.chars().take_while(|c| c.is_digit(10)).count();
+		rest[..digits].parse().ok()
+	}
+}
+
+impl fmt::Display for ShaderDiagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		try!(write!(f, "{}", self.message));
+		if let Some(ref code) = self.code {
+			try!(write!(f, "\n    | {}", code));
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+	Io { path: String, error: io::Error },
+	Compile { stage: ShaderStage, path: String, diagnostics: Vec<ShaderDiagnostic> },
+	Link { log: String },
+	Preprocess { path: String, line: usize, message: String },
+	UniformNotFound(String),
+	UniformType { name: String, ty: GlslType, requested: GlslType },
+	UniformArraySize { name: String, size: usize, count: usize },
+	UniformBlockNotFound(String),
+	StorageBlockNotFound(String),
+	OutOfTextureUnits { name: String, units: usize },
+	Unsupported(String),
+}
+
+impl fmt::Display for ShaderError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ShaderError::Io { ref path, ref error } => {
+				write!(f, "Could not read shader {}: {}", path, error)
+			},
+			ShaderError::Compile { stage, ref path, ref diagnostics } => {
+				try!(write!(f, "Could not compile {} shader {}:", stage, path));
+				for diagnostic in diagnostics {
+					match (&diagnostic.file, diagnostic.line) {
+						(&Some(ref file), Some(line)) => try!(write!(f, "\n  {}:{}: {}", file, line, diagnostic)),
+						_ => try!(write!(f, "\n  {}", diagnostic)),
+					}
+				}
+				Ok(())
+			},
+			ShaderError::Link { ref log } => {
+				write!(f, "Could not link shader program:\n  {}", log.trim())
+			},
+			ShaderError::Preprocess { ref path, line, ref message } => {
+				write!(f, "Could not preprocess shader {}:{}: {}", path, line, message)
+			},
+			ShaderError::UniformNotFound(ref name) => {
+				write!(f, "Could not find uniform {}", name)
+			},
+			ShaderError::UniformType { ref name, ty, requested } => {
+				write!(f, "Uniform {} is a {}, not a {}", name, ty, requested)
+			},
+			ShaderError::UniformArraySize { ref name, size, count } => {
+				write!(f, "Uniform {} holds {} elements, got {}", name, size, count)
+			},
+			ShaderError::UniformBlockNotFound(ref name) => {
+				write!(f, "Could not find uniform block {}", name)
+			},
+			ShaderError::StorageBlockNotFound(ref name) => {
+				write!(f, "Could not find shader storage block {}", name)
+			},
+			ShaderError::OutOfTextureUnits { ref name, units } => {
+				write!(f, "Out of texture units for sampler {}, the context has {}", name, units)
+			},
+			ShaderError::Unsupported(ref message) => {
+				write!(f, "Not supported by this context: {}", message)
+			},
+		}
+	}
+}
+
+impl error::Error for ShaderError {}
+
+// The type is filled in by InternalShader::add_uniform, setters refuse
+// to upload through a uniform that was never added.
 pub struct Uniform<'a> {
 	id: GLint,
 	name: &'a str,
+	ty: Option<GlslType>,
+	size: usize,
 }
 
 impl<'a> Uniform<'a> {
 	pub fn new(name: &'a str) -> Uniform<'a>  {
-		Uniform { id: 0, name: name }
+		Uniform { id: 0, name: name, ty: None, size: 0 }
+	}
+
+	pub fn name(&self) -> &'a str {
+		self.name
+	}
+
+	pub fn ty(&self) -> Option<GlslType> {
+		self.ty
+	}
+
+	// Number of array elements from this uniform on, 1 for non-arrays
+	pub fn size(&self) -> usize {
+		self.size
 	}
 }
 
 impl<'a> Default for Uniform<'a>  {
 	fn default() -> Uniform<'a>  {
-		Uniform { id: 0, name: "" }
+		Uniform { id: 0, name: "", ty: None, size: 0 }
+	}
+}
+
+// A file with a single stage or a file with #pragma stage sections
+#[derive(Clone, Debug)]
+enum StageSource {
+	Single(ShaderStage, String),
+	Combined(String),
+}
+
+// Collects the stages of a program and compiles and links them in one go:
+// let shader = try!(ShaderBuilder::new()
+//     .vertex("./assets/shaders/basic_shader.vs.glsl")
+//     .fragment("./assets/shaders/basic_shader.fs.glsl")
+//     .define("ALPHA_TEST", "")
+//     .cache(&program_cache)
+//     .build());
+#[derive(Clone)]
+pub struct ShaderBuilder {
+	stages: Vec<StageSource>,
+	preprocessor: Preprocessor,
+	cache: Option<ProgramCache>,
+}
+
+impl ShaderBuilder {
+	pub fn new() -> ShaderBuilder {
+		ShaderBuilder {
+			stages: Vec::new(),
+			preprocessor: Preprocessor::new(),
+			cache: None,
+		}
+	}
+
+	// Reuse linked binaries from earlier runs instead of compiling
+	pub fn cache(&mut self, cache: &ProgramCache) -> &mut ShaderBuilder {
+		self.cache = Some(cache.clone());
+		self
+	}
+
+	// Injected into every stage after its #version directive
+	pub fn define(&mut self, name: &str, value: &str) -> &mut ShaderBuilder {
+		self.preprocessor.define(name, value);
+		self
+	}
+
+	pub fn vertex(&mut self, file_path: &str) -> &mut ShaderBuilder {
+		self.stage(ShaderStage::Vertex, file_path)
+	}
+
+	pub fn fragment(&mut self, file_path: &str) -> &mut ShaderBuilder {
+		self.stage(ShaderStage::Fragment, file_path)
+	}
+
+	pub fn geometry(&mut self, file_path: &str) -> &mut ShaderBuilder {
+		self.stage(ShaderStage::Geometry, file_path)
+	}
+
+	pub fn compute(&mut self, file_path: &str) -> &mut ShaderBuilder {
+		self.stage(ShaderStage::Compute, file_path)
+	}
+
+	pub fn stage(&mut self, stage: ShaderStage, file_path: &str) -> &mut ShaderBuilder {
+		self.stages.push(StageSource::Single(stage, file_path.to_string()));
+		self
+	}
+
+	// One file holding several stages, see InternalShader::combined_shader
+	pub fn combined(&mut self, file_path: &str) -> &mut ShaderBuilder {
+		self.stages.push(StageSource::Combined(file_path.to_string()));
+		self
+	}
+
+	pub fn build(&self) -> Result<InternalShader, ShaderError> {
+		let mut shader = InternalShader::new();
+		shader.preprocessor = self.preprocessor.clone();
+		shader.cache = self.cache.clone();
+		try!(shader.load_all(&self.stages));
+
+		Ok(shader)
+	}
+}
+
+// Permutations of one program, built on first use and cached by their define set:
+// let mut variants = ShaderVariants::new(builder);
+// let skinned = try!(variants.get(&[("SKINNED", ""), ("MAX_BONES", "64")]));
+pub struct ShaderVariants {
+	builder: ShaderBuilder,
+	variants: HashMap<Vec<(String, String)>, InternalShader>,
+}
+
+impl ShaderVariants {
+	pub fn new(builder: ShaderBuilder) -> ShaderVariants {
+		ShaderVariants {
+			builder: builder,
+			variants: HashMap::new(),
+		}
+	}
+
+	pub fn get(&mut self, defines: &[(&str, &str)]) -> Result<&InternalShader, ShaderError> {
+		// Sorted so the order in which defines are passed doesn't matter
+		let key: Vec<(String, String)> = defines.iter()
+			.map(|&(name, value)| (name.to_string(), value.to_string()))
+			.collect::<BTreeMap<String, String>>()
+			.into_iter()
+			.collect();
+
+		if !self.variants.contains_key(&key) {
+			let mut builder = self.builder.clone();
+			for &(ref name, ref value) in &key {
+				builder.define(name, value);
+			}
+			let shader = try!(builder.build());
+			self.variants.insert(key.clone(), shader);
+		}
+
+		Ok(&self.variants[&key])
+	}
+
+	pub fn len(&self) -> usize {
+		self.variants.len()
+	}
+
+	pub fn clear(&mut self) {
+		self.variants.clear();
+	}
+}
+
+impl HotReload for ShaderVariants {
+	type Error = ShaderError;
+
+	fn source_files(&self) -> Vec<String> {
+		let mut files = Vec::new();
+		for shader in self.variants.values() {
+			for file in shader.files.iter() {
+				if !files.contains(file) {
+					files.push(file.clone());
+				}
+			}
+		}
+		files
+	}
+
+	// Variants that fail keep their old program, the first error is returned
+	fn reload(&mut self) -> Result<(), ShaderError> {
+		let mut result = Ok(());
+		for shader in self.variants.values_mut() {
+			if let Err(e) = shader.reload() {
+				if result.is_ok() {
+					result = Err(e);
+				}
+			}
+		}
+		result
 	}
 }
 
 pub struct InternalShader {
 	id: GLuint,
+	uniforms: HashMap<String, UniformInfo>,
+	attributes: HashMap<String, AttributeInfo>,
+	uniform_blocks: HashMap<String, UniformBlockInfo>,
+	texture_units: HashMap<String, u32>,
+	// Remembered for reloading
+	stages: Vec<StageSource>,
+	preprocessor: Preprocessor,
+	cache: Option<ProgramCache>,
+	cache_error: Option<io::Error>,
+	files: Vec<String>,
 }
 
 impl InternalShader {
@@ -45,90 +409,701 @@ impl InternalShader {
 			let id = gl::CreateProgram();
 			InternalShader {
 				id: id,
+				uniforms: HashMap::new(),
+				attributes: HashMap::new(),
+				uniform_blocks: HashMap::new(),
+				texture_units: HashMap::new(),
+				stages: Vec::new(),
+				preprocessor: Preprocessor::new(),
+				cache: None,
+				cache_error: None,
+				files: Vec::new(),
 			 }
 		}
 	}
 
-	pub fn compile(&self) {
+	pub fn compile(&mut self) -> Result<(), ShaderError> {
 		unsafe {
 			gl::LinkProgram(self.id);
 			//gl::ValidateProgram(self.id);
+
+			let mut status = gl::FALSE as GLint;
+			gl::GetProgramiv(self.id, gl::LINK_STATUS, &mut status);
+			if status != (gl::TRUE as GLint) {
+				return Err(ShaderError::Link { log: InternalShader::program_info_log(self.id) });
+			}
+		}
+
+		self.reflect()
+	}
+
+	fn reflect(&mut self) -> Result<(), ShaderError> {
+		unsafe {
+			self.uniforms = reflection::active_uniforms(self.id);
+			self.attributes = reflection::active_attributes(self.id);
+			self.uniform_blocks = reflection::active_uniform_blocks(self.id);
+		}
+		self.assign_texture_units()
+	}
+
+	// Gives every sampler uniform its own texture unit, so textures for all of them can be
+	// bound at once. Samplers with a layout(binding = n) other than 0 keep it, the others
+	// get the free units in name order. Array elements get consecutive units.
+	fn assign_texture_units(&mut self) -> Result<(), ShaderError> {
+		let mut samplers: Vec<(&String, &UniformInfo)> = self.uniforms.iter()
+			.filter(|&(_, info)| info.ty.is_sampler())
+			.collect();
+		samplers.sort_by(|a, b| a.0.cmp(b.0));
+		self.texture_units.clear();
+		if samplers.is_empty() {
+			return Ok(());
+		}
+
+		unsafe {
+			let mut max_units = 0;
+			gl::GetIntegerv(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS, &mut max_units);
+			let mut previous = 0;
+			gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut previous);
+			gl::UseProgram(self.id);
+
+			let mut used = vec![false; max_units.max(0) as usize];
+			let mut pending = Vec::new();
+			for &(name, info) in &samplers {
+				let mut unit = 0;
+				gl::GetUniformiv(self.id, info.location, &mut unit);
+				let range = unit as usize..unit as usize + info.size as usize;
+				if unit > 0 && range.end <= used.len() && !used[range.clone()].contains(&true) {
+					for used in &mut used[range] {
+						*used = true;
+					}
+					self.texture_units.insert(name.clone(), unit as u32);
+				} else {
+					pending.push((name, info));
+				}
+			}
+
+			let mut result = Ok(());
+			for (name, info) in pending {
+				let size = info.size as usize;
+				let start = (0..used.len()).find(|&start| start + size <= used.len() &&
+					!used[start..start + size].contains(&true));
+				let start = match start {
+					Some(start) => start,
+					None => {
+						result = Err(ShaderError::OutOfTextureUnits { name: name.clone(), units: used.len() });
+						break;
+					},
+				};
+
+				let units: Vec<GLint> = (start as GLint..(start + size) as GLint).collect();
+				gl::Uniform1iv(info.location, size as GLsizei, units.as_ptr());
+				for used in &mut used[start..start + size] {
+					*used = true;
+				}
+				self.texture_units.insert(name.clone(), start as u32);
+			}
+
+			gl::UseProgram(previous as GLuint);
+			result
+		}
+	}
+
+	// The linked program in the driver's binary format, None if it has none
+	// or the context lacks GL 4.1 and ARB_get_program_binary
+	pub fn binary(&self) -> Option<(GLenum, Vec<u8>)> {
+		if !gl::GetProgramBinary::is_loaded() {
+			return None;
+		}
+		unsafe {
+			let mut length = 0;
+			gl::GetProgramiv(self.id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+			if length <= 0 {
+				return None;
+			}
+
+			let mut binary: Vec<u8> = vec![0; length as usize];
+			let mut written = 0;
+			let mut format = 0;
+			gl::GetProgramBinary(self.id, length, &mut written, &mut format,
+				binary.as_mut_ptr() as *mut _);
+			binary.truncate(written as usize);
+
+			Some((format, binary))
+		}
+	}
+
+	// Links the program from a binary, false when the driver rejects it
+	fn load_binary(&mut self, format: GLenum, binary: &[u8]) -> bool {
+		unsafe {
+			gl::ProgramBinary(self.id, format, binary.as_ptr() as *const _, binary.len() as GLsizei);
+
+			let mut status = gl::FALSE as GLint;
+			gl::GetProgramiv(self.id, gl::LINK_STATUS, &mut status);
+			status == (gl::TRUE as GLint)
+		}
+	}
+
+	pub fn vertex_shader(&mut self, file_path: &str) -> Result<(), ShaderError> {
+		self.load_shader(ShaderStage::Vertex, file_path)
+	}
+
+	pub fn fragment_shader(&mut self, file_path: &str) -> Result<(), ShaderError> {
+		self.load_shader(ShaderStage::Fragment, file_path)
+	}
+
+	pub fn geometry_shader(&mut self, file_path: &str) -> Result<(), ShaderError> {
+		self.load_shader(ShaderStage::Geometry, file_path)
+	}
+
+	pub fn compute_shader(&mut self, file_path: &str) -> Result<(), ShaderError> {
+		self.load_shader(ShaderStage::Compute, file_path)
+	}
+
+	// Loads every stage from one file, split into sections by #pragma stage:
+	// #version 330 core
+	// (code shared by all stages)
+	// #pragma stage vertex
+	// ...
+	// #pragma stage fragment
+	// ...
+	// Errors report line numbers of the combined file.
+	pub fn combined_shader(&mut self, file_path: &str) -> Result<(), ShaderError> {
+		self.load(&StageSource::Combined(file_path.to_string()))
+	}
+
+	fn load_shader(&mut self, stage: ShaderStage, file_path: &str) -> Result<(), ShaderError> {
+		self.load(&StageSource::Single(stage, file_path.to_string()))
+	}
+
+	fn load(&mut self, stage_source: &StageSource) -> Result<(), ShaderError> {
+		let sections = try!(self.read_stage(stage_source));
+		for &(stage, ref section) in &sections {
+			try!(self.add_shader(section, stage));
+		}
+		self.remember(stage_source, &sections);
+
+		Ok(())
+	}
+
+	// Loads every stage and links, through the program cache when there is one
+	fn load_all(&mut self, stage_sources: &[StageSource]) -> Result<(), ShaderError> {
+		let cache = match self.cache.clone() {
+			Some(cache) => cache,
+			None => {
+				for stage_source in stage_sources {
+					try!(self.load(stage_source));
+				}
+				return self.compile();
+			},
+		};
+
+		let mut sections = Vec::new();
+		for stage_source in stage_sources {
+			let stage_sections = try!(self.read_stage(stage_source));
+			self.remember(stage_source, &stage_sections);
+			sections.extend(stage_sections);
+		}
+
+		let key = cache.key(&sections);
+		if let Some((format, binary)) = cache.load(&key) {
+			if self.load_binary(format, &binary) {
+				return self.reflect();
+			}
+
+			// Rejected, start over from source with a fresh program
+			unsafe {
+				gl::DeleteProgram(self.id);
+				self.id = gl::CreateProgram();
+			}
+		}
+
+		for &(stage, ref section) in &sections {
+			try!(self.add_shader(section, stage));
+		}
+		if cache.is_supported() {
+			unsafe {
+				gl::ProgramParameteri(self.id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
+			}
+		}
+		try!(self.compile());
+
+		if !cache.is_supported() {
+			return Ok(());
+		}
+		// Not worth failing the build for, the program works and the next launch compiles again
+		if let Some((format, binary)) = self.binary() {
+			self.cache_error = cache.store(&key, format, &binary).err();
 		}
+
+		Ok(())
 	}
 
-	pub fn vertex_shader(&self, file_path: &str) {
-		let code = &*InternalShader::read_code(file_path);
-		self.add_shader(code, gl::VERTEX_SHADER);
+	// Preprocessed code of every stage in a file
+	fn read_stage(&self, stage_source: &StageSource) -> Result<Vec<(ShaderStage, ShaderSource)>, ShaderError> {
+		match *stage_source {
+			StageSource::Single(stage, ref file_path) => {
+				let source = try!(InternalShader::read_code(file_path, &self.preprocessor));
+				Ok(vec![(stage, source)])
+			},
+			StageSource::Combined(ref file_path) => {
+				let source = try!(InternalShader::read_code(file_path, &self.preprocessor));
+				source.split_stages()
+			},
+		}
 	}
 
-	pub fn fragment_shader(&self, file_path: &str) {
-		let code = &*InternalShader::read_code(file_path);
-		self.add_shader(code, gl::FRAGMENT_SHADER); // TODO: read file and return code
+	fn remember(&mut self, stage_source: &StageSource, sections: &[(ShaderStage, ShaderSource)]) {
+		self.stages.push(stage_source.clone());
+		for &(_, ref section) in sections {
+			for file in &section.files {
+				if !self.files.contains(file) {
+					self.files.push(file.clone());
+				}
+			}
+		}
 	}
 
-	pub fn geometry_shader(&self, file_path: &str) {
-		let code = &*InternalShader::read_code(file_path);
-		self.add_shader(code, gl::GEOMETRY_SHADER);
+	// Rebuilds the program from its source files. On failure the old program is kept,
+	// on success uniform locations may have moved and have to be added again.
+	pub fn reload(&mut self) -> Result<(), ShaderError> {
+		let mut shader = InternalShader::new();
+		shader.preprocessor = self.preprocessor.clone();
+		shader.cache = self.cache.clone();
+		try!(shader.load_all(&self.stages));
+
+		// The old program is deleted when shader goes out of scope
+		mem::swap(self, &mut shader);
+		Ok(())
 	}
 
-	fn add_shader(&self, code: &str, ty: GLenum) {
+	fn add_shader(&self, source: &ShaderSource, stage: ShaderStage) -> Result<(), ShaderError> {
 		unsafe {
-			let shader = gl::CreateShader(ty);
-			let c_str = CString::new(code.as_bytes()).unwrap();
+			let shader = gl::CreateShader(stage.gl_enum());
+			let c_str = CString::new(source.code.as_bytes()).unwrap();
 			gl::ShaderSource(shader, 1, &c_str.as_ptr(), ptr::null());
 			gl::CompileShader(shader);
+
+			let mut status = gl::FALSE as GLint;
+			gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+			if status != (gl::TRUE as GLint) {
+				let log = InternalShader::shader_info_log(shader);
+				gl::DeleteShader(shader);
+
+				return Err(ShaderError::Compile {
+					stage: stage,
+					path: source.path.clone(),
+					diagnostics: log.lines()
+						.filter(|line| !line.trim().is_empty())
+						.map(|line| ShaderDiagnostic::parse(line, source))
+						.collect(),
+				});
+			}
+
 			gl::AttachShader(self.id, shader);
 			gl::DeleteShader(shader);
 		}
+
+		Ok(())
+	}
+
+	// Reads the file and resolves its includes and defines
+	fn read_code(file_path: &str, preprocessor: &Preprocessor) -> Result<ShaderSource, ShaderError> {
+		preprocessor.process(file_path)
+	}
+
+	unsafe fn shader_info_log(shader: GLuint) -> String {
+		let mut length = 0;
+		gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+		let mut log: Vec<u8> = vec![0; length.max(1) as usize];
+		gl::GetShaderInfoLog(shader, length, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar);
+
+		InternalShader::log_to_string(log)
+	}
+
+	unsafe fn program_info_log(program: GLuint) -> String {
+		let mut length = 0;
+		gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut length);
+		let mut log: Vec<u8> = vec![0; length.max(1) as usize];
+		gl::GetProgramInfoLog(program, length, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar);
+
+		InternalShader::log_to_string(log)
+	}
+
+	fn log_to_string(mut log: Vec<u8>) -> String {
+		// Strip the null terminator
+		while log.last() == Some(&0) {
+			log.pop();
+		}
+
+		String::from_utf8_lossy(&log).into_owned()
 	}
 
-	fn read_code(file_path: &str) -> String {
-		let mut file = File::open(file_path).unwrap();
-		let mut contents: Vec<u8> = Vec::new();
-		file.read_to_end(&mut contents).unwrap();
+	// Active uniforms by name, arrays are stored without their "[0]" suffix
+	pub fn uniforms(&self) -> &HashMap<String, UniformInfo> {
+		&self.uniforms
+	}
+
+	pub fn attributes(&self) -> &HashMap<String, AttributeInfo> {
+		&self.attributes
+	}
 
-		String::from_utf8(contents).unwrap()
+	pub fn uniform_blocks(&self) -> &HashMap<String, UniformBlockInfo> {
+		&self.uniform_blocks
 	}
 
-	pub fn add_uniform(&mut self, uniform: &mut Uniform) {
+	// Why the program binary of the last build or reload couldn't be written to the cache
+	pub fn cache_error(&self) -> Option<&io::Error> {
+		self.cache_error.as_ref()
+	}
+
+	// Sampler uniforms by name and the texture unit they read, the first unit for arrays
+	pub fn texture_units(&self) -> &HashMap<String, u32> {
+		&self.texture_units
+	}
+
+	pub fn texture_unit(&self, name: &str) -> Result<u32, ShaderError> {
+		match self.texture_units.get(name) {
+			Some(&unit) => Ok(unit),
+			None => Err(ShaderError::UniformNotFound(name.to_string())),
+		}
+	}
+
+	// Binds a texture to the unit of a sampler uniform, e.g. for the maps of a material:
+	// try!(shader.bind_texture("albedo_map", &albedo));
+	// try!(shader.bind_texture("normal_map", &normal));
+	pub fn bind_texture(&self, name: &str, texture: &Texture) -> Result<u32, ShaderError> {
+		let unit = try!(self.texture_unit(name));
+		texture.bind(unit);
+		Ok(unit)
+	}
+
+	pub fn uniform<'a>(&self, name: &'a str) -> Result<Uniform<'a>, ShaderError> {
+		match self.uniforms.get(name) {
+			Some(info) => Ok(Uniform {
+				id: info.location,
+				name: name,
+				ty: Some(info.ty),
+				size: info.size as usize,
+			}),
+			None => Err(ShaderError::UniformNotFound(name.to_string())),
+		}
+	}
+
+	// Element of an array uniform, e.g. uniform_element("weights", 3) for weights[3].
+	// Array setters on the result start writing at that element.
+	pub fn uniform_element<'a>(&self, name: &'a str, index: usize) -> Result<Uniform<'a>, ShaderError> {
+		let info = match self.uniforms.get(name) {
+			Some(info) => *info,
+			None => return Err(ShaderError::UniformNotFound(name.to_string())),
+		};
+
+		let size = info.size as usize;
+		if index >= size {
+			return Err(ShaderError::UniformArraySize { name: name.to_string(), size: size, count: index + 1 });
+		}
+
+		let c_str = CString::new(format!("{}[{}]", name, index)).unwrap();
+		let location = unsafe { gl::GetUniformLocation(self.id, c_str.as_ptr()) };
+
+		Ok(Uniform {
+			id: location,
+			name: name,
+			ty: Some(info.ty),
+			size: size - index,
+		})
+	}
+
+	pub fn add_uniform(&mut self, uniform: &mut Uniform) -> Result<(), ShaderError> {
+		let found = try!(self.uniform(uniform.name));
+		uniform.id = found.id;
+		uniform.ty = found.ty;
+		uniform.size = found.size;
+
+		Ok(())
+	}
+
+	// Connects a uniform block of this program to the binding point of a UniformBuffer
+	pub fn bind_uniform_block(&self, name: &str, binding: u32) -> Result<(), ShaderError> {
+		match self.uniform_blocks.get(name) {
+			Some(block) => {
+				unsafe {
+					gl::UniformBlockBinding(self.id, block.index, binding);
+				}
+				Ok(())
+			},
+			None => Err(ShaderError::UniformBlockNotFound(name.to_string())),
+		}
+	}
+
+	// Connects a shader storage block to the binding point of a ShaderStorageBuffer (OpenGL 4.3)
+	pub fn bind_storage_block(&self, name: &str, binding: u32) -> Result<(), ShaderError> {
+		if !gl::GetProgramResourceIndex::is_loaded() || !gl::ShaderStorageBlockBinding::is_loaded() {
+			let (major, minor) = capabilities::gl_version();
+			return Err(ShaderError::Unsupported(
+				format!("storage blocks need OpenGL 4.3, the context is {}.{}", major, minor)));
+		}
+
+		let c_str = CString::new(name.as_bytes()).unwrap();
 		unsafe {
-			let c_str = CString::new(uniform.name.as_bytes()).unwrap();
-			uniform.id = gl::GetUniformLocation(self.id, c_str.as_ptr());
-			if uniform.id == -1 {
-				panic!("Could not find uniform {}", uniform.name);
+			let index = gl::GetProgramResourceIndex(self.id, gl::SHADER_STORAGE_BLOCK, c_str.as_ptr());
+			if index == gl::INVALID_INDEX {
+				return Err(ShaderError::StorageBlockNotFound(name.to_string()));
 			}
+			gl::ShaderStorageBlockBinding(self.id, index, binding);
+		}
+
+		Ok(())
+	}
+
+	fn check(&self, uniform: &Uniform, requested: GlslType) -> Result<(), ShaderError> {
+		self.check_array(uniform, requested, 1)
+	}
+
+	fn check_array(&self, uniform: &Uniform, requested: GlslType, count: usize) -> Result<(), ShaderError> {
+		match uniform.ty {
+			Some(ty) if !ty.accepts(requested) => Err(ShaderError::UniformType {
+				name: uniform.name.to_string(),
+				ty: ty,
+				requested: requested,
+			}),
+			Some(_) if count > uniform.size => Err(ShaderError::UniformArraySize {
+				name: uniform.name.to_string(),
+				size: uniform.size,
+				count: count,
+			}),
+			Some(_) => Ok(()),
+			None => Err(ShaderError::UniformNotFound(uniform.name.to_string())),
 		}
 	}
 
-	pub fn set_bool(&self, uniform: &Uniform, value: bool) {
+	pub fn set_bool(&self, uniform: &Uniform, value: bool) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::Bool));
 		unsafe {
 			gl::Uniform1i(uniform.id, match value { true => 1, false => 0 });
 		}
+
+		Ok(())
 	}
 
-	pub fn set_i32(&self, uniform: &Uniform, value: i32) {
+	pub fn set_i32(&self, uniform: &Uniform, value: i32) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::Int));
 		unsafe {
 			gl::Uniform1i(uniform.id, value);
 		}
+
+		Ok(())
 	}
 
-	pub fn set_f32(&self, uniform: &Uniform, value: f32) {
+	pub fn set_u32(&self, uniform: &Uniform, value: u32) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::UInt));
+		unsafe {
+			gl::Uniform1ui(uniform.id, value);
+		}
+
+		Ok(())
+	}
+
+	pub fn set_f32(&self, uniform: &Uniform, value: f32) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::Float));
 		unsafe {
 			gl::Uniform1f(uniform.id, value);
 		}
+
+		Ok(())
+	}
+
+	pub fn set_vec2(&self, uniform: &Uniform, value: Vec2) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::Vec2));
+		unsafe {
+			gl::Uniform2f(uniform.id, value.x, value.y);
+		}
+
+		Ok(())
+	}
+
+	pub fn set_vec3(&self, uniform: &Uniform, value: Vec3) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::Vec3));
+		unsafe {
+			gl::Uniform3f(uniform.id, value.x, value.y, value.z);
+		}
+
+		Ok(())
 	}
 
-	pub fn set_vec4(&self, uniform: &Uniform, value: Vec4) {
+	pub fn set_vec4(&self, uniform: &Uniform, value: Vec4) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::Vec4));
 		unsafe {
 			gl::Uniform4f(uniform.id, value.x, value.y, value.z, value.w);
 		}
+
+		Ok(())
+	}
+
+	pub fn set_ivec2(&self, uniform: &Uniform, value: [i32; 2]) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::IVec2));
+		unsafe {
+			gl::Uniform2i(uniform.id, value[0], value[1]);
+		}
+
+		Ok(())
+	}
+
+	pub fn set_ivec3(&self, uniform: &Uniform, value: [i32; 3]) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::IVec3));
+		unsafe {
+			gl::Uniform3i(uniform.id, value[0], value[1], value[2]);
+		}
+
+		Ok(())
+	}
+
+	pub fn set_ivec4(&self, uniform: &Uniform, value: [i32; 4]) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::IVec4));
+		unsafe {
+			gl::Uniform4i(uniform.id, value[0], value[1], value[2], value[3]);
+		}
+
+		Ok(())
+	}
+
+	pub fn set_uvec2(&self, uniform: &Uniform, value: [u32; 2]) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::UVec2));
+		unsafe {
+			gl::Uniform2ui(uniform.id, value[0], value[1]);
+		}
+
+		Ok(())
+	}
+
+	pub fn set_uvec3(&self, uniform: &Uniform, value: [u32; 3]) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::UVec3));
+		unsafe {
+			gl::Uniform3ui(uniform.id, value[0], value[1], value[2]);
+		}
+
+		Ok(())
 	}
 
-	pub fn set_mat4x4(&self, uniform: &Uniform, value: &Mat4x4) {
+	pub fn set_uvec4(&self, uniform: &Uniform, value: [u32; 4]) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::UVec4));
+		unsafe {
+			gl::Uniform4ui(uniform.id, value[0], value[1], value[2], value[3]);
+		}
+
+		Ok(())
+	}
+
+	pub fn set_mat3x3(&self, uniform: &Uniform, value: &Mat3x3) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::Mat3));
+		unsafe {
+			gl::UniformMatrix3fv(uniform.id, 1, gl::TRUE, value.m.as_ptr());
+		}
+
+		Ok(())
+	}
+
+	pub fn set_mat4x4(&self, uniform: &Uniform, value: &Mat4x4) -> Result<(), ShaderError> {
+		try!(self.check(uniform, GlslType::Mat4));
 		unsafe {
 			gl::UniformMatrix4fv(uniform.id, 1,
 				gl::TRUE, std::mem::transmute(value));
 		}
+
+		Ok(())
+	}
+
+	// Points a sampler uniform at a texture unit (0 for GL_TEXTURE0 and so on). This overrides
+	// the unit from link time, texture_units() and bind_texture() don't know about it.
+	pub fn set_sampler(&self, uniform: &Uniform, unit: u32) -> Result<(), ShaderError> {
+		match uniform.ty {
+			Some(ty) if !ty.is_sampler() => {
+				return Err(ShaderError::UniformType {
+					name: uniform.name.to_string(),
+					ty: ty,
+					requested: GlslType::Sampler2D,
+				});
+			},
+			Some(_) => {},
+			None => return Err(ShaderError::UniformNotFound(uniform.name.to_string())),
+		}
+
+		unsafe {
+			gl::Uniform1i(uniform.id, unit as GLint);
+		}
+
+		Ok(())
+	}
+
+	pub fn set_i32_array(&self, uniform: &Uniform, values: &[i32]) -> Result<(), ShaderError> {
+		try!(self.check_array(uniform, GlslType::Int, values.len()));
+		unsafe {
+			gl::Uniform1iv(uniform.id, values.len() as GLsizei, values.as_ptr());
+		}
+
+		Ok(())
+	}
+
+	pub fn set_f32_array(&self, uniform: &Uniform, values: &[f32]) -> Result<(), ShaderError> {
+		try!(self.check_array(uniform, GlslType::Float, values.len()));
+		unsafe {
+			gl::Uniform1fv(uniform.id, values.len() as GLsizei, values.as_ptr());
+		}
+
+		Ok(())
+	}
+
+	pub fn set_vec2_array(&self, uniform: &Uniform, values: &[Vec2]) -> Result<(), ShaderError> {
+		try!(self.check_array(uniform, GlslType::Vec2, values.len()));
+		let data: Vec<f32> = values.iter().flat_map(|v| vec![v.x, v.y]).collect();
+		unsafe {
+			gl::Uniform2fv(uniform.id, values.len() as GLsizei, data.as_ptr());
+		}
+
+		Ok(())
+	}
+
+	pub fn set_vec3_array(&self, uniform: &Uniform, values: &[Vec3]) -> Result<(), ShaderError> {
+		try!(self.check_array(uniform, GlslType::Vec3, values.len()));
+		let data: Vec<f32> = values.iter().flat_map(|v| vec![v.x, v.y, v.z]).collect();
+		unsafe {
+			gl::Uniform3fv(uniform.id, values.len() as GLsizei, data.as_ptr());
+		}
+
+		Ok(())
+	}
+
+	pub fn set_vec4_array(&self, uniform: &Uniform, values: &[Vec4]) -> Result<(), ShaderError> {
+		try!(self.check_array(uniform, GlslType::Vec4, values.len()));
+		let data: Vec<f32> = values.iter().flat_map(|v| vec![v.x, v.y, v.z, v.w]).collect();
+		unsafe {
+			gl::Uniform4fv(uniform.id, values.len() as GLsizei, data.as_ptr());
+		}
+
+		Ok(())
+	}
+
+	pub fn set_mat3x3_array(&self, uniform: &Uniform, values: &[Mat3x3]) -> Result<(), ShaderError> {
+		try!(self.check_array(uniform, GlslType::Mat3, values.len()));
+		let data: Vec<f32> = values.iter().flat_map(|m| m.m.to_vec()).collect();
+		unsafe {
+			gl::UniformMatrix3fv(uniform.id, values.len() as GLsizei, gl::TRUE, data.as_ptr());
+		}
+
+		Ok(())
+	}
+
+	pub fn set_mat4x4_array(&self, uniform: &Uniform, values: &[Mat4x4]) -> Result<(), ShaderError> {
+		try!(self.check_array(uniform, GlslType::Mat4, values.len()));
+		let data: Vec<f32> = values.iter().flat_map(|m| m.m.to_vec()).collect();
+		unsafe {
+			gl::UniformMatrix4fv(uniform.id, values.len() as GLsizei, gl::TRUE, data.as_ptr());
+		}
+
+		Ok(())
+	}
+
+	pub fn id(&self) -> GLuint {
+		self.id
 	}
 
 	pub fn begin(&self) {
@@ -144,10 +1119,22 @@ impl InternalShader {
 	}
 }
 
+impl HotReload for InternalShader {
+	type Error = ShaderError;
+
+	fn source_files(&self) -> Vec<String> {
+		self.files.clone()
+	}
+
+	fn reload(&mut self) -> Result<(), ShaderError> {
+		InternalShader::reload(self)
+	}
+}
+
 impl Drop for InternalShader {
 	fn drop(&mut self) {
 		unsafe {
 			gl::DeleteProgram(self.id);
 		};
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,88 @@
+extern crate gl;
+
+use gl::types::*;
+
+use framework::core::{Camera, HotReload};
+use framework::graphics::{CubemapTexture, InternalShader, Mesh, ShaderBuilder, ShaderError};
+
+pub const VERTEX_SHADER: &'static str = "./assets/shaders/skybox.vs.glsl";
+pub const FRAGMENT_SHADER: &'static str = "./assets/shaders/skybox.fs.glsl";
+
+// Draws a cubemap around the camera using only its rotation. Every fragment lands on the
+// far plane, so the sky can be drawn after the opaque geometry and only fills what is left.
+pub struct Skybox {
+	shader: InternalShader,
+	cube: Mesh,
+}
+
+impl Skybox {
+	pub fn new() -> Result<Skybox, ShaderError> {
+		let shader = try!(ShaderBuilder::new()
+			.vertex(VERTEX_SHADER)
+			.fragment(FRAGMENT_SHADER)
+			.build());
+		Ok(Skybox::with_shader(shader))
+	}
+
+	// Any program with a rotation_projection matrix and a samplerCube named skybox
+	pub fn with_shader(shader: InternalShader) -> Skybox {
+		let mut cube = Mesh::cube(2.0);
+		// Every channel of a generated cube has one entry per position
+		cube.upload().expect("the skybox cube is a valid mesh");
+
+		Skybox {
+			shader: shader,
+			cube: cube,
+		}
+	}
+
+	pub fn draw(&self, cubemap: &CubemapTexture, camera: &Camera) -> Result<(), ShaderError> {
+		let uniform = try!(self.shader.uniform("rotation_projection"));
+
+		unsafe {
+			let mut depth_func = 0;
+			let mut depth_mask = 0;
+			gl::GetIntegerv(gl::DEPTH_FUNC, &mut depth_func);
+			gl::GetIntegerv(gl::DEPTH_WRITEMASK, &mut depth_mask);
+			let depth_test = gl::IsEnabled(gl::DEPTH_TEST);
+			let cull_face = gl::IsEnabled(gl::CULL_FACE);
+
+			// The far plane passes LEQUAL where nothing was drawn, and the cube is seen from inside
+			gl::Enable(gl::DEPTH_TEST);
+			gl::DepthFunc(gl::LEQUAL);
+			gl::DepthMask(gl::FALSE);
+			gl::Disable(gl::CULL_FACE);
+
+			self.shader.begin();
+			let result = self.shader.set_mat4x4(&uniform, &camera.rotation_projection())
+				.and_then(|_| self.shader.bind_texture("skybox", cubemap.texture()));
+			if let Ok(unit) = result {
+				self.cube.draw();
+				cubemap.unbind(unit);
+			}
+			self.shader.end();
+
+			gl::DepthFunc(depth_func as GLenum);
+			gl::DepthMask(depth_mask as GLboolean);
+			if depth_test == gl::FALSE {
+				gl::Disable(gl::DEPTH_TEST);
+			}
+			if cull_face == gl::TRUE {
+				gl::Enable(gl::CULL_FACE);
+			}
+			result.map(|_| ())
+		}
+	}
+}
+
+impl HotReload for Skybox {
+	type Error = ShaderError;
+
+	fn source_files(&self) -> Vec<String> {
+		self.shader.source_files()
+	}
+
+	fn reload(&mut self) -> Result<(), ShaderError> {
+		self.shader.reload()
+	}
+}
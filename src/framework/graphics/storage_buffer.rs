@@ -0,0 +1,101 @@
+extern crate gl;
+extern crate std;
+
+use gl::types::*;
+
+use std::mem;
+use std::ptr;
+
+use framework::graphics::{ComputeShader, ShaderError};
+use framework::graphics::capabilities;
+
+// Shader storage buffer object (OpenGL 4.3) for compute shaders, creating one fails
+// where ComputeShader::is_supported is false.
+// T should be plain data laid out like the std430 block, e.g. #[repr(C)] structs
+// padded to vec4 boundaries where the block has vec3 members.
+pub struct ShaderStorageBuffer {
+	id: GLuint,
+	size: usize,
+}
+
+impl ShaderStorageBuffer {
+	pub fn new(size: usize) -> Result<ShaderStorageBuffer, ShaderError> {
+		ShaderStorageBuffer::create(size, ptr::null())
+	}
+
+	pub fn from_data<T: Copy>(data: &[T]) -> Result<ShaderStorageBuffer, ShaderError> {
+		ShaderStorageBuffer::create(data.len() * mem::size_of::<T>(), data.as_ptr() as *const _)
+	}
+
+	fn create(size: usize, data: *const GLvoid) -> Result<ShaderStorageBuffer, ShaderError> {
+		if !ComputeShader::is_supported() {
+			let (major, minor) = capabilities::gl_version();
+			return Err(ShaderError::Unsupported(
+				format!("storage buffers need OpenGL 4.3, the context is {}.{}", major, minor)));
+		}
+
+		let mut id = 0;
+		unsafe {
+			gl::GenBuffers(1, &mut id);
+			gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, id);
+			gl::BufferData(gl::SHADER_STORAGE_BUFFER, size as GLsizeiptr, data, gl::DYNAMIC_COPY);
+			gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+		}
+
+		Ok(ShaderStorageBuffer {
+			id: id,
+			size: size,
+		})
+	}
+
+	pub fn id(&self) -> GLuint {
+		self.id
+	}
+
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	// Data that doesn't fit in the buffer is cut off
+	pub fn update<T: Copy>(&self, offset: usize, data: &[T]) {
+		if offset >= self.size {
+			return;
+		}
+
+		let length = (data.len() * mem::size_of::<T>()).min(self.size - offset);
+		unsafe {
+			gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+			gl::BufferSubData(gl::SHADER_STORAGE_BUFFER, offset as GLintptr, length as GLsizeiptr,
+				data.as_ptr() as *const _);
+			gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+		}
+	}
+
+	// Copies the buffer back to the CPU, place a Barrier::BufferUpdate memory barrier
+	// after the dispatch that wrote it.
+	pub fn read<T: Copy + Default>(&self) -> Vec<T> {
+		let count = self.size / mem::size_of::<T>().max(1);
+		let mut data = vec![T::default(); count];
+		unsafe {
+			gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+			gl::GetBufferSubData(gl::SHADER_STORAGE_BUFFER, 0,
+				(count * mem::size_of::<T>()) as GLsizeiptr, data.as_mut_ptr() as *mut _);
+			gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+		}
+		data
+	}
+
+	pub fn bind(&self, binding: u32) {
+		unsafe {
+			gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.id);
+		}
+	}
+}
+
+impl Drop for ShaderStorageBuffer {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteBuffers(1, &self.id);
+		}
+	}
+}
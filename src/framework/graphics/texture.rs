@@ -3,186 +3,691 @@ extern crate libc;
 extern crate std;
 
 use gl::types::*;
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
 use std::io;
 use std::io::{ Error, ErrorKind };
-use std::io::prelude::*;
-use std::fs::File;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
+
+use framework::core::HotReload;
+use framework::graphics::capabilities;
+use framework::graphics::image;
+use framework::graphics::image::bcn;
+use framework::graphics::image::{DdsFormat, DdsImage, RgbaImage};
+use framework::graphics::sampler::{Sampler, SamplerCache, SamplerDesc};
+
+// How a texture is created and sampled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureDesc {
+	pub srgb: bool,							// Color data, converted to linear when sampled
+	pub generate_mipmaps: bool,				// For images and dds files without a mipmap chain
+	pub internal_format: Option<GLenum>,	// Overrides RGBA8 for png, tga and bmp images
+	pub sampler: SamplerDesc,
+	pub fallback: bool,						// Show a checkerboard when loading fails
+}
+
+impl TextureDesc {
+	// Linear data with mipmaps and a repeating trilinear sampler. Debug builds
+	// fall back to a checkerboard so missing textures stand out.
+	pub fn new() -> TextureDesc {
+		TextureDesc {
+			srgb: false,
+			generate_mipmaps: true,
+			internal_format: None,
+			sampler: SamplerDesc::new(),
+			fallback: cfg!(debug_assertions),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+	Io { path: String, error: io::Error },
+	UnsupportedFormat { path: String, message: String },
+	Truncated { path: String },
+	GlUpload { path: String, error: GLenum },
+}
+
+impl TextureError {
+	// The image decoders report malformed files as InvalidData and short ones as UnexpectedEof
+	pub fn from_io(path: &str, error: io::Error) -> TextureError {
+		match error.kind() {
+			ErrorKind::UnexpectedEof => TextureError::Truncated { path: path.to_string() },
+			ErrorKind::InvalidData => TextureError::UnsupportedFormat {
+				path: path.to_string(),
+				message: error.to_string(),
+			},
+			_ => TextureError::Io { path: path.to_string(), error: error },
+		}
+	}
+}
+
+impl fmt::Display for TextureError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			TextureError::Io { ref path, ref error } => {
+				write!(f, "Could not read texture {}: {}", path, error)
+			},
+			TextureError::UnsupportedFormat { ref path, ref message } => {
+				write!(f, "Could not load texture {}: {}", path, message)
+			},
+			TextureError::Truncated { ref path } => {
+				write!(f, "Could not load texture {}: the file is truncated", path)
+			},
+			TextureError::GlUpload { ref path, error } => {
+				write!(f, "Could not upload texture {}: GL error 0x{:x}", path, error)
+			},
+		}
+	}
+}
+
+impl error::Error for TextureError {}
 
 pub struct Texture {
 	id: GLuint,
+	target: GLenum,
+	path: Option<String>,
+	desc: TextureDesc,
+	sampler: Option<Rc<Sampler>>,
 }
 
 impl Texture {
 	pub fn new() -> Texture {
 		Texture {
 			id: 0,
+			target: gl::TEXTURE_2D,
+			path: None,
+			desc: TextureDesc::new(),
+			sampler: None,
 		}
 	}
 
+	pub fn id(&self) -> GLuint {
+		self.id
+	}
+
+	pub fn path(&self) -> Option<&str> {
+		self.path.as_ref().map(|path| &path[..])
+	}
+
+	pub fn desc(&self) -> &TextureDesc {
+		&self.desc
+	}
+
+	// TEXTURE_2D, TEXTURE_CUBE_MAP and so on, depends on what was loaded
+	pub fn target(&self) -> GLenum {
+		self.target
+	}
+
+	// Binds the texture and its sampler to the active texture unit
 	pub fn begin(&self) {
 		unsafe {
-			gl::BindTexture(gl::TEXTURE_2D, self.id);
+			gl::BindTexture(self.target, self.id);
+			if let Some(ref sampler) = self.sampler {
+				sampler.bind(active_unit());
+			}
 		}
 	}
 
 	pub fn end(&self) {
 		unsafe {
-			gl::BindTexture(gl::TEXTURE_2D, 0);
+			gl::BindTexture(self.target, 0);
+			if self.sampler.is_some() {
+				Sampler::unbind(active_unit());
+			}
 		}
 	}
 
-	pub fn load(&mut self, file_path: &str) {
-		if file_path.to_lowercase().ends_with(".bmp") {
-			self.load_bmp(file_path);
-		} else if file_path.to_lowercase().ends_with(".dds") {
-			self.load_dds(file_path);
-		} else {
-			println!("Not a correct image format!");
+	// Binds the texture and its sampler to a texture unit (0 for GL_TEXTURE0 and so on),
+	// which becomes the active unit
+	pub fn bind(&self, unit: u32) {
+		unsafe {
+			gl::ActiveTexture(gl::TEXTURE0 + unit);
+			gl::BindTexture(self.target, self.id);
+		}
+		if let Some(ref sampler) = self.sampler {
+			sampler.bind(unit);
 		}
 	}
 
-	// Loading bmp manually for educational purposes use dds
-	fn load_bmp(&mut self, file_path: &str) -> io::Result<()> {
-		let mut file = try!(File::open(file_path));
-		let mut header: [u8; 54] = [0; 54];
+	pub fn unbind(&self, unit: u32) {
+		unsafe {
+			gl::ActiveTexture(gl::TEXTURE0 + unit);
+			gl::BindTexture(self.target, 0);
+		}
+		Sampler::unbind(unit);
+	}
 
-		// Header
-		try!(file.read(&mut header));
+	// With a fallback in the description a failed load still returns the checkerboard,
+	// together with the error so the caller can report it
+	pub fn from_file(file_path: &str, desc: &TextureDesc, samplers: &SamplerCache) -> Result<(Texture, Option<TextureError>), TextureError> {
+		let mut texture = Texture::new();
+		match texture.load(file_path, desc, samplers) {
+			Ok(()) => Ok((texture, None)),
+			Err(e) => if desc.fallback {
+				Ok((texture, Some(e)))
+			} else {
+				Err(e)
+			},
+		}
+	}
 
-		if (header[0] != 66) || (header[1] != 77) {
-			return Err(Error::new(ErrorKind::Other, "Not a bmp file!"));
- 		}
+	// Loads a png, tga, bmp or dds file. When that fails and the description asks for a
+	// fallback, the texture becomes a magenta checkerboard and still remembers the path,
+	// so fixing the file and reloading brings in the real texture.
+	pub fn load(&mut self, file_path: &str, desc: &TextureDesc, samplers: &SamplerCache) -> Result<(), TextureError> {
+		self.path = Some(file_path.to_string());
+		self.configure(desc, samplers);
+		let result = self.load_checked(file_path);
+		if result.is_err() && desc.fallback {
+			self.upload_image(&checkerboard());
+		}
+		result
+	}
 
-		let mut image_size: usize = 0;
-		let mut width = 0;
-		let mut height = 0;
+	fn configure(&mut self, desc: &TextureDesc, samplers: &SamplerCache) {
+		self.desc = *desc;
+		self.sampler = Some(samplers.get(&desc.sampler));
+	}
 
+	// Loads the file and checks that GL took the upload
+	fn load_checked(&mut self, file_path: &str) -> Result<(), TextureError> {
 		unsafe {
-			let raw_width = [header[0x12], header[0x13], header[0x14], header[0x15]];
-			width = std::mem::transmute::<[u8; 4], i32>(raw_width);
-			let raw_height = [header[0x16], header[0x17], header[0x18], header[0x19]];
-			height = std::mem::transmute::<[u8; 4], i32>(raw_height);
-			let raw_image_size = [header[0x22], header[0x23], header[0x24], header[0x25]];
-			image_size = std::mem::transmute::<[u8; 4], u32>(raw_image_size) as usize;
+			// Errors from earlier calls aren't ours, a lost context keeps reporting one
+			for _ in 0..16 {
+				if gl::GetError() == gl::NO_ERROR {
+					break;
+				}
+			}
+		}
+
+		if let Err(e) = self.load_file(file_path) {
+			return Err(TextureError::from_io(file_path, e));
+		}
+
+		match unsafe { gl::GetError() } {
+			gl::NO_ERROR => Ok(()),
+			error => Err(TextureError::GlUpload { path: file_path.to_string(), error: error }),
 		}
+	}
 
-		if image_size == 0 {
-			image_size = (width * height * 3) as usize;
+	fn load_file(&mut self, file_path: &str) -> io::Result<()> {
+		let lower = file_path.to_lowercase();
+		if lower.ends_with(".dds") {
+			self.load_dds(file_path)
+		} else if lower.ends_with(".bmp") || lower.ends_with(".png") || lower.ends_with(".tga") {
+			let image = try!(image::load(file_path));
+			self.upload_image(&image);
+			Ok(())
+		} else {
+			Err(Error::new(ErrorKind::InvalidData, "Not a correct image format!"))
 		}
+	}
 
-		// Data
-		let mut data = vec![0; image_size];
-		try!(file.read(&mut data)); // Read from where header ended
+	// Loads the texture again from its path, keeps the old texture when that fails
+	pub fn reload(&mut self) -> Result<(), TextureError> {
+		let file_path = match self.path {
+			Some(ref path) => path.clone(),
+			None => return Err(TextureError::Io {
+				path: String::new(),
+				error: Error::new(ErrorKind::NotFound, "Texture was not loaded from a file!"),
+			}),
+		};
+
+		let mut texture = Texture::new();
+		texture.path = Some(file_path.clone());
+		texture.desc = self.desc;
+		texture.sampler = self.sampler.clone();
+		try!(texture.load_checked(&file_path));
+
+		// The old texture is deleted when texture goes out of scope
+		mem::swap(self, &mut texture);
+		Ok(())
+	}
+
+	fn delete(&mut self) {
+		if self.id != 0 {
+			unsafe {
+				gl::DeleteTextures(1, &self.id);
+			}
+			self.id = 0;
+		}
+	}
+
+	// A texture name can't change its target once bound, start over with a new one
+	fn set_target(&mut self, target: GLenum) {
+		if self.target != target {
+			self.delete();
+			self.target = target;
+		}
+	}
+
+	// Uploads RGBA8 pixels. Rows are flipped so the bottom of the image is at t = 0,
+	// like bmp files and the UVs of the generated meshes expect.
+	pub fn load_image(&mut self, image: &RgbaImage, desc: &TextureDesc, samplers: &SamplerCache) {
+		self.configure(desc, samplers);
+		self.upload_image(image);
+	}
+
+	fn upload_image(&mut self, image: &RgbaImage) {
+		self.set_target(gl::TEXTURE_2D);
+		let row = image.width as usize * 4;
+		let mut data = Vec::with_capacity(image.data.len());
+		for y in (0..image.height as usize).rev() {
+			data.extend_from_slice(&image.data[y * row..(y + 1) * row]);
+		}
 
-		// Give data to OpenGL and create texture
 		unsafe {
-			gl::GenTextures(1, &mut self.id);
+			if self.id == 0 {
+				gl::GenTextures(1, &mut self.id);
+			}
 			gl::BindTexture(gl::TEXTURE_2D, self.id);
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+			let default_format = if self.desc.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+			let internal_format = self.desc.internal_format.unwrap_or(default_format);
+			gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format as i32, image.width as i32, image.height as i32,
+				0, gl::RGBA, gl::UNSIGNED_BYTE, data.as_ptr() as *const _);
+
+			if self.desc.generate_mipmaps {
+				gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 1000);
+				gl::GenerateMipmap(gl::TEXTURE_2D);
+			} else {
+				// Keeps the texture complete with mipmap filters
+				gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+			}
+			gl::BindTexture(gl::TEXTURE_2D, 0);
+		}
+	}
 
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-    		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+	// Uploads the faces of a cubemap in the order +X, -X, +Y, -Y, +Z, -Z. Unlike load_image the
+	// rows aren't flipped, cubemap faces start at the top like the image files.
+	pub fn load_cubemap_images(&mut self, faces: &[RgbaImage], desc: &TextureDesc, samplers: &SamplerCache) -> io::Result<()> {
+		if faces.len() != 6 {
+			return Err(Error::new(ErrorKind::InvalidInput, "A cubemap needs six faces!"));
+		}
+		let size = faces[0].width;
+		if faces.iter().any(|face| face.width != size || face.height != size) {
+			return Err(Error::new(ErrorKind::InvalidData, "Cubemap faces must be square and have the same size!"));
+		}
 
-			gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGB as i32, width, height,
-				0, gl::BGR, gl::UNSIGNED_BYTE, std::mem::transmute(&data[0]));
+		self.configure(desc, samplers);
+		self.set_target(gl::TEXTURE_CUBE_MAP);
+		unsafe {
+			if self.id == 0 {
+				gl::GenTextures(1, &mut self.id);
+			}
+			gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+			let default_format = if self.desc.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+			let internal_format = self.desc.internal_format.unwrap_or(default_format);
+			for (face, image) in faces.iter().enumerate() {
+				gl::TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum, 0, internal_format as i32,
+					size as i32, size as i32, 0, gl::RGBA, gl::UNSIGNED_BYTE, image.data.as_ptr() as *const _);
+			}
 
-			gl::GenerateMipmap(gl::TEXTURE_2D);
+			if self.desc.generate_mipmaps {
+				gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAX_LEVEL, 1000);
+				gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+			} else {
+				gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAX_LEVEL, 0);
+			}
+			gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+		}
+		Ok(())
+	}
 
-			data.clear();
-			gl::BindTexture(gl::TEXTURE_2D, 0);
+	// Uploads RGBA8 voxels as a 3D texture, x fastest and then y and z, e.g. a color grading LUT
+	pub fn load_volume(&mut self, size: (u32, u32, u32), data: &[u8], desc: &TextureDesc, samplers: &SamplerCache) -> io::Result<()> {
+		let (width, height, depth) = size;
+		if width == 0 || height == 0 || depth == 0 || data.len() != (width * height * depth) as usize * 4 {
+			return Err(Error::new(ErrorKind::InvalidInput, "Volume data doesn't match its size!"));
 		}
 
+		self.configure(desc, samplers);
+		self.set_target(gl::TEXTURE_3D);
+		unsafe {
+			if self.id == 0 {
+				gl::GenTextures(1, &mut self.id);
+			}
+			gl::BindTexture(gl::TEXTURE_3D, self.id);
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+			let default_format = if self.desc.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+			let internal_format = self.desc.internal_format.unwrap_or(default_format);
+			gl::TexImage3D(gl::TEXTURE_3D, 0, internal_format as i32, width as i32, height as i32, depth as i32,
+				0, gl::RGBA, gl::UNSIGNED_BYTE, data.as_ptr() as *const _);
+
+			if self.desc.generate_mipmaps {
+				gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAX_LEVEL, 1000);
+				gl::GenerateMipmap(gl::TEXTURE_3D);
+			} else {
+				gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAX_LEVEL, 0);
+			}
+			gl::BindTexture(gl::TEXTURE_3D, 0);
+		}
 		Ok(())
 	}
 
-	// Compress png images with AMDCompress(CLI) to dds
-	// Only use compressed images (DXT1 = BC1), (DXT3 = BC2), (DXT5 = BC3)
-	// For sprites compress to DXT5 for alpha (gradient) channel
-	fn load_dds(&mut self, file_path: &str) -> io::Result<()> {
-		let mut file = try!(File::open(file_path));
-		let mut header: [u8; 128] = [0; 128];
-
-		// Header
-		try!(file.read(&mut header));
-		if (header[0] != 0x44) || (header[1] != 0x44) ||
-			(header[2] != 0x53) || (header[3] != 0x20) {
-			return Err(Error::new(ErrorKind::Other, "Not a dds file!"));
- 		}
-
-		let raw_height = [header[12], header[13], header[14], header[15]];
-		let mut height = unsafe { std::mem::transmute::<[u8; 4], i32>(raw_height) };
-
-		let raw_width = [header[16], header[17], header[18], header[19]];
-		let mut width = unsafe { std::mem::transmute::<[u8; 4], i32>(raw_width) };
-
-		let raw_linear_size = [header[20], header[21], header[22], header[23]];
-		let linear_size = unsafe { std::mem::transmute::<[u8; 4], u32>(raw_linear_size) };
-
-		let raw_mipmap_count = [header[28], header[29], header[30], header[31]];
-		let mipmap_count = unsafe { std::mem::transmute::<[u8; 4], u32>(raw_mipmap_count) };
-
-		let raw_four_cc = [header[84], header[85], header[86], header[87]];
-		let four_cc = unsafe { std::mem::transmute::<[u8; 4], u32>(raw_four_cc) };
-
-		// Data
-		let image_size = if mipmap_count > 1 { linear_size * 2 } else { linear_size } as usize;
-		let mut data = vec![0; image_size];
-		try!(file.read(&mut data)); // Read from where header ended
-
-		const FOURCC_DXT1: u32 = 0x31545844;
-		const FOURCC_DXT3: u32 = 0x33545844;
-		const FOURCC_DXT5: u32 = 0x35545844;
-		const COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
-		const COMPRESSED_RGBA_S3TC_DXT3_EXT: u32= 0x83F2;
-		const COMPRESSED_RGBA_S3TC_DXT5_EXT: u32= 0x83F3;
-
-		let mut format: u32 = 0;
-		let mut block_size = 16;
-
-		match four_cc {
-			FOURCC_DXT1 => { format = COMPRESSED_RGBA_S3TC_DXT1_EXT; block_size = 8; },
-			FOURCC_DXT3 => { format = COMPRESSED_RGBA_S3TC_DXT3_EXT; },
-			FOURCC_DXT5 => { format = COMPRESSED_RGBA_S3TC_DXT5_EXT; },
-			_ => return Err(Error::new(ErrorKind::Other, "No DXTn specified.")),
+	// Allocates an empty 2D texture to render into, e.g. RGBA16F or DEPTH_COMPONENT24. The
+	// storage is immutable, so a new size gets a new texture name. With generate_mipmaps in
+	// the description there is room for a full chain, call generate_mipmaps after rendering.
+	pub fn allocate(&mut self, width: u32, height: u32, internal_format: GLenum, desc: &TextureDesc, samplers: &SamplerCache) {
+		self.configure(desc, samplers);
+		self.delete();
+		self.target = gl::TEXTURE_2D;
+		self.path = None;
+
+		let levels = if desc.generate_mipmaps {
+			32 - width.max(height).max(1).leading_zeros()
+		} else {
+			1
 		};
-
-		// Give data to OpenGL and create texture
+		let (width, height) = (width.max(1), height.max(1));
 		unsafe {
 			gl::GenTextures(1, &mut self.id);
 			gl::BindTexture(gl::TEXTURE_2D, self.id);
-			gl::PixelStorei(gl::UNPACK_ALIGNMENT,1);
+			if gl::TexStorage2D::is_loaded() {
+				gl::TexStorage2D(gl::TEXTURE_2D, levels as i32, internal_format, width as i32, height as i32);
+			} else {
+				// No GL 4.2 or ARB_texture_storage, every level gets allocated on its own
+				let (format, ty) = upload_format(internal_format);
+				for level in 0..levels {
+					gl::TexImage2D(gl::TEXTURE_2D, level as i32, internal_format as i32, (width >> level).max(1) as i32,
+						(height >> level).max(1) as i32, 0, format, ty, ptr::null());
+				}
+				gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, levels as i32 - 1);
+			}
+			gl::BindTexture(gl::TEXTURE_2D, 0);
+		}
+	}
 
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);	// REPEAT
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);	// REPEAT
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+	pub fn generate_mipmaps(&self) {
+		unsafe {
+			gl::BindTexture(self.target, self.id);
+			gl::GenerateMipmap(self.target);
+			gl::BindTexture(self.target, 0);
 		}
+	}
+
+	// Compress png images with nitrust-texconv (or AMDCompress) to dds
+	// For sprites compress to BC3 (DXT5) or BC7 for the alpha (gradient) channel
+	fn load_dds(&mut self, file_path: &str) -> io::Result<()> {
+		let image = try!(DdsImage::load(file_path));
+		self.upload_dds(&image)
+	}
 
-		// Load mipmaps
+	// Uploads every surface as is, rows aren't flipped like in load_image. nitrust-texconv
+	// flips by default, so its files come out the same way up as the images they came from.
+	// Cubemaps, arrays and volumes get their own texture targets.
+	// Block compressed formats the driver doesn't support are decoded to RGBA8 first.
+	// Formats with an sRGB variant use it when the description asks for sRGB.
+	pub fn load_dds_image(&mut self, image: &DdsImage, desc: &TextureDesc, samplers: &SamplerCache) -> io::Result<()> {
+		self.configure(desc, samplers);
+		self.upload_dds(image)
+	}
 
-		let mut level = 0;
-		let mut offset = 0;
+	fn upload_dds(&mut self, image: &DdsImage) -> io::Result<()> {
+		let dds_format = if self.desc.srgb { srgb_format(image.format) } else { image.format };
+		let software = !compression_supported(dds_format);
+		if software && !bcn::can_decode(image.format) {
+			return Err(Error::new(ErrorKind::InvalidData, "The driver doesn't support this compressed format!"));
+		}
 
-		while level < mipmap_count && width > 0 && height > 0 {
-			let size = ((width+3)/4)*((height+3)/4)*block_size;
-			unsafe {
-				gl::CompressedTexImage2D(gl::TEXTURE_2D, level as i32, format, width, height,
-					0, size, std::mem::transmute(&data[offset as usize]));
+		let target = if image.cubemap && image.array_size > 1 {
+			gl::TEXTURE_CUBE_MAP_ARRAY
+		} else if image.cubemap {
+			gl::TEXTURE_CUBE_MAP
+		} else if image.depth > 1 {
+			gl::TEXTURE_3D
+		} else if image.array_size > 1 {
+			gl::TEXTURE_2D_ARRAY
+		} else {
+			gl::TEXTURE_2D
+		};
+		self.set_target(target);
+		let format = if software {
+			GlFormat {
+				internal: if dds_format.is_srgb() { gl::SRGB8_ALPHA8 } else { gl::RGBA8 },
+				format: gl::RGBA,
+				type_: gl::UNSIGNED_BYTE,
 			}
+		} else {
+			dds_gl_format(dds_format)
+		};
 
-			offset += size;
-			width /= 2;
-			height /= 2;
+		unsafe {
+			if self.id == 0 {
+				gl::GenTextures(1, &mut self.id);
+			}
+			gl::BindTexture(self.target, self.id);
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+		}
 
-			level += 1;
+		for level in 0..image.mipmap_count {
+			let (width, height, depth) = image.level_size(level);
+			match self.target {
+				gl::TEXTURE_2D | gl::TEXTURE_3D => {
+					let data = try!(dds_surface(image, 0, level, software));
+					upload_surface(self.target, level, &format, (width, height, depth), &data);
+				},
+				gl::TEXTURE_CUBE_MAP => for face in 0..6 {
+					let target = gl::TEXTURE_CUBE_MAP_POSITIVE_X + face;
+					let data = try!(dds_surface(image, face, level, software));
+					upload_surface(target, level, &format, (width, height, 1), &data);
+				},
+				_ => {
+					// Array layers (and cubemap faces) are uploaded at once as the slices of one level
+					let mut data = Vec::with_capacity(image.level_bytes(level) * image.layers() as usize);
+					for layer in 0..image.layers() {
+						data.extend_from_slice(&try!(dds_surface(image, layer, level, software)));
+					}
+					upload_surface(self.target, level, &format, (width, height, image.layers()), &data);
+				},
+			}
 		}
 
-		data.clear();
+		// Compressed formats can't be rendered to, so the driver can't generate their mipmaps
+		let generate = self.desc.generate_mipmaps && image.mipmap_count == 1 && format.type_ != 0;
 		unsafe {
-			gl::BindTexture(gl::TEXTURE_2D, 0);
+			if generate {
+				gl::TexParameteri(self.target, gl::TEXTURE_MAX_LEVEL, 1000);
+				gl::GenerateMipmap(self.target);
+			} else {
+				// Files don't always contain the whole chain
+				gl::TexParameteri(self.target, gl::TEXTURE_MAX_LEVEL, image.mipmap_count as i32 - 1);
+			}
+			gl::BindTexture(self.target, 0);
 		}
-
 		Ok(())
 	}
-}
\ No newline at end of file
+}
+
+// Magenta and black squares, hard to miss in a scene
+fn checkerboard() -> RgbaImage {
+	let mut image = RgbaImage::new(64, 64);
+	for y in 0..64 {
+		for x in 0..64 {
+			let color = if (x / 8 + y / 8) % 2 == 0 { [255, 0, 255, 255] } else { [0, 0, 0, 255] };
+			image.set_pixel(x, y, color);
+		}
+	}
+	image
+}
+
+// Unit selected with glActiveTexture, counted from 0
+fn active_unit() -> u32 {
+	let mut unit = 0;
+	unsafe {
+		gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut unit);
+	}
+	unit as u32 - gl::TEXTURE0
+}
+
+fn srgb_format(format: DdsFormat) -> DdsFormat {
+	match format {
+		DdsFormat::Bc1 => DdsFormat::Bc1Srgb,
+		DdsFormat::Bc2 => DdsFormat::Bc2Srgb,
+		DdsFormat::Bc3 => DdsFormat::Bc3Srgb,
+		DdsFormat::Bc7 => DdsFormat::Bc7Srgb,
+		DdsFormat::Rgba8 => DdsFormat::Rgba8Srgb,
+		DdsFormat::Rgbx8 => DdsFormat::Rgbx8Srgb,
+		DdsFormat::Bgra8 => DdsFormat::Bgra8Srgb,
+		DdsFormat::Bgrx8 => DdsFormat::Bgrx8Srgb,
+		format => format,
+	}
+}
+
+// RGTC is core since GL 3.0, the others depend on the driver
+fn compression_supported(format: DdsFormat) -> bool {
+	let s3tc = || capabilities::has_extension("GL_EXT_texture_compression_s3tc");
+	let bptc = || capabilities::gl_version_at_least(4, 2) || capabilities::has_extension("GL_ARB_texture_compression_bptc");
+	match format {
+		DdsFormat::Bc1 | DdsFormat::Bc2 | DdsFormat::Bc3 => s3tc(),
+		DdsFormat::Bc1Srgb | DdsFormat::Bc2Srgb | DdsFormat::Bc3Srgb => s3tc() &&
+			(capabilities::has_extension("GL_EXT_texture_sRGB") || capabilities::has_extension("GL_EXT_texture_compression_s3tc_srgb")),
+		DdsFormat::Bc6hUfloat | DdsFormat::Bc6hSfloat | DdsFormat::Bc7 | DdsFormat::Bc7Srgb => bptc(),
+		_ => true,
+	}
+}
+
+// A surface as it is uploaded, decoded to RGBA8 slice by slice when software is set
+fn dds_surface(image: &DdsImage, layer: u32, level: u32, software: bool) -> io::Result<Cow<'_, [u8]>> {
+	let data = image.surface(layer, level);
+	if !software {
+		return Ok(Cow::Borrowed(data));
+	}
+
+	let (width, height, _) = image.level_size(level);
+	let mut pixels = Vec::new();
+	for slice in data.chunks(image.format.surface_size(width, height)) {
+		let decoded = try!(bcn::decode(image.format, width, height, slice));
+		pixels.extend_from_slice(&decoded.data);
+	}
+	Ok(Cow::Owned(pixels))
+}
+
+// Internal format, or the compressed format when type_ is 0
+struct GlFormat {
+	internal: GLenum,
+	format: GLenum,
+	type_: GLenum,
+}
+
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3_EXT: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = 0x83F3;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: GLenum = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: GLenum = 0x8C4F;
+
+fn dds_gl_format(format: DdsFormat) -> GlFormat {
+	let (internal, format, type_) = match format {
+		DdsFormat::Bc1 => (COMPRESSED_RGBA_S3TC_DXT1_EXT, 0, 0),
+		DdsFormat::Bc1Srgb => (COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT, 0, 0),
+		DdsFormat::Bc2 => (COMPRESSED_RGBA_S3TC_DXT3_EXT, 0, 0),
+		DdsFormat::Bc2Srgb => (COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT, 0, 0),
+		DdsFormat::Bc3 => (COMPRESSED_RGBA_S3TC_DXT5_EXT, 0, 0),
+		DdsFormat::Bc3Srgb => (COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT, 0, 0),
+		DdsFormat::Bc4 => (gl::COMPRESSED_RED_RGTC1, 0, 0),
+		DdsFormat::Bc4Snorm => (gl::COMPRESSED_SIGNED_RED_RGTC1, 0, 0),
+		DdsFormat::Bc5 => (gl::COMPRESSED_RG_RGTC2, 0, 0),
+		DdsFormat::Bc5Snorm => (gl::COMPRESSED_SIGNED_RG_RGTC2, 0, 0),
+		DdsFormat::Bc6hUfloat => (gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT, 0, 0),
+		DdsFormat::Bc6hSfloat => (gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, 0, 0),
+		DdsFormat::Bc7 => (gl::COMPRESSED_RGBA_BPTC_UNORM, 0, 0),
+		DdsFormat::Bc7Srgb => (gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM, 0, 0),
+		DdsFormat::Rgba8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+		DdsFormat::Rgba8Srgb => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE),
+		DdsFormat::Rgbx8 => (gl::RGB8, gl::RGBA, gl::UNSIGNED_BYTE),
+		DdsFormat::Rgbx8Srgb => (gl::SRGB8, gl::RGBA, gl::UNSIGNED_BYTE),
+		DdsFormat::Bgra8 => (gl::RGBA8, gl::BGRA, gl::UNSIGNED_BYTE),
+		DdsFormat::Bgra8Srgb => (gl::SRGB8_ALPHA8, gl::BGRA, gl::UNSIGNED_BYTE),
+		DdsFormat::Bgrx8 => (gl::RGB8, gl::BGRA, gl::UNSIGNED_BYTE),
+		DdsFormat::Bgrx8Srgb => (gl::SRGB8, gl::BGRA, gl::UNSIGNED_BYTE),
+		DdsFormat::Bgr8 => (gl::RGB8, gl::BGR, gl::UNSIGNED_BYTE),
+		DdsFormat::B5g6r5 => (gl::RGB565, gl::RGB, gl::UNSIGNED_SHORT_5_6_5),
+		DdsFormat::B5g5r5a1 => (gl::RGB5_A1, gl::BGRA, gl::UNSIGNED_SHORT_1_5_5_5_REV),
+		DdsFormat::B4g4r4a4 => (gl::RGBA4, gl::BGRA, gl::UNSIGNED_SHORT_4_4_4_4_REV),
+		DdsFormat::Rgb10a2 => (gl::RGB10_A2, gl::RGBA, gl::UNSIGNED_INT_2_10_10_10_REV),
+		DdsFormat::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+		DdsFormat::Rg8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+		DdsFormat::Rgba16 => (gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT),
+		DdsFormat::R16f => (gl::R16F, gl::RED, gl::HALF_FLOAT),
+		DdsFormat::Rg16f => (gl::RG16F, gl::RG, gl::HALF_FLOAT),
+		DdsFormat::Rgba16f => (gl::RGBA16F, gl::RGBA, gl::HALF_FLOAT),
+		DdsFormat::R11g11b10f => (gl::R11F_G11F_B10F, gl::RGB, gl::UNSIGNED_INT_10F_11F_11F_REV),
+		DdsFormat::R32f => (gl::R32F, gl::RED, gl::FLOAT),
+		DdsFormat::Rg32f => (gl::RG32F, gl::RG, gl::FLOAT),
+		DdsFormat::Rgb32f => (gl::RGB32F, gl::RGB, gl::FLOAT),
+		DdsFormat::Rgba32f => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+	};
+
+	GlFormat {
+		internal: internal,
+		format: format,
+		type_: type_,
+	}
+}
+
+// Format and type for TexImage2D without data. Depth and integer formats need their own,
+// the others accept anything valid.
+fn upload_format(internal_format: GLenum) -> (GLenum, GLenum) {
+	match internal_format {
+		gl::DEPTH_COMPONENT16 | gl::DEPTH_COMPONENT24 | gl::DEPTH_COMPONENT32 => (gl::DEPTH_COMPONENT, gl::UNSIGNED_INT),
+		gl::DEPTH_COMPONENT32F => (gl::DEPTH_COMPONENT, gl::FLOAT),
+		gl::DEPTH24_STENCIL8 => (gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+		gl::DEPTH32F_STENCIL8 => (gl::DEPTH_STENCIL, gl::FLOAT_32_UNSIGNED_INT_24_8_REV),
+		gl::R8I | gl::R16I | gl::R32I => (gl::RED_INTEGER, gl::INT),
+		gl::R8UI | gl::R16UI | gl::R32UI => (gl::RED_INTEGER, gl::UNSIGNED_INT),
+		gl::RG8I | gl::RG16I | gl::RG32I => (gl::RG_INTEGER, gl::INT),
+		gl::RG8UI | gl::RG16UI | gl::RG32UI => (gl::RG_INTEGER, gl::UNSIGNED_INT),
+		gl::RGB8I | gl::RGB16I | gl::RGB32I => (gl::RGB_INTEGER, gl::INT),
+		gl::RGB8UI | gl::RGB16UI | gl::RGB32UI => (gl::RGB_INTEGER, gl::UNSIGNED_INT),
+		gl::RGBA8I | gl::RGBA16I | gl::RGBA32I => (gl::RGBA_INTEGER, gl::INT),
+		gl::RGBA8UI | gl::RGBA16UI | gl::RGBA32UI | gl::RGB10_A2UI => (gl::RGBA_INTEGER, gl::UNSIGNED_INT),
+		_ => (gl::RGBA, gl::UNSIGNED_BYTE),
+	}
+}
+
+// Uploads one mipmap level. Depth is the slice or layer count for 3D targets.
+fn upload_surface(target: GLenum, level: u32, format: &GlFormat, size: (u32, u32, u32), data: &[u8]) {
+	let (width, height, depth) = (size.0 as i32, size.1 as i32, size.2 as i32);
+	let three_d = target == gl::TEXTURE_3D || target == gl::TEXTURE_2D_ARRAY || target == gl::TEXTURE_CUBE_MAP_ARRAY;
+	let pixels = data.as_ptr() as *const _;
+
+	unsafe {
+		match (format.type_ == 0, three_d) {
+			(true, false) => gl::CompressedTexImage2D(target, level as i32, format.internal,
+				width, height, 0, data.len() as i32, pixels),
+			(true, true) => gl::CompressedTexImage3D(target, level as i32, format.internal,
+				width, height, depth, 0, data.len() as i32, pixels),
+			(false, false) => gl::TexImage2D(target, level as i32, format.internal as i32,
+				width, height, 0, format.format, format.type_, pixels),
+			(false, true) => gl::TexImage3D(target, level as i32, format.internal as i32,
+				width, height, depth, 0, format.format, format.type_, pixels),
+		}
+	}
+}
+
+impl Drop for Texture {
+	fn drop(&mut self) {
+		self.delete();
+	}
+}
+
+impl HotReload for Texture {
+	type Error = TextureError;
+
+	fn source_files(&self) -> Vec<String> {
+		self.path.iter().cloned().collect()
+	}
+
+	fn reload(&mut self) -> Result<(), TextureError> {
+		Texture::reload(self)
+	}
+}
@@ -0,0 +1,198 @@
+extern crate gl;
+extern crate std;
+
+use gl::types::*;
+
+use std::ptr;
+
+use framework::math::{Mat3x3, Mat4x4, Vec2, Vec3, Vec4};
+
+// Uniform buffer object shared between programs, e.g. camera and light data.
+// Programs declare a std140 block and bind it to the same binding point:
+// layout (std140) uniform Camera { mat4 view_projection; vec3 eye; };
+//
+// shader.bind_uniform_block("Camera", 0);
+// camera_buffer.bind(0);
+pub struct UniformBuffer {
+	id: GLuint,
+	size: usize,
+}
+
+impl UniformBuffer {
+	pub fn new(size: usize) -> UniformBuffer {
+		let mut id = 0;
+		unsafe {
+			gl::GenBuffers(1, &mut id);
+			gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+			gl::BufferData(gl::UNIFORM_BUFFER, size as GLsizeiptr, ptr::null(), gl::DYNAMIC_DRAW);
+			gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+		}
+
+		UniformBuffer {
+			id: id,
+			size: size,
+		}
+	}
+
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	// Data that doesn't fit in the buffer is cut off
+	pub fn update(&self, data: &Std140Writer) {
+		let length = data.len().min(self.size);
+		unsafe {
+			gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+			gl::BufferSubData(gl::UNIFORM_BUFFER, 0, length as GLsizeiptr,
+				data.as_bytes().as_ptr() as *const _);
+			gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+		}
+	}
+
+	pub fn bind(&self, binding: u32) {
+		unsafe {
+			gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.id);
+		}
+	}
+}
+
+impl Drop for UniformBuffer {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteBuffers(1, &self.id);
+		}
+	}
+}
+
+// Lays out values following the std140 rules:
+// scalars align to 4 bytes, vec2 to 8, vec3 and vec4 to 16,
+// array elements and matrix columns are padded to 16 bytes.
+// Members of a struct are written between begin_struct and end_struct.
+pub struct Std140Writer {
+	data: Vec<u8>,
+}
+
+impl Std140Writer {
+	pub fn new() -> Std140Writer {
+		Std140Writer {
+			data: Vec::new(),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.data.len()
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.data
+	}
+
+	pub fn clear(&mut self) {
+		self.data.clear();
+	}
+
+	fn align(&mut self, alignment: usize) {
+		while self.data.len() % alignment != 0 {
+			self.data.push(0);
+		}
+	}
+
+	fn write_f32(&mut self, value: f32) {
+		let bits = value.to_bits();
+		self.write_u32(bits);
+	}
+
+	fn write_u32(&mut self, value: u32) {
+		self.data.extend_from_slice(&[
+			value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8
+		]);
+	}
+
+	pub fn begin_struct(&mut self) {
+		self.align(16);
+	}
+
+	pub fn end_struct(&mut self) {
+		self.align(16);
+	}
+
+	pub fn push_f32(&mut self, value: f32) {
+		self.align(4);
+		self.write_f32(value);
+	}
+
+	pub fn push_i32(&mut self, value: i32) {
+		self.align(4);
+		self.write_u32(value as u32);
+	}
+
+	pub fn push_u32(&mut self, value: u32) {
+		self.align(4);
+		self.write_u32(value);
+	}
+
+	pub fn push_bool(&mut self, value: bool) {
+		self.push_u32(if value { 1 } else { 0 });
+	}
+
+	pub fn push_vec2(&mut self, value: &Vec2) {
+		self.align(8);
+		self.write_f32(value.x);
+		self.write_f32(value.y);
+	}
+
+	pub fn push_vec3(&mut self, value: &Vec3) {
+		self.align(16);
+		self.write_f32(value.x);
+		self.write_f32(value.y);
+		self.write_f32(value.z);
+	}
+
+	pub fn push_vec4(&mut self, value: &Vec4) {
+		self.align(16);
+		self.write_f32(value.x);
+		self.write_f32(value.y);
+		self.write_f32(value.z);
+		self.write_f32(value.w);
+	}
+
+	// Matrices are stored column by column, our matrices are row-major
+	pub fn push_mat3x3(&mut self, value: &Mat3x3) {
+		for column in 0..3 {
+			self.align(16);
+			for row in 0..3 {
+				self.write_f32(value.m[row * 3 + column]);
+			}
+		}
+		self.align(16);
+	}
+
+	pub fn push_mat4x4(&mut self, value: &Mat4x4) {
+		self.align(16);
+		for column in 0..4 {
+			for row in 0..4 {
+				self.write_f32(value.m[row * 4 + column]);
+			}
+		}
+	}
+
+	pub fn push_f32_array(&mut self, values: &[f32]) {
+		for value in values {
+			self.align(16);
+			self.write_f32(*value);
+		}
+		self.align(16);
+	}
+
+	pub fn push_vec4_array(&mut self, values: &[Vec4]) {
+		for value in values {
+			self.push_vec4(value);
+		}
+	}
+
+	pub fn push_mat4x4_array(&mut self, values: &[Mat4x4]) {
+		for value in values {
+			self.push_mat4x4(value);
+		}
+	}
+}
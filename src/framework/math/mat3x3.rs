@@ -0,0 +1,73 @@
+use framework::math::Mat4x4;
+
+// Row-major like Mat4x4, upload with transpose set to true
+#[derive(Clone, Copy, Debug)]
+pub struct Mat3x3 {
+	pub m: [f32; 9],
+}
+
+impl Mat3x3 {
+	pub fn new() -> Mat3x3 {
+		Mat3x3::identity()
+	}
+
+	pub fn identity() -> Mat3x3 {
+		Mat3x3 { m: [
+			1.0, 0.0, 0.0,
+			0.0, 1.0, 0.0,
+			0.0, 0.0, 1.0,
+		]}
+	}
+
+	// Upper left 3x3 part, i.e. the rotation and scale of a transform
+	pub fn from_mat4x4(m: &Mat4x4) -> Mat3x3 {
+		Mat3x3 { m: [
+			m.m[0], m.m[1], m.m[2],
+			m.m[4], m.m[5], m.m[6],
+			m.m[8], m.m[9], m.m[10],
+		]}
+	}
+
+	pub fn transpose(m: &Mat3x3) -> Mat3x3 {
+		Mat3x3 { m: [
+			m.m[0], m.m[3], m.m[6],
+			m.m[1], m.m[4], m.m[7],
+			m.m[2], m.m[5], m.m[8],
+		]}
+	}
+
+	pub fn determinant(&self) -> f32 {
+		let m = &self.m;
+		m[0] * (m[4] * m[8] - m[5] * m[7]) -
+		m[1] * (m[3] * m[8] - m[5] * m[6]) +
+		m[2] * (m[3] * m[7] - m[4] * m[6])
+	}
+
+	// Returns the identity for singular matrices
+	pub fn inverse(&self) -> Mat3x3 {
+		let det = self.determinant();
+		if det.abs() < 1.0e-8 {
+			return Mat3x3::identity();
+		}
+
+		let inv_det = 1.0 / det;
+		let m = &self.m;
+		Mat3x3 { m: [
+			(m[4] * m[8] - m[5] * m[7]) * inv_det,
+			(m[2] * m[7] - m[1] * m[8]) * inv_det,
+			(m[1] * m[5] - m[2] * m[4]) * inv_det,
+			(m[5] * m[6] - m[3] * m[8]) * inv_det,
+			(m[0] * m[8] - m[2] * m[6]) * inv_det,
+			(m[2] * m[3] - m[0] * m[5]) * inv_det,
+			(m[3] * m[7] - m[4] * m[6]) * inv_det,
+			(m[1] * m[6] - m[0] * m[7]) * inv_det,
+			(m[0] * m[4] - m[1] * m[3]) * inv_det,
+		]}
+	}
+
+	// Inverse transpose of the model matrix, keeps normals perpendicular
+	// to surfaces under non-uniform scaling.
+	pub fn normal_matrix(model: &Mat4x4) -> Mat3x3 {
+		Mat3x3::transpose(&Mat3x3::from_mat4x4(model).inverse())
+	}
+}
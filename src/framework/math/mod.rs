@@ -1,9 +1,15 @@
+mod mat3x3;
+pub use self::mat3x3::Mat3x3;
+
 mod mat4x4;
 pub use self::mat4x4::Mat4x4;
 
 mod quaternion;
 pub use self::quaternion::Quaternion;
 
+mod vec2;
+pub use self::vec2::Vec2;
+
 mod vec3;
 pub use self::vec3::Vec3;
 
@@ -0,0 +1,104 @@
+use std::f32;
+use std::ops::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vec2 {
+	pub x: f32,
+	pub y: f32,
+}
+
+impl Vec2 {
+	pub fn new() -> Vec2 {
+		Vec2 { x: 0.0, y: 0.0 }
+	}
+
+	pub fn set(&mut self, x: f32, y: f32) {
+		self.x = x;
+		self.y = y;
+	}
+
+	pub fn dot(l: &Vec2, r: &Vec2) -> f32  {
+		l.x * r.x + l.y * r.y
+	}
+
+	pub fn normalized(&self) -> Vec2 {
+		let inv_length = 1.0 / self.length();
+		Vec2 {
+			x: self.x * inv_length,
+			y: self.y * inv_length,
+		}
+	}
+
+	pub fn length(&self) -> f32 {
+		(self.x * self.x + self.y * self.y).sqrt()
+	}
+
+	pub fn length_squared(&self) -> f32 {
+		self.x * self.x + self.y * self.y
+	}
+
+	pub fn lerp(v1: &Vec2, v2: &Vec2, amount: f32) -> Vec2 {
+		let diff = 1.0 - amount;
+		Vec2 {
+			x: diff * v1.x + amount * v2.x,
+			y: diff * v1.y + amount * v2.y,
+		}
+	}
+}
+
+impl Add for Vec2 {
+	type Output = Vec2;
+
+	fn add(self, r: Vec2) -> Vec2 {
+		Vec2 { x: self.x + r.x, y: self.y + r.y }
+	}
+}
+
+impl<'a> Add for &'a Vec2 {
+	type Output = Vec2;
+
+	fn add(self, r: &Vec2) -> Vec2 {
+		Vec2 { x: self.x + r.x, y: self.y + r.y }
+	}
+}
+
+impl Sub for Vec2 {
+	type Output = Vec2;
+
+	fn sub(self, r: Vec2) -> Vec2 {
+		Vec2 { x: self.x - r.x, y: self.y - r.y }
+	}
+}
+
+impl<'a> Sub for &'a Vec2 {
+	type Output = Vec2;
+
+	fn sub(self, r: &Vec2) -> Vec2 {
+		Vec2 { x: self.x - r.x, y: self.y - r.y }
+	}
+}
+
+impl Mul<f32> for Vec2 {
+	type Output = Vec2;
+
+	fn mul(self, r: f32) -> Vec2 {
+		Vec2 { x: self.x * r, y: self.y * r }
+	}
+}
+
+impl<'a> Div<f32> for &'a Vec2 {
+	type Output = Vec2;
+
+	fn div(self, r: f32) -> Vec2 {
+		let inv = if r != 0.0 { 1.0 / r } else { f32::MAX };
+		Vec2 { x: self.x * inv, y: self.y * inv }
+	}
+}
+
+impl Neg for Vec2 {
+	type Output = Vec2;
+
+	fn neg(self) -> Vec2 {
+		Vec2 { x: -self.x, y: -self.y }
+	}
+}
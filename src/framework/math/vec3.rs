@@ -2,6 +2,7 @@ use std::f32;
 use std::f32::consts::PI;
 use std::ops::*;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vec3 {
 	pub x: f32,
 	pub y: f32,
@@ -1,3 +1,4 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vec4 {
 	pub x: f32,
 	pub y: f32,
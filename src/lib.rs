@@ -0,0 +1,7 @@
+// The engine as a library, shared by the demo and the asset tools in src/bin
+
+extern crate gl;
+extern crate libc;
+extern crate time;
+
+pub mod framework;
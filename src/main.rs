@@ -9,6 +9,7 @@ extern crate gl;
 extern crate libc;
 extern crate sdl2;
 extern crate time;
+extern crate nitrust_oxide;
 
 use libc::c_void;
 use gl::types::*;
@@ -25,58 +26,13 @@ use std::ptr;
 use std::str;
 use std::ffi::CString;
 
-mod framework;
-use framework::math::{Mat4x4, Quaternion, Vec3, Vec4};
-use framework::graphics::{Texture, InternalShader, Shader, Uniform};
-use framework::core::{Camera, Clock, Transform};
+use nitrust_oxide::framework::math::{Mat4x4, Quaternion, Vec3, Vec4};
+use nitrust_oxide::framework::graphics::{Texture, TextureDesc, Mesh, SamplerCache, SamplerDesc, Shader, Wrap};
+use nitrust_oxide::framework::graphics::{Light, LightSet, LitShader, Material};
+use nitrust_oxide::framework::graphics::{Antialiasing, PostProcessStack};
+use nitrust_oxide::framework::core::{Camera, Clock, FileWatcher, HotReload, Transform};
 
 
-// Shaders
-// ________________________________________________________________________________________________
-pub struct BasicShader<'a> {
-	shader: InternalShader,
-	uniform_transform: Uniform<'a>,
-}
-
-impl<'a> BasicShader<'a> {
-	pub fn new() -> BasicShader<'a> {
-		let shader = InternalShader::new();
-
-		BasicShader {
-			shader: shader,
-			uniform_transform: Uniform::new("transform"),
-		}
-	}
-
-	pub fn load_vertex_shader(&self, file_path: &str) {
-		self.shader.vertex_shader(file_path);
-	}
-
-	pub fn load_fragment_shader(&self, file_path: &str) {
-		self.shader.fragment_shader(file_path);
-	}
-}
-
-impl<'a> Shader for BasicShader<'a> {
-	fn init(&mut self) {
-		self.shader.compile();
-		self.shader.add_uniform(&mut self.uniform_transform);
-	}
-
-	fn begin(&self) {
-		self.shader.begin();
-	}
-
-	fn end(&self) {
-		self.shader.end();
-	}
-
-	fn update_uniforms(&self, transform: &Transform, camera: &Camera, dt: f32) {
-		// Unique implementation
-		self.shader.set_mat4x4(&(self.uniform_transform), &transform.mvp(&camera)); // TODO:  &transform.mvp(&camera)
-	}
-}
-
 // Try to write everything in a modular way
 
 pub mod engine {
@@ -254,20 +210,6 @@ pub mod engine {
 
 // TODO: Make SimpleSprite struct
 
-// Quad
-static VERTICES: [GLfloat; 32] = [
-	// Positions		Colors			Texture Coordinates
-	0.5, 0.5, 0.0,		1.0, 1.0, 1.0,	1.0, 1.0,			// Top Right
-	-0.5, 0.5, 0.0,		1.0, 1.0, 1.0,	0.0, 1.0,			// Top Left
-	-0.5, -0.5, 0.0,	1.0, 1.0, 1.0,	0.0, 0.0,			// Bottom Left
-	0.5, -0.5, 0.0,		1.0, 1.0, 1.0,	1.0, 0.0			// Bottom Right
-];
-
-static INDICES: [GLuint; 6] = [
-	0, 1, 2,   // First Triangle
-	2, 3, 0	   // Second Triangle
-];
-
 use engine::console::ConsoleSystem;
 use engine::core::CoreSystem;
 use engine::graphics::GraphicsSystem;
@@ -297,7 +239,6 @@ fn main() {
 	gl_attr.set_blue_size(8);
 	gl_attr.set_alpha_size(8);
 	gl_attr.set_stencil_size(8);
-	gl_attr.set_multisample_samples(4); // 4x MSAA
 	gl_attr.set_context_version(3 as u8, 3 as u8); // OpenGL 3.3
 	gl_attr.set_context_profile(GLProfile::Core);
 
@@ -319,53 +260,11 @@ fn main() {
 
 	// Initialize Rendering
 
-
-	let mut vao = 0;
-	let mut vbo = 0;
-	let mut ebo = 0;
+	let mut mesh = Mesh::quad(1.0, 1.0);
+	mesh.set_color(&Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 });
+	mesh.upload().unwrap();
 
 	unsafe {
-		// Create Vertex Array Object
-		gl::GenVertexArrays(1, &mut vao);
-
-		gl::BindVertexArray(vao);
-
-		// Create a Vertex Buffer Object and copy the vertex data to it
-		gl::GenBuffers(1, &mut vbo);
-		gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-		gl::BufferData(gl::ARRAY_BUFFER,
-			(VERTICES.len() * std::mem::size_of::<GLfloat>()) as GLsizeiptr,
-			std::mem::transmute(&VERTICES[0]),
-			gl::STATIC_DRAW);
-
-		// Create a Element Buffer Object and copy the index data to it
-		gl::GenBuffers(1, &mut ebo);
-		gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-		gl::BufferData(gl::ELEMENT_ARRAY_BUFFER,
-			(INDICES.len() * std::mem::size_of::<GLuint>()) as GLsizeiptr,
-			std::mem::transmute(&INDICES[0]),
-			gl::STATIC_DRAW);
-
-		// Position attribute
-		gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE as GLboolean,
-			(8 * std::mem::size_of::<GLfloat>()) as i32, 0 as *const _);
-		gl::EnableVertexAttribArray(0);
-
-		// Color attribute
-		gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE as GLboolean,
-			(8 * std::mem::size_of::<GLfloat>()) as i32,
-			(3 * std::mem::size_of::<GLfloat>()) as *const _);
-		gl::EnableVertexAttribArray(1);
-
-		// Texture attribute
-		gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE as GLboolean,
-			(8 * std::mem::size_of::<GLfloat>()) as i32,
-			(6 * std::mem::size_of::<GLfloat>()) as *const _);
-		gl::EnableVertexAttribArray(2);
-
-		gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-		//gl::BindVertexArray(0);
-
 		// Uncomment for wireframe mode
 		//gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
 
@@ -399,13 +298,44 @@ fn main() {
     
     let camera = Camera::new_perspective(&camera_transform, 45.0, 800, 600, 0.1, 100.0);
     
-	let mut texture = Texture::new();
-	texture.load("./assets/textures/board_alpha.dds");
-
-	let mut shader = BasicShader::new();
-	shader.load_vertex_shader("./assets/shaders/basic_shader.vs.glsl");
-	shader.load_fragment_shader("./assets/shaders/basic_shader.fs.glsl");
-	shader.init();
+	let samplers = SamplerCache::new();
+	// Colors are gamma corrected at the end of the post-process stack, so textures are read as sRGB
+	let texture_desc = TextureDesc { srgb: true, sampler: SamplerDesc::with_wrap(Wrap::ClampToEdge), ..TextureDesc::new() };
+	let mut texture = match Texture::from_file("./assets/textures/board_alpha.dds", &texture_desc, &samplers) {
+		Ok((texture, None)) => texture,
+		Ok((texture, Some(e))) => { println!("{}, using a checkerboard", e); texture },
+		Err(e) => { println!("{}", e); return; },
+	};
+
+	let mut shader = match LitShader::new() {
+		Ok(shader) => shader,
+		Err(e) => { println!("{}", e); return; },
+	};
+	let mut material = Material::new("board");
+	material.ambient = Vec3 { x: 1.0, y: 1.0, z: 1.0 };
+	material.specular = Vec3 { x: 0.5, y: 0.5, z: 0.5 };
+	material.shininess = 32.0;
+
+	// A dim sun and a point light that circles the quad, L switches the point light on and off
+	let mut lights = LightSet::new();
+	lights.add(Light::directional(Quaternion::from_axis(&Vec3{ x: 1.0, y: 0.0, z: 0.0 }, 30.0),
+		Vec3{ x: 1.0, y: 0.95, z: 0.9 }, 0.3));
+	let point_light = lights.add(Light::point(Vec3{ x: 0.0, y: 0.0, z: 1.0 }, 5.0,
+		Vec3{ x: 1.0, y: 0.6, z: 0.3 }, 3.0));
+	let mut light_angle: f32 = 0.0;
+
+	// Watch shader and texture files, edits are picked up between frames
+	let mut file_watcher = FileWatcher::new();
+	file_watcher.watch_all(&shader.source_files());
+	file_watcher.watch_all(&texture.source_files());
+
+	// The scene is rendered to an HDR target and goes through bloom, tonemapping and gamma
+	// correction. Anti-aliasing is 4x MSAA on that target or FXAA, F toggles between them.
+	let mut post_process = match PostProcessStack::with_defaults(800, 600, Antialiasing::Msaa(4), &samplers) {
+		Ok(post_process) => post_process,
+		Err(e) => { println!("{}", e); return; },
+	};
+	file_watcher.watch_all(&post_process.source_files());
 
 	// Initialize input
 	let mut event_pump = sdl_context.event_pump().unwrap();
@@ -454,6 +384,24 @@ fn main() {
 					running = false;
 				},
                 
+                // Toggles the point light
+                Event::KeyDown { keycode: Some(Keycode::L), .. } => {
+                    let light = &mut lights.lights[point_light];
+                    light.enabled = !light.enabled;
+                },
+
+                // Switches between FXAA and 4x MSAA
+                Event::KeyDown { keycode: Some(Keycode::F), .. } => {
+                    let antialiasing = match post_process.antialiasing() {
+                        Antialiasing::Fxaa => Antialiasing::Msaa(4),
+                        _ => Antialiasing::Fxaa,
+                    };
+                    match post_process.set_antialiasing(antialiasing, &samplers) {
+                        Ok(()) => println!("Anti-aliasing: {:?}", antialiasing),
+                        Err(e) => println!("{}", e),
+                    }
+                },
+
                 // Messaging test
                 Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
                     let msg = Message::Graphics_Change_Color{r: 255, g: 255, b: 255,};
@@ -473,22 +421,45 @@ fn main() {
 		}
 
 		// Do non fixed stuff
+		light_angle += dt;
+		lights.lights[point_light].transform.position = Vec3{ x: light_angle.cos(), y: light_angle.sin(), z: 1.0 };
+
+		// Hot reload
+		let changed = file_watcher.poll();
+		if !changed.is_empty() {
+			match shader.reload_if_changed(&changed) {
+				Some(Ok(())) => file_watcher.watch_all(&shader.source_files()),
+				Some(Err(e)) => println!("{}", e),
+				None => {},
+			}
+			if let Some(Err(e)) = post_process.reload_if_changed(&changed) {
+				println!("{}", e);
+			}
+			if let Some(Err(e)) = texture.reload_if_changed(&changed) {
+				println!("Could not reload texture: {}", e);
+			}
+		}
 
 		// Rendering
+		post_process.begin();
 		unsafe {
 			gl::ClearColor(0.0, 0.0, 0.0, 1.0);
 			gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
 
-			texture.begin();
 			shader.begin();
-			shader.update_uniforms(&transform, &camera, dt);
-			gl::BindVertexArray(vao);
-			gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, 0 as *const _);
+			let result = shader.update_uniforms(&transform, &camera, dt)
+				.and_then(|_| shader.set_material(&material))
+				.and_then(|_| shader.set_diffuse_texture(Some(&texture)))
+				.and_then(|_| shader.set_lights(&lights, &transform.position));
+			match result {
+				Ok(()) => mesh.draw(),
+				Err(e) => println!("{}", e),
+			}
 			shader.end();
-			texture.end();
-
-			gl::BindVertexArray(0);
+		}
+		if let Err(e) = post_process.end(&samplers) {
+			println!("{}", e);
 		}
 
 		window.gl_swap_window();
@@ -496,11 +467,5 @@ fn main() {
 	}
 
 	// Shutdown
-
-	// Rendering
-	unsafe {
-		gl::DeleteBuffers(1, &ebo);
-		gl::DeleteBuffers(1, &vbo);
-		gl::DeleteVertexArrays(1, &vao);
-	}
+	// GPU resources are freed when they go out of scope
 }
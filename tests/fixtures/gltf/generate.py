@@ -0,0 +1,210 @@
+#!/usr/bin/env python3
+# Writes the glTF fixtures of tests/gltf_loader.rs: quad.gltf with its buffer in quad.bin,
+# quad_embedded.gltf with the same buffer in a data URI, quad.glb with it in the BIN chunk
+# and skinned.gltf, a skinned and animated strip. Run from this directory.
+
+import base64
+import json
+import struct
+
+# Position and UV of each vertex, UVs have the glTF top left origin
+VERTICES = [
+	((-1.0, -1.0, 0.0), (0.0, 1.0)),
+	((1.0, -1.0, 0.0), (1.0, 1.0)),
+	((1.0, 1.0, 0.0), (1.0, 0.0)),
+	((-1.0, 1.0, 0.0), (0.0, 0.0)),
+]
+COLORS = [(255, 0, 0, 255), (0, 255, 0, 255), (0, 0, 255, 255), (255, 255, 255, 51)]
+INDICES = [0, 1, 2, 2, 3, 0]
+
+
+def pad(data):
+	return data + b'\0' * (-len(data) % 4)
+
+
+indices = struct.pack('<6H', *INDICES)
+vertices = b''.join(struct.pack('<5f', *(position + uv)) for position, uv in VERTICES)
+colors = b''.join(struct.pack('<4B', *color) for color in COLORS)
+sparse_indices = struct.pack('<B', 2)
+sparse_values = struct.pack('<3f', 0.0, 2.0, 0.0)
+
+views = []
+buffer = b''
+for data, extra in [(indices, {}), (vertices, {'byteStride': 20}), (colors, {}),
+		(sparse_indices, {}), (sparse_values, {})]:
+	view = {'buffer': 0, 'byteOffset': len(buffer), 'byteLength': len(data)}
+	view.update(extra)
+	views.append(view)
+	buffer += pad(data)
+
+
+def gltf(uri):
+	return {
+		'asset': {'version': '2.0'},
+		'buffers': [{'uri': uri, 'byteLength': len(buffer)}],
+		'bufferViews': views,
+		'accessors': [
+			{'bufferView': 0, 'componentType': 5123, 'count': 6, 'type': 'SCALAR'},
+			{'bufferView': 1, 'componentType': 5126, 'count': 4, 'type': 'VEC3',
+				'min': [-1, -1, 0], 'max': [1, 1, 0]},
+			{'bufferView': 1, 'byteOffset': 12, 'componentType': 5126, 'count': 4, 'type': 'VEC2'},
+			{'bufferView': 2, 'componentType': 5121, 'normalized': True, 'count': 4, 'type': 'VEC4'},
+			# The quad positions with the third one moved to (0, 2, 0)
+			{'bufferView': 1, 'componentType': 5126, 'count': 4, 'type': 'VEC3', 'sparse': {
+				'count': 1,
+				'indices': {'bufferView': 3, 'componentType': 5121},
+				'values': {'bufferView': 4},
+			}},
+		],
+		'meshes': [
+			{'name': 'quad', 'primitives': [{
+				'attributes': {'POSITION': 1, 'TEXCOORD_0': 2, 'COLOR_0': 3},
+				'indices': 0,
+				'material': 0,
+			}]},
+			{'name': 'fan', 'primitives': [{'attributes': {'POSITION': 4}, 'mode': 6, 'material': 1}]},
+		],
+		'materials': [
+			{
+				'name': 'red',
+				'pbrMetallicRoughness': {
+					'baseColorFactor': [1, 0, 0, 1],
+					'baseColorTexture': {'index': 0},
+					'metallicFactor': 0.25,
+					'roughnessFactor': 0.75,
+				},
+				'emissiveFactor': [0.5, 0.25, 0],
+				'alphaMode': 'MASK',
+				'alphaCutoff': 0.25,
+				'doubleSided': True,
+			},
+			{'name': 'defaults'},
+		],
+		'textures': [{'source': 0, 'sampler': 0}],
+		'images': [{'uri': 'checker.png'}],
+		'samplers': [{'magFilter': 9728, 'wrapS': 33071}],
+		'nodes': [{'name': 'quad', 'mesh': 0, 'translation': [0, 0, -2]}, {'name': 'fan', 'mesh': 1}],
+		'scenes': [{'nodes': [0, 1]}],
+		'scene': 0,
+	}
+
+
+def glb(document, binary):
+	text = json.dumps(document).encode('utf-8')
+	text += b' ' * (-len(text) % 4)
+	binary = pad(binary)
+	chunks = (struct.pack('<I', len(text)) + b'JSON' + text +
+		struct.pack('<I', len(binary)) + b'BIN\0' + binary)
+	return b'glTF' + struct.pack('<II', 2, 12 + len(chunks)) + chunks
+
+
+# Skinned strip of two quads over the joints hip and spine, one above the other
+SKIN_POSITIONS = [(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0), (0, 2, 0), (1, 2, 0)]
+SKIN_INDICES = [0, 1, 2, 2, 1, 3, 2, 3, 4, 4, 3, 5]
+# The middle row is shared between both joints
+SKIN_JOINTS = [(0, 0, 0, 0), (0, 0, 0, 0), (0, 1, 0, 0), (0, 1, 0, 0), (1, 0, 0, 0), (1, 0, 0, 0)]
+SKIN_WEIGHTS = [(1, 0, 0, 0), (1, 0, 0, 0), (0.5, 0.5, 0, 0), (0.5, 0.5, 0, 0), (1, 0, 0, 0), (1, 0, 0, 0)]
+
+
+def column_major_translation(x, y, z):
+	return [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, x, y, z, 1]
+
+
+def skinned():
+	data = []
+	data.append(b''.join(struct.pack('<3f', *p) for p in SKIN_POSITIONS))
+	data.append(bytes(SKIN_INDICES))
+	data.append(b''.join(struct.pack('<4B', *j) for j in SKIN_JOINTS))
+	data.append(b''.join(struct.pack('<4f', *w) for w in SKIN_WEIGHTS))
+	# Inverse bind matrices undo the world translations of hip (0, 1, 0) and spine (0, 2, 0)
+	data.append(struct.pack('<16f', *column_major_translation(0, -1, 0)) +
+		struct.pack('<16f', *column_major_translation(0, -2, 0)))
+	# Rotation: a quarter turn around Z in one second
+	data.append(struct.pack('<2f', 0, 1))
+	data.append(struct.pack('<8f', 0, 0, 0, 1, 0, 0, 0.70710677, 0.70710677))
+	# Translation keys, the values are a sparse accessor without a buffer view
+	data.append(struct.pack('<3f', 0, 0.5, 1))
+	data.append(struct.pack('<B', 1))
+	data.append(struct.pack('<3f', 0, 0.5, 0))
+	# Scale, cubic spline with in-tangent, value and out-tangent per key
+	data.append(struct.pack('<2f', 0, 2))
+	data.append(struct.pack('<18f', 0, 0, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 2, 2, 2, 0, 0, 0))
+
+	views, buffer = [], b''
+	for chunk in data:
+		views.append({'buffer': 0, 'byteOffset': len(buffer), 'byteLength': len(chunk)})
+		buffer += pad(chunk)
+
+	def accessor(view, component, count, kind, **extra):
+		result = {'bufferView': view, 'componentType': component, 'count': count, 'type': kind}
+		result.update(extra)
+		return result
+
+	uri = 'data:application/octet-stream;base64,' + base64.b64encode(buffer).decode('ascii')
+	return {
+		'asset': {'version': '2.0'},
+		'buffers': [{'uri': uri, 'byteLength': len(buffer)}],
+		'bufferViews': views,
+		'accessors': [
+			accessor(0, 5126, 6, 'VEC3', min=[0, 0, 0], max=[1, 2, 0]),
+			accessor(1, 5121, 12, 'SCALAR'),
+			accessor(2, 5121, 6, 'VEC4'),
+			accessor(3, 5126, 6, 'VEC4'),
+			accessor(4, 5126, 2, 'MAT4'),
+			accessor(5, 5126, 2, 'SCALAR', min=[0], max=[1]),
+			accessor(6, 5126, 2, 'VEC4'),
+			accessor(7, 5126, 3, 'SCALAR', min=[0], max=[1]),
+			{'componentType': 5126, 'count': 3, 'type': 'VEC3', 'sparse': {
+				'count': 1,
+				'indices': {'bufferView': 8, 'componentType': 5121},
+				'values': {'bufferView': 9},
+			}},
+			accessor(10, 5126, 2, 'SCALAR', min=[0], max=[2]),
+			accessor(11, 5126, 6, 'VEC3'),
+		],
+		'meshes': [{'name': 'strip', 'primitives': [{
+			'attributes': {'POSITION': 0, 'JOINTS_0': 2, 'WEIGHTS_0': 3},
+			'indices': 1,
+		}]}],
+		'skins': [{'name': 'rig', 'joints': [1, 2], 'inverseBindMatrices': 4, 'skeleton': 1}],
+		'animations': [{
+			'name': 'bend',
+			'samplers': [
+				{'input': 5, 'output': 6},
+				{'input': 7, 'output': 8, 'interpolation': 'STEP'},
+				{'input': 9, 'output': 10, 'interpolation': 'CUBICSPLINE'},
+			],
+			'channels': [
+				{'sampler': 0, 'target': {'node': 1, 'path': 'rotation'}},
+				{'sampler': 1, 'target': {'node': 2, 'path': 'translation'}},
+				{'sampler': 2, 'target': {'node': 2, 'path': 'scale'}},
+				# Without a node the target belongs to an extension and is skipped
+				{'sampler': 0, 'target': {'path': 'rotation'}},
+			],
+		}],
+		'nodes': [
+			{'name': 'root', 'children': [1, 3]},
+			{'name': 'hip', 'translation': [0, 1, 0], 'children': [2]},
+			{'name': 'spine', 'translation': [0, 1, 0]},
+			{'name': 'strip', 'mesh': 0, 'skin': 0},
+		],
+		'scenes': [{'nodes': [0]}],
+	}
+
+
+with open('quad.bin', 'wb') as f:
+	f.write(buffer)
+with open('quad.gltf', 'w') as f:
+	json.dump(gltf('quad.bin'), f, indent='\t')
+	f.write('\n')
+with open('quad_embedded.gltf', 'w') as f:
+	uri = 'data:application/octet-stream;base64,' + base64.b64encode(buffer).decode('ascii')
+	json.dump(gltf(uri), f, indent='\t')
+	f.write('\n')
+with open('quad.glb', 'wb') as f:
+	document = gltf(None)
+	del document['buffers'][0]['uri']
+	f.write(glb(document, buffer))
+with open('skinned.gltf', 'w') as f:
+	json.dump(skinned(), f, indent='\t')
+	f.write('\n')
@@ -0,0 +1,221 @@
+#!/usr/bin/env python3
+# Writes the bmp, tga and png fixtures of tests/image_decoders.rs with nothing but the
+# standard library, so they don't depend on the decoders they test. Run from this directory.
+# Every image shows the same 5x3 grid of palette indices, except the png gradients.
+
+import struct
+import zlib
+
+PALETTE = [
+	(0, 0, 0), (255, 255, 255), (255, 0, 0), (0, 255, 0),
+	(0, 0, 255), (255, 255, 0), (0, 255, 255), (255, 0, 255),
+]
+
+# Rows from top to bottom, the last row has runs for the RLE encoders
+INDICES = [
+	[0, 1, 2, 3, 4],
+	[5, 6, 7, 0, 1],
+	[2, 2, 2, 3, 3],
+]
+WIDTH, HEIGHT = 5, 3
+
+
+def write(name, data):
+	with open(name, 'wb') as f:
+		f.write(data)
+
+
+# BMP
+
+def bmp(bit_count, rows, palette=None, compression=0, top_down=False, masks=None):
+	info_size = 40 if masks is None else 108
+	colors = len(palette) if palette else 0
+	offset = 14 + info_size + colors * 4
+	data = b''.join(rows)
+	height = -HEIGHT if top_down else HEIGHT
+	info = struct.pack('<IiiHHIIiiII', info_size, WIDTH, height, 1, bit_count, compression,
+		len(data), 2835, 2835, colors, 0)
+	if masks is not None:
+		# BITMAPV4HEADER: masks, color space and 48 unused bytes
+		info += struct.pack('<IIII', *masks) + b'sRGB' + b'\0' * 48
+	file_header = b'BM' + struct.pack('<IHHI', offset + len(data), 0, 0, offset)
+	table = b''.join(struct.pack('<BBBB', b, g, r, 0) for (r, g, b) in (palette or []))
+	return file_header + info + table + data
+
+
+def pad(row):
+	return row + b'\0' * (-len(row) % 4)
+
+
+def packed(indices, bits):
+	value, count, out = 0, 0, bytearray()
+	for index in indices:
+		value = value << bits | index
+		count += bits
+		if count == 8:
+			out.append(value)
+			value, count = 0, 0
+	if count:
+		out.append(value << (8 - count))
+	return bytes(out)
+
+
+def bottom_up(rows):
+	return list(reversed(rows))
+
+
+def write_bmps():
+	one_bit = [[index % 2 for index in row] for row in INDICES]
+	write('indexed1.bmp', bmp(1, bottom_up([pad(packed(row, 1)) for row in one_bit]), PALETTE[:2]))
+	write('indexed4.bmp', bmp(4, bottom_up([pad(packed(row, 4)) for row in INDICES]), PALETTE))
+	write('indexed8.bmp', bmp(8, bottom_up([pad(bytes(row)) for row in INDICES]), PALETTE))
+
+	def bgr(row):
+		return b''.join(struct.pack('<BBB', *reversed(PALETTE[index])) for index in row)
+	write('rgb24.bmp', bmp(24, bottom_up([pad(bgr(row)) for row in INDICES])))
+	write('rgb24_top_down.bmp', bmp(24, [pad(bgr(row)) for row in INDICES], top_down=True))
+
+	# Alpha 255 - 16 * x in the fourth byte
+	def bgra(row):
+		return b''.join(struct.pack('<BBBB', b, g, r, 255 - 16 * x) for x, (r, g, b) in
+			enumerate(PALETTE[index] for index in row))
+	write('rgba32.bmp', bmp(32, bottom_up([bgra(row) for row in INDICES])))
+
+	# RGBA stored as R G B A bytes through V4 bitfields
+	def rgba(row):
+		return b''.join(struct.pack('<BBBB', r, g, b, 255 - 16 * x) for x, (r, g, b) in
+			enumerate(PALETTE[index] for index in row))
+	write('bitfields32.bmp', bmp(32, bottom_up([rgba(row) for row in INDICES]), compression=3,
+		masks=(0x000000ff, 0x0000ff00, 0x00ff0000, 0xff000000)))
+
+	# RLE8: absolute runs for the first two rows, encoded runs for the last one
+	rle8 = b''
+	for row in bottom_up(INDICES):
+		if row == [2, 2, 2, 3, 3]:
+			rle8 += bytes([3, 2, 2, 3])
+		else:
+			rle8 += bytes([0, 5]) + bytes(row) + b'\0'
+		rle8 += bytes([0, 0])
+	rle8 = rle8[:-2] + bytes([0, 1])
+	write('indexed8_rle.bmp', bmp(8, [rle8], PALETTE, compression=1))
+
+	# RLE4: runs alternate between the two nibbles, absolute runs are padded to 16 bits
+	rle4 = b''
+	for row in bottom_up(INDICES):
+		if row == [2, 2, 2, 3, 3]:
+			rle4 += bytes([3, 0x22, 2, 0x33])
+		else:
+			rle4 += bytes([0, 5]) + packed(row, 4) + b'\0'
+		rle4 += bytes([0, 0])
+	rle4 = rle4[:-2] + bytes([0, 1])
+	write('indexed4_rle.bmp', bmp(4, [rle4], PALETTE, compression=2))
+
+
+# TGA
+
+def tga(image_type, depth, pixels, descriptor, color_map=b'', map_first=0, map_depth=0):
+	map_length = len(color_map) // (map_depth // 8) if map_depth else 0
+	header = struct.pack('<BBBHHBHHHHBB', 0, 1 if color_map else 0, image_type,
+		map_first, map_length, map_depth, 0, 0, WIDTH, HEIGHT, depth, descriptor)
+	return header + color_map + pixels
+
+
+def tga_rle(pixels, size):
+	# Repeated pixels as run packets, everything else as raw packets
+	units = [pixels[i:i + size] for i in range(0, len(pixels), size)]
+	out, i = b'', 0
+	while i < len(units):
+		run = 1
+		while i + run < len(units) and units[i + run] == units[i] and run < 128:
+			run += 1
+		if run > 1:
+			out += bytes([0x80 | (run - 1)]) + units[i]
+			i += run
+			continue
+		raw = 1
+		while i + raw < len(units) and raw < 128 and (i + raw + 1 >= len(units) or units[i + raw] != units[i + raw + 1]):
+			raw += 1
+		out += bytes([raw - 1]) + b''.join(units[i:i + raw])
+		i += raw
+	return out
+
+
+def write_tgas():
+	def bgr(index):
+		return struct.pack('<BBB', *reversed(PALETTE[index]))
+
+	def bgra(x, index):
+		return bgr(index) + bytes([255 - 16 * x])
+
+	rows_bottom_up = bottom_up(INDICES)
+	rgb_bottom_up = b''.join(bgr(index) for row in rows_bottom_up for index in row)
+	rgb_top_down = b''.join(bgr(index) for row in INDICES for index in row)
+	write('rgb24.tga', tga(2, 24, rgb_bottom_up, 0x00))
+	write('rgb24_top_down.tga', tga(2, 24, rgb_top_down, 0x20))
+	# Top down and right to left
+	rgb_mirrored = b''.join(bgr(index) for row in INDICES for index in reversed(row))
+	write('rgb24_top_right.tga', tga(2, 24, rgb_mirrored, 0x30))
+
+	rgba_top_down = b''.join(bgra(x, index) for row in INDICES for x, index in enumerate(row))
+	write('rgba32_rle.tga', tga(10, 32, tga_rle(rgba_top_down, 4), 0x28))
+	write('rgb24_rle.tga', tga(10, 24, tga_rle(rgb_bottom_up, 3), 0x00))
+
+	# The color map starts at index 2, pixels hold the palette index plus 2
+	color_map = b''.join(bgr(index) for index in range(len(PALETTE)))
+	mapped = bytes(index + 2 for row in rows_bottom_up for index in row)
+	write('mapped8.tga', tga(1, 8, mapped, 0x00, color_map, map_first=2, map_depth=24))
+	write('mapped8_rle.tga', tga(9, 8, tga_rle(mapped, 1), 0x00, color_map, map_first=2, map_depth=24))
+
+
+# PNG
+
+def chunk(kind, body):
+	return struct.pack('>I', len(body)) + kind + body + struct.pack('>I', zlib.crc32(kind + body))
+
+
+def png(width, height, bit_depth, color_type, scanlines, interlaced=False, extra=b''):
+	header = struct.pack('>IIBBBBB', width, height, bit_depth, color_type, 0, 0, 1 if interlaced else 0)
+	return (b'\x89PNG\r\n\x1a\n' + chunk(b'IHDR', header) + extra +
+		chunk(b'IDAT', zlib.compress(scanlines)) + chunk(b'IEND', b''))
+
+
+# Column start, row start, column step and row step of the Adam7 passes
+ADAM7 = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)]
+
+
+def gradient(x, y):
+	return (x * 28, y * 28, 255 - (x + y) * 14)
+
+
+def write_pngs():
+	# 9x9 RGB gradient in all seven passes, every row unfiltered
+	size = 9
+	scanlines = b''
+	for (x0, y0, dx, dy) in ADAM7:
+		columns = range(x0, size, dx)
+		if not columns:
+			continue
+		for y in range(y0, size, dy):
+			scanlines += b'\0' + b''.join(bytes(gradient(x, y)) for x in columns)
+	write('rgb8_interlaced.png', png(size, size, 8, 2, scanlines, interlaced=True))
+
+	# 4 bit palette with tRNS alpha for the first three entries only
+	palette = b''.join(bytes(color) for color in PALETTE)
+	alpha = bytes([0, 128, 200])
+	scanlines = b''.join(b'\0' + packed(row, 4) for row in INDICES)
+	write('palette4_trns.png', png(WIDTH, HEIGHT, 4, 3, scanlines,
+		extra=chunk(b'PLTE', palette) + chunk(b'tRNS', alpha)))
+
+	# 16 bit RGBA, the samples of row y are 0x1234 * (x + 1) + y, alpha 0xffff - 0x1111 * x
+	scanlines = b''
+	for y in range(2):
+		scanlines += b'\0'
+		for x in range(3):
+			value = 0x1234 * (x + 1) + y
+			scanlines += struct.pack('>HHHH', value, 0xffff - value, value // 2, 0xffff - 0x1111 * x)
+	write('rgba16.png', png(3, 2, 16, 6, scanlines))
+
+
+write_bmps()
+write_tgas()
+write_pngs()
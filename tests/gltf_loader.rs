@@ -0,0 +1,224 @@
+// Imports the glTF fixtures in tests/fixtures/gltf, written by generate.py in the same
+// directory. quad.gltf reads its buffer from quad.bin, quad_embedded.gltf carries the same
+// buffer as a data URI and quad.glb in its BIN chunk, so all three have to come out the same.
+// skinned.gltf has a skin, a joint hierarchy and an animation. Importing never needs a GL
+// context, none of these tests create one.
+
+extern crate nitrust_oxide;
+
+use nitrust_oxide::framework::graphics::loaders::gltf;
+use nitrust_oxide::framework::graphics::loaders::gltf::{AlphaMode, AnimationProperty, GltfAsset, ImageSource, Interpolation};
+use nitrust_oxide::framework::math::{Mat4x4, Vec2, Vec3, Vec4};
+
+fn load(path: &str) -> GltfAsset {
+	match gltf::load(path) {
+		Ok(asset) => asset,
+		Err(e) => panic!("{}", e),
+	}
+}
+
+fn vec2(x: f32, y: f32) -> Vec2 {
+	Vec2 { x: x, y: y }
+}
+
+fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+	Vec3 { x: x, y: y, z: z }
+}
+
+fn check_meshes(asset: &GltfAsset) {
+	assert_eq!(asset.meshes.len(), 2);
+
+	let quad = &asset.meshes[0];
+	assert_eq!(quad.name, "quad");
+	assert_eq!(quad.primitives.len(), 1);
+	let primitive = &quad.primitives[0];
+	assert_eq!(primitive.material, Some(0));
+	assert!(primitive.joints.is_empty() && primitive.weights.is_empty());
+
+	// Positions and UVs share an interleaved buffer view with a stride of 20 bytes
+	let mesh = &primitive.mesh;
+	assert_eq!(mesh.positions, vec![vec3(-1.0, -1.0, 0.0), vec3(1.0, -1.0, 0.0), vec3(1.0, 1.0, 0.0), vec3(-1.0, 1.0, 0.0)]);
+	assert_eq!(mesh.indices, vec![0, 1, 2, 2, 3, 0]);
+	// V is flipped to a bottom left origin
+	assert_eq!(mesh.uvs.len(), 1);
+	assert_eq!(mesh.uvs[0], vec![vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)]);
+	// Normalized unsigned bytes
+	assert_eq!(mesh.colors, vec![
+		Vec4 { x: 1.0, y: 0.0, z: 0.0, w: 1.0 },
+		Vec4 { x: 0.0, y: 1.0, z: 0.0, w: 1.0 },
+		Vec4 { x: 0.0, y: 0.0, z: 1.0, w: 1.0 },
+		Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 51.0 / 255.0 },
+	]);
+	// The file has no normals or tangents, they are computed
+	assert_eq!(mesh.normals, vec![vec3(0.0, 0.0, 1.0); 4]);
+	assert_eq!(mesh.tangents.len(), 4);
+
+	// A sparse accessor over the quad positions moves the third vertex, the fan becomes two triangles
+	let fan = &asset.meshes[1];
+	assert_eq!(fan.name, "fan");
+	let primitive = &fan.primitives[0];
+	assert_eq!(primitive.material, Some(1));
+	assert_eq!(primitive.mesh.positions, vec![vec3(-1.0, -1.0, 0.0), vec3(1.0, -1.0, 0.0), vec3(0.0, 2.0, 0.0), vec3(-1.0, 1.0, 0.0)]);
+	assert_eq!(primitive.mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+	assert!(primitive.mesh.uvs.is_empty() && primitive.mesh.colors.is_empty());
+}
+
+fn check_materials(asset: &GltfAsset) {
+	assert_eq!(asset.materials.len(), 2);
+
+	let red = &asset.materials[0];
+	assert_eq!(red.name, "red");
+	assert_eq!(red.base_color, Vec4 { x: 1.0, y: 0.0, z: 0.0, w: 1.0 });
+	let texture = red.base_color_texture.expect("base color texture");
+	assert_eq!((texture.texture, texture.uv_channel), (0, 0));
+	assert_eq!((red.metallic, red.roughness), (0.25, 0.75));
+	assert_eq!(red.emissive, vec3(0.5, 0.25, 0.0));
+	assert_eq!((red.alpha_mode, red.alpha_cutoff, red.double_sided), (AlphaMode::Mask, 0.25, true));
+	assert!(red.normal_texture.is_none() && red.occlusion_texture.is_none());
+
+	let defaults = &asset.materials[1];
+	assert_eq!(defaults.name, "defaults");
+	assert_eq!(defaults.base_color, Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 });
+	assert!(defaults.base_color_texture.is_none());
+	assert_eq!((defaults.metallic, defaults.roughness), (1.0, 1.0));
+	assert_eq!((defaults.alpha_mode, defaults.alpha_cutoff, defaults.double_sided), (AlphaMode::Opaque, 0.5, false));
+
+	assert_eq!(asset.textures.len(), 1);
+	assert_eq!((asset.textures[0].image, asset.textures[0].sampler), (Some(0), Some(0)));
+	// Relative to the .gltf file, it doesn't have to exist for the import
+	match asset.images[0].source {
+		ImageSource::File(ref path) => assert_eq!(path, "./tests/fixtures/gltf/checker.png"),
+		_ => panic!("the image should be a file"),
+	}
+	let sampler = &asset.samplers[0];
+	assert_eq!((sampler.mag_filter, sampler.min_filter, sampler.wrap_s, sampler.wrap_t), (Some(9728), None, 33071, 10497));
+}
+
+fn check_scene(asset: &GltfAsset) {
+	assert_eq!(asset.root_nodes(), vec![0, 1]);
+	assert_eq!((asset.nodes[0].mesh, asset.nodes[1].mesh), (Some(0), Some(1)));
+	assert_eq!(asset.nodes[0].transform.position, vec3(0.0, 0.0, -2.0));
+}
+
+#[test]
+fn external_buffer() {
+	let asset = load("./tests/fixtures/gltf/quad.gltf");
+	check_meshes(&asset);
+	check_materials(&asset);
+	check_scene(&asset);
+}
+
+#[test]
+fn embedded_buffer() {
+	let asset = load("./tests/fixtures/gltf/quad_embedded.gltf");
+	check_meshes(&asset);
+	check_materials(&asset);
+	check_scene(&asset);
+}
+
+#[test]
+fn binary_glb() {
+	let asset = load("./tests/fixtures/gltf/quad.glb");
+	check_meshes(&asset);
+	check_materials(&asset);
+	check_scene(&asset);
+}
+
+#[test]
+fn truncated_glb() {
+	let data = include_bytes!("fixtures/gltf/quad.glb");
+	match gltf::parse("quad.glb", &data[..data.len() - 4]) {
+		Err(gltf::GltfError::Invalid { ref message, .. }) => assert!(message.starts_with("GLB is"), "{}", message),
+		Err(e) => panic!("unexpected error: {}", e),
+		Ok(_) => panic!("imported a truncated .glb file"),
+	}
+}
+
+#[test]
+fn node_hierarchy() {
+	let asset = load("./tests/fixtures/gltf/skinned.gltf");
+	let names: Vec<&str> = asset.nodes.iter().map(|node| &node.name[..]).collect();
+	assert_eq!(names, vec!["root", "hip", "spine", "strip"]);
+	let parents: Vec<Option<usize>> = asset.nodes.iter().map(|node| node.parent).collect();
+	assert_eq!(parents, vec![None, Some(0), Some(1), Some(0)]);
+	assert_eq!(asset.nodes[0].children, vec![1, 3]);
+	assert_eq!(asset.nodes[1].children, vec![2]);
+	assert_eq!(asset.root_nodes(), vec![0]);
+
+	// Translations add up along the chain
+	let world = asset.world_matrix(2);
+	assert_eq!((world.m[3], world.m[7], world.m[11]), (0.0, 2.0, 0.0));
+	assert_eq!((asset.nodes[3].mesh, asset.nodes[3].skin), (Some(0), Some(0)));
+}
+
+#[test]
+fn skin() {
+	let asset = load("./tests/fixtures/gltf/skinned.gltf");
+	assert_eq!(asset.skins.len(), 1);
+	let skin = &asset.skins[0];
+	assert_eq!(skin.name, "rig");
+	assert_eq!(skin.joints, vec![1, 2]);
+	assert_eq!(skin.skeleton, Some(1));
+	assert_eq!(skin.inverse_bind_matrices.len(), 2);
+
+	// Each inverse bind matrix undoes the world matrix of its joint
+	for (&joint, inverse) in skin.joints.iter().zip(&skin.inverse_bind_matrices) {
+		let product = &asset.world_matrix(joint) * inverse;
+		let identity = Mat4x4::identity();
+		for i in 0..16 {
+			assert!((product.m[i] - identity.m[i]).abs() < 1e-6, "joint {}: {:?}", joint, &product.m[..]);
+		}
+	}
+
+	let primitive = &asset.meshes[0].primitives[0];
+	assert_eq!(primitive.mesh.positions.len(), 6);
+	assert_eq!(primitive.mesh.indices.len(), 12);
+	assert_eq!(primitive.joints, vec![[0, 0, 0, 0], [0, 0, 0, 0], [0, 1, 0, 0], [0, 1, 0, 0], [1, 0, 0, 0], [1, 0, 0, 0]]);
+	assert_eq!(primitive.weights[2], Vec4 { x: 0.5, y: 0.5, z: 0.0, w: 0.0 });
+	assert_eq!(primitive.weights[5], Vec4 { x: 1.0, y: 0.0, z: 0.0, w: 0.0 });
+}
+
+#[test]
+fn animation_channels() {
+	let asset = load("./tests/fixtures/gltf/skinned.gltf");
+	assert_eq!(asset.animations.len(), 1);
+	let animation = &asset.animations[0];
+	assert_eq!(animation.name, "bend");
+	assert_eq!(animation.duration(), 2.0);
+
+	// The channel without a node is left out
+	let targets: Vec<(usize, AnimationProperty, Interpolation, usize)> = animation.channels.iter()
+		.map(|channel| (channel.node, channel.property, channel.interpolation, channel.components()))
+		.collect();
+	assert_eq!(targets, vec![
+		(1, AnimationProperty::Rotation, Interpolation::Linear, 4),
+		(2, AnimationProperty::Translation, Interpolation::Step, 3),
+		(2, AnimationProperty::Scale, Interpolation::CubicSpline, 3),
+	]);
+
+	// Rotations get the axis negated like node transforms
+	let rotation = &animation.channels[0];
+	assert_eq!(rotation.times, vec![0.0, 1.0]);
+	assert_eq!(&rotation.values[4..], &[0.0, 0.0, -0.70710677, 0.70710677]);
+
+	// Sparse values over an accessor without a buffer view, everything else is zero
+	let translation = &animation.channels[1];
+	assert_eq!(translation.times, vec![0.0, 0.5, 1.0]);
+	assert_eq!(translation.values, vec![0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0]);
+
+	// In-tangent, value and out-tangent per keyframe
+	let scale = &animation.channels[2];
+	assert_eq!(scale.values.len(), 2 * 3 * 3);
+	assert_eq!(&scale.values[3..6], &[1.0, 1.0, 1.0]);
+	assert_eq!(&scale.values[12..15], &[2.0, 2.0, 2.0]);
+}
+
+#[test]
+fn missing_buffer_file() {
+	let data = include_bytes!("fixtures/gltf/quad.gltf");
+	match gltf::parse("./tests/fixtures/gltf/missing/quad.gltf", data) {
+		Err(gltf::GltfError::Io { ref path, .. }) => assert_eq!(path, "./tests/fixtures/gltf/missing/quad.bin"),
+		Err(e) => panic!("unexpected error: {}", e),
+		Ok(_) => panic!("imported a glTF file without its buffer"),
+	}
+}
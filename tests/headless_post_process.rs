@@ -0,0 +1,93 @@
+// Runs the default post-process stack over an HDR scene without a window and compares
+// the result with tests/golden/post_process_*.png. Needs EGL, run with:
+// LIBGL_ALWAYS_SOFTWARE=1 cargo test --features headless
+// Set NITRUST_UPDATE_GOLDEN=1 to accept the new output, see headless_quad.rs.
+#![cfg(feature = "headless")]
+
+extern crate gl;
+extern crate nitrust_oxide;
+
+use nitrust_oxide::framework::core::{Camera, Transform};
+use nitrust_oxide::framework::graphics::{capture_framebuffer, HeadlessContext, Mesh, MipFilter, RgbaImage};
+use nitrust_oxide::framework::graphics::{SamplerCache, SamplerDesc, ShaderBuilder, Texture, TextureDesc, Filter, Wrap};
+use nitrust_oxide::framework::graphics::{Antialiasing, PostProcessStack};
+use nitrust_oxide::framework::graphics::image::golden;
+use nitrust_oxide::framework::math::{Quaternion, Vec3, Vec4};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+
+// 8x8 checkerboard, the light squares go above 1 through the vertex color and bloom
+fn checkerboard() -> RgbaImage {
+	let mut image = RgbaImage::new(8, 8);
+	for y in 0..8 {
+		for x in 0..8 {
+			let pixel = if (x + y) % 2 == 0 { [255 - x as u8 * 32, 64, y as u8 * 32, 255] } else { [16, 16, 16, 255] };
+			image.set_pixel(x, y, pixel);
+		}
+	}
+	image
+}
+
+#[test]
+fn default_stack_matches_golden_images() {
+	let _context = HeadlessContext::new(WIDTH, HEIGHT).unwrap();
+
+	let shader = ShaderBuilder::new()
+		.vertex("./assets/shaders/basic_shader.vs.glsl")
+		.fragment("./assets/shaders/basic_shader.fs.glsl")
+		.build()
+		.unwrap();
+
+	let mut mesh = Mesh::quad(1.0, 1.0);
+	mesh.set_color(&Vec4 { x: 4.0, y: 4.0, z: 4.0, w: 1.0 });
+	mesh.upload().unwrap();
+
+	let samplers = SamplerCache::new();
+	let mut sampler = SamplerDesc::with_wrap(Wrap::ClampToEdge);
+	sampler.min_filter = Filter::Nearest;
+	sampler.mag_filter = Filter::Nearest;
+	sampler.mip_filter = MipFilter::None;
+	let desc = TextureDesc { generate_mipmaps: false, sampler: sampler, fallback: false, ..TextureDesc::new() };
+	let mut texture = Texture::new();
+	texture.load_image(&checkerboard(), &desc, &samplers);
+
+	// Tilted, so the edges of the quad are not aligned with the pixels
+	let transform = Transform {
+		position: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
+		scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
+		orientation: Quaternion::from_euler(&Vec3 { x: 180.0, y: 0.0, z: 20.0 }),
+	};
+	let camera_transform = Transform {
+		position: Vec3 { x: 0.0, y: 0.0, z: 3.0 },
+		scale: Vec3 { x: 1.0, y: 1.0, z: 1.0 },
+		orientation: Quaternion::from_axis(&Vec3 { x: 0.0, y: 1.0, z: 0.0 }, 180.0),
+	};
+	let camera = Camera::new_perspective(&camera_transform, 45.0, WIDTH, HEIGHT, 0.1, 100.0);
+
+	let modes = [
+		(Antialiasing::Fxaa, "./tests/golden/post_process_fxaa.png"),
+		(Antialiasing::Msaa(4), "./tests/golden/post_process_msaa.png"),
+	];
+	for &(antialiasing, golden_path) in &modes {
+		let mut post_process = PostProcessStack::with_defaults(WIDTH, HEIGHT, antialiasing, &samplers).unwrap();
+
+		post_process.begin();
+		unsafe {
+			gl::ClearColor(0.1, 0.2, 0.3, 1.0);
+			gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+		}
+		shader.begin();
+		shader.set_mat4x4(&shader.uniform("transform").unwrap(), &transform.mvp(&camera)).unwrap();
+		shader.bind_texture("app_texture", &texture).unwrap();
+		mesh.draw();
+		shader.end();
+		post_process.end(&samplers).unwrap();
+
+		assert_eq!(unsafe { gl::GetError() }, gl::NO_ERROR, "{:?}", antialiasing);
+		let image = capture_framebuffer();
+		if let Err(error) = golden::check(&image, golden_path, 2) {
+			panic!("{:?}: {}", antialiasing, error);
+		}
+	}
+}
@@ -0,0 +1,174 @@
+// Decodes the small fixtures in tests/fixtures/images and checks every pixel. The fixtures
+// are written by tests/fixtures/images/generate.py, most show the same 5x3 grid of palette
+// colors stored in different ways.
+
+extern crate nitrust_oxide;
+
+use nitrust_oxide::framework::graphics::RgbaImage;
+use nitrust_oxide::framework::graphics::image;
+
+const FIXTURES: &'static str = "./tests/fixtures/images/";
+
+const PALETTE: [[u8; 3]; 8] = [
+	[0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 255, 0],
+	[0, 0, 255], [255, 255, 0], [0, 255, 255], [255, 0, 255],
+];
+
+// Rows from top to bottom
+const INDICES: [[usize; 5]; 3] = [
+	[0, 1, 2, 3, 4],
+	[5, 6, 7, 0, 1],
+	[2, 2, 2, 3, 3],
+];
+
+fn load(name: &str) -> RgbaImage {
+	match image::load(&format!("{}{}", FIXTURES, name)) {
+		Ok(image) => image,
+		Err(e) => panic!("{}: {}", name, e),
+	}
+}
+
+// The grid with the alpha of each pixel from alpha(x, index)
+fn grid<F: Fn(u32, usize) -> u8>(alpha: F) -> RgbaImage {
+	let mut expected = RgbaImage::new(5, 3);
+	for (y, row) in INDICES.iter().enumerate() {
+		for (x, &index) in row.iter().enumerate() {
+			let color = PALETTE[index];
+			expected.set_pixel(x as u32, y as u32, [color[0], color[1], color[2], alpha(x as u32, index)]);
+		}
+	}
+	expected
+}
+
+fn opaque_grid() -> RgbaImage {
+	grid(|_, _| 255)
+}
+
+fn assert_pixels(name: &str, expected: &RgbaImage) {
+	let image = load(name);
+	assert_eq!((image.width, image.height), (expected.width, expected.height), "size of {}", name);
+	for y in 0..expected.height {
+		for x in 0..expected.width {
+			assert_eq!(image.pixel(x, y), expected.pixel(x, y), "pixel ({}, {}) of {}", x, y, name);
+		}
+	}
+}
+
+#[test]
+fn bmp_1_bit() {
+	let mut expected = RgbaImage::new(5, 3);
+	for (y, row) in INDICES.iter().enumerate() {
+		for (x, &index) in row.iter().enumerate() {
+			let value = if index % 2 == 0 { 0 } else { 255 };
+			expected.set_pixel(x as u32, y as u32, [value, value, value, 255]);
+		}
+	}
+	assert_pixels("indexed1.bmp", &expected);
+}
+
+#[test]
+fn bmp_4_bit() {
+	assert_pixels("indexed4.bmp", &opaque_grid());
+}
+
+#[test]
+fn bmp_8_bit() {
+	assert_pixels("indexed8.bmp", &opaque_grid());
+}
+
+#[test]
+fn bmp_24_bit_bottom_up() {
+	assert_pixels("rgb24.bmp", &opaque_grid());
+}
+
+#[test]
+fn bmp_24_bit_top_down() {
+	assert_pixels("rgb24_top_down.bmp", &opaque_grid());
+}
+
+#[test]
+fn bmp_32_bit_alpha() {
+	assert_pixels("rgba32.bmp", &grid(|x, _| 255 - 16 * x as u8));
+}
+
+#[test]
+fn bmp_32_bit_bitfields() {
+	assert_pixels("bitfields32.bmp", &grid(|x, _| 255 - 16 * x as u8));
+}
+
+#[test]
+fn bmp_rle8() {
+	assert_pixels("indexed8_rle.bmp", &opaque_grid());
+}
+
+#[test]
+fn bmp_rle4() {
+	assert_pixels("indexed4_rle.bmp", &opaque_grid());
+}
+
+#[test]
+fn tga_24_bit_bottom_up() {
+	assert_pixels("rgb24.tga", &opaque_grid());
+}
+
+#[test]
+fn tga_24_bit_top_down() {
+	assert_pixels("rgb24_top_down.tga", &opaque_grid());
+}
+
+#[test]
+fn tga_24_bit_right_to_left() {
+	assert_pixels("rgb24_top_right.tga", &opaque_grid());
+}
+
+#[test]
+fn tga_24_bit_rle() {
+	assert_pixels("rgb24_rle.tga", &opaque_grid());
+}
+
+#[test]
+fn tga_32_bit_rle() {
+	assert_pixels("rgba32_rle.tga", &grid(|x, _| 255 - 16 * x as u8));
+}
+
+#[test]
+fn tga_color_mapped() {
+	assert_pixels("mapped8.tga", &opaque_grid());
+}
+
+#[test]
+fn tga_color_mapped_rle() {
+	assert_pixels("mapped8_rle.tga", &opaque_grid());
+}
+
+#[test]
+fn png_interlaced() {
+	let mut expected = RgbaImage::new(9, 9);
+	for y in 0..9 {
+		for x in 0..9 {
+			expected.set_pixel(x, y, [x as u8 * 28, y as u8 * 28, 255 - (x + y) as u8 * 14, 255]);
+		}
+	}
+	assert_pixels("rgb8_interlaced.png", &expected);
+}
+
+#[test]
+fn png_palette_with_transparency() {
+	// tRNS covers the first three entries, the others stay opaque
+	let alpha = [0, 128, 200];
+	assert_pixels("palette4_trns.png", &grid(|_, index| *alpha.get(index).unwrap_or(&255)));
+}
+
+#[test]
+fn png_16_bit() {
+	// Samples are scaled from 16 to 8 bits with value * 255 / 65535
+	let scale = |value: u32| (value * 255 / 65535) as u8;
+	let mut expected = RgbaImage::new(3, 2);
+	for y in 0..2 {
+		for x in 0..3 {
+			let value = 0x1234 * (x + 1) + y;
+			expected.set_pixel(x, y, [scale(value), scale(0xffff - value), scale(value / 2), scale(0xffff - 0x1111 * x)]);
+		}
+	}
+	assert_pixels("rgba16.png", &expected);
+}
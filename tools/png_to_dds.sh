@@ -0,0 +1,26 @@
+#!/bin/sh
+# Converts every image in a folder to dds with nitrust-texconv, the portable replacement
+# for the AMDCompress scripts. Extra arguments go to the converter, for example -f bc3.
+#
+# tools/png_to_dds.sh <input folder> <output folder> [options]
+
+set -e
+
+if [ $# -lt 2 ]; then
+	echo "usage: $0 <input folder> <output folder> [nitrust-texconv options]" >&2
+	exit 2
+fi
+
+input=$1
+output=$2
+shift 2
+
+root=$(cd "$(dirname "$0")/.." && pwd)
+cargo build --release --manifest-path "$root/Cargo.toml" --bin nitrust-texconv
+mkdir -p "$output"
+
+for image in "$input"/*.png "$input"/*.tga "$input"/*.bmp; do
+	[ -e "$image" ] || continue
+	name=$(basename "$image")
+	"$root/target/release/nitrust-texconv" "$@" "$image" "$output/${name%.*}.dds"
+done